@@ -2,14 +2,15 @@
 //!
 //! Exposes ChainDB public API for external applications via REST (axum) and gRPC (tonic).
 //!
-//! - REST endpoints: /block/{id}, /utxo/{block_id}/{tx_id}/{index}, /blocks, /utxos/{block_id}
+//! - REST endpoints: /block/{id}, /utxo/{block_id}/{tx_id}/{index} (tx_id in hex), /blocks, /utxos/{block_id},
+//!   /protocol-parameters/{block_id}
 //! - gRPC service: ChainDBService (see .proto definition below)
 //!
 //! All endpoints are async and use strong typing and error handling.
 
-use crate::chaindb::SharedChainDB;
+use crate::chaindb::{ChainDBError, SharedChainDB};
 use crate::wallet;
-use crate::ledger::Transaction;
+use crate::ledger::{Address, Transaction};
 use axum::{extract::Path, response::IntoResponse, routing::get, Json, Router, extract::State};
 use axum::routing::post;
 use serde::Deserialize;
@@ -43,6 +44,7 @@ pub fn rest_router(app_state: SharedAppState) -> Router {
         .route("/utxo/:block_id/:tx_id/:index", get(get_utxo))
         .route("/blocks", get(stream_blocks))
         .route("/utxos/:block_id", get(stream_utxos))
+        .route("/protocol-parameters/:block_id", get(get_protocol_parameters))
         // Wallet endpoints
         .route("/wallet/create", post(create_wallet))
         .route("/wallet/:idx/balance", get(get_wallet_balance))
@@ -51,6 +53,26 @@ pub fn rest_router(app_state: SharedAppState) -> Router {
         .with_state(app_state)
 }
 
+impl IntoResponse for ChainDBError {
+    /// Map ChainDB errors to HTTP status codes: missing items are 404, a rollback beyond `k` is
+    /// 409, a block that does not extend the tip is 422, an incompatible on-disk format or chunk
+    /// size is 503, everything else is 500.
+    fn into_response(self) -> axum::response::Response {
+        let status = match &self {
+            ChainDBError::NotFound { .. } => axum::http::StatusCode::NOT_FOUND,
+            ChainDBError::VersionMismatch { .. } | ChainDBError::ChunkSizeMismatch { .. } => {
+                axum::http::StatusCode::SERVICE_UNAVAILABLE
+            }
+            ChainDBError::RollbackTooDeep { .. } => axum::http::StatusCode::CONFLICT,
+            ChainDBError::InvalidBlock { .. } => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            ChainDBError::Corrupt { .. } | ChainDBError::Encode { .. } | ChainDBError::Io(_) => {
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
 async fn get_block(
     Path(id): Path<u64>,
     State(app_state): State<SharedAppState>,
@@ -59,20 +81,23 @@ async fn get_block(
     let db = state.db.read().await;
     match db.api_get_block(id).await {
         Ok(block) => Json(block).into_response(),
-        Err(e) => (axum::http::StatusCode::NOT_FOUND, e.to_string()).into_response(),
+        Err(e) => e.into_response(),
     }
 }
 
 async fn get_utxo(
-    Path((block_id, tx_id, index)): Path<(u64, u64, u32)>,
+    Path((block_id, tx_id, index)): Path<(u64, String, u32)>,
     State(app_state): State<SharedAppState>,
 ) -> impl IntoResponse {
+    let Some(tx_id) = crate::chaindb::parse_hex32(&tx_id) else {
+        return (axum::http::StatusCode::BAD_REQUEST, "Invalid transaction id").into_response();
+    };
     let state = app_state.read().await;
     let db = state.db.read().await;
-    match db.api_get_utxo(block_id, tx_id, index).await {
+    match db.api_get_utxo(block_id, &tx_id, index).await {
         Ok(Some(utxo)) => Json(utxo).into_response(),
         Ok(None) => (axum::http::StatusCode::NOT_FOUND, "UTXO not found").into_response(),
-        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => e.into_response(),
     }
 }
 
@@ -83,12 +108,15 @@ async fn stream_blocks(
     let db = state.db.read().await;
     let mut stream = match db.api_stream_blocks().await {
         Ok(s) => s,
-        Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => return e.into_response(),
     };
     use futures::StreamExt;
     let mut blocks = Vec::new();
     while let Some(block) = stream.next().await {
-        blocks.push(block);
+        match block {
+            Ok(block) => blocks.push(block),
+            Err(e) => return e.into_response(),
+        }
     }
     Json(blocks).into_response()
 }
@@ -101,7 +129,7 @@ async fn stream_utxos(
     let db = state.db.read().await;
     let mut stream = match db.api_stream_utxos(block_id).await {
         Ok(s) => s,
-        Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => return e.into_response(),
     };
     use futures::StreamExt;
     let mut utxos = Vec::new();
@@ -111,6 +139,18 @@ async fn stream_utxos(
     Json(utxos).into_response()
 }
 
+async fn get_protocol_parameters(
+    Path(block_id): Path<u64>,
+    State(app_state): State<SharedAppState>,
+) -> impl IntoResponse {
+    let state = app_state.read().await;
+    let db = state.db.read().await;
+    match db.api_get_protocol_parameters(block_id).await {
+        Ok(params) => Json(params).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
 /// Create a new wallet
 ///
 /// # Arguments
@@ -185,8 +225,8 @@ pub struct CreateWalletRequest {
 
 #[derive(Deserialize)]
 pub struct BuildTxRequest {
-    /// Recipient's address for the transaction.
-    pub to_address: String,
+    /// Recipient's address for the transaction, in bech32 (or base58 for Byron).
+    pub to_address: Address,
     /// Amount of lovelace to send.
     pub amount: u64,
 }
@@ -203,15 +243,18 @@ pub struct BuildTxRequest {
 //   rpc GetUTXO (GetUTXORequest) returns (UTXOResponse);
 //   rpc StreamBlocks (StreamBlocksRequest) returns (stream Block);
 //   rpc StreamUTXOs (StreamUTXOsRequest) returns (stream UTXOEntry);
+//   rpc GetProtocolParameters (GetProtocolParametersRequest) returns (ProtocolParameters);
 // }
 //
 // message GetBlockRequest { uint64 id = 1; }
-// message GetUTXORequest { uint64 block_id = 1; uint64 tx_id = 2; uint32 index = 3; }
+// message GetUTXORequest { uint64 block_id = 1; bytes tx_id = 2; uint32 index = 3; }
 // message StreamBlocksRequest {}
 // message StreamUTXOsRequest { uint64 block_id = 1; }
+// message GetProtocolParametersRequest { uint64 block_id = 1; }
 // message Block { ... }
-// message UTXOEntry { uint64 tx_id = 1; uint32 index = 2; TxOutput output = 3; }
+// message UTXOEntry { bytes tx_id = 1; uint32 index = 2; TxOutput output = 3; }
 // message TxOutput { ... }
 // message UTXOResponse { optional TxOutput output = 1; }
+// message ProtocolParameters { ... }
 
 // See src/api_grpc.rs for full tonic implementation (to be created)
@@ -1,6 +1,8 @@
 //! gRPC API endpoints for Super Cardano Node
 //!
 //! Implements Tonic-based gRPC services for node, chain, and wallet.
+use crate::ledger::Transaction;
+use crate::ledger::cbor::{Cbor, CborError, from_hex};
 use serde::{Deserialize, Serialize};
 use tonic::{Request, Response, Status};
 
@@ -36,8 +38,16 @@ pub struct BlockResponse {
 /// Submit transaction request/response
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct SubmitTxRequest {
+    /// Hex-encoded CBOR of a Cardano transaction.
     pub cbor: String,
 }
+
+impl SubmitTxRequest {
+    /// Decode the submitted transaction.
+    pub fn decode(&self) -> Result<Transaction, CborError> {
+        Transaction::from_cbor(&from_hex(&self.cbor)?)
+    }
+}
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct SubmitTxResponse {
     pub tx_hash: String,
@@ -1,6 +1,8 @@
 //! REST API endpoints for Super Cardano Node
 //!
 //! Implements OpenAPI-compatible endpoints for node, chain, and wallet.
+use crate::ledger::Transaction;
+use crate::ledger::cbor::{Cbor, CborError, from_hex};
 use serde::{Deserialize, Serialize};
 
 /// Health check endpoint: GET /health
@@ -48,20 +50,34 @@ pub async fn get_block(_hash: String) -> BlockResponse {
 /// Submit a transaction: POST /tx
 #[derive(Serialize, Deserialize)]
 pub struct SubmitTxRequest {
+    /// Hex-encoded CBOR of a Cardano transaction.
     pub cbor: String,
 }
 
+impl SubmitTxRequest {
+    /// Decode the submitted transaction.
+    pub fn decode(&self) -> Result<Transaction, CborError> {
+        Transaction::from_cbor(&from_hex(&self.cbor)?)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SubmitTxResponse {
     pub tx_hash: String,
     pub accepted: bool,
 }
 
-pub async fn submit_tx(_req: SubmitTxRequest) -> SubmitTxResponse {
-    // TODO: Validate and submit transaction
-    SubmitTxResponse {
-        tx_hash: "dummy_tx_hash".to_string(),
-        accepted: true,
+pub async fn submit_tx(req: SubmitTxRequest) -> SubmitTxResponse {
+    // TODO: Validate against the ledger and add to the mempool
+    match req.decode() {
+        Ok(tx) => SubmitTxResponse {
+            tx_hash: crate::chaindb::hex(&tx.id()),
+            accepted: true,
+        },
+        Err(_) => SubmitTxResponse {
+            tx_hash: String::new(),
+            accepted: false,
+        },
     }
 }
 
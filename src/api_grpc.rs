@@ -3,20 +3,36 @@
 //! Implements the ChainDBService as described in the proto definition in api.rs.
 //! All methods are async and use strong typing and error handling.
 
-use crate::chaindb::SharedChainDB;
+use crate::chaindb::{ChainDBError, SharedChainDB};
 use futures::StreamExt;
 use futures_core::stream::Stream;
 use std::pin::Pin;
 use tonic::{Request, Response, Status, transport::Server};
 
 use crate::chaindb_proto;
-use crate::proto_convert::{block_to_proto, transaction_to_proto, txoutput_to_proto};
+use crate::proto_convert::{
+    block_to_proto, protocol_parameters_to_proto, transaction_to_proto, txoutput_to_proto,
+};
 // Correct import for generated server trait (prost/tonic lowercases and underscores service names)
 use chaindb_proto::chain_db_service_server::{ChainDbService, ChainDbServiceServer};
 pub struct ChainDbGrpcServer {
     pub db: SharedChainDB,
 }
 
+impl From<ChainDBError> for Status {
+    fn from(e: ChainDBError) -> Self {
+        match e {
+            ChainDBError::NotFound { .. } => Status::not_found(e.to_string()),
+            ChainDBError::Corrupt { .. } => Status::data_loss(e.to_string()),
+            ChainDBError::InvalidBlock { .. } => Status::invalid_argument(e.to_string()),
+            ChainDBError::VersionMismatch { .. }
+            | ChainDBError::ChunkSizeMismatch { .. }
+            | ChainDBError::RollbackTooDeep { .. } => Status::failed_precondition(e.to_string()),
+            ChainDBError::Encode { .. } | ChainDBError::Io(_) => Status::internal(e.to_string()),
+        }
+    }
+}
+
 #[tonic::async_trait]
 impl chaindb_proto::chain_db_service_server::ChainDbService for ChainDbGrpcServer {
     type StreamBlocksStream =
@@ -30,10 +46,7 @@ impl chaindb_proto::chain_db_service_server::ChainDbService for ChainDbGrpcServe
     ) -> Result<Response<chaindb_proto::Block>, Status> {
         let id = request.into_inner().id;
         let db = self.db.read().await;
-        let block = db
-            .api_get_block(id)
-            .await
-            .map_err(|e| Status::not_found(e.to_string()))?;
+        let block = db.api_get_block(id).await?;
         Ok(Response::new(block_to_proto(&block)))
     }
 
@@ -42,16 +55,28 @@ impl chaindb_proto::chain_db_service_server::ChainDbService for ChainDbGrpcServe
         request: Request<chaindb_proto::GetUtxoRequest>,
     ) -> Result<Response<chaindb_proto::UtxoResponse>, Status> {
         let req = request.into_inner();
+        let tx_id: crate::ledger::TxId = req
+            .tx_id
+            .as_slice()
+            .try_into()
+            .map_err(|_| Status::invalid_argument("tx_id must be a 32-byte hash"))?;
         let db = self.db.read().await;
-        let utxo = db
-            .api_get_utxo(req.block_id, req.tx_id, req.index)
-            .await
-            .map_err(|e| Status::not_found(e.to_string()))?;
+        let utxo = db.api_get_utxo(req.block_id, &tx_id, req.index).await?;
         Ok(Response::new(chaindb_proto::UtxoResponse {
             output: utxo.as_ref().map(|o| txoutput_to_proto(o)),
         }))
     }
 
+    async fn get_protocol_parameters(
+        &self,
+        request: Request<chaindb_proto::GetProtocolParametersRequest>,
+    ) -> Result<Response<chaindb_proto::ProtocolParameters>, Status> {
+        let block_id = request.into_inner().block_id;
+        let db = self.db.read().await;
+        let params = db.api_get_protocol_parameters(block_id).await?;
+        Ok(Response::new(protocol_parameters_to_proto(&params)))
+    }
+
     async fn stream_blocks(
         &self,
         _request: Request<chaindb_proto::StreamBlocksRequest>,
@@ -63,14 +88,11 @@ impl chaindb_proto::chain_db_service_server::ChainDbService for ChainDbGrpcServe
     > {
         let blocks: Vec<_> = {
             let db = self.db.read().await;
-            let mut stream = db
-                .api_stream_blocks()
-                .await
-                .map_err(|e| Status::internal(e.to_string()))?;
+            let mut stream = db.api_stream_blocks().await?;
             let mut blocks = Vec::new();
             use futures::StreamExt;
             while let Some(block) = stream.next().await {
-                blocks.push(block_to_proto(&block));
+                blocks.push(block_to_proto(&block?));
             }
             blocks
         };
@@ -90,15 +112,12 @@ impl chaindb_proto::chain_db_service_server::ChainDbService for ChainDbGrpcServe
         let utxos: Vec<_> = {
             let block_id = request.into_inner().block_id;
             let db = self.db.read().await;
-            let mut stream = db
-                .api_stream_utxos(block_id)
-                .await
-                .map_err(|e| Status::internal(e.to_string()))?;
+            let mut stream = db.api_stream_utxos(block_id).await?;
             let mut utxos = Vec::new();
             use futures::StreamExt;
             while let Some(((tx_id, index), output)) = stream.next().await {
                 utxos.push(chaindb_proto::UtxoEntry {
-                    tx_id,
+                    tx_id: tx_id.to_vec(),
                     index,
                     output: Some(txoutput_to_proto(&output)),
                 });
@@ -74,9 +74,12 @@ impl AppState {
         consensus: Consensus,
         tracer: Arc<Tracer>,
     ) -> Result<Self> {
-        let chaindb_path = config.chaindb.path.clone();
         let chaindb = Arc::new(RwLock::new(
-            ChainDB::open(&chaindb_path).await?
+            ChainDB::open_with_config(
+                &config.database.path,
+                config.chaindb.clone(),
+                (*tracer).clone(),
+            ).await?
         ));
 
         let wallet_manager = WalletManager::new(
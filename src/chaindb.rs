@@ -3,9 +3,21 @@
 //! Handles block storage, UTXO/state snapshots, rollback, and chain selection.
 //!
 //! # Overview
-//! - Stores blocks and ledger state on disk for full chain sync and recovery.
-//! - Supports rollback to previous blocks/slots for fork handling.
-//! - Provides atomic, crash-safe operations.
+//! - Keeps the last `k` blocks (the security parameter) in a [`volatile`] store that may hold
+//!   forks, and promotes older blocks to an append-only, chunked log with a secondary offset
+//!   index (see [`immutable`]) as the chain grows.
+//! - Identifies blocks by their Blake2b-256 header hash and only accepts appends that extend the
+//!   tip through `prev_hash`, so forks are told apart by hash ancestry rather than by block id.
+//! - Looks blocks up by id, slot or hash in O(1) without scanning the directory.
+//! - Stores ledger state as periodic snapshots plus per-block diffs (see [`ledgerdb`]), rebuilding
+//!   the state at any retained block by replay, with retention driven by [`LedgerDBConfig`].
+//! - Supports rollback of at most `k` blocks for fork handling; deeper rollbacks are rejected, so
+//!   blocks in the immutable store are final.
+//! - Makes appends crash-safe with a write-ahead [`journal`], synced writes and atomic renames.
+//! - Runs a recovery scan on open that rolls back interrupted appends and truncates torn entries,
+//!   reporting every repair through the tracer.
+//! - Reports failures as a typed [`ChainDBError`] and refuses databases written with an
+//!   incompatible on-disk format version or immutable chunk size.
 //!
 //! # Usage Example
 //! ```rust,ignore
@@ -15,20 +27,106 @@
 //! // db.append_block(&block, &state).await.unwrap();
 //! ```
 
-use crate::ledger::{Block, LedgerState};
+pub mod error;
+pub mod fsutil;
+pub mod immutable;
+pub mod journal;
+pub mod ledgerdb;
+pub mod volatile;
+
+use crate::configuration::ChainDBConfig;
+use crate::ledger::cbor::Cbor;
+use crate::ledger::{Block, BlockHash, LedgerState, TxId};
+use crate::tracing::tracers::{TraceEvent, Tracer};
+pub use error::ChainDBError;
 use futures::Stream;
+use immutable::{DEFAULT_CHUNK_SIZE, ImmutableDB};
+use journal::{Journal, JournalRecord, JournalState, WriteStep};
+use ledgerdb::LedgerDB;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use tokio::fs;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::RwLock;
+use volatile::{ChainEntry, VolatileDB};
+
+/// On-disk format version written to the `version` file of every ChainDB, followed by the chunk
+/// size of its immutable store.
+pub const DB_FORMAT_VERSION: u32 = 21;
 
 /// Persistent chain database for blocks and ledger state.
 #[derive(Debug)]
 pub struct ChainDB {
     path: PathBuf,
+    security_param: u64,
+    immutable: ImmutableDB,
+    volatile: VolatileDB,
+    ledger: LedgerDB,
+    journal: Journal,
+    tracer: Tracer,
+    recovery: Vec<Repair>,
+    crash_after: Option<WriteStep>,
+}
+
+/// A repair performed by the recovery scan when the ChainDB is opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// A chunk created by an unfinished append, holding no complete index record, was removed.
+    OrphanChunk { chunk: u64 },
+    /// A partially written secondary index record was truncated.
+    TornIndexRecord { chunk: u64 },
+    /// An index record whose block bytes were missing or corrupt was dropped.
+    TornBlock { chunk: u64, block_id: u64 },
+    /// Bytes of an unindexed block after the end of the log were truncated.
+    TrailingBytes { chunk: u64, bytes: u64 },
+    /// An append interrupted before it was committed was rolled back.
+    RolledBackAppend { block_id: u64 },
+    /// A torn journal record was discarded.
+    DiscardedJournal,
+    /// A leftover temporary file was removed.
+    RemovedTempFile { path: PathBuf },
+    /// A partially written volatile chain record was truncated.
+    TornVolatileRecord,
+    /// A volatile chain record whose block file was missing was dropped.
+    MissingVolatileBlock { block_id: u64 },
+    /// Blocks already copied to the immutable store were removed from the volatile chain.
+    FinishedPromotion { blocks: usize },
+}
+
+impl std::fmt::Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repair::OrphanChunk { chunk } => write!(f, "removed orphan chunk {}", chunk),
+            Repair::TornIndexRecord { chunk } => {
+                write!(f, "truncated torn index record in chunk {}", chunk)
+            }
+            Repair::TornBlock { chunk, block_id } => {
+                write!(f, "dropped torn block {} in chunk {}", block_id, chunk)
+            }
+            Repair::TrailingBytes { chunk, bytes } => {
+                write!(f, "truncated {} trailing bytes in chunk {}", bytes, chunk)
+            }
+            Repair::RolledBackAppend { block_id } => {
+                write!(f, "rolled back interrupted append of block {}", block_id)
+            }
+            Repair::DiscardedJournal => write!(f, "discarded torn journal record"),
+            Repair::RemovedTempFile { path } => {
+                write!(f, "removed temporary file {}", path.display())
+            }
+            Repair::TornVolatileRecord => write!(f, "truncated torn volatile chain record"),
+            Repair::MissingVolatileBlock { block_id } => {
+                write!(f, "dropped volatile block {} with missing data", block_id)
+            }
+            Repair::FinishedPromotion { blocks } => {
+                write!(
+                    f,
+                    "finished promotion of {} blocks to the immutable store",
+                    blocks
+                )
+            }
+        }
+    }
 }
 
 /// Shared, concurrent ChainDB handle for node and applications.
@@ -36,94 +134,460 @@ pub type SharedChainDB = Arc<RwLock<ChainDB>>;
 
 impl ChainDB {
     /// Open or create a ChainDB at the given path.
-    pub async fn open<P: Into<PathBuf>>(path: P) -> std::io::Result<Self> {
+    pub async fn open<P: Into<PathBuf>>(path: P) -> Result<Self, ChainDBError> {
+        Self::open_with_tracer(path, Tracer::default()).await
+    }
+
+    /// Open or create a ChainDB at the given path, reporting recovery repairs to `tracer`.
+    pub async fn open_with_tracer<P: Into<PathBuf>>(
+        path: P,
+        tracer: Tracer,
+    ) -> Result<Self, ChainDBError> {
+        Self::open_with_config(path, ChainDBConfig::default(), tracer).await
+    }
+
+    /// Open or create a ChainDB at the given path with the given security parameter and ledger
+    /// state storage, reporting recovery repairs to `tracer`.
+    pub async fn open_with_config<P: Into<PathBuf>>(
+        path: P,
+        config: ChainDBConfig,
+        tracer: Tracer,
+    ) -> Result<Self, ChainDBError> {
         let path = path.into();
         fs::create_dir_all(&path).await?;
-        Ok(Self { path })
+        Self::check_version(&path, DEFAULT_CHUNK_SIZE).await?;
+        let immutable = ImmutableDB::open(path.join("immutable"), DEFAULT_CHUNK_SIZE).await?;
+        let volatile = VolatileDB::open(path.join("volatile")).await?;
+        let ledger_dir = if config.ledger.db_path.is_empty() {
+            path.join("ledger")
+        } else {
+            PathBuf::from(&config.ledger.db_path)
+        };
+        let ledger = LedgerDB::open(ledger_dir, config.ledger).await?;
+        let journal = Journal::new(path.join("journal"));
+        let mut db = Self {
+            path,
+            security_param: config.security_param,
+            immutable,
+            volatile,
+            ledger,
+            journal,
+            tracer,
+            recovery: Vec::new(),
+            crash_after: None,
+        };
+        db.recover().await?;
+        Ok(db)
+    }
+
+    /// Check the on-disk format version and the chunk size the immutable store was written with,
+    /// stamping both on a new database: the `version` file holds one per line.
+    async fn check_version(path: &std::path::Path, chunk_size: u64) -> Result<(), ChainDBError> {
+        let version_path = path.join("version");
+        match fs::read_to_string(&version_path).await {
+            Ok(text) => {
+                let mut lines = text.lines().map(str::trim);
+                let found = lines
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<u32>()
+                    .map_err(|e| ChainDBError::corrupt(&version_path, e))?;
+                if found != DB_FORMAT_VERSION {
+                    return Err(ChainDBError::VersionMismatch {
+                        found,
+                        expected: DB_FORMAT_VERSION,
+                    });
+                }
+                let found = lines
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<u64>()
+                    .map_err(|e| ChainDBError::corrupt(&version_path, e))?;
+                if found != chunk_size {
+                    return Err(ChainDBError::ChunkSizeMismatch {
+                        found,
+                        expected: chunk_size,
+                    });
+                }
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let text = format!("{}\n{}\n", DB_FORMAT_VERSION, chunk_size);
+                Ok(fsutil::write_atomic(&version_path, text.as_bytes()).await?)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Bring the on-disk stores back to a consistent state after an unclean shutdown.
+    async fn recover(&mut self) -> Result<(), ChainDBError> {
+        let mut repairs = self.immutable.recover().await?;
+        repairs.extend(self.volatile.recover().await?);
+        match self.journal.read().await? {
+            JournalState::Clean => {}
+            JournalState::Torn => {
+                self.journal.commit().await?;
+                repairs.push(Repair::DiscardedJournal);
+            }
+            JournalState::Pending(record) => {
+                self.roll_back_append(record).await?;
+                repairs.push(Repair::RolledBackAppend {
+                    block_id: record.block_id,
+                });
+            }
+        }
+        // A crash while promoting leaves the promoted blocks in both stores.
+        let promoted = self
+            .volatile
+            .entries()
+            .iter()
+            .take_while(|e| self.immutable.get_by_hash(&e.hash).is_some())
+            .count();
+        if promoted > 0 {
+            self.volatile.drop_oldest(promoted).await?;
+            repairs.push(Repair::FinishedPromotion { blocks: promoted });
+        }
+        let dirs = [
+            self.path.clone(),
+            self.volatile.dir().to_path_buf(),
+            self.ledger.dir().to_path_buf(),
+        ];
+        for dir in dirs {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_name().to_string_lossy().ends_with(".tmp") {
+                    fs::remove_file(entry.path()).await?;
+                    repairs.push(Repair::RemovedTempFile { path: entry.path() });
+                }
+            }
+        }
+        let chain = self.chain();
+        self.ledger.retain(&chain).await?;
+        self.ledger.reset_tip(&chain).await?;
+        self.promote().await?;
+        for repair in &repairs {
+            self.tracer
+                .trace(TraceEvent::ChainDB(format!("Recovery: {}", repair)));
+        }
+        self.recovery = repairs;
+        Ok(())
+    }
+
+    /// Repairs performed by the recovery scan when this ChainDB was opened.
+    pub fn recovery_report(&self) -> &[Repair] {
+        &self.recovery
+    }
+
+    /// Undo every effect of the append described by a journal record.
+    async fn roll_back_append(&mut self, record: JournalRecord) -> Result<(), ChainDBError> {
+        let keep = (record.position as usize).saturating_sub(self.immutable.len());
+        self.volatile.truncate(keep).await?;
+        self.volatile.remove_block(&record.hash).await?;
+        self.ledger.remove(record.block_id).await?;
+        Ok(self.journal.commit().await?)
+    }
+
+    /// Move blocks more than `k` deep from the volatile store to the immutable store.
+    async fn promote(&mut self) -> Result<(), ChainDBError> {
+        let excess = self
+            .volatile
+            .len()
+            .saturating_sub(self.security_param as usize);
+        for entry in &self.volatile.entries()[..excess] {
+            let block = self.volatile.read(&entry.hash).await?;
+            self.immutable.append(&block).await?;
+        }
+        self.volatile.drop_oldest(excess).await
+    }
+
+    /// Security parameter `k`: the deepest rollback this ChainDB accepts.
+    pub fn security_param(&self) -> u64 {
+        self.security_param
+    }
+
+    /// Id of the newest block that can no longer be rolled back, if any.
+    pub fn immutable_tip(&self) -> Option<u64> {
+        self.immutable.tip().map(|e| e.block_id)
+    }
+
+    /// Ledger state store, holding the snapshots and diffs.
+    pub fn ledger(&self) -> &LedgerDB {
+        &self.ledger
+    }
+
+    /// Hash of the newest block on the chain, or `None` for an empty chain.
+    pub fn tip_hash(&self) -> Option<BlockHash> {
+        self.volatile
+            .entries()
+            .last()
+            .map(|e| e.hash)
+            .or_else(|| self.immutable.tip().map(|e| e.hash))
+    }
+
+    /// Hash of the block on the current chain with the given id.
+    pub fn hash_of(&self, id: u64) -> Option<BlockHash> {
+        self.volatile
+            .get_by_id(id)
+            .map(|e| e.hash)
+            .or_else(|| self.immutable.get_by_id(id).map(|e| e.hash))
+    }
+
+    /// Block ids of the chain, in order.
+    fn chain(&self) -> Vec<u64> {
+        let immutable = self.immutable.entries().iter().map(|e| e.block_id);
+        let volatile = self.volatile.entries().iter().map(|e| e.block_id);
+        immutable.chain(volatile).collect()
+    }
+
+    /// Number of blocks on the chain.
+    fn chain_len(&self) -> usize {
+        self.immutable.len() + self.volatile.len()
+    }
+
+    /// Position on the chain of the block with the given id.
+    fn position_of(&self, id: u64) -> Option<usize> {
+        match self
+            .volatile
+            .entries()
+            .iter()
+            .position(|e| e.block_id == id)
+        {
+            Some(pos) => Some(self.immutable.len() + pos),
+            None => self.immutable.position_of(id),
+        }
+    }
+
+    /// Simulate a crash right after `step` of the next appends: the append stops without cleaning
+    /// up, as if the process had died. The ChainDB must be reopened afterwards.
+    #[doc(hidden)]
+    pub fn simulate_crash_after(&mut self, step: Option<WriteStep>) {
+        self.crash_after = step;
+    }
+
+    /// Fail with a simulated crash if one was requested for `step`.
+    fn crash_point(&self, step: WriteStep) -> Result<(), ChainDBError> {
+        if self.crash_after == Some(step) {
+            return Err(std::io::Error::other(format!("simulated crash after {:?}", step)).into());
+        }
+        Ok(())
     }
 
     /// Append a block and update the ledger state atomically.
+    ///
+    /// The block must extend the tip: its `prev_hash` has to be the tip hash (`None` on an empty
+    /// chain) and its body has to match the header's body hash, otherwise the append fails with
+    /// [`ChainDBError::InvalidBlock`] and nothing is written. The append is journalled first; the
+    /// block file, volatile chain record and ledger diff (plus a snapshot when one is due) are
+    /// then synced in order, and the journal is cleared last. If any step fails the append is
+    /// rolled back, and a crash at any point is rolled back by the recovery scan on the next open.
+    /// Once the append is committed, blocks more than `k` deep are promoted to the immutable
+    /// store and old snapshots and diffs are pruned.
     pub async fn append_block(
         &mut self,
         block: &Block,
         state: &LedgerState,
-    ) -> std::io::Result<()> {
-        let block_path = self.path.join(format!("block_{}.json", block.id));
-        let state_path = self.path.join(format!("state_{}.json", block.id));
-        let block_data = serde_json::to_vec(block).unwrap();
-        let state_data = serde_json::to_vec(state).unwrap();
-        fs::write(block_path, block_data).await?;
-        fs::write(state_path, state_data).await?;
+    ) -> Result<(), ChainDBError> {
+        self.check_linkage(block)?;
+        let position = self.chain_len();
+        let diff = self.ledger.encode_diff(state)?;
+        let snapshot = if self.ledger.snapshot_due(position) {
+            Some(LedgerDB::encode_snapshot(state)?)
+        } else {
+            None
+        };
+        let (bytes, hash) = encode_block(block);
+        let entry = ChainEntry {
+            block_id: block.id,
+            slot: block.header.slot,
+            hash,
+        };
+        let record = JournalRecord {
+            block_id: block.id,
+            position: position as u64,
+            hash,
+        };
+        self.journal.begin(record).await?;
+        let result = self
+            .write_append(entry, &bytes, &diff, snapshot.as_deref())
+            .await;
+        if let Err(e) = result {
+            if self.crash_after.is_none() {
+                self.roll_back_append(record).await?;
+            }
+            return Err(e);
+        }
+        self.ledger
+            .committed(block.id, snapshot.is_some(), state.clone());
+        self.promote().await?;
+        let floor = self
+            .chain_len()
+            .saturating_sub(self.security_param as usize + 1);
+        self.ledger.prune(&self.chain(), floor).await
+    }
+
+    /// Check that a block extends the tip by hash and carries the body its header commits to.
+    fn check_linkage(&self, block: &Block) -> Result<(), ChainDBError> {
+        let tip = self.tip_hash();
+        if block.header.prev_hash != tip {
+            return Err(ChainDBError::InvalidBlock {
+                block_id: block.id,
+                reason: format!(
+                    "previous hash {} does not match tip {}",
+                    block.header.prev_hash.map_or("none".into(), |h| hex(&h)),
+                    tip.map_or("none".into(), |h| hex(&h)),
+                ),
+            });
+        }
+        if !block.body_matches() {
+            return Err(ChainDBError::InvalidBlock {
+                block_id: block.id,
+                reason: "body does not match header body hash".into(),
+            });
+        }
         Ok(())
     }
 
-    /// Load a block by id.
-    pub async fn load_block(&self, id: u64) -> std::io::Result<Block> {
-        let block_path = self.path.join(format!("block_{}.json", id));
-        let mut file = fs::File::open(block_path).await?;
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf).await?;
-        Ok(serde_json::from_slice(&buf).unwrap())
+    /// Write the journalled steps of an append, then commit the journal.
+    async fn write_append(
+        &mut self,
+        entry: ChainEntry,
+        bytes: &[u8],
+        diff: &[u8],
+        snapshot: Option<&[u8]>,
+    ) -> Result<(), ChainDBError> {
+        self.crash_point(WriteStep::Journal)?;
+        self.volatile.write_block(&entry, bytes).await?;
+        self.crash_point(WriteStep::Block)?;
+        self.volatile.push(entry).await?;
+        self.crash_point(WriteStep::Index)?;
+        let diff_path = self.ledger.diff_path(entry.block_id);
+        fsutil::write_temp(&diff_path, diff).await?;
+        self.crash_point(WriteStep::StateTemp)?;
+        fsutil::publish_temp(&diff_path).await?;
+        if let Some(snapshot) = snapshot {
+            let snapshot_path = self.ledger.snapshot_path(entry.block_id);
+            fsutil::write_atomic(&snapshot_path, snapshot).await?;
+        }
+        self.crash_point(WriteStep::State)?;
+        Ok(self.journal.commit().await?)
+    }
+
+    /// Load a block on the current chain by id.
+    pub async fn load_block(&self, id: u64) -> Result<Block, ChainDBError> {
+        if let Some(entry) = self.volatile.get_by_id(id) {
+            return self.volatile.read(&entry.hash).await;
+        }
+        let entry = self
+            .immutable
+            .get_by_id(id)
+            .ok_or_else(|| ChainDBError::not_found("block", id))?;
+        self.immutable.read(entry).await
+    }
+
+    /// Load the block on the current chain produced in the given slot.
+    pub async fn load_block_by_slot(&self, slot: u64) -> Result<Block, ChainDBError> {
+        if let Some(entry) = self.volatile.get_by_slot(slot) {
+            return self.volatile.read(&entry.hash).await;
+        }
+        let entry = self
+            .immutable
+            .get_by_slot(slot)
+            .ok_or_else(|| ChainDBError::not_found("slot", slot))?;
+        self.immutable.read(entry).await
+    }
+
+    /// Load a block by its hash, including blocks of forks still held in the volatile store.
+    pub async fn load_block_by_hash(&self, hash: &BlockHash) -> Result<Block, ChainDBError> {
+        if self.volatile.contains(hash) {
+            return self.volatile.read(hash).await;
+        }
+        let entry = self
+            .immutable
+            .get_by_hash(hash)
+            .ok_or_else(|| ChainDBError::not_found("block hash", hex(hash)))?;
+        self.immutable.read(entry).await
     }
 
-    /// Load ledger state by block id.
-    pub async fn load_state(&self, id: u64) -> std::io::Result<LedgerState> {
-        let state_path = self.path.join(format!("state_{}.json", id));
-        let mut file = fs::File::open(state_path).await?;
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf).await?;
-        Ok(serde_json::from_slice(&buf).unwrap())
+    /// Load ledger state by block id, replaying diffs from the nearest snapshot.
+    pub async fn load_state(&self, id: u64) -> Result<LedgerState, ChainDBError> {
+        let position = self
+            .position_of(id)
+            .ok_or_else(|| ChainDBError::not_found("ledger state", id))?;
+        if position + 1 == self.chain_len() {
+            return Ok(self.ledger.tip().clone());
+        }
+        self.ledger.state_at(&self.chain(), position).await
     }
 
     /// Roll back to a previous block id (removes all blocks/states after).
-    pub async fn rollback_to(&mut self, id: u64) -> std::io::Result<()> {
-        let mut entries = fs::read_dir(&self.path).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let fname = entry.file_name();
-            let fname = fname.to_string_lossy();
-            if let Some(num) = fname.split('_').nth(1).and_then(|s| s.split('.').next()) {
-                if let Ok(num) = num.parse::<u64>() {
-                    if num > id {
-                        fs::remove_file(entry.path()).await?;
-                    }
-                }
+    ///
+    /// Rolling back more than `k` blocks fails with [`ChainDBError::RollbackTooDeep`], and
+    /// nothing is changed. Rolled-back blocks stay in the volatile store as a fork until they
+    /// fall behind the immutable tip.
+    pub async fn rollback_to(&mut self, id: u64) -> Result<(), ChainDBError> {
+        let chain = self.chain();
+        let keep = chain.iter().position(|&b| b > id).unwrap_or(chain.len());
+        self.truncate_chain(keep).await
+    }
+
+    /// Roll back so that the block with the given hash becomes the tip, or to an empty chain for
+    /// `None`. This is how a fork is switched to: roll back to the intersection by hash, then
+    /// append the fork's blocks.
+    ///
+    /// Fails with [`ChainDBError::NotFound`] if the hash is not on the current chain, and with
+    /// [`ChainDBError::RollbackTooDeep`] under the same rule as [`ChainDB::rollback_to`].
+    pub async fn rollback_to_hash(&mut self, hash: Option<&BlockHash>) -> Result<(), ChainDBError> {
+        let keep = match hash {
+            None => 0,
+            Some(hash) => {
+                let pos = match self.volatile.get_by_hash(hash) {
+                    Some(entry) => self.position_of(entry.block_id),
+                    None => self
+                        .immutable
+                        .get_by_hash(hash)
+                        .and_then(|e| self.immutable.position_of(e.block_id)),
+                };
+                pos.ok_or_else(|| ChainDBError::not_found("block hash", hex(hash)))? + 1
             }
+        };
+        self.truncate_chain(keep).await
+    }
+
+    /// Keep the first `keep` blocks of the chain, rolling back the rest within the `k` limit.
+    async fn truncate_chain(&mut self, keep: usize) -> Result<(), ChainDBError> {
+        let chain = self.chain();
+        let depth = (chain.len() - keep) as u64;
+        if depth > self.security_param || keep < self.immutable.len() {
+            return Err(ChainDBError::RollbackTooDeep {
+                depth,
+                max: self.security_param,
+            });
         }
+        let tip = match keep {
+            0 => LedgerState::default(),
+            n => self.ledger.state_at(&chain, n - 1).await?,
+        };
+        self.volatile.truncate(keep - self.immutable.len()).await?;
+        self.ledger.retain(&chain[..keep]).await?;
+        self.ledger.set_tip(tip);
         Ok(())
     }
 
-    /// List all block ids in the database (sorted).
-    pub async fn block_ids(&self) -> std::io::Result<Vec<u64>> {
-        let mut ids = Vec::new();
-        let mut entries = fs::read_dir(&self.path).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let fname = entry.file_name();
-            let fname = fname.to_string_lossy();
-            if fname.starts_with("block_") && fname.ends_with(".json") {
-                if let Some(num) = fname
-                    .trim_start_matches("block_")
-                    .trim_end_matches(".json")
-                    .parse::<u64>()
-                    .ok()
-                {
-                    ids.push(num);
-                }
-            }
-        }
-        ids.sort_unstable();
-        Ok(ids)
+    /// List all block ids in the database, in chain order.
+    pub async fn block_ids(&self) -> Result<Vec<u64>, ChainDBError> {
+        Ok(self.chain())
     }
 
-    /// Stream all blocks from the database in order (async iterator).
+    /// Stream all blocks from the database in order (async iterator). A block that cannot be
+    /// read yields its error.
     pub async fn stream_blocks(
         &self,
-    ) -> std::io::Result<Pin<Box<dyn Stream<Item = Block> + Send + '_>>> {
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Block, ChainDBError>> + Send + '_>>, ChainDBError>
+    {
         use futures::stream::{self, StreamExt};
         let ids = self.block_ids().await?;
         let this = self;
-        let blocks = stream::iter(ids)
-            .then(move |id| this.load_block(id))
-            .filter_map(|res| async move { res.ok() });
+        let blocks = stream::iter(ids).then(move |id| this.load_block(id));
         Ok(Box::pin(blocks))
     }
 
@@ -131,51 +595,100 @@ impl ChainDB {
     pub async fn query_utxo(
         &self,
         block_id: u64,
-        tx_id: u64,
+        tx_id: &TxId,
         index: u32,
-    ) -> std::io::Result<Option<crate::ledger::TxOutput>> {
+    ) -> Result<Option<crate::ledger::TxOutput>, ChainDBError> {
         let state = self.load_state(block_id).await?;
-        Ok(state.utxos.get(&(tx_id, index)).cloned())
+        Ok(state.utxos.get(&(*tx_id, index)).cloned())
     }
 
     /// Query the full UTXO set at a given block id.
     pub async fn query_utxo_set(
         &self,
         block_id: u64,
-    ) -> std::io::Result<std::collections::HashMap<(u64, u32), crate::ledger::TxOutput>> {
+    ) -> Result<std::collections::HashMap<(TxId, u32), crate::ledger::TxOutput>, ChainDBError> {
         let state = self.load_state(block_id).await?;
         Ok(state.utxos)
     }
 
     /// Expose a REST-like async API for applications (stub, extend as needed)
     /// Example: Get block by id
-    pub async fn api_get_block(&self, id: u64) -> std::io::Result<Block> {
+    pub async fn api_get_block(&self, id: u64) -> Result<Block, ChainDBError> {
         self.load_block(id).await
     }
     /// Example: Get UTXO by (tx_id, index) at a given block id
     pub async fn api_get_utxo(
         &self,
         block_id: u64,
-        tx_id: u64,
+        tx_id: &TxId,
         index: u32,
-    ) -> std::io::Result<Option<crate::ledger::TxOutput>> {
+    ) -> Result<Option<crate::ledger::TxOutput>, ChainDBError> {
         self.query_utxo(block_id, tx_id, index).await
     }
+    /// Protocol parameters in effect at a given block id.
+    pub async fn api_get_protocol_parameters(
+        &self,
+        block_id: u64,
+    ) -> Result<crate::ledger::ProtocolParameters, ChainDBError> {
+        Ok(self.load_state(block_id).await?.params)
+    }
     /// Example: Stream all blocks (for sync or explorer)
     pub async fn api_stream_blocks(
         &self,
-    ) -> std::io::Result<Pin<Box<dyn Stream<Item = Block> + Send + '_>>> {
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Block, ChainDBError>> + Send + '_>>, ChainDBError>
+    {
         self.stream_blocks().await
     }
     /// Example: Stream all UTXOs at a given block id
     pub async fn api_stream_utxos(
         &self,
         block_id: u64,
-    ) -> std::io::Result<
-        Pin<Box<dyn Stream<Item = ((u64, u32), crate::ledger::TxOutput)> + Send + '_>>,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = ((TxId, u32), crate::ledger::TxOutput)> + Send + '_>>,
+        ChainDBError,
     > {
         let utxos = self.query_utxo_set(block_id).await?;
         use futures::stream;
-        Ok(Box::pin(stream::iter(utxos.into_iter())))
+        Ok(Box::pin(stream::iter(utxos)))
+    }
+}
+
+/// Encode a block in its stored (canonical CBOR) form, returning the bytes and the block hash.
+pub(crate) fn encode_block(block: &Block) -> (Vec<u8>, BlockHash) {
+    (block.to_cbor(), block.hash())
+}
+
+/// Decode stored block bytes read from `path`, checking the block against its recorded hash and
+/// its body against the header's body hash.
+pub(crate) fn decode_block(
+    path: &Path,
+    buf: &[u8],
+    hash: &BlockHash,
+    block_id: u64,
+) -> Result<Block, ChainDBError> {
+    let block = Block::from_cbor(buf).map_err(|e| ChainDBError::corrupt(path, e))?;
+    if block.hash() != *hash || !block.body_matches() {
+        return Err(ChainDBError::corrupt(
+            path,
+            format!("hash mismatch for block {}", block_id),
+        ));
+    }
+    Ok(block)
+}
+
+/// Lowercase hex rendering of a hash, for file names and error messages.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parse a 32-byte hash from its 64-character hex rendering.
+pub(crate) fn parse_hex32(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
     }
+    Some(bytes)
 }
@@ -0,0 +1,63 @@
+//! Error types for the ChainDB.
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors returned by the ChainDB API.
+#[derive(Debug, Error)]
+pub enum ChainDBError {
+    /// The requested block, slot, hash or state is not stored.
+    #[error("{what} not found: {key}")]
+    NotFound { what: &'static str, key: String },
+
+    /// Stored data could not be decoded or failed an integrity check.
+    #[error("Corrupt data in {}: {reason}", path.display())]
+    Corrupt { path: PathBuf, reason: String },
+
+    /// The database was written by an incompatible on-disk format version.
+    #[error("Unsupported ChainDB format version {found} (expected {expected})")]
+    VersionMismatch { found: u32, expected: u32 },
+
+    /// The immutable store was written with a different number of blocks per chunk.
+    #[error("ChainDB chunk size {found} does not match the configured {expected}")]
+    ChunkSizeMismatch { found: u64, expected: u64 },
+
+    /// A rollback would discard more blocks than the security parameter allows.
+    #[error("Rollback of {depth} blocks exceeds the security parameter k = {max}")]
+    RollbackTooDeep { depth: u64, max: u64 },
+
+    /// A block does not extend the chain tip or does not match its own header.
+    #[error("Invalid block {block_id}: {reason}")]
+    InvalidBlock { block_id: u64, reason: String },
+
+    /// A value could not be encoded for storage.
+    #[error("Failed to encode {what}: {reason}")]
+    Encode { what: &'static str, reason: String },
+
+    /// Underlying file-system error.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl ChainDBError {
+    /// Build a [`ChainDBError::NotFound`] for a missing key.
+    pub fn not_found(what: &'static str, key: impl std::fmt::Display) -> Self {
+        ChainDBError::NotFound {
+            what,
+            key: key.to_string(),
+        }
+    }
+
+    /// Build a [`ChainDBError::Corrupt`] for data read from `path`.
+    pub fn corrupt(path: impl Into<PathBuf>, reason: impl std::fmt::Display) -> Self {
+        ChainDBError::Corrupt {
+            path: path.into(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Whether this error means the requested item does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ChainDBError::NotFound { .. })
+    }
+}
@@ -0,0 +1,82 @@
+//! Durable file-system helpers shared by the ChainDB stores.
+//!
+//! Every helper that mutates a file flushes it to stable storage before returning, so that callers
+//! can reason about which writes survive a crash.
+
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// Append bytes to a file, creating it if needed, and sync it to disk.
+pub async fn append_to(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let created = !fs::try_exists(path).await?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(bytes).await?;
+    file.sync_data().await?;
+    if created {
+        sync_parent(path).await?;
+    }
+    Ok(())
+}
+
+/// Truncate a file to `len` bytes and sync it to disk.
+pub async fn set_len(path: &Path, len: u64) -> std::io::Result<()> {
+    let file = OpenOptions::new().write(true).open(path).await?;
+    file.set_len(len).await?;
+    file.sync_all().await
+}
+
+/// Remove a file, ignoring it if it is already gone.
+pub async fn remove_if_exists(path: &Path) -> std::io::Result<bool> {
+    match fs::remove_file(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Path of the temporary file used by [`write_atomic`] for `path`.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Write the temporary file for `path` and sync it, without publishing it yet.
+pub async fn write_temp(path: &Path, bytes: &[u8]) -> std::io::Result<PathBuf> {
+    let tmp = temp_path(path);
+    let mut file = fs::File::create(&tmp).await?;
+    file.write_all(bytes).await?;
+    file.sync_all().await?;
+    Ok(tmp)
+}
+
+/// Atomically replace `path` with the previously written temporary file.
+pub async fn publish_temp(path: &Path) -> std::io::Result<()> {
+    fs::rename(temp_path(path), path).await?;
+    sync_parent(path).await
+}
+
+/// Atomically replace the contents of `path`: readers see either the old or the new bytes.
+pub async fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    write_temp(path, bytes).await?;
+    publish_temp(path).await
+}
+
+/// Sync the directory containing `path`, making created, renamed or removed entries durable.
+pub async fn sync_parent(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        fs::File::open(dir).await?.sync_all().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,392 @@
+//! Append-only, chunked block store for the ChainDB.
+//!
+//! Blocks are appended to fixed-capacity chunk files (`chunk-00000.dat`, `chunk-00001.dat`, ...).
+//! Every chunk has a companion secondary index (`chunk-00000.idx`) made of fixed-size records that
+//! describe where each block lives inside the chunk. Opening the store only reads the secondary
+//! indices, never the blocks themselves, and builds an in-memory primary index so that lookups by
+//! block id, slot or hash are O(1).
+//!
+//! Every write is synced before it returns. [`ImmutableDB::recover`] repairs the tail of the log
+//! after a crash: torn index records, index records whose block bytes never fully reached the disk,
+//! trailing bytes of unindexed blocks and chunks that were created but never indexed.
+
+use super::error::ChainDBError;
+use super::fsutil::{append_to, remove_if_exists, set_len};
+use super::{Repair, decode_block, encode_block};
+use crate::ledger::Block;
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Default number of blocks stored in a single chunk file.
+pub const DEFAULT_CHUNK_SIZE: u64 = 1000;
+
+/// Size in bytes of one secondary index record.
+pub const INDEX_RECORD_SIZE: usize = 8 + 8 + 32 + 8 + 4;
+
+/// Secondary index record: identity and location of one block in the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Block id.
+    pub block_id: u64,
+    /// Slot the block was produced in.
+    pub slot: u64,
+    /// Block hash (Blake2b-256 of the block header).
+    pub hash: [u8; 32],
+    /// Chunk file holding the block.
+    pub chunk: u64,
+    /// Byte offset of the block inside its chunk file.
+    pub offset: u64,
+    /// Length in bytes of the stored block.
+    pub size: u32,
+}
+
+impl IndexEntry {
+    /// Encode the record in its fixed-size, big-endian on-disk form.
+    pub fn to_bytes(&self) -> [u8; INDEX_RECORD_SIZE] {
+        let mut buf = [0u8; INDEX_RECORD_SIZE];
+        buf[0..8].copy_from_slice(&self.block_id.to_be_bytes());
+        buf[8..16].copy_from_slice(&self.slot.to_be_bytes());
+        buf[16..48].copy_from_slice(&self.hash);
+        buf[48..56].copy_from_slice(&self.offset.to_be_bytes());
+        buf[56..60].copy_from_slice(&self.size.to_be_bytes());
+        buf
+    }
+
+    /// Decode a record of `chunk` from its on-disk form. `buf` must hold exactly one record.
+    pub fn from_bytes(chunk: u64, buf: &[u8]) -> Self {
+        let u64_at = |i: usize| u64::from_be_bytes(buf[i..i + 8].try_into().unwrap());
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&buf[16..48]);
+        Self {
+            block_id: u64_at(0),
+            slot: u64_at(8),
+            hash,
+            chunk,
+            offset: u64_at(48),
+            size: u32::from_be_bytes(buf[56..60].try_into().unwrap()),
+        }
+    }
+}
+
+/// Append-only block log split into chunks, with an in-memory primary index.
+#[derive(Debug)]
+pub struct ImmutableDB {
+    dir: PathBuf,
+    chunk_size: u64,
+    entries: Vec<IndexEntry>,
+    by_id: HashMap<u64, usize>,
+    by_slot: HashMap<u64, usize>,
+    by_hash: HashMap<[u8; 32], usize>,
+}
+
+impl ImmutableDB {
+    /// Open or create a block log in `dir`, holding at most `chunk_size` blocks per chunk.
+    pub async fn open<P: Into<PathBuf>>(dir: P, chunk_size: u64) -> Result<Self, ChainDBError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await?;
+        let mut db = Self {
+            dir,
+            chunk_size: chunk_size.max(1),
+            entries: Vec::new(),
+            by_id: HashMap::new(),
+            by_slot: HashMap::new(),
+            by_hash: HashMap::new(),
+        };
+        let mut chunk = 0;
+        loop {
+            let index = match fs::read(db.index_path(chunk)).await {
+                Ok(index) => index,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => break,
+                Err(e) => return Err(e.into()),
+            };
+            for record in index.chunks_exact(INDEX_RECORD_SIZE) {
+                db.insert(IndexEntry::from_bytes(chunk, record));
+            }
+            chunk += 1;
+        }
+        Ok(db)
+    }
+
+    /// Path of the data file for a chunk.
+    fn data_path(&self, chunk: u64) -> PathBuf {
+        self.dir.join(format!("chunk-{:05}.dat", chunk))
+    }
+
+    /// Path of the secondary index file for a chunk.
+    fn index_path(&self, chunk: u64) -> PathBuf {
+        self.dir.join(format!("chunk-{:05}.idx", chunk))
+    }
+
+    /// Chunk number holding the block at position `pos` in the log.
+    fn chunk_of(&self, pos: usize) -> u64 {
+        pos as u64 / self.chunk_size
+    }
+
+    /// Add a record to the in-memory index.
+    fn insert(&mut self, entry: IndexEntry) {
+        let pos = self.entries.len();
+        self.by_id.insert(entry.block_id, pos);
+        self.by_slot.insert(entry.slot, pos);
+        self.by_hash.insert(entry.hash, pos);
+        self.entries.push(entry);
+    }
+
+    /// Encode a block and compute the index record it will get when appended next.
+    pub fn prepare(&self, block: &Block) -> Result<(IndexEntry, Vec<u8>), ChainDBError> {
+        let (bytes, hash) = encode_block(block);
+        let chunk = self.chunk_of(self.entries.len());
+        let offset = match self.entries.last() {
+            Some(last) if last.chunk == chunk => last.offset + last.size as u64,
+            _ => 0,
+        };
+        let size = u32::try_from(bytes.len()).map_err(|e| ChainDBError::Encode {
+            what: "block",
+            reason: e.to_string(),
+        })?;
+        let entry = IndexEntry {
+            block_id: block.id,
+            slot: block.header.slot,
+            hash,
+            chunk,
+            offset,
+            size,
+        };
+        Ok((entry, bytes))
+    }
+
+    /// Durably append prepared block bytes to their chunk file.
+    pub async fn write_data(&self, entry: &IndexEntry, bytes: &[u8]) -> Result<(), ChainDBError> {
+        Ok(append_to(&self.data_path(entry.chunk), bytes).await?)
+    }
+
+    /// Durably append a prepared index record, making the block visible.
+    pub async fn write_index(&mut self, entry: IndexEntry) -> Result<(), ChainDBError> {
+        append_to(&self.index_path(entry.chunk), &entry.to_bytes()).await?;
+        self.insert(entry);
+        Ok(())
+    }
+
+    /// Append a block at the end of the log and return its index record.
+    pub async fn append(&mut self, block: &Block) -> Result<IndexEntry, ChainDBError> {
+        let (entry, bytes) = self.prepare(block)?;
+        self.write_data(&entry, &bytes).await?;
+        self.write_index(entry).await?;
+        Ok(entry)
+    }
+
+    /// Read the raw stored bytes of the block described by an index record.
+    async fn read_raw(&self, entry: &IndexEntry) -> std::io::Result<Vec<u8>> {
+        let mut file = fs::File::open(self.data_path(entry.chunk)).await?;
+        file.seek(SeekFrom::Start(entry.offset)).await?;
+        let mut buf = vec![0u8; entry.size as usize];
+        file.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+
+    /// Read the block described by an index record.
+    pub async fn read(&self, entry: &IndexEntry) -> Result<Block, ChainDBError> {
+        let path = self.data_path(entry.chunk);
+        let buf = self.read_raw(entry).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => ChainDBError::corrupt(&path, "truncated block"),
+            _ => e.into(),
+        })?;
+        decode_block(&path, &buf, &entry.hash, entry.block_id)
+    }
+
+    /// Length of a file, or `None` if it does not exist.
+    async fn file_len(path: &PathBuf) -> std::io::Result<Option<u64>> {
+        match fs::metadata(path).await {
+            Ok(meta) => Ok(Some(meta.len())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Repair the tail of the log after an unclean shutdown and report what was fixed.
+    pub async fn recover(&mut self) -> Result<Vec<Repair>, ChainDBError> {
+        let mut repairs = Vec::new();
+        // Chunks past the last whole index record hold nothing recoverable.
+        let last_chunk = self.entries.last().map_or(0, |e| e.chunk);
+        let mut chunk = last_chunk + 1;
+        loop {
+            let data = remove_if_exists(&self.data_path(chunk)).await?;
+            let index = remove_if_exists(&self.index_path(chunk)).await?;
+            if !data && !index {
+                break;
+            }
+            repairs.push(Repair::OrphanChunk { chunk });
+            chunk += 1;
+        }
+        // A crash while appending an index record leaves a partial record behind.
+        let index_path = self.index_path(last_chunk);
+        if let Some(len) = Self::file_len(&index_path).await? {
+            let whole = len - len % INDEX_RECORD_SIZE as u64;
+            if whole != len {
+                set_len(&index_path, whole).await?;
+                repairs.push(Repair::TornIndexRecord { chunk: last_chunk });
+            }
+        }
+        // Drop index records whose block bytes are missing or do not decode to the indexed block.
+        while let Some(last) = self.entries.last().copied() {
+            let data_len = Self::file_len(&self.data_path(last.chunk))
+                .await?
+                .unwrap_or(0);
+            let intact =
+                last.offset + last.size as u64 <= data_len && self.read(&last).await.is_ok();
+            if intact {
+                break;
+            }
+            self.truncate(self.entries.len() - 1).await?;
+            repairs.push(Repair::TornBlock {
+                chunk: last.chunk,
+                block_id: last.block_id,
+            });
+        }
+        // Bytes after the last indexed block belong to an append that never completed.
+        let last_chunk = self.entries.last().map_or(0, |e| e.chunk);
+        let end = self.entries.last().map_or(0, |e| e.offset + e.size as u64);
+        let data_path = self.data_path(last_chunk);
+        if let Some(len) = Self::file_len(&data_path).await?
+            && len > end
+        {
+            set_len(&data_path, end).await?;
+            repairs.push(Repair::TrailingBytes {
+                chunk: last_chunk,
+                bytes: len - end,
+            });
+        }
+        Ok(repairs)
+    }
+
+    /// Index record of the block with the given id.
+    pub fn get_by_id(&self, id: u64) -> Option<&IndexEntry> {
+        self.by_id.get(&id).map(|&pos| &self.entries[pos])
+    }
+
+    /// Position in the log of the block with the given id.
+    pub fn position_of(&self, id: u64) -> Option<usize> {
+        self.by_id.get(&id).copied()
+    }
+
+    /// Index record of the block produced in the given slot.
+    pub fn get_by_slot(&self, slot: u64) -> Option<&IndexEntry> {
+        self.by_slot.get(&slot).map(|&pos| &self.entries[pos])
+    }
+
+    /// Index record of the block with the given hash.
+    pub fn get_by_hash(&self, hash: &[u8; 32]) -> Option<&IndexEntry> {
+        self.by_hash.get(hash).map(|&pos| &self.entries[pos])
+    }
+
+    /// All index records, in append order.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Index record of the most recently appended block.
+    pub fn tip(&self) -> Option<&IndexEntry> {
+        self.entries.last()
+    }
+
+    /// Number of blocks in the log.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the log holds no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop every block from position `len` onwards, truncating chunk and index files.
+    pub async fn truncate(&mut self, len: usize) -> Result<(), ChainDBError> {
+        if len >= self.entries.len() {
+            return Ok(());
+        }
+        let last_chunk = self.chunk_of(self.entries.len() - 1);
+        let keep_chunk = self.chunk_of(len);
+        let first_in_chunk = (keep_chunk * self.chunk_size) as usize;
+        if len == first_in_chunk {
+            remove_if_exists(&self.data_path(keep_chunk)).await?;
+            remove_if_exists(&self.index_path(keep_chunk)).await?;
+        } else {
+            let cut = &self.entries[len];
+            set_len(&self.data_path(keep_chunk), cut.offset).await?;
+            set_len(
+                &self.index_path(keep_chunk),
+                ((len - first_in_chunk) * INDEX_RECORD_SIZE) as u64,
+            )
+            .await?;
+        }
+        for chunk in keep_chunk + 1..=last_chunk {
+            remove_if_exists(&self.data_path(chunk)).await?;
+            remove_if_exists(&self.index_path(chunk)).await?;
+        }
+        self.entries.truncate(len);
+        self.by_id.retain(|_, pos| *pos < len);
+        self.by_slot.retain(|_, pos| *pos < len);
+        self.by_hash.retain(|_, pos| *pos < len);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(id: u64) -> Block {
+        let mut block = Block::default();
+        block.id = id;
+        block.header.slot = id * 10;
+        block
+    }
+
+    #[test]
+    fn append_lookup_and_reopen_across_chunks() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = "./testdata/immutable_append";
+            let _ = std::fs::remove_dir_all(dir);
+            let mut db = ImmutableDB::open(dir, 2).await.unwrap();
+            let mut hashes = Vec::new();
+            for id in 1..=5 {
+                hashes.push(db.append(&block(id)).await.unwrap().hash);
+            }
+            assert!(std::path::Path::new(dir).join("chunk-00002.dat").exists());
+            let db = ImmutableDB::open(dir, 2).await.unwrap();
+            assert_eq!(db.len(), 5);
+            let entry = *db.get_by_slot(30).unwrap();
+            assert_eq!(entry.block_id, 3);
+            assert_eq!(db.get_by_hash(&hashes[2]), Some(&entry));
+            assert_eq!(db.read(&entry).await.unwrap().id, 3);
+            assert_eq!(
+                db.read(db.get_by_id(5).unwrap()).await.unwrap().header.slot,
+                50
+            );
+        });
+    }
+
+    #[test]
+    fn truncate_drops_tail_and_allows_reappend() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = "./testdata/immutable_truncate";
+            let _ = std::fs::remove_dir_all(dir);
+            let mut db = ImmutableDB::open(dir, 2).await.unwrap();
+            for id in 1..=5 {
+                db.append(&block(id)).await.unwrap();
+            }
+            db.truncate(3).await.unwrap();
+            assert!(db.get_by_id(4).is_none());
+            assert!(!std::path::Path::new(dir).join("chunk-00002.dat").exists());
+            db.append(&block(6)).await.unwrap();
+            let db = ImmutableDB::open(dir, 2).await.unwrap();
+            let ids: Vec<u64> = db.entries().iter().map(|e| e.block_id).collect();
+            assert_eq!(ids, vec![1, 2, 3, 6]);
+            assert_eq!(db.read(db.get_by_id(6).unwrap()).await.unwrap().id, 6);
+        });
+    }
+}
@@ -0,0 +1,124 @@
+//! Write-ahead journal for ChainDB appends.
+//!
+//! Appending a block touches several files (volatile block, chain fragment, ledger diff, snapshot).
+//! Before any of them is modified, a [`JournalRecord`] describing the pending append is written and
+//! synced. The journal is cleared only once every file has been durably written. On startup, a
+//! record still present in the journal means the node crashed mid-append, and the append is rolled
+//! back.
+
+use super::fsutil;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Magic prefix identifying a journal record.
+const MAGIC: &[u8; 8] = b"CDBJRNL1";
+
+/// Size in bytes of an encoded journal record.
+const RECORD_SIZE: usize = 8 + 8 + 8 + 32 + 32;
+
+/// Steps of a ChainDB append, in the order they are made durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStep {
+    /// The journal record for the append has been synced.
+    Journal,
+    /// The block file has been written to the volatile DB.
+    Block,
+    /// The block has been added to the volatile chain fragment.
+    Index,
+    /// The ledger diff has been written to its temporary file.
+    StateTemp,
+    /// The ledger diff, and the snapshot if one was due, have been published.
+    State,
+}
+
+/// A pending append, as recorded in the journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalRecord {
+    /// Id of the block being appended.
+    pub block_id: u64,
+    /// Number of blocks in the chain before the append.
+    pub position: u64,
+    /// Hash of the block being appended.
+    pub hash: [u8; 32],
+}
+
+impl JournalRecord {
+    /// Encode the record together with its checksum.
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let mut buf = [0u8; RECORD_SIZE];
+        buf[0..8].copy_from_slice(MAGIC);
+        buf[8..16].copy_from_slice(&self.block_id.to_be_bytes());
+        buf[16..24].copy_from_slice(&self.position.to_be_bytes());
+        buf[24..56].copy_from_slice(&self.hash);
+        let checksum = Blake2b::<U32>::digest(&buf[0..56]);
+        buf[56..].copy_from_slice(&checksum);
+        buf
+    }
+
+    /// Decode a record, returning `None` if it is torn or corrupt.
+    fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() != RECORD_SIZE || &buf[0..8] != MAGIC {
+            return None;
+        }
+        if Blake2b::<U32>::digest(&buf[0..56]).as_slice() != &buf[56..] {
+            return None;
+        }
+        Some(Self {
+            block_id: u64::from_be_bytes(buf[8..16].try_into().ok()?),
+            position: u64::from_be_bytes(buf[16..24].try_into().ok()?),
+            hash: buf[24..56].try_into().ok()?,
+        })
+    }
+}
+
+/// What the journal held when the ChainDB was opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalState {
+    /// No append was in progress.
+    Clean,
+    /// An append was in progress and must be rolled back.
+    Pending(JournalRecord),
+    /// The journal record itself was torn; no store had been touched yet.
+    Torn,
+}
+
+/// Single-record write-ahead journal.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Open the journal file at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Inspect the journal left behind by the previous run.
+    pub async fn read(&self) -> std::io::Result<JournalState> {
+        let buf = match fs::read(&self.path).await {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(JournalState::Clean),
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            return Ok(JournalState::Clean);
+        }
+        Ok(match JournalRecord::from_bytes(&buf) {
+            Some(record) => JournalState::Pending(record),
+            None => JournalState::Torn,
+        })
+    }
+
+    /// Durably record an append that is about to start.
+    pub async fn begin(&self, record: JournalRecord) -> std::io::Result<()> {
+        fsutil::write_atomic(&self.path, &record.to_bytes()).await
+    }
+
+    /// Mark the pending append as complete.
+    pub async fn commit(&self) -> std::io::Result<()> {
+        fsutil::write_atomic(&self.path, &[]).await
+    }
+}
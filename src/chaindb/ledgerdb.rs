@@ -0,0 +1,469 @@
+//! Ledger state storage for the ChainDB: periodic full snapshots plus per-block diffs.
+//!
+//! Instead of a full [`LedgerState`] copy per block, the LedgerDB stores a [`LedgerDiff`] for every
+//! block (`diff-{id}.bin`) and a full snapshot (`snapshot-{id}.bin`) every `snapshot_interval`
+//! blocks. The state at a retained block is rebuilt by loading the nearest snapshot at or before it
+//! and replaying the diffs that follow. Retention is driven by [`LedgerDBConfig`]: with pruning
+//! enabled, only the newest `max_snapshots` snapshots are kept, together with the diffs needed to
+//! replay forward from the oldest of them.
+
+use super::{ChainDBError, fsutil};
+use crate::configuration::LedgerDBConfig;
+use crate::consensus::nonce::NonceState;
+use crate::ledger::{
+    Credential, DRep, Governance, LedgerState, ParamUpdate, ProtocolParameters, RewardUpdate,
+    Snapshots, StakePool, TxId, TxOutput,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Changes to one map of the ledger state between two consecutive blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapDiff<K, V> {
+    /// Entries that were added or whose value changed.
+    pub inserted: Vec<(K, V)>,
+    /// Keys that were removed.
+    pub removed: Vec<K>,
+}
+
+impl<K, V> Default for MapDiff<K, V> {
+    fn default() -> Self {
+        Self {
+            inserted: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone + PartialEq> MapDiff<K, V> {
+    /// Compute the changes that turn `old` into `new`.
+    pub fn between(old: &HashMap<K, V>, new: &HashMap<K, V>) -> Self {
+        let inserted = new
+            .iter()
+            .filter(|(k, v)| old.get(*k) != Some(*v))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let removed = old
+            .keys()
+            .filter(|k| !new.contains_key(*k))
+            .cloned()
+            .collect();
+        Self { inserted, removed }
+    }
+
+    /// Apply the changes to `map`.
+    pub fn apply(&self, map: &mut HashMap<K, V>) {
+        for k in &self.removed {
+            map.remove(k);
+        }
+        for (k, v) in &self.inserted {
+            map.insert(k.clone(), v.clone());
+        }
+    }
+
+    /// Whether the diff changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Changes made to the ledger state by one block: UTXOs, stake key, stake and pool bookkeeping,
+/// deposit, fee, treasury and reserve pots, protocol parameters, pending update proposals and
+/// their proposers, governance, stake snapshots, block counts, operational certificate counters,
+/// pending rewards and nonces.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LedgerDiff {
+    pub utxos: MapDiff<(TxId, u32), TxOutput>,
+    pub delegations: MapDiff<Credential, String>,
+    pub stake_pools: MapDiff<String, StakePool>,
+    pub pool_retirements: MapDiff<String, u64>,
+    pub rewards: MapDiff<Credential, u64>,
+    pub stake_keys: MapDiff<Credential, u64>,
+    /// New deposit pot, if it changed.
+    pub deposited: Option<u64>,
+    /// New fee pot, if it changed.
+    pub fees: Option<u64>,
+    /// New protocol parameters, if they changed.
+    pub params: Option<ProtocolParameters>,
+    /// New pending update proposals, if they changed.
+    pub proposals: Option<BTreeMap<String, ParamUpdate>>,
+    /// New genesis delegates, if they changed.
+    pub genesis_delegates: Option<BTreeSet<String>>,
+    /// New treasury, if it changed.
+    pub treasury: Option<u64>,
+    pub dreps: MapDiff<Credential, u64>,
+    pub vote_delegations: MapDiff<Credential, DRep>,
+    /// New governance state, if it changed.
+    pub governance: Option<Governance>,
+    /// New stake snapshots, if they were rotated.
+    pub snapshots: Option<Snapshots>,
+    /// New reserves, if they changed.
+    pub reserves: Option<u64>,
+    pub blocks_made: MapDiff<String, u64>,
+    pub opcert_counters: MapDiff<String, u64>,
+    /// New pending reward update, if it changed.
+    pub reward_update: Option<RewardUpdate>,
+    /// New nonces, if they changed.
+    pub nonces: Option<NonceState>,
+}
+
+/// `new` if it differs from `old`.
+fn changed<T: Clone + PartialEq>(old: &T, new: &T) -> Option<T> {
+    (old != new).then(|| new.clone())
+}
+
+impl LedgerDiff {
+    /// Compute the diff that turns `old` into `new`.
+    pub fn between(old: &LedgerState, new: &LedgerState) -> Self {
+        Self {
+            utxos: MapDiff::between(&old.utxos, &new.utxos),
+            delegations: MapDiff::between(&old.delegations, &new.delegations),
+            stake_pools: MapDiff::between(&old.stake_pools, &new.stake_pools),
+            pool_retirements: MapDiff::between(&old.pool_retirements, &new.pool_retirements),
+            rewards: MapDiff::between(&old.rewards, &new.rewards),
+            stake_keys: MapDiff::between(&old.stake_keys, &new.stake_keys),
+            deposited: changed(&old.deposited, &new.deposited),
+            fees: changed(&old.fees, &new.fees),
+            params: changed(&old.params, &new.params),
+            proposals: changed(&old.proposals, &new.proposals),
+            genesis_delegates: changed(&old.genesis_delegates, &new.genesis_delegates),
+            treasury: changed(&old.treasury, &new.treasury),
+            dreps: MapDiff::between(&old.dreps, &new.dreps),
+            vote_delegations: MapDiff::between(&old.vote_delegations, &new.vote_delegations),
+            governance: changed(&old.governance, &new.governance),
+            snapshots: changed(&old.snapshots, &new.snapshots),
+            reserves: changed(&old.reserves, &new.reserves),
+            blocks_made: MapDiff::between(&old.blocks_made, &new.blocks_made),
+            opcert_counters: MapDiff::between(&old.opcert_counters, &new.opcert_counters),
+            reward_update: changed(&old.reward_update, &new.reward_update),
+            nonces: changed(&old.nonces, &new.nonces),
+        }
+    }
+
+    /// Apply the diff to `state`.
+    pub fn apply(&self, state: &mut LedgerState) {
+        self.utxos.apply(&mut state.utxos);
+        self.delegations.apply(&mut state.delegations);
+        self.stake_pools.apply(&mut state.stake_pools);
+        self.pool_retirements.apply(&mut state.pool_retirements);
+        self.rewards.apply(&mut state.rewards);
+        self.stake_keys.apply(&mut state.stake_keys);
+        if let Some(deposited) = self.deposited {
+            state.deposited = deposited;
+        }
+        if let Some(fees) = self.fees {
+            state.fees = fees;
+        }
+        if let Some(params) = &self.params {
+            state.params = params.clone();
+        }
+        if let Some(proposals) = &self.proposals {
+            state.proposals = proposals.clone();
+        }
+        if let Some(delegates) = &self.genesis_delegates {
+            state.genesis_delegates = delegates.clone();
+        }
+        if let Some(treasury) = self.treasury {
+            state.treasury = treasury;
+        }
+        self.dreps.apply(&mut state.dreps);
+        self.vote_delegations.apply(&mut state.vote_delegations);
+        if let Some(governance) = &self.governance {
+            state.governance = governance.clone();
+        }
+        if let Some(snapshots) = &self.snapshots {
+            state.snapshots = snapshots.clone();
+        }
+        if let Some(reserves) = self.reserves {
+            state.reserves = reserves;
+        }
+        self.blocks_made.apply(&mut state.blocks_made);
+        self.opcert_counters.apply(&mut state.opcert_counters);
+        if let Some(update) = &self.reward_update {
+            state.reward_update = update.clone();
+        }
+        if let Some(nonces) = &self.nonces {
+            state.nonces = nonces.clone();
+        }
+    }
+}
+
+/// Encode a value in the LedgerDB's binary format.
+fn encode<T: Serialize>(what: &'static str, value: &T) -> Result<Vec<u8>, ChainDBError> {
+    bincode::serde::encode_to_vec(value, bincode::config::standard()).map_err(|e| {
+        ChainDBError::Encode {
+            what,
+            reason: e.to_string(),
+        }
+    })
+}
+
+/// Read and decode a value written by [`encode`].
+async fn decode<T: DeserializeOwned>(path: &Path) -> Result<T, ChainDBError> {
+    let buf = fs::read(path).await?;
+    let (value, read) = bincode::serde::decode_from_slice(&buf, bincode::config::standard())
+        .map_err(|e| ChainDBError::corrupt(path, e))?;
+    if read != buf.len() {
+        return Err(ChainDBError::corrupt(
+            path,
+            "trailing bytes after encoded value",
+        ));
+    }
+    Ok(value)
+}
+
+/// Snapshot and diff store for ledger states.
+#[derive(Debug)]
+pub struct LedgerDB {
+    dir: PathBuf,
+    config: LedgerDBConfig,
+    diffs: HashSet<u64>,
+    snapshots: HashSet<u64>,
+    tip: LedgerState,
+}
+
+impl LedgerDB {
+    /// Open or create the store in `dir`, indexing the diffs and snapshots already on disk.
+    ///
+    /// The tip state starts empty; the ChainDB rebuilds it with [`LedgerDB::reset_tip`] once the
+    /// block log has been recovered.
+    pub async fn open<P: Into<PathBuf>>(
+        dir: P,
+        config: LedgerDBConfig,
+    ) -> Result<Self, ChainDBError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await?;
+        let mut diffs = HashSet::new();
+        let mut snapshots = HashSet::new();
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let Some(stem) = name.strip_suffix(".bin") else {
+                continue;
+            };
+            if let Some(id) = stem.strip_prefix("diff-").and_then(|s| s.parse().ok()) {
+                diffs.insert(id);
+            } else if let Some(id) = stem.strip_prefix("snapshot-").and_then(|s| s.parse().ok()) {
+                snapshots.insert(id);
+            }
+        }
+        Ok(Self {
+            dir,
+            config,
+            diffs,
+            snapshots,
+            tip: LedgerState::default(),
+        })
+    }
+
+    /// Directory holding the diffs and snapshots.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Retention settings of this store.
+    pub fn config(&self) -> &LedgerDBConfig {
+        &self.config
+    }
+
+    /// Path of the diff stored for a block id.
+    pub fn diff_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("diff-{}.bin", id))
+    }
+
+    /// Path of the snapshot stored for a block id.
+    pub fn snapshot_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("snapshot-{}.bin", id))
+    }
+
+    /// Whether a snapshot is stored for a block id.
+    pub fn has_snapshot(&self, id: u64) -> bool {
+        self.snapshots.contains(&id)
+    }
+
+    /// Ledger state at the tip of the chain.
+    pub fn tip(&self) -> &LedgerState {
+        &self.tip
+    }
+
+    /// Whether the block appended at `position` in the chain gets a full snapshot.
+    pub fn snapshot_due(&self, position: usize) -> bool {
+        let interval = self.config.snapshot_interval;
+        interval > 0 && (position as u64 + 1).is_multiple_of(interval)
+    }
+
+    /// Encode the diff from the tip state to `state`.
+    pub fn encode_diff(&self, state: &LedgerState) -> Result<Vec<u8>, ChainDBError> {
+        encode("ledger diff", &LedgerDiff::between(&self.tip, state))
+    }
+
+    /// Encode a full snapshot of `state`.
+    pub fn encode_snapshot(state: &LedgerState) -> Result<Vec<u8>, ChainDBError> {
+        encode("ledger snapshot", state)
+    }
+
+    /// Record that the diff (and snapshot, if any) for block `id` is durable and `state` is the new
+    /// tip state.
+    pub fn committed(&mut self, id: u64, snapshot: bool, state: LedgerState) {
+        self.diffs.insert(id);
+        if snapshot {
+            self.snapshots.insert(id);
+        }
+        self.tip = state;
+    }
+
+    /// Rebuild the state after block `chain[position]`, replaying diffs from the nearest snapshot.
+    ///
+    /// `chain` lists the block ids of the chain in order. Fails with
+    /// [`ChainDBError::NotFound`] if the state was pruned.
+    pub async fn state_at(
+        &self,
+        chain: &[u64],
+        position: usize,
+    ) -> Result<LedgerState, ChainDBError> {
+        let target = chain[position];
+        let base = chain[..=position]
+            .iter()
+            .rposition(|id| self.snapshots.contains(id));
+        let (mut state, start) = match base {
+            Some(p) => (decode(&self.snapshot_path(chain[p])).await?, p + 1),
+            None => (LedgerState::default(), 0),
+        };
+        for &id in &chain[start..=position] {
+            if !self.diffs.contains(&id) {
+                return Err(ChainDBError::not_found("ledger state", target));
+            }
+            let diff: LedgerDiff = decode(&self.diff_path(id)).await?;
+            diff.apply(&mut state);
+        }
+        Ok(state)
+    }
+
+    /// Replace the tip state, after the chain was rolled back to it.
+    pub fn set_tip(&mut self, state: LedgerState) {
+        self.tip = state;
+    }
+
+    /// Rebuild the tip state for `chain`.
+    pub async fn reset_tip(&mut self, chain: &[u64]) -> Result<(), ChainDBError> {
+        self.tip = match chain.len() {
+            0 => LedgerState::default(),
+            len => self.state_at(chain, len - 1).await?,
+        };
+        Ok(())
+    }
+
+    /// Remove the diff and snapshot of a block, including unpublished temporary files.
+    pub async fn remove(&mut self, id: u64) -> Result<(), ChainDBError> {
+        for path in [self.diff_path(id), self.snapshot_path(id)] {
+            fsutil::remove_if_exists(&fsutil::temp_path(&path)).await?;
+            fsutil::remove_if_exists(&path).await?;
+        }
+        self.diffs.remove(&id);
+        self.snapshots.remove(&id);
+        Ok(())
+    }
+
+    /// Remove the diffs and snapshots of blocks that are no longer part of `chain`.
+    pub async fn retain(&mut self, chain: &[u64]) -> Result<(), ChainDBError> {
+        let keep: HashSet<u64> = chain.iter().copied().collect();
+        let stale: Vec<u64> = self
+            .diffs
+            .union(&self.snapshots)
+            .filter(|id| !keep.contains(id))
+            .copied()
+            .collect();
+        for id in stale {
+            self.remove(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Apply the retention policy: keep the newest `max_snapshots` snapshots and the diffs that
+    /// follow the oldest of them. Does nothing unless pruning is enabled.
+    ///
+    /// States from chain position `floor` onwards stay reachable whatever the policy says, so that
+    /// every rollback within the security parameter can be served.
+    pub async fn prune(&mut self, chain: &[u64], floor: usize) -> Result<(), ChainDBError> {
+        if !self.config.pruning {
+            return Ok(());
+        }
+        let positions: Vec<usize> = (0..chain.len())
+            .filter(|&p| self.snapshots.contains(&chain[p]))
+            .collect();
+        // The newest snapshot at or below `floor` anchors the replay of every state above it.
+        let Some(anchor) = positions.iter().rposition(|&p| p <= floor) else {
+            return Ok(());
+        };
+        let keep = self.config.max_snapshots.max(1);
+        let first_kept = anchor.min(positions.len().saturating_sub(keep));
+        for &p in &positions[..first_kept] {
+            let path = self.snapshot_path(chain[p]);
+            fsutil::remove_if_exists(&path).await?;
+            self.snapshots.remove(&chain[p]);
+        }
+        for &id in &chain[..=positions[first_kept]] {
+            if self.diffs.remove(&id) {
+                fsutil::remove_if_exists(&self.diff_path(id)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{Address, Network};
+
+    fn cred(name: &str) -> Credential {
+        Credential::KeyHash([name.as_bytes()[0]; 28])
+    }
+
+    fn output(name: &str, amount: u64) -> TxOutput {
+        TxOutput {
+            address: Address::base(Network::Testnet, cred(name), cred(name)),
+            amount,
+            assets: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn diff_between_states_replays_exactly() {
+        let mut old = LedgerState::default();
+        old.utxos.insert(([1u8; 32], 0), output("A", 10));
+        old.utxos.insert(([1u8; 32], 1), output("B", 20));
+        old.rewards.insert(cred("A"), 1);
+
+        let mut new = old.clone();
+        new.utxos.remove(&([1u8; 32], 0));
+        new.utxos.insert(([2u8; 32], 0), output("C", 10));
+        new.snapshots.mark = new.stake_snapshot();
+        new.rewards.clear();
+        new.deposited = 500;
+        new.nonces.evolving_nonce = [3u8; 32];
+
+        let diff = LedgerDiff::between(&old, &new);
+        assert_eq!(diff.utxos.inserted, vec![(([2u8; 32], 0), output("C", 10))]);
+        assert_eq!(diff.utxos.removed, vec![([1u8; 32], 0)]);
+        assert!(diff.delegations.is_empty());
+        assert_eq!(diff.params, None);
+
+        let bytes = encode("ledger diff", &diff).unwrap();
+        let (decoded, _): (LedgerDiff, usize) =
+            bincode::serde::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
+        let mut replayed = old.clone();
+        decoded.apply(&mut replayed);
+        assert_eq!(replayed.utxos, new.utxos);
+        assert_eq!(replayed.snapshots, new.snapshots);
+        assert_eq!(replayed.rewards, new.rewards);
+        assert_eq!(replayed.deposited, 500);
+        assert_eq!(replayed.nonces, new.nonces);
+    }
+}
@@ -0,0 +1,274 @@
+//! Volatile block store for the most recent blocks of the ChainDB.
+//!
+//! Blocks within `k` (the security parameter) of the tip can still be rolled back, so they live
+//! here instead of in the append-only [`ImmutableDB`](super::immutable::ImmutableDB). Every block
+//! is stored in its own file (`{slot}-{hash}.block`), so blocks of abandoned forks can sit next to
+//! the current chain until they fall behind the immutable tip and are garbage-collected. The
+//! current chain fragment is recorded in `chain.idx`, a log of fixed-size records that is
+//! truncated on rollback and rewritten when its oldest blocks are promoted to the ImmutableDB.
+
+use super::fsutil::{append_to, remove_if_exists, set_len, write_atomic};
+use super::{ChainDBError, Repair, decode_block, hex, parse_hex32};
+use crate::ledger::Block;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Size in bytes of one chain fragment record.
+pub const CHAIN_RECORD_SIZE: usize = 8 + 8 + 32;
+
+/// Identity of one block on the volatile chain fragment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainEntry {
+    /// Block id.
+    pub block_id: u64,
+    /// Slot the block was produced in.
+    pub slot: u64,
+    /// Block hash (Blake2b-256 of the block header).
+    pub hash: [u8; 32],
+}
+
+impl ChainEntry {
+    /// Encode the record in its fixed-size, big-endian on-disk form.
+    pub fn to_bytes(&self) -> [u8; CHAIN_RECORD_SIZE] {
+        let mut buf = [0u8; CHAIN_RECORD_SIZE];
+        buf[0..8].copy_from_slice(&self.block_id.to_be_bytes());
+        buf[8..16].copy_from_slice(&self.slot.to_be_bytes());
+        buf[16..48].copy_from_slice(&self.hash);
+        buf
+    }
+
+    /// Decode a record from its on-disk form. `buf` must hold exactly one record.
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&buf[16..48]);
+        Self {
+            block_id: u64::from_be_bytes(buf[0..8].try_into().unwrap()),
+            slot: u64::from_be_bytes(buf[8..16].try_into().unwrap()),
+            hash,
+        }
+    }
+}
+
+/// Parse a block file name of the form `{slot}-{hash}.block`.
+fn parse_block_file(name: &str) -> Option<(u64, [u8; 32])> {
+    let (slot, hash) = name.strip_suffix(".block")?.split_once('-')?;
+    Some((slot.parse().ok()?, parse_hex32(hash)?))
+}
+
+/// Store of recent blocks, possibly on several forks, with the current chain fragment.
+#[derive(Debug)]
+pub struct VolatileDB {
+    dir: PathBuf,
+    chain: Vec<ChainEntry>,
+    by_id: HashMap<u64, usize>,
+    by_slot: HashMap<u64, usize>,
+    by_hash: HashMap<[u8; 32], usize>,
+    /// Slot of every stored block, on the chain fragment or not.
+    stored: HashMap<[u8; 32], u64>,
+}
+
+impl VolatileDB {
+    /// Open or create a volatile store in `dir`.
+    pub async fn open<P: Into<PathBuf>>(dir: P) -> Result<Self, ChainDBError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await?;
+        let mut stored = HashMap::new();
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some((slot, hash)) = parse_block_file(&entry.file_name().to_string_lossy()) {
+                stored.insert(hash, slot);
+            }
+        }
+        let mut db = Self {
+            dir,
+            chain: Vec::new(),
+            by_id: HashMap::new(),
+            by_slot: HashMap::new(),
+            by_hash: HashMap::new(),
+            stored,
+        };
+        match fs::read(db.chain_path()).await {
+            Ok(records) => {
+                db.chain = records
+                    .chunks_exact(CHAIN_RECORD_SIZE)
+                    .map(ChainEntry::from_bytes)
+                    .collect();
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        db.reindex();
+        Ok(db)
+    }
+
+    /// Path of the chain fragment log.
+    fn chain_path(&self) -> PathBuf {
+        self.dir.join("chain.idx")
+    }
+
+    /// Path of the file holding a block.
+    fn block_path(&self, slot: u64, hash: &[u8; 32]) -> PathBuf {
+        self.dir.join(format!("{}-{}.block", slot, hex(hash)))
+    }
+
+    /// Directory holding the store.
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+
+    /// Rebuild the in-memory lookups of the chain fragment.
+    fn reindex(&mut self) {
+        self.by_id.clear();
+        self.by_slot.clear();
+        self.by_hash.clear();
+        for (pos, entry) in self.chain.iter().enumerate() {
+            self.by_id.insert(entry.block_id, pos);
+            self.by_slot.insert(entry.slot, pos);
+            self.by_hash.insert(entry.hash, pos);
+        }
+    }
+
+    /// Durably store the encoded bytes of a block, without adding it to the chain fragment.
+    pub async fn write_block(
+        &mut self,
+        entry: &ChainEntry,
+        bytes: &[u8],
+    ) -> Result<(), ChainDBError> {
+        write_atomic(&self.block_path(entry.slot, &entry.hash), bytes).await?;
+        self.stored.insert(entry.hash, entry.slot);
+        Ok(())
+    }
+
+    /// Durably extend the chain fragment with a stored block.
+    pub async fn push(&mut self, entry: ChainEntry) -> Result<(), ChainDBError> {
+        append_to(&self.chain_path(), &entry.to_bytes()).await?;
+        let pos = self.chain.len();
+        self.by_id.insert(entry.block_id, pos);
+        self.by_slot.insert(entry.slot, pos);
+        self.by_hash.insert(entry.hash, pos);
+        self.chain.push(entry);
+        Ok(())
+    }
+
+    /// Read a stored block by hash, whether or not it is on the chain fragment.
+    pub async fn read(&self, hash: &[u8; 32]) -> Result<Block, ChainDBError> {
+        let slot = *self
+            .stored
+            .get(hash)
+            .ok_or_else(|| ChainDBError::not_found("block hash", hex(hash)))?;
+        let path = self.block_path(slot, hash);
+        let buf = fs::read(&path).await?;
+        let block_id = self.get_by_hash(hash).map_or(0, |e| e.block_id);
+        decode_block(&path, &buf, hash, block_id)
+    }
+
+    /// Whether a block with the given hash is stored, on any fork.
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.stored.contains_key(hash)
+    }
+
+    /// Chain fragment entry of the block with the given id.
+    pub fn get_by_id(&self, id: u64) -> Option<&ChainEntry> {
+        self.by_id.get(&id).map(|&pos| &self.chain[pos])
+    }
+
+    /// Chain fragment entry of the block produced in the given slot.
+    pub fn get_by_slot(&self, slot: u64) -> Option<&ChainEntry> {
+        self.by_slot.get(&slot).map(|&pos| &self.chain[pos])
+    }
+
+    /// Chain fragment entry of the block with the given hash.
+    pub fn get_by_hash(&self, hash: &[u8; 32]) -> Option<&ChainEntry> {
+        self.by_hash.get(hash).map(|&pos| &self.chain[pos])
+    }
+
+    /// Entries of the chain fragment, oldest first.
+    pub fn entries(&self) -> &[ChainEntry] {
+        &self.chain
+    }
+
+    /// Number of blocks on the chain fragment.
+    pub fn len(&self) -> usize {
+        self.chain.len()
+    }
+
+    /// Whether the chain fragment is empty.
+    pub fn is_empty(&self) -> bool {
+        self.chain.is_empty()
+    }
+
+    /// Drop the chain fragment from position `len` onwards. The blocks stay stored as a fork.
+    pub async fn truncate(&mut self, len: usize) -> Result<(), ChainDBError> {
+        if len >= self.chain.len() {
+            return Ok(());
+        }
+        set_len(&self.chain_path(), (len * CHAIN_RECORD_SIZE) as u64).await?;
+        self.chain.truncate(len);
+        self.reindex();
+        Ok(())
+    }
+
+    /// Delete a stored block that is not on the chain fragment.
+    pub async fn remove_block(&mut self, hash: &[u8; 32]) -> Result<(), ChainDBError> {
+        if self.by_hash.contains_key(hash) {
+            return Ok(());
+        }
+        if let Some(slot) = self.stored.remove(hash) {
+            remove_if_exists(&self.block_path(slot, hash)).await?;
+        }
+        Ok(())
+    }
+
+    /// Drop the `count` oldest blocks of the chain fragment after they were copied to the
+    /// ImmutableDB, together with every stored block at or before the newest promoted slot.
+    pub async fn drop_oldest(&mut self, count: usize) -> Result<(), ChainDBError> {
+        let count = count.min(self.chain.len());
+        if count == 0 {
+            return Ok(());
+        }
+        let floor = self.chain[count - 1].slot;
+        let rest: Vec<u8> = self.chain[count..]
+            .iter()
+            .flat_map(|e| e.to_bytes())
+            .collect();
+        write_atomic(&self.chain_path(), &rest).await?;
+        self.chain.drain(..count);
+        self.reindex();
+        let stale: Vec<([u8; 32], u64)> = self
+            .stored
+            .iter()
+            .filter(|&(hash, &slot)| slot <= floor && !self.by_hash.contains_key(hash))
+            .map(|(hash, slot)| (*hash, *slot))
+            .collect();
+        for (hash, slot) in stale {
+            remove_if_exists(&self.block_path(slot, &hash)).await?;
+            self.stored.remove(&hash);
+        }
+        Ok(())
+    }
+
+    /// Repair the chain fragment after an unclean shutdown and report what was fixed.
+    pub async fn recover(&mut self) -> Result<Vec<Repair>, ChainDBError> {
+        let mut repairs = Vec::new();
+        let path = self.chain_path();
+        if let Ok(meta) = fs::metadata(&path).await {
+            let len = meta.len();
+            let whole = len - len % CHAIN_RECORD_SIZE as u64;
+            if whole != len {
+                set_len(&path, whole).await?;
+                repairs.push(Repair::TornVolatileRecord);
+            }
+        }
+        while let Some(last) = self.chain.last().copied() {
+            if self.stored.contains_key(&last.hash) {
+                break;
+            }
+            self.truncate(self.chain.len() - 1).await?;
+            repairs.push(Repair::MissingVolatileBlock {
+                block_id: last.block_id,
+            });
+        }
+        Ok(repairs)
+    }
+}
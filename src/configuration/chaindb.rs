@@ -0,0 +1,29 @@
+//! ChainDB configuration for Super Cardano Node
+//!
+//! Handles configuration for the chain database: finality and ledger state storage.
+
+use super::ledgerdb::LedgerDBConfig;
+use serde::{Serialize, Deserialize};
+
+/// Default security parameter `k` (mainnet value).
+pub const DEFAULT_SECURITY_PARAM: u64 = 2160;
+
+/// Configuration for the chain database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainDBConfig {
+    /// Security parameter `k`: the deepest allowed rollback, and the number of recent blocks kept
+    /// in the volatile DB before they are promoted to the immutable DB.
+    pub security_param: u64,
+    /// Ledger state snapshot and retention settings.
+    pub ledger: LedgerDBConfig,
+}
+
+impl Default for ChainDBConfig {
+    fn default() -> Self {
+        Self {
+            security_param: DEFAULT_SECURITY_PARAM,
+            ledger: LedgerDBConfig::default(),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use crate::configuration::types::Configuration;
-use crate::configuration::{ConsensusConfig, DatabaseConfig, LoggingConfig, NetworkConfig};
+use crate::configuration::{
+    ChainDBConfig, ConsensusConfig, DatabaseConfig, LoggingConfig, NetworkConfig,
+};
 
 /// Provides default configuration values.
 pub fn default_configuration() -> Configuration {
@@ -15,6 +17,7 @@ pub fn default_configuration() -> Configuration {
             path: "./data".to_string(),
             cache_size: 1024,
         },
+        chaindb: ChainDBConfig::default(),
         consensus: ConsensusConfig {
             protocol: "Ouroboros".to_string(),
             slot_duration: 1000,
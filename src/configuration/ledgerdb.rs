@@ -5,12 +5,26 @@
 use serde::{Serialize, Deserialize};
 
 /// Configuration for the ledger database and state snapshots.
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LedgerDBConfig {
-    /// Path to the ledger database directory.
+    /// Path to the ledger database directory (empty: `ledger/` inside the ChainDB directory).
     pub db_path: String,
     /// Maximum number of state snapshots to retain.
     pub max_snapshots: usize,
     /// Enable or disable automatic pruning.
     pub pruning: bool,
+    /// Number of blocks between full state snapshots (0 disables snapshots).
+    pub snapshot_interval: u64,
+}
+
+impl Default for LedgerDBConfig {
+    fn default() -> Self {
+        Self {
+            db_path: String::new(),
+            max_snapshots: 2,
+            pruning: false,
+            snapshot_interval: 100,
+        }
+    }
 }
@@ -2,7 +2,9 @@ pub mod types;
 pub mod sources;
 pub mod validation;
 pub mod defaults;
+pub mod chaindb;
 pub mod error;
+pub mod ledgerdb;
 
 // Re-export commonly used items
 pub use types::*;
@@ -10,6 +12,8 @@ pub use sources::*;
 pub use validation::*;
 pub use defaults::*;
 pub use error::*;
+pub use chaindb::ChainDBConfig;
+pub use ledgerdb::LedgerDBConfig;
 
 // Main entry point for loading configuration
 use anyhow::Result;
@@ -1,5 +1,7 @@
 use crate::configuration::types::Configuration;
-use crate::configuration::{ConsensusConfig, DatabaseConfig, LoggingConfig, NetworkConfig};
+use crate::configuration::{
+    ChainDBConfig, ConsensusConfig, DatabaseConfig, LoggingConfig, NetworkConfig,
+};
 use anyhow::Result;
 use clap::{Arg, Command};
 
@@ -44,6 +46,7 @@ pub fn load_cli_args() -> Result<Configuration> {
             path: "./data".to_string(),
             cache_size: 1024,
         },
+        chaindb: ChainDBConfig::default(),
         consensus: ConsensusConfig {
             protocol: "Ouroboros".to_string(),
             slot_duration: 1000,
@@ -1,4 +1,4 @@
-use crate::configuration::{NetworkConfig, DatabaseConfig, ConsensusConfig, LoggingConfig};
+use crate::configuration::{NetworkConfig, DatabaseConfig, ChainDBConfig, ConsensusConfig, LoggingConfig};
 // src/configuration/sources/defaults.rs
 // Minimal stub for configuration defaults source
 
@@ -20,6 +20,7 @@ pub fn load_defaults() -> Configuration {
             path: "./data".to_string(),
             cache_size: 1024,
         },
+        chaindb: ChainDBConfig::default(),
         consensus: ConsensusConfig {
             protocol: "Ouroboros".to_string(),
             slot_duration: 1000,
@@ -1,5 +1,7 @@
 use crate::configuration::types::Configuration;
-use crate::configuration::{ConsensusConfig, DatabaseConfig, LoggingConfig, NetworkConfig};
+use crate::configuration::{
+    ChainDBConfig, ConsensusConfig, DatabaseConfig, LoggingConfig, NetworkConfig,
+};
 use anyhow::Result;
 use std::env;
 
@@ -26,6 +28,7 @@ pub fn load_env_vars() -> Result<Configuration> {
                 .parse()
                 .unwrap_or(1024),
         },
+        chaindb: ChainDBConfig::default(),
         consensus: ConsensusConfig {
             protocol: env::var("CONSENSUS_PROTOCOL").unwrap_or("Ouroboros".to_string()),
             slot_duration: env::var("SLOT_DURATION")
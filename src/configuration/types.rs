@@ -12,6 +12,7 @@ pub enum DiscoveryMethod {
     Dns,
     Upnp,
 }
+use super::chaindb::ChainDBConfig;
 use serde::{Deserialize, Serialize};
 
 /// Represents the main configuration structure.
@@ -19,6 +20,9 @@ use serde::{Deserialize, Serialize};
 pub struct Configuration {
     pub network: NetworkConfig,
     pub database: DatabaseConfig,
+    /// Chain and ledger database settings; the ChainDB lives at `database.path`.
+    #[serde(default)]
+    pub chaindb: ChainDBConfig,
     pub consensus: ConsensusConfig,
     pub logging: LoggingConfig,
 }
@@ -31,6 +35,7 @@ impl Default for Configuration {
                 path: "./data/chaindb".to_string(),
                 cache_size: 1024,
             },
+            chaindb: ChainDBConfig::default(),
             consensus: ConsensusConfig {
                 protocol: "OuroborosPraos".to_string(),
                 slot_duration: 1000,
@@ -1,6 +1,6 @@
 impl Consensus {
     /// Returns a context object for compatibility (stub).
-    pub fn context(&self) -> () {
+    pub fn context(&self) {
         // TODO: Replace with real context type if needed
     }
 }
@@ -9,63 +9,328 @@ impl Consensus {
 /// Implements the Ouroboros consensus algorithm and related logic.
 /// All consensus code must be robust, secure, and performant.
 use crate::configuration::ConsensusConfig;
+use crate::ledger::{BlockHeader, OperationalCert, ProtocolParameters, StakeSnapshot};
 use crate::mempool::Mempool;
 use crate::networking::NetworkExt;
 use crate::tracing::tracers::{TraceEvent, Tracer};
 use rand::Rng;
 use std::sync::Arc;
 use std::time::Instant;
+use thiserror::Error;
 use tokio::sync::Mutex as AsyncMutex;
 use tokio::time::{Duration, sleep};
 
+pub mod kes;
+pub mod nonce;
+pub mod opcert;
+pub mod vrf;
+
+/// Epoch nonce, mixed into the VRF input of every slot of an epoch.
+pub type Nonce = [u8; 32];
+
+/// Nonce of the first epoch, before blocks contribute randomness.
+pub const NEUTRAL_NONCE: Nonce = [0u8; 32];
+
+/// KES periods an operational certificate is valid for, as on mainnet (at most
+/// [`kes::TOTAL_PERIODS`]).
+pub const MAX_KES_EVOLUTIONS: u64 = 62;
+
+/// Errors of header validation.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConsensusError {
+    /// The block leader is not a registered stake pool.
+    #[error("Block leader {0} is not a registered stake pool")]
+    UnknownPool(String),
+    /// The header's VRF key is not the one the pool registered.
+    #[error("VRF key of the header does not match the one registered by pool {0}")]
+    WrongVrfKey(String),
+    /// The VRF proof does not verify.
+    #[error("Invalid VRF proof: {0}")]
+    InvalidVrfProof(#[from] vrf::VrfError),
+    /// The leader value is not below the threshold of the pool's stake.
+    #[error("Pool {pool} is not leader of slot {slot}")]
+    NotLeader { pool: String, slot: u64 },
+    /// The header's cold key is not the key of the leader's pool.
+    #[error("Cold key of the header is not the key of pool {0}")]
+    WrongIssuer(String),
+    /// The operational certificate is not signed by the header's cold key.
+    #[error("Operational certificate of pool {0} is not signed by its cold key")]
+    InvalidOpCert(String),
+    /// The KES period of the slot is before the operational certificate's.
+    #[error("KES period {current} is before the start {start} of the operational certificate")]
+    KesPeriodBeforeStart { start: u64, current: u64 },
+    /// The operational certificate is no longer valid in the KES period of the slot.
+    #[error("Operational certificate from KES period {start} has expired at KES period {current}")]
+    OpCertExpired { start: u64, current: u64 },
+    /// The operational certificate's counter is below one the pool already used.
+    #[error("Operational certificate counter {counter} of pool {pool} is below {last}")]
+    OpCertReplayed {
+        pool: String,
+        counter: u64,
+        last: u64,
+    },
+    /// The operational certificate's counter skips ahead of the pool's last one.
+    #[error(
+        "Operational certificate counter {counter} of pool {pool} is more than one above {last}"
+    )]
+    OpCertCounterTooHigh {
+        pool: String,
+        counter: u64,
+        last: u64,
+    },
+    /// The KES signature does not verify, or the KES key cannot sign.
+    #[error("KES: {0}")]
+    Kes(#[from] kes::KesError),
+    /// The header is valid, but the ledger rejects the block.
+    #[error("Ledger: {0}")]
+    Ledger(#[from] crate::ledger::LedgerError),
+}
+
 /// Represents the consensus engine of the node.
 #[allow(dead_code)]
 pub struct Consensus {
     pub config: ConsensusConfig,
     // ... add consensus state fields as needed
     pub mempool: Option<Mempool>,
+    /// Praos keys of the pool the node produces blocks for, if it is a block producer
+    pub praos_keys: Option<PraosKeys>,
     pub tracer: Tracer,
 }
 
-/// Praos cryptographic keys (stub: replace with real cryptography)
+/// Praos cryptographic keys of a block producer: the VRF key that proves slot leadership, and the
+/// KES key that signs headers under an operational certificate of the pool's cold key
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct PraosKeys {
-    /// VRF secret key (stub)
-    pub vrf_sk: Vec<u8>,
-    /// KES secret key (stub)
-    pub kes_sk: Vec<u8>,
-    /// VRF public key (stub)
-    pub vrf_pk: Vec<u8>,
-    /// KES public key (stub)
-    pub kes_pk: Vec<u8>,
+    /// VRF signing key
+    pub vrf: vrf::SigningKey,
+    /// KES signing key, evolved up to the current KES period
+    pub kes: kes::SigningKey,
+    /// Cold verification key of the pool
+    pub cold_vkey: [u8; 32],
+    /// Certificate of the KES key by the cold key
+    pub opcert: OperationalCert,
 }
 
 #[allow(dead_code)]
 impl PraosKeys {
-    /// Generate dummy keys (replace with real keygen)
-    pub fn generate() -> Self {
+    /// Random VRF and KES keys, the KES key certified by `cold_key` from `kes_period` on with
+    /// issue number `counter`. The cold key itself is not kept.
+    pub fn new(cold_key: &ed25519_dalek::SigningKey, counter: u64, kes_period: u64) -> Self {
+        let kes = kes::SigningKey::generate();
+        let opcert = opcert::issue(cold_key, &kes.verification_key(), counter, kes_period);
         Self {
-            vrf_sk: vec![0; 32],
-            kes_sk: vec![0; 32],
-            vrf_pk: vec![1; 32],
-            kes_pk: vec![1; 32],
+            vrf: vrf::SigningKey::generate(),
+            kes,
+            cold_vkey: cold_key.verifying_key().to_bytes(),
+            opcert,
+        }
+    }
+
+    /// Keys of a random cold key, certified from KES period 0
+    pub fn generate() -> Self {
+        Self::new(
+            &ed25519_dalek::SigningKey::from_bytes(&rand::random()),
+            0,
+            0,
+        )
+    }
+
+    /// Pool id: the key hash of the cold key
+    pub fn pool_id(&self) -> String {
+        crate::ledger::witness::key_hash(&self.cold_vkey)
+    }
+
+    /// Unsigned header of a block of `slot` in `epoch`, led by the pool with the VRF `proof`
+    pub fn leader_header(&self, slot: u64, epoch: u64, proof: &vrf::Proof) -> BlockHeader {
+        BlockHeader {
+            slot,
+            epoch,
+            leader: self.pool_id(),
+            issuer_vkey: vec![],
+            vrf_vkey: self.vrf.verification_key().to_vec(),
+            vrf_proof: proof.to_vec(),
+            opcert: OperationalCert::default(),
+            kes_signature: vec![],
+            prev_hash: None,
+            body_hash: [0u8; 32],
+        }
+    }
+
+    /// Evolve the KES key to `kes_period`, erasing its keys of earlier periods
+    pub fn evolve(&mut self, kes_period: u64) -> Result<(), kes::KesError> {
+        let target = kes_period.saturating_sub(self.opcert.kes_period);
+        while u64::from(self.kes.period()) < target {
+            self.kes.update()?;
+        }
+        Ok(())
+    }
+
+    /// Sign `header` in `kes_period`: fill in the cold key and the operational certificate,
+    /// evolve the KES key to the period and sign the header body with it
+    pub fn sign_header(
+        &mut self,
+        header: &mut BlockHeader,
+        kes_period: u64,
+    ) -> Result<(), ConsensusError> {
+        let start = self.opcert.kes_period;
+        if kes_period < start {
+            return Err(ConsensusError::KesPeriodBeforeStart {
+                start,
+                current: kes_period,
+            });
+        }
+        if kes_period >= start + MAX_KES_EVOLUTIONS {
+            return Err(ConsensusError::OpCertExpired {
+                start,
+                current: kes_period,
+            });
+        }
+        self.evolve(kes_period)?;
+        if u64::from(self.kes.period()) != kes_period - start {
+            return Err(kes::KesError::KeyEvolved(kes_period - start).into());
         }
+        header.issuer_vkey = self.cold_vkey.to_vec();
+        header.opcert = self.opcert.clone();
+        header.kes_signature = self.kes.sign(&header.body_bytes()).to_vec();
+        Ok(())
     }
 }
 
-/// Praos slot leadership check (stub: replace with real VRF)
+/// VRF input of `slot`: Blake2b-256 of the slot number (big-endian) and the epoch nonce.
+pub fn vrf_input(slot: u64, epoch_nonce: &Nonce) -> [u8; 32] {
+    crate::ledger::blake2b_256(&[&slot.to_be_bytes()[..], epoch_nonce].concat())
+}
+
+/// Leader value of a VRF output: Blake2b-256 of `"L"` and the output, as a fraction in [0, 1).
+pub fn leader_value(output: &vrf::Output) -> f64 {
+    let hash = crate::ledger::blake2b_256(&[&b"L"[..], output].concat());
+    let mut high = [0u8; 8];
+    high.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(high) as f64 / 2f64.powi(64)
+}
+
+/// Probability `1 - (1 - f)^σ` that a pool with relative stake `σ` leads a slot, for the active
+/// slot coefficient `f`. Splitting stake among pools does not change the chance of leading.
+pub fn leader_threshold(active_slot_coeff: f64, relative_stake: f64) -> f64 {
+    1.0 - (1.0 - active_slot_coeff).powf(relative_stake)
+}
+
+/// Praos slot leadership check: the VRF proof over `slot` and the epoch nonce, if its leader value
+/// falls below the threshold of a pool holding `stake` of `total_stake`.
 #[allow(dead_code)]
-pub fn praos_is_leader(slot: u64, _keys: &PraosKeys, stake: f64, total_stake: f64) -> bool {
-    // Example leader election logic for Ouroboros Praos
-    // In real Cardano, this is cryptographic and probabilistic
+pub fn praos_is_leader(
+    slot: u64,
+    epoch_nonce: &Nonce,
+    keys: &PraosKeys,
+    stake: f64,
+    total_stake: f64,
+    active_slot_coeff: f64,
+) -> Option<vrf::Proof> {
     if total_stake == 0.0 {
-        return false;
+        return None;
+    }
+    let (proof, output) = keys.vrf.prove(&vrf_input(slot, epoch_nonce));
+    let threshold = leader_threshold(active_slot_coeff, stake / total_stake);
+    (leader_value(&output) < threshold).then_some(proof)
+}
+
+/// Check that the leader of `header` was elected for its slot: the VRF key is the one its pool
+/// registered in the leader stake `distribution` of the header's epoch, the proof over the slot and
+/// `epoch_nonce` verifies, and the leader value is below the threshold of the pool's stake.
+/// Returns the VRF output.
+pub fn validate_leader(
+    header: &BlockHeader,
+    distribution: &StakeSnapshot,
+    active_slot_coeff: f64,
+    epoch_nonce: &Nonce,
+) -> Result<vrf::Output, ConsensusError> {
+    let pool = distribution
+        .pools
+        .get(&header.leader)
+        .ok_or_else(|| ConsensusError::UnknownPool(header.leader.clone()))?;
+    if crate::ledger::blake2b_256(&header.vrf_vkey) != pool.vrf_keyhash {
+        return Err(ConsensusError::WrongVrfKey(header.leader.clone()));
+    }
+    let output = vrf::verify(
+        &header.vrf_vkey,
+        &header.vrf_proof,
+        &vrf_input(header.slot, epoch_nonce),
+    )?;
+    let (stake, total_stake) = election_stake(distribution, &header.leader);
+    let relative_stake = if total_stake == 0.0 {
+        0.0
+    } else {
+        stake / total_stake
+    };
+    if leader_value(&output) >= leader_threshold(active_slot_coeff, relative_stake) {
+        return Err(ConsensusError::NotLeader {
+            pool: header.leader.clone(),
+            slot: header.slot,
+        });
+    }
+    Ok(output)
+}
+
+/// Check the operational certificate and KES signature of `header` in `kes_period`, the KES
+/// period of its slot: the header's cold key is the key of the leader's pool and signed the
+/// certificate, the certificate is valid in the period, its counter is `last_counter`, the last
+/// one the pool used, or one above, and its KES key signed the header body at the period. Returns
+/// the certificate counter.
+pub fn validate_kes(
+    header: &BlockHeader,
+    kes_period: u64,
+    last_counter: u64,
+) -> Result<u64, ConsensusError> {
+    if crate::ledger::witness::key_hash(&header.issuer_vkey) != header.leader {
+        return Err(ConsensusError::WrongIssuer(header.leader.clone()));
+    }
+    let cert = &header.opcert;
+    if !opcert::verify(cert, &header.issuer_vkey) {
+        return Err(ConsensusError::InvalidOpCert(header.leader.clone()));
+    }
+    if kes_period < cert.kes_period {
+        return Err(ConsensusError::KesPeriodBeforeStart {
+            start: cert.kes_period,
+            current: kes_period,
+        });
+    }
+    if kes_period >= cert.kes_period + MAX_KES_EVOLUTIONS {
+        return Err(ConsensusError::OpCertExpired {
+            start: cert.kes_period,
+            current: kes_period,
+        });
+    }
+    if cert.counter < last_counter {
+        return Err(ConsensusError::OpCertReplayed {
+            pool: header.leader.clone(),
+            counter: cert.counter,
+            last: last_counter,
+        });
+    }
+    if cert.counter > last_counter.saturating_add(1) {
+        return Err(ConsensusError::OpCertCounterTooHigh {
+            pool: header.leader.clone(),
+            counter: cert.counter,
+            last: last_counter,
+        });
     }
-    let threshold = stake / total_stake;
-    // For demonstration, use slot as a pseudo-random seed
-    let pseudo_random = (slot as f64 / 1_000_000.0).fract();
-    pseudo_random < threshold
+    kes::verify(
+        &cert.hot_vkey,
+        kes_period - cert.kes_period,
+        &header.body_bytes(),
+        &header.kes_signature,
+    )?;
+    Ok(cert.counter)
+}
+
+/// Stake of `pool_id` and the total stake delegated to active pools in `distribution`, as the
+/// `stake` and `total_stake` leader election takes.
+pub fn election_stake(distribution: &StakeSnapshot, pool_id: &str) -> (f64, f64) {
+    (
+        distribution.pool_stake(pool_id) as f64,
+        distribution.active_stake() as f64,
+    )
 }
 
 /// Consensus state for Ouroboros slot/epoch management and KES/VRF
@@ -83,10 +348,13 @@ pub struct ConsensusState {
     pub stake: f64,
     /// Total stake in the system
     pub total_stake: f64,
-    /// KES period (stub)
+    /// Nonce of the current epoch, mixed into VRF inputs
+    pub epoch_nonce: Nonce,
+    /// Protocol parameters of the current epoch: the active slot coefficient and KES period
+    /// length
+    pub params: ProtocolParameters,
+    /// Current KES period, which the node's KES key is evolved to
     pub kes_period: u64,
-    /// KES key rotation interval (slots)
-    pub kes_rotation_interval: u64,
 }
 
 impl ConsensusState {
@@ -96,7 +364,7 @@ impl ConsensusState {
         praos_keys: PraosKeys,
         stake: f64,
         total_stake: f64,
-        kes_rotation_interval: u64,
+        params: ProtocolParameters,
     ) -> Self {
         Self {
             slot: 0,
@@ -105,21 +373,66 @@ impl ConsensusState {
             praos_keys,
             stake,
             total_stake,
+            epoch_nonce: NEUTRAL_NONCE,
+            params,
             kes_period: 0,
-            kes_rotation_interval,
         }
     }
     /// Advance to the next slot, handling epoch and KES rotation
     pub fn advance_slot(&mut self) {
         self.slot += 1;
-        if self.slot % self.slots_per_epoch == 0 {
+        if self.slot.is_multiple_of(self.slots_per_epoch) {
             self.epoch += 1;
         }
-        if self.slot % self.kes_rotation_interval == 0 {
-            self.kes_period += 1;
-            // TODO: Rotate KES key (stub)
+        let kes_period = self.params.kes_period_of(self.slot);
+        if kes_period != self.kes_period {
+            self.kes_period = kes_period;
+            // Erase the key of the past period. A key that cannot evolve has outlived its
+            // operational certificate, which must be renewed before the node can sign again.
+            let _ = self.praos_keys.evolve(self.kes_period);
         }
     }
+
+    /// Validate the header of a received block: its slot follows the tip of `ledger` and its
+    /// epoch is the one of its slot (see [`crate::ledger::Ledger::validate_slot`]), its leader's
+    /// VRF proof (see [`validate_leader`]) verifies against the stake distribution, active slot
+    /// coefficient and epoch nonce of `ledger`, and its operational certificate and KES signature
+    /// (see [`validate_kes`]) against the last certificate counter of the pool in `ledger`.
+    pub fn validate_header(
+        &self,
+        header: &BlockHeader,
+        ledger: &crate::ledger::Ledger,
+    ) -> Result<vrf::Output, ConsensusError> {
+        ledger.validate_slot(header)?;
+        let kes_period = ledger.protocol_parameters().kes_period_of(header.slot);
+        validate_kes(header, kes_period, ledger.opcert_counter(&header.leader))?;
+        let distribution = ledger.leader_stake_distribution(header.epoch);
+        let active_slot_coeff = ledger.protocol_parameters().active_slot_coeff.to_f64();
+        let epoch_nonce = ledger.epoch_nonce(header.epoch);
+        validate_leader(header, &distribution, active_slot_coeff, &epoch_nonce)
+    }
+
+    /// Accept a block received from a peer: validate its header against `ledger` (see
+    /// [`Self::validate_header`]), then apply it to `ledger`. The ledger is unchanged on error.
+    pub fn receive_block(
+        &self,
+        block: &crate::ledger::Block,
+        ledger: &mut crate::ledger::Ledger,
+    ) -> Result<(), ConsensusError> {
+        self.validate_header(&block.header, ledger)?;
+        Ok(ledger.try_apply_block(block)?)
+    }
+}
+
+/// Round-robin leader schedule: node `node_id` of `total_nodes` leads every slot `s` with
+/// `s % total_nodes == node_id`
+#[derive(Debug, Clone, Copy)]
+pub struct RoundRobin {
+    pub node_id: u64,
+    pub total_nodes: u64,
+    /// Epoch of slot 0
+    pub epoch: u64,
+    pub slots_per_epoch: u64,
 }
 
 /// Praos consensus state
@@ -130,6 +443,8 @@ pub struct PraosState {
     pub keys: PraosKeys,
     pub stake: f64,
     pub total_stake: f64,
+    pub epoch_nonce: Nonce,
+    pub active_slot_coeff: f64,
 }
 
 #[allow(dead_code)]
@@ -139,6 +454,7 @@ impl Consensus {
         Self {
             config,
             mempool: None,
+            praos_keys: None,
             tracer,
         }
     }
@@ -168,16 +484,19 @@ impl Consensus {
     }
 
     /// Validate a block according to consensus rules.
-    /// Checks slot, leader, and cryptographic proofs (VRF/KES stubs).
+    /// Checks slot, leader, and the shape of the keys, VRF proof and KES signature. The proof and
+    /// signatures themselves are checked by [`ConsensusState::validate_header`].
     /// Returns true if the block is valid under consensus rules.
     pub async fn validate_block(&self, block: &crate::ledger::Block) -> bool {
         // Check slot and leader are present
         if block.header.slot == 0 || block.header.leader.is_empty() {
             return false;
         }
-        // TODO: Add real VRF/KES cryptographic checks here
-        // Example: check VRF/KES proof length (stub)
-        if block.header.vrf_proof.len() != 32 || block.header.kes_signature.len() != 32 {
+        if block.header.issuer_vkey.len() != 32
+            || block.header.vrf_vkey.len() != 32
+            || block.header.vrf_proof.len() != vrf::PROOF_SIZE
+            || block.header.kes_signature.len() != kes::SIGNATURE_SIZE
+        {
             return false;
         }
         // Additional consensus checks (e.g., slot timing, duplicate leaders) can be added here
@@ -189,51 +508,43 @@ impl Consensus {
         // TODO: Implement real slot leadership and block production
     }
 
-    /// Produce a block by pulling transactions from the mempool.
+    /// Produce a block by pulling transactions from the mempool, led and signed by the node's
+    /// Praos keys with the VRF `proof` of `slot`. None without a mempool, keys or transactions.
     pub async fn produce_block_from_mempool(
         &mut self,
-        ledger: &mut crate::ledger::Ledger,
+        ledger: &crate::ledger::Ledger,
         protocol: &crate::protocol::Protocol,
         slot: u64,
         epoch: u64,
-        leader: String,
-        vrf_proof: Vec<u8>,
-        kes_signature: Vec<u8>,
+        proof: &vrf::Proof,
     ) -> Option<crate::ledger::Block> {
-        if let Some(ref mempool) = self.mempool {
-            let txs = mempool.get_transactions();
-            if txs.is_empty() {
-                return None;
-            }
-            let valid_txs: Vec<_> = txs
-                .into_iter()
-                .filter(|tx| {
-                    futures::executor::block_on(
-                        ledger.validate_transaction_with_protocol(tx, protocol),
-                    )
-                })
-                .collect();
-            if valid_txs.is_empty() {
-                return None;
-            }
-            let block = crate::ledger::Block {
-                id: rand::random(),
-                header: crate::ledger::BlockHeader {
-                    slot,
-                    epoch,
-                    leader,
-                    vrf_proof,
-                    kes_signature,
-                },
-                transactions: valid_txs,
-            };
-            self.tracer.trace(TraceEvent::LeadershipCheck(
-                "Checked leadership".to_string(),
-            ));
-            Some(block)
-        } else {
-            None
+        let (Some(mempool), Some(keys)) = (&self.mempool, &mut self.praos_keys) else {
+            return None;
+        };
+        let txs = mempool.get_transactions();
+        if txs.is_empty() {
+            return None;
+        }
+        let valid_txs: Vec<_> = txs
+            .into_iter()
+            .filter(|tx| {
+                futures::executor::block_on(ledger.validate_transaction_with_protocol(tx, protocol))
+            })
+            .collect();
+        let valid_txs = ledger.select_transactions(valid_txs, slot, epoch);
+        if valid_txs.is_empty() {
+            return None;
         }
+        let tip = ledger.tip();
+        let mut header = keys.leader_header(slot, epoch, proof);
+        header.prev_hash = tip.map(|t| t.hash);
+        let mut block = crate::ledger::Block::new(tip.map_or(1, |t| t.id + 1), header, valid_txs);
+        let kes_period = ledger.protocol_parameters().kes_period_of(slot);
+        keys.sign_header(&mut block.header, kes_period).ok()?;
+        self.tracer.trace(TraceEvent::LeadershipCheck(
+            "Checked leadership".to_string(),
+        ));
+        Some(block)
     }
 
     /// Ouroboros consensus: slot timing, leader election, and block validation.
@@ -245,53 +556,57 @@ impl Consensus {
         mempool: std::sync::Arc<tokio::sync::Mutex<crate::mempool::Mempool>>,
         network: std::sync::Arc<crate::networking::Network>,
         slot_duration_ms: u64,
-        node_id: u64,
-        total_nodes: u64,
-        mut current_epoch: u64,
-        slots_per_epoch: u64,
+        schedule: RoundRobin,
     ) {
         let mut slot: u64 = 0;
+        let mut current_epoch = schedule.epoch;
         loop {
             let slot_start = Instant::now();
             // Check for era transition at epoch boundary
-            if slot % slots_per_epoch == 0 && slot > 0 {
+            if slot.is_multiple_of(schedule.slots_per_epoch) && slot > 0 {
                 current_epoch += 1;
                 let mut protocol_guard = protocol.lock().await;
                 protocol_guard.handle_upgrade(current_epoch).await;
+                ledger
+                    .lock()
+                    .await
+                    .enter_era(protocol_guard.hard_fork.current_logic().name());
                 println!(
                     "[Consensus] Checked for protocol upgrade at epoch {}",
                     current_epoch
                 );
             }
             // Era-aware leader election: round-robin by node_id (can be extended per era)
-            let is_leader = (slot % total_nodes) == node_id;
+            let is_leader = (slot % schedule.total_nodes) == schedule.node_id;
             if is_leader {
                 let mut ledger_guard = ledger.lock().await;
                 let protocol_guard = protocol.lock().await;
                 let mempool_ref = mempool.lock().await;
                 let era_logic = protocol_guard.hard_fork.current_logic();
                 // Fill consensus header fields for block production
-                let slot = slot; // current slot
-                let epoch = current_epoch;
-                let leader = format!("node-{}", node_id);
-                let vrf_proof = vec![0u8; 32]; // stub
-                let kes_signature = vec![0u8; 32]; // stub
+                let header = BlockHeader {
+                    slot,
+                    epoch: current_epoch,
+                    leader: format!("node-{}", schedule.node_id),
+                    issuer_vkey: vec![],
+                    vrf_vkey: vec![0u8; 32],               // stub
+                    vrf_proof: vec![0u8; vrf::PROOF_SIZE], // stub
+                    opcert: OperationalCert::default(),
+                    kes_signature: vec![0u8; kes::SIGNATURE_SIZE], // stub
+                    prev_hash: None,
+                    body_hash: [0u8; 32],
+                };
                 if let Some(block) = crate::ledger::Block::new_from_mempool(
-                    &mut *ledger_guard,
+                    &ledger_guard,
                     era_logic.as_ref(),
-                    &*mempool_ref,
-                    slot,
-                    epoch,
-                    leader,
-                    vrf_proof,
-                    kes_signature,
+                    &mempool_ref,
+                    header,
                 )
                 .await
+                    && protocol_guard.validate_block(&block).await
                 {
-                    if protocol_guard.validate_block(&block).await {
-                        ledger_guard.apply_block(&block);
-                        network.broadcast_block(&block).await;
-                    }
+                    ledger_guard.apply_block(&block);
+                    network.broadcast_block(&block).await;
                 }
             }
             slot += 1;
@@ -309,23 +624,34 @@ impl Consensus {
         protocol: Arc<crate::protocol::Protocol>,
         mempool: Arc<AsyncMutex<crate::mempool::Mempool>>,
         network: Arc<crate::networking::Network>,
-        praos_state: PraosState,
+        mut praos_state: PraosState,
         slot_duration_ms: u64,
     ) {
         let mut slot = praos_state.slot;
-        let _epoch = praos_state.epoch;
         loop {
             let slot_start = std::time::Instant::now();
-            // Epoch transition
-            if slot % 21600 == 0 && slot > 0 {
-                // TODO: update stake distribution, rotate KES, etc.
+            // Epoch transition: leaders of the new epoch are elected with the set snapshot and
+            // the new epoch nonce
+            if slot.is_multiple_of(21600) && slot > 0 {
+                praos_state.epoch += 1;
+                let ledger_guard = ledger.lock().await;
+                let distribution = ledger_guard.leader_stake_distribution(praos_state.epoch);
+                (praos_state.stake, praos_state.total_stake) =
+                    election_stake(&distribution, &praos_state.keys.pool_id());
+                praos_state.active_slot_coeff = ledger_guard
+                    .protocol_parameters()
+                    .active_slot_coeff
+                    .to_f64();
+                praos_state.epoch_nonce = ledger_guard.epoch_nonce(praos_state.epoch);
             }
             // Praos leader check
-            if praos_is_leader(
+            if let Some(proof) = praos_is_leader(
                 slot,
+                &praos_state.epoch_nonce,
                 &praos_state.keys,
                 praos_state.stake,
                 praos_state.total_stake,
+                praos_state.active_slot_coeff,
             ) {
                 let mut ledger_guard = ledger.lock().await;
                 let protocol_guard = protocol.clone();
@@ -333,24 +659,20 @@ impl Consensus {
                 let mempool_ref = mempool.lock().await;
                 let era_logic = protocol_guard.hard_fork.current_logic();
                 // Fill consensus header fields for block production
-                let slot = slot; // current slot
-                let epoch = _epoch;
-                let leader = "praos-leader".to_string(); // stub
-                let vrf_proof = vec![0u8; 32]; // stub
-                let kes_signature = vec![0u8; 32]; // stub
-                if let Some(block) = crate::ledger::Block::new_from_mempool(
-                    &mut *ledger_guard,
+                let header = praos_state
+                    .keys
+                    .leader_header(slot, praos_state.epoch, &proof);
+                if let Some(mut block) = crate::ledger::Block::new_from_mempool(
+                    &ledger_guard,
                     era_logic.as_ref(),
-                    &*mempool_ref,
-                    slot,
-                    epoch,
-                    leader,
-                    vrf_proof,
-                    kes_signature,
+                    &mempool_ref,
+                    header, // signed below
                 )
                 .await
                 {
-                    if protocol_guard.validate_block(&block).await {
+                    let kes_period = ledger_guard.protocol_parameters().kes_period_of(slot);
+                    let signed = praos_state.keys.sign_header(&mut block.header, kes_period);
+                    if signed.is_ok() && protocol_guard.validate_block(&block).await {
                         ledger_guard.apply_block(&block);
                         network.broadcast_block(&block).await;
                     }
@@ -375,45 +697,53 @@ impl Consensus {
         mempool: Arc<AsyncMutex<crate::mempool::Mempool>>,
         network: Arc<crate::networking::Network>,
         slot_duration_ms: u64,
-        node_id: u64,
-        total_nodes: u64,
     ) {
         loop {
             let slot_start = Instant::now();
-            // Leader election (Praos stub)
-            let is_leader = praos_is_leader(
+            // Praos leader election
+            if let Some(proof) = praos_is_leader(
                 state.slot,
+                &state.epoch_nonce,
                 &state.praos_keys,
                 state.stake,
                 state.total_stake,
-            );
-            if is_leader {
+                state.params.active_slot_coeff.to_f64(),
+            ) {
                 let mut ledger_guard = ledger.lock().await;
                 let protocol_guard = protocol.clone();
                 let mempool_ref = mempool.lock().await;
                 let era_logic = protocol_guard.hard_fork.current_logic();
-                let leader = format!("node-{}", node_id);
-                let vrf_proof = vec![0u8; 32]; // stub
-                let kes_signature = vec![0u8; 32]; // stub
-                if let Some(block) = crate::ledger::Block::new_from_mempool(
-                    &mut *ledger_guard,
+                let header = state
+                    .praos_keys
+                    .leader_header(state.slot, state.epoch, &proof);
+                if let Some(mut block) = crate::ledger::Block::new_from_mempool(
+                    &ledger_guard,
                     era_logic.as_ref(),
-                    &*mempool_ref,
-                    state.slot,
-                    state.epoch,
-                    leader,
-                    vrf_proof,
-                    kes_signature,
+                    &mempool_ref,
+                    header, // signed below
                 )
                 .await
                 {
-                    if protocol_guard.validate_block(&block).await {
+                    let signed = state
+                        .praos_keys
+                        .sign_header(&mut block.header, state.kes_period);
+                    if signed.is_ok() && protocol_guard.validate_block(&block).await {
                         ledger_guard.apply_block(&block);
                         network.broadcast_block(&block).await;
                     }
                 }
             }
+            let epoch = state.epoch;
             state.advance_slot();
+            if state.epoch != epoch {
+                // Leaders of the new epoch are elected with the set snapshot and the new nonce
+                let ledger_guard = ledger.lock().await;
+                let distribution = ledger_guard.leader_stake_distribution(state.epoch);
+                (state.stake, state.total_stake) =
+                    election_stake(&distribution, &state.praos_keys.pool_id());
+                state.params = ledger_guard.protocol_parameters().clone();
+                state.epoch_nonce = ledger_guard.epoch_nonce(state.epoch);
+            }
             let elapsed = slot_start.elapsed();
             if elapsed < Duration::from_millis(slot_duration_ms) {
                 sleep(Duration::from_millis(slot_duration_ms) - elapsed).await;
@@ -425,14 +755,59 @@ impl Consensus {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::configuration::ConsensusConfig;
-    use crate::ledger::{Block, BlockHeader, Transaction, TxInput, TxOutput};
-    use crate::tracing::tracers::Tracer;
+    use crate::ledger::{
+        Block, Credential, Ledger, LedgerError, LedgerState, Rational, StakePoolRegistration,
+    };
+
+    /// Leader distribution in which `pool_id`, registered with `keys`, holds `stake` of
+    /// `total_stake`.
+    fn leader_distribution(
+        pool_id: &str,
+        keys: &PraosKeys,
+        stake: u64,
+        total_stake: u64,
+    ) -> StakeSnapshot {
+        let mut distribution = StakeSnapshot::default();
+        let registration = StakePoolRegistration {
+            pool_id: pool_id.to_string(),
+            vrf_keyhash: crate::ledger::blake2b_256(&keys.vrf.verification_key()),
+            owner: Credential::KeyHash([1u8; 28]),
+            pledge: 0,
+            cost: 340_000_000,
+            margin: 0.0,
+            reward_account: Credential::KeyHash([1u8; 28]),
+        };
+        distribution.pools.insert(pool_id.to_string(), registration);
+        distribution.pool_stake.insert(pool_id.to_string(), stake);
+        distribution
+            .pool_stake
+            .insert("other".to_string(), total_stake - stake);
+        distribution
+    }
+
+    fn header(slot: u64, leader: &str, keys: &PraosKeys, vrf_proof: Vec<u8>) -> BlockHeader {
+        BlockHeader {
+            slot,
+            epoch: 0,
+            leader: leader.to_string(),
+            issuer_vkey: keys.cold_vkey.to_vec(),
+            vrf_vkey: keys.vrf.verification_key().to_vec(),
+            vrf_proof,
+            opcert: keys.opcert.clone(),
+            kes_signature: vec![],
+            prev_hash: None,
+            body_hash: [0u8; 32],
+        }
+    }
 
     #[test]
     fn test_consensus_state_slot_epoch_kes() {
         let keys = PraosKeys::generate();
-        let mut state = ConsensusState::new(10, keys, 0.1, 1.0, 5);
+        let params = ProtocolParameters {
+            slots_per_kes_period: 5,
+            ..Default::default()
+        };
+        let mut state = ConsensusState::new(10, keys, 0.1, 1.0, params);
         assert_eq!(state.slot, 0);
         assert_eq!(state.epoch, 0);
         assert_eq!(state.kes_period, 0);
@@ -445,5 +820,214 @@ mod tests {
                 assert_eq!(state.kes_period, i / 5);
             }
         }
+        // The KES key evolves with the period.
+        assert_eq!(state.praos_keys.kes.period(), 4);
+        // A KES period of zero slots counts as one slot long.
+        state.params.slots_per_kes_period = 0;
+        state.advance_slot();
+        assert_eq!(state.kes_period, 21);
+    }
+
+    #[test]
+    fn leader_threshold_grows_with_stake_up_to_the_active_slot_coefficient() {
+        assert_eq!(leader_threshold(0.05, 0.0), 0.0);
+        assert!((leader_threshold(0.05, 1.0) - 0.05).abs() < 1e-12);
+        assert_eq!(leader_threshold(1.0, 0.5), 1.0);
+        assert!(leader_threshold(0.05, 0.1) < leader_threshold(0.05, 0.2));
+        // Splitting stake into two pools does not raise the chance of leading a slot.
+        let half = leader_threshold(0.05, 0.05);
+        assert!((1.0 - (1.0 - half) * (1.0 - half) - leader_threshold(0.05, 0.1)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn elected_leaders_prove_their_slot_to_other_nodes() {
+        let keys = PraosKeys::generate();
+        let nonce = [9u8; 32];
+        let distribution = leader_distribution("pool1", &keys, 1_000, 1_000);
+        // With f = 1 and all the stake, the pool leads every slot.
+        let proof = praos_is_leader(7, &nonce, &keys, 1_000.0, 1_000.0, 1.0).unwrap();
+        let good = header(7, "pool1", &keys, proof.to_vec());
+        let output = validate_leader(&good, &distribution, 1.0, &nonce).unwrap();
+        assert_eq!(vrf::proof_to_hash(&proof), Ok(output));
+        // The proof is bound to the slot, the nonce, the key and the pool.
+        let mut other_slot = good.clone();
+        other_slot.slot = 8;
+        assert_eq!(
+            validate_leader(&other_slot, &distribution, 1.0, &nonce),
+            Err(ConsensusError::InvalidVrfProof(vrf::VrfError::InvalidProof))
+        );
+        assert!(validate_leader(&good, &distribution, 1.0, &[0u8; 32]).is_err());
+        let impostor = PraosKeys::generate();
+        let mut wrong_key = good.clone();
+        wrong_key.vrf_vkey = impostor.vrf.verification_key().to_vec();
+        assert_eq!(
+            validate_leader(&wrong_key, &distribution, 1.0, &nonce),
+            Err(ConsensusError::WrongVrfKey("pool1".to_string()))
+        );
+        let mut unknown = good.clone();
+        unknown.leader = "pool2".to_string();
+        assert_eq!(
+            validate_leader(&unknown, &distribution, 1.0, &nonce),
+            Err(ConsensusError::UnknownPool("pool2".to_string()))
+        );
+        // A valid proof does not make a pool without stake a leader.
+        let no_stake = leader_distribution("pool1", &keys, 0, 1_000);
+        assert_eq!(
+            validate_leader(&good, &no_stake, 1.0, &nonce),
+            Err(ConsensusError::NotLeader {
+                pool: "pool1".to_string(),
+                slot: 7,
+            })
+        );
+        assert_eq!(praos_is_leader(7, &nonce, &keys, 0.0, 1_000.0, 1.0), None);
+    }
+
+    #[test]
+    fn leaders_are_elected_at_the_rate_of_their_stake() {
+        let keys = PraosKeys::generate();
+        let nonce = [1u8; 32];
+        let led = (0..2_000)
+            .filter(|&slot| praos_is_leader(slot, &nonce, &keys, 1.0, 2.0, 0.5).is_some())
+            .count();
+        // 1 - (1 - 1/2)^(1/2) ≈ 0.29 of the slots.
+        assert!((480..700).contains(&led), "led {led} of 2000 slots");
+    }
+
+    #[test]
+    fn headers_are_signed_with_evolving_kes_keys_under_an_opcert() {
+        let cold = ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]);
+        let mut keys = PraosKeys::new(&cold, 1, 10);
+        let pool = keys.pool_id();
+        let mut signed = header(12 * 100, &pool, &keys, vec![0u8; vrf::PROOF_SIZE]);
+        keys.sign_header(&mut signed, 12).unwrap();
+        assert_eq!(keys.kes.period(), 2);
+        assert_eq!(validate_kes(&signed, 12, 0), Ok(1));
+        assert_eq!(validate_kes(&signed, 12, 1), Ok(1));
+        // The signature covers the header body, at the KES period of the slot.
+        assert_eq!(
+            validate_kes(&signed, 13, 0),
+            Err(ConsensusError::Kes(kes::KesError::InvalidSignature))
+        );
+        let mut tampered = signed.clone();
+        tampered.slot += 1;
+        assert_eq!(
+            validate_kes(&tampered, 12, 0),
+            Err(ConsensusError::Kes(kes::KesError::InvalidSignature))
+        );
+        // The key has erased its past periods.
+        let mut late = signed.clone();
+        assert_eq!(
+            keys.sign_header(&mut late, 11),
+            Err(ConsensusError::Kes(kes::KesError::KeyEvolved(1)))
+        );
+        // Certificates are only valid for MAX_KES_EVOLUTIONS periods from their start.
+        assert_eq!(
+            validate_kes(&signed, 9, 0),
+            Err(ConsensusError::KesPeriodBeforeStart {
+                start: 10,
+                current: 9
+            })
+        );
+        assert_eq!(
+            validate_kes(&signed, 10 + MAX_KES_EVOLUTIONS, 0),
+            Err(ConsensusError::OpCertExpired {
+                start: 10,
+                current: 72
+            })
+        );
+        assert_eq!(
+            keys.sign_header(&mut late, 72),
+            Err(ConsensusError::OpCertExpired {
+                start: 10,
+                current: 72
+            })
+        );
+        // Once a pool used counter 2, its certificates with counter 1 are replays.
+        assert_eq!(
+            validate_kes(&signed, 12, 2),
+            Err(ConsensusError::OpCertReplayed {
+                pool: pool.clone(),
+                counter: 1,
+                last: 2
+            })
+        );
+        // A renewed certificate is the next issue after the pool's last one.
+        let mut renewed = PraosKeys::new(&cold, 3, 10);
+        let mut skipped = header(12 * 100, &pool, &renewed, vec![0u8; vrf::PROOF_SIZE]);
+        renewed.sign_header(&mut skipped, 12).unwrap();
+        assert_eq!(validate_kes(&skipped, 12, 2), Ok(3));
+        assert_eq!(
+            validate_kes(&skipped, 12, 1),
+            Err(ConsensusError::OpCertCounterTooHigh {
+                pool: pool.clone(),
+                counter: 3,
+                last: 1
+            })
+        );
+        // The certificate must be the pool's own, and signed by its cold key.
+        let mut other_pool = signed.clone();
+        other_pool.leader = PraosKeys::generate().pool_id();
+        assert_eq!(
+            validate_kes(&other_pool, 12, 0),
+            Err(ConsensusError::WrongIssuer(other_pool.leader.clone()))
+        );
+        let mut forged = signed.clone();
+        forged.opcert.counter = 5;
+        assert_eq!(
+            validate_kes(&forged, 12, 0),
+            Err(ConsensusError::InvalidOpCert(pool.clone()))
+        );
+        // A stolen KES key cannot sign under a certificate for another key.
+        let thief = PraosKeys::new(&cold, 1, 10);
+        let mut stolen = signed;
+        stolen.kes_signature = thief.kes.sign(&stolen.body_bytes()).to_vec();
+        assert!(validate_kes(&stolen, 10, 0).is_err());
+    }
+
+    #[test]
+    fn received_blocks_are_applied_only_with_a_valid_header() {
+        let cold = ed25519_dalek::SigningKey::from_bytes(&[2u8; 32]);
+        let mut keys = PraosKeys::new(&cold, 0, 0);
+        let pool = keys.pool_id();
+        // With f = 1 and all the stake, the pool leads every slot.
+        let mut genesis = LedgerState::default();
+        genesis.params.active_slot_coeff = Rational::new(1, 1);
+        genesis.snapshots.set = leader_distribution(&pool, &keys, 1_000, 1_000);
+        let mut ledger = Ledger::from_genesis(genesis, Tracer::default());
+        let params = ledger.protocol_parameters().clone();
+        let state = ConsensusState::new(10, PraosKeys::generate(), 0.0, 1_000.0, params);
+
+        let proof = praos_is_leader(1, &ledger.epoch_nonce(0), &keys, 1.0, 1.0, 1.0).unwrap();
+        let mut block = Block::new(1, header(1, &pool, &keys, proof.to_vec()), vec![]);
+        let kes_period = ledger.protocol_parameters().kes_period_of(1);
+        keys.sign_header(&mut block.header, kes_period).unwrap();
+        let mut forged = block.clone();
+        forged.header.kes_signature[0] ^= 1;
+        assert!(matches!(
+            state.receive_block(&forged, &mut ledger),
+            Err(ConsensusError::Kes(_))
+        ));
+        assert_eq!(ledger.tip(), None);
+        assert_eq!(state.receive_block(&block, &mut ledger), Ok(()));
+        assert_eq!(ledger.tip().map(|tip| tip.hash), Some(block.hash()));
+        // A block must be in a later slot than the tip, and in the epoch of its slot.
+        assert_eq!(
+            state.receive_block(&block, &mut ledger),
+            Err(ConsensusError::Ledger(LedgerError::SlotNotIncreasing {
+                slot: 1,
+                tip_slot: 1
+            }))
+        );
+        let mut mislabelled = block.clone();
+        mislabelled.header.slot = 2;
+        mislabelled.header.epoch = 1;
+        assert_eq!(
+            state.validate_header(&mislabelled.header, &ledger),
+            Err(ConsensusError::Ledger(LedgerError::WrongEpoch {
+                slot: 2,
+                epoch: 1,
+                expected: 0
+            }))
+        );
     }
 }
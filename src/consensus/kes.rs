@@ -0,0 +1,287 @@
+//! Sum6KES key-evolving signatures (Sum composition over Ed25519), which pools sign block headers
+//! with.
+//!
+//! A Sum6 key is a binary tree of depth 6 whose 64 leaves are Ed25519 keys, one per KES period.
+//! A node's verification key is the Blake2b-256 hash of its children's, and its seed expands into
+//! theirs as `Blake2b-256(1 || seed)` and `Blake2b-256(2 || seed)`. A signature at period `t` is
+//! the leaf's Ed25519 signature followed by the children's verification keys of each node on the
+//! path to it, deepest first: 64 + 6 × 64 bytes.
+//!
+//! The signing key only holds the current leaf, the verification keys along its path and the
+//! seeds of the right subtrees not yet entered. Evolving to the next period drops the current
+//! leaf and the seed it came from, so a key stolen at period `t` cannot sign for earlier periods.
+
+use crate::ledger::blake2b_256;
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use std::fmt;
+use thiserror::Error;
+
+/// Depth of the tree.
+pub const DEPTH: u32 = 6;
+/// Number of periods a key can sign in.
+pub const TOTAL_PERIODS: u32 = 1 << DEPTH;
+/// Size of a signature.
+pub const SIGNATURE_SIZE: usize = 64 + DEPTH as usize * 64;
+
+/// KES verification key, the root of the tree.
+pub type VerificationKey = [u8; 32];
+/// KES signature: the leaf signature and the verification key pairs up to the root.
+pub type KesSignature = [u8; SIGNATURE_SIZE];
+
+type Seed = [u8; 32];
+
+/// Errors of KES key evolution and signature verification.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KesError {
+    /// The key has reached its last period and cannot evolve further.
+    #[error("KES key has evolved past its last period")]
+    KeyExpired,
+    /// The key has evolved past the period, and can no longer sign in it.
+    #[error("KES key has evolved past period {0}")]
+    KeyEvolved(u64),
+    /// The period is outside the tree.
+    #[error("KES period {0} is outside the {TOTAL_PERIODS} periods of the key")]
+    PeriodOutOfRange(u64),
+    /// The verification key has the wrong size, or a leaf key is not a curve point.
+    #[error("Invalid KES verification key")]
+    InvalidKey,
+    /// The signature has the wrong size.
+    #[error("Malformed KES signature")]
+    MalformedSignature,
+    /// The signature is not a signature of the message under the key at the period.
+    #[error("KES signature does not verify")]
+    InvalidSignature,
+}
+
+/// Children's verification keys of a node on the path to the current leaf.
+#[derive(Clone)]
+struct Level {
+    /// Seed of the right child, until the key evolves into it
+    right_seed: Option<Seed>,
+    left: VerificationKey,
+    right: VerificationKey,
+}
+
+/// KES signing key at its current period.
+#[derive(Clone)]
+pub struct SigningKey {
+    period: u32,
+    leaf: ed25519_dalek::SigningKey,
+    /// Nodes from the root down to the leaf's parent
+    levels: Vec<Level>,
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey")
+            .field(
+                "verification_key",
+                &crate::chaindb::hex(&self.verification_key()),
+            )
+            .field("period", &self.period)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// Key of a 32-byte seed, at period 0.
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        let mut levels = Vec::with_capacity(DEPTH as usize);
+        let leaf = grow(*seed, DEPTH, &mut levels);
+        SigningKey {
+            period: 0,
+            leaf,
+            levels,
+        }
+    }
+
+    /// Key of a random seed, at period 0.
+    pub fn generate() -> Self {
+        Self::from_seed(&rand::random())
+    }
+
+    /// Verification key, the same at every period.
+    pub fn verification_key(&self) -> VerificationKey {
+        let root = &self.levels[0];
+        hash_pair(&root.left, &root.right)
+    }
+
+    /// Current period, counted from the key's creation.
+    pub fn period(&self) -> u32 {
+        self.period
+    }
+
+    /// Evolve the key to the next period, erasing the key of the current one.
+    pub fn update(&mut self) -> Result<(), KesError> {
+        let next = self.period + 1;
+        if next >= TOTAL_PERIODS {
+            return Err(KesError::KeyExpired);
+        }
+        // The next leaf is the leftmost of the right subtree of the node where the path turns
+        // right, the level of the lowest set bit of the period.
+        let height = next.trailing_zeros();
+        let index = (DEPTH - 1 - height) as usize;
+        let seed = self.levels[index]
+            .right_seed
+            .take()
+            .ok_or(KesError::KeyExpired)?;
+        self.levels.truncate(index + 1);
+        self.leaf = grow(seed, height, &mut self.levels);
+        self.period = next;
+        Ok(())
+    }
+
+    /// Signature of `message` at the current period.
+    pub fn sign(&self, message: &[u8]) -> KesSignature {
+        let mut signature = [0u8; SIGNATURE_SIZE];
+        signature[..64].copy_from_slice(&self.leaf.sign(message).to_bytes());
+        for (i, level) in self.levels.iter().rev().enumerate() {
+            let at = 64 + 64 * i;
+            signature[at..at + 32].copy_from_slice(&level.left);
+            signature[at + 32..at + 64].copy_from_slice(&level.right);
+        }
+        signature
+    }
+}
+
+/// Check that `signature` is a signature of `message` at `period` under `verification_key`.
+pub fn verify(
+    verification_key: &[u8],
+    period: u64,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), KesError> {
+    let mut key: VerificationKey = verification_key
+        .try_into()
+        .map_err(|_| KesError::InvalidKey)?;
+    if signature.len() != SIGNATURE_SIZE {
+        return Err(KesError::MalformedSignature);
+    }
+    if period >= TOTAL_PERIODS as u64 {
+        return Err(KesError::PeriodOutOfRange(period));
+    }
+    // Walk from the root, whose pair ends the signature, down to the leaf of the period.
+    for height in (0..DEPTH as usize).rev() {
+        let at = 64 + 64 * height;
+        let (left, right) = (&signature[at..at + 32], &signature[at + 32..at + 64]);
+        if hash_pair(left, right) != key {
+            return Err(KesError::InvalidSignature);
+        }
+        let child = if period >> height & 1 == 1 {
+            right
+        } else {
+            left
+        };
+        key.copy_from_slice(child);
+    }
+    let leaf = VerifyingKey::from_bytes(&key).map_err(|_| KesError::InvalidKey)?;
+    let leaf_signature =
+        Signature::from_slice(&signature[..64]).map_err(|_| KesError::MalformedSignature)?;
+    leaf.verify(message, &leaf_signature)
+        .map_err(|_| KesError::InvalidSignature)
+}
+
+/// Verification key of a node: the hash of its children's.
+fn hash_pair(left: &[u8], right: &[u8]) -> VerificationKey {
+    blake2b_256(&[left, right].concat())
+}
+
+/// Seeds of the left and right children of a node.
+fn expand_seed(seed: &Seed) -> (Seed, Seed) {
+    (
+        blake2b_256(&[&[1u8][..], seed].concat()),
+        blake2b_256(&[&[2u8][..], seed].concat()),
+    )
+}
+
+/// Verification key of the subtree of `height` grown from `seed`.
+fn subtree_key(seed: &Seed, height: u32) -> VerificationKey {
+    if height == 0 {
+        return ed25519_dalek::SigningKey::from_bytes(seed)
+            .verifying_key()
+            .to_bytes();
+    }
+    let (left, right) = expand_seed(seed);
+    hash_pair(
+        &subtree_key(&left, height - 1),
+        &subtree_key(&right, height - 1),
+    )
+}
+
+/// Append the nodes of the subtree of `height` grown from `seed` on the path to its leftmost
+/// leaf to `levels`, and return the leaf's key.
+fn grow(mut seed: Seed, height: u32, levels: &mut Vec<Level>) -> ed25519_dalek::SigningKey {
+    for height in (1..=height).rev() {
+        let (left, right) = expand_seed(&seed);
+        levels.push(Level {
+            right_seed: Some(right),
+            left: subtree_key(&left, height - 1),
+            right: subtree_key(&right, height - 1),
+        });
+        seed = left;
+    }
+    ed25519_dalek::SigningKey::from_bytes(&seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_sign_in_each_of_their_periods_until_they_expire() {
+        let mut key = SigningKey::from_seed(&[5u8; 32]);
+        let vkey = key.verification_key();
+        for period in 0..TOTAL_PERIODS as u64 {
+            assert_eq!(key.period() as u64, period);
+            assert_eq!(key.verification_key(), vkey);
+            let signature = key.sign(b"header");
+            assert_eq!(verify(&vkey, period, b"header", &signature), Ok(()));
+            // A signature only verifies at its own period.
+            let other = (period + 1) % TOTAL_PERIODS as u64;
+            assert_eq!(
+                verify(&vkey, other, b"header", &signature),
+                Err(KesError::InvalidSignature)
+            );
+            if period + 1 < TOTAL_PERIODS as u64 {
+                key.update().unwrap();
+            }
+        }
+        assert_eq!(key.update(), Err(KesError::KeyExpired));
+        assert_eq!(key.period(), TOTAL_PERIODS - 1);
+    }
+
+    #[test]
+    fn signatures_only_verify_for_their_key_and_message() {
+        let mut key = SigningKey::from_seed(&[6u8; 32]);
+        for _ in 0..37 {
+            key.update().unwrap();
+        }
+        let vkey = key.verification_key();
+        let signature = key.sign(b"header");
+        assert_eq!(verify(&vkey, 37, b"header", &signature), Ok(()));
+        assert_eq!(
+            verify(&vkey, 37, b"other header", &signature),
+            Err(KesError::InvalidSignature)
+        );
+        let stranger = SigningKey::from_seed(&[7u8; 32]).verification_key();
+        assert_eq!(
+            verify(&stranger, 37, b"header", &signature),
+            Err(KesError::InvalidSignature)
+        );
+        assert_eq!(
+            verify(&vkey, 37, b"header", &signature[..64]),
+            Err(KesError::MalformedSignature)
+        );
+        assert_eq!(
+            verify(&vkey, 64, b"header", &signature),
+            Err(KesError::PeriodOutOfRange(64))
+        );
+        // Keys grown from the same seed agree on every period.
+        let mut again = SigningKey::from_seed(&[6u8; 32]);
+        assert_eq!(again.verification_key(), vkey);
+        for _ in 0..37 {
+            again.update().unwrap();
+        }
+        assert_eq!(again.sign(b"header"), signature);
+    }
+}
@@ -0,0 +1,152 @@
+//! Epoch nonces, the randomness Praos mixes into the VRF input of every slot.
+//!
+//! Each block contributes its VRF output to the evolving nonce. The candidate nonce follows the
+//! evolving nonce until the last [stabilisation window][window] of an epoch, then stays put
+//! so that the next epoch's nonce is settled before anyone can see the stake distribution it
+//! will be used with. At the epoch boundary, the new epoch nonce is the candidate combined with
+//! the previous-block hash of the last block of the epoch before, and with the extra entropy
+//! protocol parameter.
+//!
+//! [window]: crate::ledger::ProtocolParameters::randomness_stabilisation_window
+
+use super::{NEUTRAL_NONCE, Nonce, vrf};
+use crate::ledger::{BlockHeader, blake2b_256};
+use serde::{Deserialize, Serialize};
+
+/// Combine two nonces (`⭒`): Blake2b-256 of both, with the neutral nonce as identity.
+pub fn combine(a: &Nonce, b: &Nonce) -> Nonce {
+    if *a == NEUTRAL_NONCE {
+        *b
+    } else if *b == NEUTRAL_NONCE {
+        *a
+    } else {
+        blake2b_256(&[&a[..], b].concat())
+    }
+}
+
+/// Nonce contributed by a VRF output: Blake2b-256 of Blake2b-256 of `"N"` and the output.
+pub fn vrf_nonce(output: &vrf::Output) -> Nonce {
+    blake2b_256(&blake2b_256(&[&b"N"[..], output].concat()))
+}
+
+/// Nonces of the chain at its tip.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceState {
+    /// Nonce of the tip's epoch
+    pub epoch_nonce: Nonce,
+    /// All VRF outputs so far, combined
+    pub evolving_nonce: Nonce,
+    /// Evolving nonce as of the start of the stabilisation window of the tip's epoch
+    pub candidate_nonce: Nonce,
+    /// Previous-block hash of the tip
+    pub lab_nonce: Nonce,
+    /// Previous-block hash of the last block of the epoch before the tip's
+    pub last_epoch_block_nonce: Nonce,
+}
+
+impl NonceState {
+    /// Nonce of the epoch after the tip's, given the extra entropy in effect.
+    pub fn next_epoch_nonce(&self, extra_entropy: &Nonce) -> Nonce {
+        combine(
+            &combine(&self.candidate_nonce, &self.last_epoch_block_nonce),
+            extra_entropy,
+        )
+    }
+
+    /// Cross into the next epoch.
+    pub fn tick(&mut self, extra_entropy: &Nonce) {
+        self.epoch_nonce = self.next_epoch_nonce(extra_entropy);
+        self.last_epoch_block_nonce = self.lab_nonce;
+    }
+
+    /// Fold `header` into the nonces. Its slot is stable if it lies before the last
+    /// `stability_window` slots of the epoch of `epoch_length` slots it falls in; the epoch is
+    /// derived from the slot, not taken from the header. A proof that has no output contributes
+    /// nothing; consensus rejects such headers before they reach the ledger.
+    pub fn update(&mut self, header: &BlockHeader, stability_window: u64, epoch_length: u64) {
+        if let Ok(output) = vrf::proof_to_hash(&header.vrf_proof) {
+            self.evolving_nonce = combine(&self.evolving_nonce, &vrf_nonce(&output));
+        }
+        let epoch_length = epoch_length.max(1);
+        let next_epoch = (header.slot / epoch_length + 1).saturating_mul(epoch_length);
+        if header.slot.saturating_add(stability_window) < next_epoch {
+            self.candidate_nonce = self.evolving_nonce;
+        }
+        self.lab_nonce = header.prev_hash.unwrap_or(NEUTRAL_NONCE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(slot: u64, prev_hash: Option<Nonce>, seed: u8) -> BlockHeader {
+        let (proof, _) = vrf::SigningKey::from_seed(&[seed; 32]).prove(&slot.to_be_bytes());
+        BlockHeader {
+            slot,
+            epoch: 0,
+            leader: "pool".to_string(),
+            issuer_vkey: vec![],
+            vrf_vkey: vec![],
+            vrf_proof: proof.to_vec(),
+            opcert: Default::default(),
+            kes_signature: vec![],
+            prev_hash,
+            body_hash: [0u8; 32],
+        }
+    }
+
+    #[test]
+    fn the_neutral_nonce_combines_as_identity() {
+        let a = [1u8; 32];
+        let b = [2u8; 32];
+        assert_eq!(combine(&NEUTRAL_NONCE, &a), a);
+        assert_eq!(combine(&a, &NEUTRAL_NONCE), a);
+        assert_eq!(combine(&NEUTRAL_NONCE, &NEUTRAL_NONCE), NEUTRAL_NONCE);
+        assert_ne!(combine(&a, &b), combine(&b, &a));
+    }
+
+    #[test]
+    fn the_candidate_nonce_freezes_at_the_stability_window() {
+        let mut nonces = NonceState::default();
+        // Epochs of 100 slots, the last 30 of which are in the window.
+        nonces.update(&header(10, None, 1), 30, 100);
+        assert_ne!(nonces.evolving_nonce, NEUTRAL_NONCE);
+        assert_eq!(nonces.candidate_nonce, nonces.evolving_nonce);
+        nonces.update(&header(69, Some([7u8; 32]), 2), 30, 100);
+        let frozen = nonces.evolving_nonce;
+        assert_eq!(nonces.candidate_nonce, frozen);
+        nonces.update(&header(70, Some([8u8; 32]), 3), 30, 100);
+        assert_ne!(nonces.evolving_nonce, frozen);
+        assert_eq!(nonces.candidate_nonce, frozen);
+        assert_eq!(nonces.lab_nonce, [8u8; 32]);
+        // A malformed proof leaves the evolving nonce as it was.
+        let evolving = nonces.evolving_nonce;
+        let mut malformed = header(71, Some([9u8; 32]), 4);
+        malformed.vrf_proof.truncate(10);
+        nonces.update(&malformed, 30, 100);
+        assert_eq!(nonces.evolving_nonce, evolving);
+        // The window is the one of the epoch the slot falls in, whatever the header claims.
+        nonces.update(&header(110, Some([9u8; 32]), 5), 30, 100);
+        assert_ne!(nonces.evolving_nonce, evolving);
+        assert_eq!(nonces.candidate_nonce, nonces.evolving_nonce);
+    }
+
+    #[test]
+    fn epoch_nonces_mix_the_candidate_previous_epoch_block_and_extra_entropy() {
+        let mut nonces = NonceState::default();
+        nonces.update(&header(10, Some([7u8; 32]), 1), 30, 100);
+        let candidate = nonces.candidate_nonce;
+        // No block of an earlier epoch yet: the first epoch nonce is the candidate alone.
+        nonces.tick(&NEUTRAL_NONCE);
+        assert_eq!(nonces.epoch_nonce, candidate);
+        assert_eq!(nonces.last_epoch_block_nonce, [7u8; 32]);
+        // The next one also takes in the last block of the epoch before, and extra entropy.
+        let entropy = [3u8; 32];
+        let expected = combine(&combine(&candidate, &[7u8; 32]), &entropy);
+        assert_eq!(nonces.next_epoch_nonce(&entropy), expected);
+        nonces.tick(&entropy);
+        assert_eq!(nonces.epoch_nonce, expected);
+        assert_ne!(nonces.next_epoch_nonce(&NEUTRAL_NONCE), expected);
+    }
+}
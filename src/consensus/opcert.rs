@@ -0,0 +1,71 @@
+//! Operational certificates, which bind a pool's KES key to its cold key.
+//!
+//! The cold key identifies the pool (the pool id is its key hash) and stays offline. It signs
+//! `hot_vkey || counter || kes_period`, the integers as 8 big-endian bytes, to let the KES key
+//! sign headers from `kes_period` on. A pool replaces a compromised KES key by issuing a
+//! certificate with a higher counter; headers under a lower counter are then rejected.
+
+use super::kes;
+use crate::ledger::OperationalCert;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+/// Certificate by `cold_key` of `hot_vkey`, valid from `kes_period`, with issue number `counter`.
+pub fn issue(
+    cold_key: &SigningKey,
+    hot_vkey: &kes::VerificationKey,
+    counter: u64,
+    kes_period: u64,
+) -> OperationalCert {
+    OperationalCert {
+        hot_vkey: hot_vkey.to_vec(),
+        counter,
+        kes_period,
+        sigma: cold_key
+            .sign(&signable(hot_vkey, counter, kes_period))
+            .to_bytes()
+            .to_vec(),
+    }
+}
+
+/// Whether `cert` is signed by `cold_vkey`. Keys and signatures of the wrong length never verify.
+pub fn verify(cert: &OperationalCert, cold_vkey: &[u8]) -> bool {
+    let Ok(cold_vkey) = <&[u8; 32]>::try_from(cold_vkey) else {
+        return false;
+    };
+    let Ok(sigma) = Signature::from_slice(&cert.sigma) else {
+        return false;
+    };
+    let message = signable(&cert.hot_vkey, cert.counter, cert.kes_period);
+    VerifyingKey::from_bytes(cold_vkey).is_ok_and(|key| key.verify(&message, &sigma).is_ok())
+}
+
+/// Bytes the cold key signs.
+fn signable(hot_vkey: &[u8], counter: u64, kes_period: u64) -> Vec<u8> {
+    [hot_vkey, &counter.to_be_bytes(), &kes_period.to_be_bytes()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificates_only_verify_under_their_cold_key_unchanged() {
+        let cold = SigningKey::from_bytes(&[1u8; 32]);
+        let hot = kes::SigningKey::from_seed(&[2u8; 32]).verification_key();
+        let cert = issue(&cold, &hot, 3, 40);
+        let cold_vkey = cold.verifying_key().to_bytes();
+        assert!(verify(&cert, &cold_vkey));
+        let other = SigningKey::from_bytes(&[3u8; 32])
+            .verifying_key()
+            .to_bytes();
+        assert!(!verify(&cert, &other));
+        assert!(!verify(&cert, &cold_vkey[..31]));
+        // Raising the counter or moving the start period voids the signature.
+        let mut bumped = cert.clone();
+        bumped.counter = 4;
+        assert!(!verify(&bumped, &cold_vkey));
+        let mut moved = cert;
+        moved.kes_period = 39;
+        assert!(!verify(&moved, &cold_vkey));
+    }
+}
@@ -0,0 +1,421 @@
+//! ECVRF-ED25519-SHA512-Elligator2 verifiable random function (draft-irtf-cfrg-vrf-03), the VRF
+//! Praos elects slot leaders with.
+//!
+//! A VRF key pair is an Ed25519 key pair. Proving an input `alpha` gives an 80-byte proof,
+//! `Gamma (32) || c (16) || s (32)`, from which anyone holding the verification key can check
+//! and recover the 64-byte output. Inputs are hashed to the curve with Elligator 2 (suite
+//! `0x04`), as in the libsodium fork the Haskell node uses, so proofs and outputs match its own.
+
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+use std::fmt;
+use thiserror::Error;
+
+/// Size of a proof.
+pub const PROOF_SIZE: usize = 80;
+/// Size of an output.
+pub const OUTPUT_SIZE: usize = 64;
+
+/// VRF proof, `Gamma || c || s`.
+pub type Proof = [u8; PROOF_SIZE];
+/// VRF output, the hash of `Gamma` multiplied by the cofactor.
+pub type Output = [u8; OUTPUT_SIZE];
+
+/// Suite string of ECVRF-ED25519-SHA512-Elligator2.
+const SUITE: u8 = 0x04;
+
+/// Errors of VRF proof verification.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VrfError {
+    /// The verification key is not a point of the curve, or one of small order.
+    #[error("Invalid VRF verification key")]
+    InvalidKey,
+    /// The proof has the wrong size, or `Gamma` or `s` do not decode.
+    #[error("Malformed VRF proof")]
+    MalformedProof,
+    /// The proof is not the proof of the input under the key.
+    #[error("VRF proof does not verify")]
+    InvalidProof,
+}
+
+/// VRF signing key: a 32-byte seed, expanded as an Ed25519 secret key.
+#[derive(Clone)]
+pub struct SigningKey {
+    scalar: Scalar,
+    nonce_prefix: [u8; 32],
+    verification_key: [u8; 32],
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey")
+            .field(
+                "verification_key",
+                &crate::chaindb::hex(&self.verification_key),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// Key of a 32-byte seed.
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        let expanded = Sha512::digest(seed);
+        let mut scalar = [0u8; 32];
+        scalar.copy_from_slice(&expanded[..32]);
+        scalar[0] &= 248;
+        scalar[31] &= 127;
+        scalar[31] |= 64;
+        let scalar = Scalar::from_bytes_mod_order(scalar);
+        let mut nonce_prefix = [0u8; 32];
+        nonce_prefix.copy_from_slice(&expanded[32..]);
+        SigningKey {
+            scalar,
+            nonce_prefix,
+            verification_key: EdwardsPoint::mul_base(&scalar).compress().to_bytes(),
+        }
+    }
+
+    /// Key of a random seed.
+    pub fn generate() -> Self {
+        Self::from_seed(&rand::random())
+    }
+
+    /// Verification key, the Ed25519 public key of the seed.
+    pub fn verification_key(&self) -> [u8; 32] {
+        self.verification_key
+    }
+
+    /// Proof of `alpha` and the output it proves.
+    pub fn prove(&self, alpha: &[u8]) -> (Proof, Output) {
+        let h = hash_to_curve(&self.verification_key, alpha);
+        let gamma = self.scalar * h;
+        let k = Scalar::from_bytes_mod_order_wide(
+            &Sha512::new()
+                .chain_update(self.nonce_prefix)
+                .chain_update(h.compress().as_bytes())
+                .finalize()
+                .into(),
+        );
+        let c = hash_points(&[h, gamma, EdwardsPoint::mul_base(&k), k * h]);
+        let s = challenge(&c) * self.scalar + k;
+        let mut proof = [0u8; PROOF_SIZE];
+        proof[..32].copy_from_slice(gamma.compress().as_bytes());
+        proof[32..48].copy_from_slice(&c);
+        proof[48..].copy_from_slice(s.as_bytes());
+        (proof, output(&gamma))
+    }
+}
+
+/// Check that `proof` is the proof of `alpha` under `verification_key`, and return its output.
+pub fn verify(verification_key: &[u8], proof: &[u8], alpha: &[u8]) -> Result<Output, VrfError> {
+    let key: [u8; 32] = verification_key
+        .try_into()
+        .map_err(|_| VrfError::InvalidKey)?;
+    let y = CompressedEdwardsY(key)
+        .decompress()
+        .filter(|y| !y.is_small_order())
+        .ok_or(VrfError::InvalidKey)?;
+    let (gamma, c, s) = decode_proof(proof)?;
+    let h = hash_to_curve(&key, alpha);
+    let c_scalar = challenge(&c);
+    let u = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-c_scalar, &y, &s);
+    let v = s * h - c_scalar * gamma;
+    if hash_points(&[h, gamma, u, v]) != c {
+        return Err(VrfError::InvalidProof);
+    }
+    Ok(output(&gamma))
+}
+
+/// Output of a proof, without verifying it.
+pub fn proof_to_hash(proof: &[u8]) -> Result<Output, VrfError> {
+    decode_proof(proof).map(|(gamma, _, _)| output(&gamma))
+}
+
+/// Split a proof into `Gamma`, `c` and a canonical `s`.
+fn decode_proof(proof: &[u8]) -> Result<(EdwardsPoint, [u8; 16], Scalar), VrfError> {
+    if proof.len() != PROOF_SIZE {
+        return Err(VrfError::MalformedProof);
+    }
+    let gamma = CompressedEdwardsY::from_slice(&proof[..32])
+        .ok()
+        .and_then(|gamma| gamma.decompress())
+        .ok_or(VrfError::MalformedProof)?;
+    let mut c = [0u8; 16];
+    c.copy_from_slice(&proof[32..48]);
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&proof[48..]);
+    let s = Option::from(Scalar::from_canonical_bytes(s)).ok_or(VrfError::MalformedProof)?;
+    Ok((gamma, c, s))
+}
+
+/// The 16-byte challenge as a scalar.
+fn challenge(c: &[u8; 16]) -> Scalar {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(c);
+    Scalar::from_bytes_mod_order(bytes)
+}
+
+/// Challenge of the proof points: the first 16 bytes of `SHA512(suite || 0x02 || points)`.
+fn hash_points(points: &[EdwardsPoint; 4]) -> [u8; 16] {
+    let mut hasher = Sha512::new().chain_update([SUITE, 0x02]);
+    for point in points {
+        hasher.update(point.compress().as_bytes());
+    }
+    let mut c = [0u8; 16];
+    c.copy_from_slice(&hasher.finalize()[..16]);
+    c
+}
+
+/// Output of `Gamma`: `SHA512(suite || 0x03 || 8 * Gamma)`.
+fn output(gamma: &EdwardsPoint) -> Output {
+    Sha512::new()
+        .chain_update([SUITE, 0x03])
+        .chain_update(gamma.mul_by_cofactor().compress().as_bytes())
+        .finalize()
+        .into()
+}
+
+/// Hash `alpha` under `verification_key` to a point of the prime-order subgroup with Elligator 2:
+/// `r` is the first 32 bytes of `SHA512(suite || 0x01 || key || alpha)` without the sign bit,
+/// mapped to the Montgomery coordinate `u = -A / (1 + 2r^2)`, or `-u - A` when `u` is not that of
+/// a curve point, then to the Edwards point with a positive `x` and multiplied by the cofactor.
+fn hash_to_curve(verification_key: &[u8; 32], alpha: &[u8]) -> EdwardsPoint {
+    let digest = Sha512::new()
+        .chain_update([SUITE, 0x01])
+        .chain_update(verification_key)
+        .chain_update(alpha)
+        .finalize();
+    let mut r = [0u8; 32];
+    r.copy_from_slice(&digest[..32]);
+    r[31] &= 0x7f;
+    let r = Fe::from_bytes(&r);
+    let a = Fe([486_662, 0, 0, 0, 0]);
+    let r2 = r.mul(&r);
+    let u = a.mul(&Fe::ONE.add(&r2.add(&r2)).invert()).neg();
+    let e = u.mul(&u.mul(&u).add(&a.mul(&u)).add(&Fe::ONE));
+    let u = if e.is_square() { u } else { u.neg().sub(&a) };
+    // u = -1 has no Edwards point. libsodium maps it to y = 0, a point of order 4 that the
+    // cofactor clears.
+    MontgomeryPoint(u.to_bytes())
+        .to_edwards(0)
+        .map_or(EdwardsPoint::default(), |point| point.mul_by_cofactor())
+}
+
+/// Element of GF(2^255 - 19) in five 51-bit limbs, for the Elligator 2 map.
+#[derive(Clone, Copy)]
+struct Fe([u64; 5]);
+
+const LOW_51_BITS: u64 = (1 << 51) - 1;
+
+impl Fe {
+    const ONE: Fe = Fe([1, 0, 0, 0, 0]);
+
+    /// Element of 255 little-endian bits; the top bit is ignored.
+    fn from_bytes(bytes: &[u8; 32]) -> Fe {
+        let load = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        Fe([
+            load(0) & LOW_51_BITS,
+            (load(6) >> 3) & LOW_51_BITS,
+            (load(12) >> 6) & LOW_51_BITS,
+            (load(19) >> 1) & LOW_51_BITS,
+            (load(24) >> 12) & LOW_51_BITS,
+        ])
+    }
+
+    /// Canonical little-endian encoding.
+    fn to_bytes(self) -> [u8; 32] {
+        let mut limbs = Fe::carry(self.0).0;
+        // Subtract p if the element is at least p: q is 1 exactly when it is.
+        let mut q = (limbs[0] + 19) >> 51;
+        for limb in &limbs[1..] {
+            q = (limb + q) >> 51;
+        }
+        limbs[0] += 19 * q;
+        for i in 0..4 {
+            limbs[i + 1] += limbs[i] >> 51;
+            limbs[i] &= LOW_51_BITS;
+        }
+        limbs[4] &= LOW_51_BITS;
+        let mut bytes = [0u8; 32];
+        let (mut acc, mut bits, mut i) = (0u128, 0, 0);
+        for limb in limbs {
+            acc |= u128::from(limb) << bits;
+            bits += 51;
+            while bits >= 8 {
+                bytes[i] = acc as u8;
+                acc >>= 8;
+                bits -= 8;
+                i += 1;
+            }
+        }
+        bytes[i] = acc as u8;
+        bytes
+    }
+
+    /// Carry limbs of up to 64 bits into 51 bits, plus a small excess in the lowest.
+    fn carry(mut limbs: [u64; 5]) -> Fe {
+        let carries = limbs.map(|limb| limb >> 51);
+        for limb in &mut limbs {
+            *limb &= LOW_51_BITS;
+        }
+        limbs[0] += carries[4] * 19;
+        for i in 1..5 {
+            limbs[i] += carries[i - 1];
+        }
+        Fe(limbs)
+    }
+
+    fn add(&self, rhs: &Fe) -> Fe {
+        let (a, b) = (&self.0, &rhs.0);
+        Fe::carry([
+            a[0] + b[0],
+            a[1] + b[1],
+            a[2] + b[2],
+            a[3] + b[3],
+            a[4] + b[4],
+        ])
+    }
+
+    fn sub(&self, rhs: &Fe) -> Fe {
+        // Add 16p first so that limbs do not underflow.
+        let (a, b) = (&self.0, &rhs.0);
+        Fe::carry([
+            (a[0] + 36_028_797_018_963_664) - b[0],
+            (a[1] + 36_028_797_018_963_952) - b[1],
+            (a[2] + 36_028_797_018_963_952) - b[2],
+            (a[3] + 36_028_797_018_963_952) - b[3],
+            (a[4] + 36_028_797_018_963_952) - b[4],
+        ])
+    }
+
+    fn neg(&self) -> Fe {
+        Fe([0; 5]).sub(self)
+    }
+
+    fn mul(&self, rhs: &Fe) -> Fe {
+        let (a, b) = (&self.0, &rhs.0);
+        let m = |x: u64, y: u64| u128::from(x) * u128::from(y);
+        let b19 = [0, b[1] * 19, b[2] * 19, b[3] * 19, b[4] * 19];
+        let c = [
+            m(a[0], b[0]) + m(a[4], b19[1]) + m(a[3], b19[2]) + m(a[2], b19[3]) + m(a[1], b19[4]),
+            m(a[1], b[0]) + m(a[0], b[1]) + m(a[4], b19[2]) + m(a[3], b19[3]) + m(a[2], b19[4]),
+            m(a[2], b[0]) + m(a[1], b[1]) + m(a[0], b[2]) + m(a[4], b19[3]) + m(a[3], b19[4]),
+            m(a[3], b[0]) + m(a[2], b[1]) + m(a[1], b[2]) + m(a[0], b[3]) + m(a[4], b19[4]),
+            m(a[4], b[0]) + m(a[3], b[1]) + m(a[2], b[2]) + m(a[1], b[3]) + m(a[0], b[4]),
+        ];
+        let mut limbs = [0u64; 5];
+        let mut carry = 0u128;
+        for i in 0..5 {
+            let sum = c[i] + carry;
+            limbs[i] = (sum as u64) & LOW_51_BITS;
+            carry = sum >> 51;
+        }
+        limbs[0] += carry as u64 * 19;
+        limbs[1] += limbs[0] >> 51;
+        limbs[0] &= LOW_51_BITS;
+        Fe(limbs)
+    }
+
+    /// `self^exponent`, for a little-endian exponent.
+    fn pow(&self, exponent: &[u8; 32]) -> Fe {
+        let mut result = Fe::ONE;
+        for bit in (0..256).rev() {
+            result = result.mul(&result);
+            if (exponent[bit / 8] >> (bit % 8)) & 1 == 1 {
+                result = result.mul(self);
+            }
+        }
+        result
+    }
+
+    /// Inverse, `self^(p - 2)`; zero for zero.
+    fn invert(&self) -> Fe {
+        let mut exponent = [0xff; 32];
+        exponent[0] = 0xeb;
+        exponent[31] = 0x7f;
+        self.pow(&exponent)
+    }
+
+    /// Whether the element is a square, by Euler's criterion: `self^((p - 1) / 2)` is not -1.
+    fn is_square(&self) -> bool {
+        let mut exponent = [0xff; 32];
+        exponent[0] = 0xf6;
+        exponent[31] = 0x3f;
+        let minus_one = Fe([0; 5]).sub(&Fe::ONE).to_bytes();
+        self.pow(&exponent).to_bytes() != minus_one
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::cbor::from_hex;
+
+    #[test]
+    fn draft_03_test_vectors() {
+        // Appendix A.4 of draft-irtf-cfrg-vrf-03: (secret key, public key, alpha, pi, beta).
+        let vectors = [
+            (
+                "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+                "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+                "",
+                "b6b4699f87d56126c9117a7da55bd0085246f4c56dbc95d20172612e9d38e8d7ca65e573a126ed88d4e30a46f80a666854d675cf3ba81de0de043c3774f061560f55edc256a787afe701677c0f602900",
+                "5b49b554d05c0cd5a5325376b3387de59d924fd1e13ded44648ab33c21349a603f25b84ec5ed887995b33da5e3bfcb87cd2f64521c4c62cf825cffabbe5d31cc",
+            ),
+            (
+                "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+                "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+                "72",
+                "ae5b66bdf04b4c010bfe32b2fc126ead2107b697634f6f7337b9bff8785ee111200095ece87dde4dbe87343f6df3b107d91798c8a7eb1245d3bb9c5aafb093358c13e6ae1111a55717e895fd15f99f07",
+                "94f4487e1b2fec954309ef1289ecb2e15043a2461ecc7b2ae7d4470607ef82eb1cfa97d84991fe4a7bfdfd715606bc27e2967a6c557cfb5875879b671740b7d8",
+            ),
+            (
+                "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+                "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+                "af82",
+                "dfa2cba34b611cc8c833a6ea83b8eb1bb5e2ef2dd1b0c481bc42ff36ae7847f6ab52b976cfd5def172fa412defde270c8b8bdfbaae1c7ece17d9833b1bcf31064fff78ef493f820055b561ece45e1009",
+                "2031837f582cd17a9af9e0c7ef5a6540e3453ed894b62c293686ca3c1e319dde9d0aa489a4b59a9594fc2328bc3deff3c8a0929a369a72b1180a596e016b5ded",
+            ),
+        ];
+        for (sk, pk, alpha, pi, beta) in vectors {
+            let key = SigningKey::from_seed(&from_hex(sk).unwrap().try_into().unwrap());
+            let alpha = from_hex(alpha).unwrap();
+            assert_eq!(key.verification_key().to_vec(), from_hex(pk).unwrap());
+            let (proof, output) = key.prove(&alpha);
+            assert_eq!(proof.to_vec(), from_hex(pi).unwrap());
+            assert_eq!(output.to_vec(), from_hex(beta).unwrap());
+            assert_eq!(verify(&key.verification_key(), &proof, &alpha), Ok(output));
+        }
+    }
+
+    #[test]
+    fn proofs_only_verify_for_their_key_and_input() {
+        let key = SigningKey::from_seed(&[1; 32]);
+        let (proof, output) = key.prove(b"slot");
+        assert_eq!(proof_to_hash(&proof), Ok(output));
+        let vkey = key.verification_key();
+        assert_eq!(verify(&vkey, &proof, b"slot2"), Err(VrfError::InvalidProof));
+        let other = SigningKey::from_seed(&[2; 32]).verification_key();
+        assert_eq!(verify(&other, &proof, b"slot"), Err(VrfError::InvalidProof));
+        let mut tampered = proof;
+        tampered[40] ^= 1;
+        assert_eq!(
+            verify(&vkey, &tampered, b"slot"),
+            Err(VrfError::InvalidProof)
+        );
+        assert_eq!(
+            verify(&vkey, &proof[..79], b"slot"),
+            Err(VrfError::MalformedProof)
+        );
+        // The identity has small order.
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        assert_eq!(
+            verify(&identity, &proof, b"slot"),
+            Err(VrfError::InvalidKey)
+        );
+    }
+}
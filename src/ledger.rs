@@ -1,6 +1,6 @@
 impl Ledger {
     /// Returns a context object for compatibility (stub).
-    pub fn context(&self) -> () {
+    pub fn context(&self) {
         // TODO: Replace with real context type if needed
     }
 }
@@ -9,10 +9,53 @@ impl Ledger {
 /// Handles block/tx validation, ledger state, and database operations.
 /// Implements the extended UTXO model and state transitions.
 use crate::chaindb::ChainDB;
+use crate::consensus::Nonce;
+use crate::consensus::nonce::NonceState;
 use crate::protocol::wallet;
 use crate::tracing::tracers::Tracer;
+use blake2::digest::consts::{U28, U32};
+use blake2::{Blake2b, Digest};
+use cbor::Cbor;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+pub mod address;
+pub mod cbor;
+pub mod error;
+pub mod governance;
+pub mod native_script;
+pub mod params;
+pub mod plutus;
+pub mod rewards;
+pub mod rules;
+pub mod stake;
+pub mod value;
+pub mod witness;
+
+pub use address::{Address, Credential, Network};
+pub use error::LedgerError;
+pub use governance::{
+    DRep, GovAction, GovActionId, Governance, ProposalProcedure, Vote, Voter, VotingProcedure,
+};
+pub use native_script::NativeScript;
+pub use params::{ParamUpdate, ProtocolParameters, Rational, Update};
+pub use plutus::{PlutusData, PlutusError};
+pub use rewards::RewardUpdate;
+pub use stake::{Snapshots, StakeSnapshot};
+pub use value::{MultiAsset, Value};
+pub use witness::VKeyWitness;
+
+/// Blake2b-256 hash identifying a block (the hash of its header) or a block body.
+pub type BlockHash = [u8; 32];
+
+/// Blake2b-256 hash of a transaction body, identifying the transaction.
+pub type TxId = [u8; 32];
+
+/// Blake2b-256 digest of `bytes`.
+pub(crate) fn blake2b_256(bytes: &[u8]) -> BlockHash {
+    Blake2b::<U32>::digest(bytes).into()
+}
 
 /// Represents a native asset (multi-asset support, Mary era)
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -27,17 +70,19 @@ pub struct Asset {
 pub struct StakePoolRegistration {
     /// Unique pool identifier (e.g., hash of the pool's cold key)
     pub pool_id: String,
-    /// Pool owner's reward address
-    pub owner: String,
-    /// Amount pledged by the pool owner (in ADA)
+    /// Blake2b-256 hash of the pool's VRF verification key, which proves its slot leadership
+    pub vrf_keyhash: BlockHash,
+    /// Stake credential of the pool owner, whose stake must cover the pledge
+    pub owner: Credential,
+    /// Amount pledged by the pool owner (in lovelace)
     pub pledge: u64,
-    /// Fixed cost per epoch (in ADA)
+    /// Fixed cost per epoch (in lovelace)
     pub cost: u64,
     /// Pool margin (fraction, e.g., 0.05 for 5%)
     pub margin: f64, // NOTE: f64 does not implement Eq, so remove Eq from derives
     /// Reward account for pool rewards
-    pub reward_account: String,
-    // TODO: Add metadata, relays, VRF key, etc.
+    pub reward_account: Credential,
+    // TODO: Add metadata, relays, etc.
 }
 
 /// Stake pool retirement certificate
@@ -52,18 +97,43 @@ pub struct StakePoolRetirement {
 /// Delegation certificate
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct DelegationCertificate {
-    /// Delegator's staking key (address)
-    pub delegator: String,
+    /// Delegator's stake credential
+    pub delegator: Credential,
     /// Pool identifier to delegate to
     pub pool_id: String,
 }
 
-/// Certificate for transaction inclusion (stake pool ops and delegation)
+/// DRep registration or retirement certificate (Conway)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DRepCertificate {
+    /// DRep credential
+    pub drep: Credential,
+    /// Deposit paid on registration, or refunded on retirement
+    pub deposit: u64,
+}
+
+/// Vote delegation certificate (Conway)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VoteDelegationCertificate {
+    /// Delegator's stake credential
+    pub delegator: Credential,
+    /// DRep or predefined voting option to delegate to
+    pub drep: DRep,
+}
+
+/// Certificate for transaction inclusion (stake pool ops, delegation and governance)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Certificate {
+    /// Register a stake credential, paying the key deposit
+    StakeRegistration(Credential),
+    /// Deregister a stake credential with an empty reward account, refunding its deposit
+    StakeDeregistration(Credential),
     PoolRegistration(StakePoolRegistration),
     PoolRetirement(StakePoolRetirement),
     Delegation(DelegationCertificate),
+    DRepRegistration(DRepCertificate),
+    DRepDeregistration(DRepCertificate),
+    VoteDelegation(VoteDelegationCertificate),
 }
 
 /// Stake pool parameters and state
@@ -75,19 +145,56 @@ pub struct StakePool {
 }
 
 /// Ledger state (e.g., UTXO set, stake distribution, etc.)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LedgerState {
-    pub utxos: HashMap<(u64, u32), TxOutput>, // (tx_id, index) -> output
-    pub stake_distribution: HashMap<String, u64>, // address -> stake
-    pub delegations: HashMap<String, String>, // delegator -> pool
+    pub utxos: HashMap<(TxId, u32), TxOutput>, // (tx_id, index) -> output
+    pub delegations: HashMap<Credential, String>, // delegator -> pool
     pub stake_pools: HashMap<String, StakePool>, // pool_id -> pool
     pub pool_retirements: HashMap<String, u64>, // pool_id -> retirement epoch
-    pub rewards: HashMap<String, u64>,        // reward address -> ADA
+    pub rewards: HashMap<Credential, u64>,     // reward account -> ADA
+    pub stake_keys: HashMap<Credential, u64>,  // registered stake credential -> deposit
+    pub deposited: u64,                        // lovelace held as deposits
+    pub fees: u64,                             // fee pot of the current epoch
+    pub params: ProtocolParameters,            // protocol parameters in effect
+    pub proposals: BTreeMap<String, ParamUpdate>, // proposer -> update proposed this epoch
+    pub genesis_delegates: BTreeSet<String>,   // key hashes of the update proposers
+    pub treasury: u64,                         // lovelace in the treasury
+    pub dreps: HashMap<Credential, u64>,       // DRep credential -> deposit
+    pub vote_delegations: HashMap<Credential, DRep>, // delegator -> DRep
+    pub governance: Governance,                // open governance actions, committee, constitution
+    pub snapshots: Snapshots,                  // mark/set/go stake distributions
+    pub reserves: u64,                         // lovelace not yet in circulation
+    pub blocks_made: HashMap<String, u64>,     // pool_id -> blocks made this epoch
+    pub opcert_counters: HashMap<String, u64>, // pool_id -> last operational certificate counter
+    pub reward_update: RewardUpdate,           // rewards paid at the next epoch boundary
+    pub nonces: NonceState,                    // epoch nonce and the nonces evolving into the next
+}
+
+/// Block the ledger state was last advanced to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tip {
+    pub id: u64,
+    pub slot: u64,
+    pub epoch: u64,
+    pub hash: BlockHash,
+}
+
+impl Tip {
+    /// Tip after applying `block`.
+    pub fn of(block: &Block) -> Self {
+        Tip {
+            id: block.id,
+            slot: block.header.slot,
+            epoch: block.header.epoch,
+            hash: block.hash(),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Ledger {
     state: LedgerState,
+    tip: Option<Tip>,
     pub tracer: crate::tracing::tracers::Tracer,
     // ... add more fields as needed
 }
@@ -96,17 +203,52 @@ impl Ledger {
     /// Initialize the ledger state
     pub fn new(tracer: Tracer) -> Self {
         Self {
-            state: LedgerState {
-                utxos: HashMap::new(),
-                stake_distribution: HashMap::new(),
-                delegations: HashMap::new(),
-                stake_pools: HashMap::new(),
-                pool_retirements: HashMap::new(),
-                rewards: HashMap::new(),
-            },
+            state: LedgerState::default(),
+            tip: None,
+            tracer,
+        }
+    }
+
+    /// Ledger at genesis with the state `genesis`: its UTXOs, stake pools and stake snapshots.
+    pub fn from_genesis(genesis: LedgerState, tracer: Tracer) -> Self {
+        Self {
+            state: genesis,
+            tip: None,
             tracer,
         }
     }
+
+    /// Block the ledger was last advanced to, or `None` at genesis.
+    pub fn tip(&self) -> Option<Tip> {
+        self.tip
+    }
+
+    /// Whether `block` extends the ledger tip and its body matches its header.
+    pub fn extends_tip(&self, block: &Block) -> bool {
+        block.header.prev_hash == self.tip.map(|t| t.hash) && block.body_matches()
+    }
+    /// Slot of the ledger tip, at which transactions are validated; 0 at genesis.
+    pub fn current_slot(&self) -> u64 {
+        self.tip.map_or(0, |t| t.slot)
+    }
+
+    /// Epoch of the ledger tip; 0 at genesis.
+    pub fn current_epoch(&self) -> u64 {
+        self.tip.map_or(0, |t| t.epoch)
+    }
+
+    /// Check a transaction against the ledger rules at `slot`, as the next one to be applied.
+    pub fn check_transaction(&self, tx: &Transaction, slot: u64) -> Result<(), LedgerError> {
+        if !tx.has_stateful_parts() && tx.update.is_none() && tx.is_valid {
+            self.state.validate_utxo(tx, slot)?;
+            self.state.validate_plutus_scripts(tx)
+        } else {
+            // Certificates, governance, update proposals and transactions marked invalid can only
+            // be checked by applying them.
+            self.state.clone().apply_tx(tx, slot, self.current_epoch())
+        }
+    }
+
     /// Validate a block and update ledger state, enforcing protocol rules
     pub async fn validate_block(
         &mut self,
@@ -117,14 +259,21 @@ impl Ledger {
         if block.header.slot == 0 || block.header.leader.is_empty() {
             return false;
         }
-        // TODO: Add real VRF/KES cryptographic checks here
-        // Check all transactions in the block using protocol-aware validation
+        // VRF proofs and KES signatures are checked by consensus
+        // Check all transactions in order against a scratch state, so later transactions may
+        // spend outputs of earlier ones but not the same inputs.
+        let mut state = self.state.clone();
         for tx in &block.transactions {
-            if !self.validate_transaction_with_protocol(tx, protocol).await {
+            if !protocol.validate_transaction(&wallet::Transaction {}).await {
+                return false;
+            }
+            if state
+                .apply_tx(tx, block.header.slot, block.header.epoch)
+                .is_err()
+            {
                 return false;
             }
         }
-        // In a full implementation, update UTXO, apply state, and verify block signature here.
         true
     }
     /// Validate a transaction
@@ -132,17 +281,9 @@ impl Ledger {
     pub async fn validate_transaction(
         &self,
         tx: &Transaction,
-        protocol: &crate::protocol::Protocol,
+        _protocol: &crate::protocol::Protocol,
     ) -> bool {
-        // Call protocol-level validation first
-        // FIXME: This is a stub to allow compilation. Real implementation must convert or use correct type.
-        // Skipping protocol-level validation for now
-        // Example: Check that all outputs are positive
-        if tx.outputs.iter().any(|o| o.amount == 0) {
-            return false;
-        }
-        // TODO: Check UTXO, signatures, double-spend, etc.
-        true
+        self.check_transaction(tx, self.current_slot()).is_ok()
     }
     /// Validate a transaction, enforcing both UTXO and protocol rules
     pub async fn validate_transaction_with_protocol(
@@ -156,12 +297,8 @@ impl Ledger {
         if !protocol.validate_transaction(&wallet_tx).await {
             return false;
         }
-        // Then, check UTXO and ledger-level rules
-        if tx.outputs.iter().any(|o| o.amount == 0) {
-            return false;
-        }
-        // TODO: Check UTXO, signatures, double-spend, etc.
-        true
+        // Then, check the UTXO rules at the tip
+        self.check_transaction(tx, self.current_slot()).is_ok()
     }
     /// Persist ledger state to disk
     #[allow(dead_code)]
@@ -174,39 +311,205 @@ impl Ledger {
         // TODO: Implement state loading
     }
 
-    /// Apply a transaction to the UTXO set (returns false if invalid)
-    pub fn apply_transaction(&mut self, tx: &Transaction) -> bool {
-        // Check all inputs exist in UTXO set
-        for input in &tx.inputs {
-            if !self.state.utxos.contains_key(&(input.prev_tx, input.index)) {
-                return false; // double-spend or missing input
+    /// Transactions of `txs` that apply in order on top of the tip at `slot`, for a block in
+    /// `epoch`, after crossing every epoch boundary since the tip. Invalid, underpaying or
+    /// conflicting transactions are left out; transactions whose Plutus scripts fail are marked
+    /// invalid, so that the block collects their collateral.
+    pub fn select_transactions(
+        &self,
+        txs: Vec<Transaction>,
+        slot: u64,
+        epoch: u64,
+    ) -> Vec<Transaction> {
+        let mut state = self.state.clone();
+        if let Some(tip) = self.tip {
+            state.cross_epochs(tip.epoch, epoch);
+        }
+        txs.into_iter()
+            .filter_map(|mut tx| {
+                // Only certificates and governance can fail after validation, leaving the state
+                // half-applied.
+                tx.is_valid = if !tx.has_stateful_parts() {
+                    state.apply_tx_or_collateral(&tx, slot, epoch).ok()?
+                } else {
+                    let mut next = state.clone();
+                    let valid = next.apply_tx_or_collateral(&tx, slot, epoch).ok()?;
+                    state = next;
+                    valid
+                };
+                Some(tx)
+            })
+            .collect()
+    }
+
+    /// Switch to the protocol parameters of `era` at a hard fork. Entering the current era again
+    /// keeps the parameters, including those adopted through update proposals.
+    pub fn enter_era(&mut self, era: &str) {
+        let params = ProtocolParameters::for_era(era);
+        if params.protocol_version > self.state.params.protocol_version {
+            self.state.params = params;
+        }
+    }
+
+    /// Set the genesis delegates, by key hash: the only proposers of protocol parameter updates.
+    pub fn set_genesis_delegates(&mut self, delegates: impl IntoIterator<Item = String>) {
+        self.state.genesis_delegates = delegates.into_iter().collect();
+    }
+
+    /// Protocol parameters in effect at the tip.
+    pub fn protocol_parameters(&self) -> &ProtocolParameters {
+        &self.state.params
+    }
+
+    /// Operational certificate counter of the last block of `pool_id`, or 0 before its first
+    /// block.
+    pub fn opcert_counter(&self, pool_id: &str) -> u64 {
+        self.state
+            .opcert_counters
+            .get(pool_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Stake distribution slot leaders of `epoch` are elected with: the set snapshot, or the one
+    /// that becomes it when a block of `epoch` takes the ledger across the boundaries since the
+    /// tip. Across one boundary that is the mark snapshot; across more, the snapshots of the
+    /// skipped epochs are taken on a copy of the state.
+    pub fn leader_stake_distribution(&self, epoch: u64) -> Cow<'_, StakeSnapshot> {
+        match self.tip {
+            Some(tip) if epoch > tip.epoch + 1 => {
+                let mut state = self.state.clone();
+                state.cross_epochs(tip.epoch, epoch);
+                Cow::Owned(state.snapshots.set)
             }
+            Some(tip) if epoch > tip.epoch => Cow::Borrowed(&self.state.snapshots.mark),
+            _ => Cow::Borrowed(&self.state.snapshots.set),
         }
-        // Remove spent inputs
-        for input in &tx.inputs {
-            self.state.utxos.remove(&(input.prev_tx, input.index));
+    }
+
+    /// Nonce slot leaders of `epoch` are elected with: the tip epoch's nonce, or the one derived
+    /// from it when a block of `epoch` takes the ledger across the boundaries since the tip, with
+    /// the extra entropy in effect at the tip.
+    pub fn epoch_nonce(&self, epoch: u64) -> Nonce {
+        let mut nonces = self.state.nonces.clone();
+        let boundaries = self.tip.map_or(0, |tip| epoch.saturating_sub(tip.epoch));
+        for _ in 0..boundaries {
+            nonces.tick(&self.state.params.extra_entropy);
         }
-        // Add new outputs
-        for (idx, output) in tx.outputs.iter().enumerate() {
-            self.state.utxos.insert((tx.id, idx as u32), output.clone());
+        nonces.epoch_nonce
+    }
+
+    /// Apply a transaction at `slot` under the UTXO rules. The state is unchanged on error.
+    pub fn apply_tx(&mut self, tx: &Transaction, slot: u64) -> Result<(), LedgerError> {
+        let mut state = self.state.clone();
+        state.apply_tx(tx, slot, self.current_epoch())?;
+        self.state = state;
+        Ok(())
+    }
+
+    /// Apply a transaction to the UTXO set at the tip slot (returns false if invalid). If its
+    /// Plutus scripts fail, only its collateral is collected into the fee pot.
+    pub fn apply_transaction(&mut self, tx: &Transaction) -> bool {
+        let mut state = self.state.clone();
+        let applied = state
+            .apply_tx_or_collateral(tx, self.current_slot(), self.current_epoch())
+            .is_ok();
+        if applied {
+            self.state = state;
         }
-        true
+        applied
     }
 
-    /// Apply a block to the ledger (returns false if any tx is invalid)
-    pub fn apply_block(&mut self, block: &Block) -> bool {
-        for tx in &block.transactions {
-            if !self.apply_transaction(tx) {
-                return false;
-            }
+    /// Check that a block with `header` can follow the tip: its slot is after the tip's, and its
+    /// epoch is the one its slot falls in.
+    pub fn validate_slot(&self, header: &BlockHeader) -> Result<(), LedgerError> {
+        if let Some(tip) = self.tip
+            && header.slot <= tip.slot
+        {
+            return Err(LedgerError::SlotNotIncreasing {
+                slot: header.slot,
+                tip_slot: tip.slot,
+            });
         }
-        true
+        let expected = self.state.params.epoch_of(header.slot);
+        if header.epoch != expected {
+            return Err(LedgerError::WrongEpoch {
+                slot: header.slot,
+                epoch: header.epoch,
+                expected,
+            });
+        }
+        Ok(())
+    }
+
+    /// Apply a block to the ledger: it must extend the tip by hash, in a later slot and in the
+    /// epoch of its slot (see [`Self::validate_slot`]), and its transactions must
+    /// pass the UTXO rules in order at the block's slot. A block in a later epoch than the tip
+    /// first crosses every epoch boundary since the tip (see [`LedgerState::cross_epochs`]), deriving
+    /// the nonce of each new epoch. The block counts towards its leader's blocks made in the
+    /// epoch, its operational certificate counter becomes the leader's last, and its VRF output
+    /// evolves the nonces. The ledger is unchanged on error.
+    pub fn try_apply_block(&mut self, block: &Block) -> Result<(), LedgerError> {
+        if block.header.prev_hash != self.tip_hash() {
+            return Err(LedgerError::DoesNotExtendTip { block_id: block.id });
+        }
+        self.validate_slot(&block.header)?;
+        if !block.body_matches() {
+            return Err(LedgerError::BodyHashMismatch { block_id: block.id });
+        }
+        let mut state = self.state.clone();
+        if let Some(tip) = self.tip {
+            state.cross_epochs(tip.epoch, block.header.epoch);
+        }
+        let size = block.body_size();
+        if size > state.params.max_block_size {
+            return Err(LedgerError::BlockTooLarge {
+                block_id: block.id,
+                size,
+                max: state.params.max_block_size,
+            });
+        }
+        for (index, tx) in block.transactions.iter().enumerate() {
+            state
+                .apply_tx(tx, block.header.slot, block.header.epoch)
+                .map_err(|error| LedgerError::InvalidTransaction {
+                    block_id: block.id,
+                    index,
+                    error: Box::new(error),
+                })?;
+        }
+        *state
+            .blocks_made
+            .entry(block.header.leader.clone())
+            .or_insert(0) += 1;
+        state
+            .opcert_counters
+            .insert(block.header.leader.clone(), block.header.opcert.counter);
+        let window = state.params.randomness_stabilisation_window();
+        state
+            .nonces
+            .update(&block.header, window, state.params.epoch_length);
+        self.state = state;
+        self.tip = Some(Tip::of(block));
+        Ok(())
+    }
+
+    /// Apply a block to the ledger (returns false if any tx is invalid or the block does not
+    /// extend the tip by hash)
+    pub fn apply_block(&mut self, block: &Block) -> bool {
+        self.try_apply_block(block).is_ok()
     }
 
     /// Apply a block to the ledger and return a new chain.
+    ///
+    /// The block must extend `chain` by hash; the ledger itself is left untouched.
     pub fn apply_block_to_chain(&mut self, chain: &Chain, block: Block) -> Option<Chain> {
+        if block.header.prev_hash != chain.tip_hash() {
+            return None;
+        }
         // Validate block (basic check: all txs valid)
         let mut new_ledger = self.clone();
+        new_ledger.tip = chain.blocks.last().map(Tip::of);
         if new_ledger.apply_block(&block) {
             let mut new_chain = chain.clone();
             new_chain.blocks.push(block);
@@ -220,16 +523,10 @@ impl Ledger {
         }
     }
 
-    /// Returns a hash representing the tip (last block) for test comparison
+    /// Returns the hash of the tip (last applied block), or `None` at genesis
     #[allow(dead_code)]
-    pub fn tip_hash(&self) -> u64 {
-        // For test: just use the highest tx id in the UTXO set, or 0 if empty
-        self.state
-            .utxos
-            .keys()
-            .map(|(txid, _)| *txid)
-            .max()
-            .unwrap_or(0)
+    pub fn tip_hash(&self) -> Option<BlockHash> {
+        self.tip.map(|t| t.hash)
     }
 
     /// Apply a block to the ledger (returns false if any tx is invalid)
@@ -239,10 +536,8 @@ impl Ledger {
         block: &Block,
         chaindb: Option<&mut ChainDB>,
     ) -> bool {
-        for tx in &block.transactions {
-            if !self.apply_transaction(tx) {
-                return false;
-            }
+        if !self.apply_block(block) {
+            return false;
         }
         if let Some(db) = chaindb {
             // Persist block and state atomically
@@ -256,15 +551,19 @@ impl Ledger {
         &mut self,
         chaindb: &ChainDB,
         block_id: u64,
-    ) -> std::io::Result<()> {
+    ) -> Result<(), crate::chaindb::ChainDBError> {
         let state = chaindb.load_state(block_id).await?;
+        let block = chaindb.load_block(block_id).await?;
         self.state = state;
+        self.tip = Some(Tip::of(&block));
         Ok(())
     }
 }
 
 impl LedgerState {
-    /// Apply a certificate to the ledger state (stake pool registration, retirement, or delegation).
+    /// Apply a certificate to the ledger state (stake key registration and deregistration, stake
+    /// pool registration, retirement, delegation, DRep registration and retirement, or vote
+    /// delegation). Delegating requires a registered stake key.
     /// Returns an error string if the certificate is invalid.
     pub fn apply_certificate(
         &mut self,
@@ -273,6 +572,9 @@ impl LedgerState {
     ) -> Result<(), String> {
         match cert {
             Certificate::PoolRegistration(reg) => {
+                if reg.cost < self.params.min_pool_cost {
+                    return Err("Pool cost is below the minimum pool cost".to_string());
+                }
                 // Register or update the stake pool
                 let pool = StakePool {
                     registration: reg.clone(),
@@ -297,12 +599,40 @@ impl LedgerState {
                     Err("Pool not found".to_string())
                 }
             }
+            Certificate::StakeRegistration(credential) => {
+                if self.stake_keys.contains_key(credential) {
+                    return Err("Stake key already registered".to_string());
+                }
+                self.stake_keys.insert(*credential, self.params.key_deposit);
+                self.rewards.entry(*credential).or_insert(0);
+                Ok(())
+            }
+            Certificate::StakeDeregistration(credential) => {
+                if !self.stake_keys.contains_key(credential) {
+                    return Err("Stake key not registered".to_string());
+                }
+                if self
+                    .rewards
+                    .get(credential)
+                    .is_some_and(|&balance| balance > 0)
+                {
+                    return Err("Reward account is not empty".to_string());
+                }
+                self.stake_keys.remove(credential);
+                self.rewards.remove(credential);
+                self.delegations.remove(credential);
+                self.vote_delegations.remove(credential);
+                Ok(())
+            }
             Certificate::Delegation(deleg) => {
+                if !self.stake_keys.contains_key(&deleg.delegator) {
+                    return Err("Stake key not registered".to_string());
+                }
                 // Only allow delegation to active pools
                 if let Some(pool) = self.stake_pools.get(&deleg.pool_id) {
                     if pool.active {
                         self.delegations
-                            .insert(deleg.delegator.clone(), deleg.pool_id.clone());
+                            .insert(deleg.delegator, deleg.pool_id.clone());
                         Ok(())
                     } else {
                         Err("Cannot delegate to inactive pool".to_string())
@@ -311,10 +641,52 @@ impl LedgerState {
                     Err("Pool not found".to_string())
                 }
             }
+            Certificate::DRepRegistration(reg) => {
+                if self.dreps.contains_key(&reg.drep) {
+                    return Err("DRep already registered".to_string());
+                }
+                if reg.deposit != self.params.drep_deposit {
+                    return Err("DRep deposit differs from the protocol parameter".to_string());
+                }
+                self.dreps.insert(reg.drep, reg.deposit);
+                Ok(())
+            }
+            Certificate::DRepDeregistration(dereg) => match self.dreps.get(&dereg.drep) {
+                Some(&deposit) if deposit == dereg.deposit => {
+                    self.dreps.remove(&dereg.drep);
+                    Ok(())
+                }
+                Some(_) => Err("Refund differs from the DRep deposit".to_string()),
+                None => Err("DRep not registered".to_string()),
+            },
+            Certificate::VoteDelegation(deleg) => {
+                if !self.stake_keys.contains_key(&deleg.delegator) {
+                    return Err("Stake key not registered".to_string());
+                }
+                if let DRep::Credential(drep) = &deleg.drep
+                    && !self.dreps.contains_key(drep)
+                {
+                    return Err("DRep not registered".to_string());
+                }
+                self.vote_delegations
+                    .insert(deleg.delegator, deleg.drep.clone());
+                Ok(())
+            }
         }
     }
 
-    /// Process pool retirements at the end of an epoch.
+    /// Total value held by the unspent outputs of an address.
+    pub fn balance(&self, address: &Address) -> Value {
+        self.utxos
+            .values()
+            .filter(|o| o.address == *address)
+            .fold(Value::default(), |total, o| {
+                total.saturating_add(&Value::from(o))
+            })
+    }
+
+    /// Process pool retirements at the end of an epoch: pools retiring by `current_epoch` become
+    /// inactive and their deposit is refunded to their reward account.
     pub fn process_pool_retirements(&mut self, current_epoch: u64) {
         let retiring: Vec<String> = self
             .stake_pools
@@ -331,37 +703,21 @@ impl LedgerState {
                 }
             })
             .collect();
+        let deposit = self.params.pool_deposit;
         for pool_id in retiring {
-            if let Some(pool) = self.stake_pools.get_mut(&pool_id) {
+            let Some(pool) = self.stake_pools.get_mut(&pool_id) else {
+                continue;
+            };
+            if pool.active {
                 pool.active = false;
+                let account = pool.registration.reward_account;
+                self.deposited = self.deposited.saturating_sub(deposit);
+                self.credit_reward(account, deposit);
             }
         }
         self.pool_retirements
             .retain(|_, &mut epoch| epoch > current_epoch);
     }
-
-    /// Calculate and distribute rewards (stub: proportional to stake for now).
-    pub fn distribute_rewards(&mut self, total_rewards: u64) {
-        let total_stake: u64 = self.stake_distribution.values().sum();
-        if total_stake == 0 || total_rewards == 0 {
-            return;
-        }
-        for (addr, stake) in &self.stake_distribution {
-            let reward = (*stake as u128 * total_rewards as u128 / total_stake as u128) as u64;
-            *self.rewards.entry(addr.clone()).or_insert(0) += reward;
-        }
-    }
-
-    /// Validate all Plutus scripts in a transaction (stub: always returns true)
-    pub fn validate_plutus_scripts(&self, tx: &Transaction) -> bool {
-        for witness in &tx.plutus_witnesses {
-            // TODO: Integrate real Plutus interpreter
-            if witness.script.code.is_empty() {
-                return false;
-            }
-        }
-        true
-    }
 }
 
 /// Consensus-aware block header for Ouroboros
@@ -369,9 +725,40 @@ impl LedgerState {
 pub struct BlockHeader {
     pub slot: u64,
     pub epoch: u64,
-    pub leader: String,         // Stake pool or node id
-    pub vrf_proof: Vec<u8>,     // VRF proof (stub)
-    pub kes_signature: Vec<u8>, // KES signature (stub)
+    pub leader: String,               // Stake pool or node id
+    pub issuer_vkey: Vec<u8>,         // Cold verification key of the leader's pool
+    pub vrf_vkey: Vec<u8>,            // VRF verification key of the leader
+    pub vrf_proof: Vec<u8>,           // VRF proof of leadership of the slot
+    pub opcert: OperationalCert,      // Certificate of the KES key, signed by the cold key
+    pub kes_signature: Vec<u8>,       // KES signature of the header body
+    pub prev_hash: Option<BlockHash>, // Hash of the previous block, None for the first block
+    pub body_hash: BlockHash,         // Hash of the block body (transactions)
+}
+
+impl BlockHeader {
+    /// Header hash, which is also the block hash: Blake2b-256 of the CBOR-encoded header.
+    pub fn hash(&self) -> BlockHash {
+        blake2b_256(&self.to_cbor())
+    }
+
+    /// CBOR-encoded header body: the whole header but the KES signature, which signs it.
+    pub fn body_bytes(&self) -> Vec<u8> {
+        cbor::encode_with(|e| cbor::encode_header_body(e, self))
+    }
+}
+
+/// Operational certificate: a KES verification key of a pool, signed by the pool's cold key so
+/// that the cold key can stay offline.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OperationalCert {
+    /// KES verification key the pool signs headers with (32 bytes)
+    pub hot_vkey: Vec<u8>,
+    /// Issue number; a pool's chain may not go back to a certificate with a lower one
+    pub counter: u64,
+    /// KES period from which the KES key signs
+    pub kes_period: u64,
+    /// Ed25519 signature by the cold key of the KES key, counter and period (64 bytes)
+    pub sigma: Vec<u8>,
 }
 
 /// Represents a candidate chain for fork choice.
@@ -380,25 +767,54 @@ pub struct Chain {
     pub blocks: Vec<Block>,
 }
 
+impl Chain {
+    /// Hash of the newest block, or `None` for an empty chain.
+    pub fn tip_hash(&self) -> Option<BlockHash> {
+        self.blocks.last().map(Block::hash)
+    }
+
+    /// Whether every block points to its predecessor by hash and matches its body hash.
+    pub fn is_linked(&self) -> bool {
+        self.blocks.iter().all(Block::body_matches)
+            && self
+                .blocks
+                .windows(2)
+                .all(|w| w[1].header.prev_hash == Some(w[0].hash()))
+    }
+
+    /// Hash of the newest block shared by both chains, found by hash ancestry.
+    pub fn intersection(&self, other: &Chain) -> Option<BlockHash> {
+        let ours: std::collections::HashSet<BlockHash> =
+            self.blocks.iter().map(Block::hash).collect();
+        other
+            .blocks
+            .iter()
+            .rev()
+            .map(Block::hash)
+            .find(|h| ours.contains(h))
+    }
+}
+
 /// Cardano-style chain selection: prefer longest chain, then highest density, then heaviest (most blocks), then tie-break by lowest tip hash.
-pub fn select_chain<'a>(chains: &'a [Chain]) -> Option<&'a Chain> {
+/// Blocks are identified by hash, so forks that reuse block ids are told apart.
+pub fn select_chain(chains: &[Chain]) -> Option<&Chain> {
     chains.iter().max_by(|a, b| {
         let len_a = a.blocks.len();
         let len_b = b.blocks.len();
         if len_a != len_b {
             return len_a.cmp(&len_b);
         }
-        // Density: count unique block ids (simulate density)
+        // Density: count unique block hashes (simulate density)
         let density_a = a
             .blocks
             .iter()
-            .map(|blk| blk.id)
+            .map(Block::hash)
             .collect::<std::collections::HashSet<_>>()
             .len();
         let density_b = b
             .blocks
             .iter()
-            .map(|blk| blk.id)
+            .map(Block::hash)
             .collect::<std::collections::HashSet<_>>()
             .len();
         if density_a != density_b {
@@ -418,10 +834,10 @@ pub fn select_chain<'a>(chains: &'a [Chain]) -> Option<&'a Chain> {
         if txs_a != txs_b {
             return txs_a.cmp(&txs_b);
         }
-        // Tie-break: lowest tip block id
-        let tip_a = a.blocks.last().map(|b| b.id).unwrap_or(0);
-        let tip_b = b.blocks.last().map(|b| b.id).unwrap_or(0);
-        tip_b.cmp(&tip_a) // lower id wins
+        // Tie-break: lowest tip block hash
+        let tip_a = a.tip_hash().unwrap_or_default();
+        let tip_b = b.tip_hash().unwrap_or_default();
+        tip_b.cmp(&tip_a) // lower hash wins
     })
 }
 
@@ -436,63 +852,123 @@ pub struct Block {
 
 impl Default for Block {
     fn default() -> Self {
-        Block {
-            id: 0,
-            header: BlockHeader {
+        Block::new(
+            0,
+            BlockHeader {
                 slot: 0,
                 epoch: 0,
                 leader: String::new(),
+                issuer_vkey: vec![],
+                vrf_vkey: vec![],
                 vrf_proof: vec![],
+                opcert: OperationalCert::default(),
                 kes_signature: vec![],
+                prev_hash: None,
+                body_hash: [0u8; 32],
             },
-            transactions: vec![],
+            vec![],
+        )
+    }
+}
+
+impl Block {
+    /// Build a block, filling in the header's body hash from `transactions`.
+    pub fn new(id: u64, mut header: BlockHeader, transactions: Vec<Transaction>) -> Self {
+        header.body_hash = body_hash(&transactions);
+        Block {
+            id,
+            header,
+            transactions,
         }
     }
+
+    /// Block hash: the hash of its header, which commits to the body through `body_hash`.
+    pub fn hash(&self) -> BlockHash {
+        self.header.hash()
+    }
+
+    /// Size in bytes of the block body: the sum of its encoded transactions.
+    pub fn body_size(&self) -> usize {
+        self.transactions.iter().map(Transaction::size).sum()
+    }
+
+    /// Whether the header's body hash matches the transactions.
+    pub fn body_matches(&self) -> bool {
+        self.header.body_hash == body_hash(&self.transactions)
+    }
+}
+
+/// Blake2b-256 hash of a block body: the CBOR-encoded transaction bodies, witness sets and
+/// indices of the transactions whose scripts fail.
+pub fn body_hash(transactions: &[Transaction]) -> BlockHash {
+    blake2b_256(&cbor::encode_with(|e| {
+        cbor::encode_block_body(e, transactions)
+    }))
 }
 
 #[allow(dead_code)]
 impl Block {
+    /// Block on top of the ledger's tip with the mempool transactions that fit. `header`
+    /// supplies the slot, epoch and leader fields; its previous-block hash is filled in.
     pub async fn new_from_mempool(
         ledger: &Ledger,
         era_logic: &dyn crate::protocol::EraLogic,
         mempool: &crate::mempool::Mempool,
-        slot: u64,
-        epoch: u64,
-        leader: String,
-        vrf_proof: Vec<u8>,
-        kes_signature: Vec<u8>,
+        mut header: BlockHeader,
     ) -> Option<Block> {
-        let txs = mempool.get_transactions();
+        let txs = ledger.select_transactions(mempool.get_transactions(), header.slot, header.epoch);
         if txs.is_empty() {
             None
         } else {
-            Some(Block {
-                id: ledger.tip_hash() + 1,
-                header: BlockHeader {
-                    slot,
-                    epoch,
-                    leader,
-                    vrf_proof,
-                    kes_signature,
-                },
-                transactions: txs,
-            })
+            let tip = ledger.tip();
+            header.prev_hash = tip.map(|t| t.hash);
+            Some(Block::new(tip.map_or(1, |t| t.id + 1), header, txs))
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TxInput {
-    pub prev_tx: u64,
+    pub prev_tx: TxId, // Id (body hash) of the transaction whose output is spent
     pub index: u32,
 }
 
-/// Transaction output supporting multi-asset (Mary era)
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Transaction output supporting multi-asset (Mary era), datums and reference scripts (Babbage)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TxOutput {
-    pub address: String,
+    pub address: Address,
     pub amount: u64,                // ADA amount
     pub assets: Option<Vec<Asset>>, // Optional multi-asset bundle
+    #[serde(default)]
+    pub datum: Option<DatumOption>, // Datum hash or inline datum for the locking script
+    #[serde(default)]
+    pub script_ref: Option<ScriptRef>, // Script transactions can use without carrying it
+}
+
+/// Datum of an output: the hash of a datum the spending transaction supplies, or the datum
+/// itself (Babbage).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DatumOption {
+    Hash([u8; 32]),
+    Inline(PlutusDatum),
+}
+
+/// Script held by an output. Transactions spending or referencing the output can use it in
+/// place of a script witness.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ScriptRef {
+    Native(NativeScript),
+    Plutus(PlutusScript),
+}
+
+impl ScriptRef {
+    /// Hash of the script.
+    pub fn hash(&self) -> String {
+        match self {
+            ScriptRef::Native(script) => script.hash(),
+            ScriptRef::Plutus(script) => script.hash(),
+        }
+    }
 }
 
 /// Plutus script (Alonzo+)
@@ -502,12 +978,38 @@ pub struct PlutusScript {
     pub code: Vec<u8>,
 }
 
+impl PlutusScript {
+    /// Script hash as hex: Blake2b-224 of the Plutus V1 language tag followed by the script
+    /// bytes. A minting policy id is the hash of its policy script.
+    pub fn hash(&self) -> String {
+        crate::chaindb::hex(self.credential().hash())
+    }
+
+    /// Script hash credential, which locks outputs and reward accounts with this script.
+    pub fn credential(&self) -> Credential {
+        let mut hasher = Blake2b::<U28>::new();
+        hasher.update([PLUTUS_V1_TAG]);
+        hasher.update(&self.code);
+        Credential::ScriptHash(hasher.finalize().into())
+    }
+}
+
+/// Language tag prefixed to Plutus V1 scripts when hashing them.
+const PLUTUS_V1_TAG: u8 = 1;
+
 /// Plutus datum (arbitrary data attached to outputs)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PlutusDatum {
     pub data: Vec<u8>,
 }
 
+impl PlutusDatum {
+    /// Datum hash: Blake2b-256 of the datum bytes.
+    pub fn hash(&self) -> [u8; 32] {
+        blake2b_256(&self.data)
+    }
+}
+
 /// Plutus redeemer (input to script validation)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PlutusRedeemer {
@@ -515,6 +1017,9 @@ pub struct PlutusRedeemer {
 }
 
 /// Alonzo/Plutus script witness for a transaction input
+///
+/// The script is empty when it comes from a reference script, and the datum is empty when the
+/// spent output holds it inline.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PlutusWitness {
     pub script: PlutusScript,
@@ -524,28 +1029,109 @@ pub struct PlutusWitness {
 }
 
 /// Transaction supporting certificates (for staking/pool ops)
+///
+/// A transaction has no stored id: its id is the hash of its body, see [`Transaction::id`].
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Transaction {
-    pub id: u64,
     pub inputs: Vec<TxInput>,
+    #[serde(default)]
+    pub reference_inputs: Vec<TxInput>, // Outputs read (for their scripts and datums) but not spent
     pub outputs: Vec<TxOutput>,
+    #[serde(default)]
+    pub fee: u64, // Lovelace paid as fee
+    #[serde(default)]
+    pub ttl: Option<u64>, // First slot the transaction is no longer valid in
     pub certificates: Vec<Certificate>,
+    #[serde(default)]
+    pub withdrawals: BTreeMap<Credential, u64>, // reward account -> lovelace withdrawn
+    #[serde(default)]
+    pub validity_start: Option<u64>, // First slot the transaction is valid in
+    #[serde(default)]
+    pub mint: MultiAsset<i64>, // Tokens minted (positive) or burned (negative)
+    #[serde(default)]
+    pub update: Option<Update>, // Protocol parameter update proposal
+    #[serde(default)]
+    pub required_signers: BTreeSet<String>, // Key hashes that must sign, visible to Plutus scripts
+    #[serde(default)]
+    pub witnesses: Vec<VKeyWitness>, // Ed25519 signatures of the transaction id
+    #[serde(default)]
+    pub native_scripts: Vec<NativeScript>, // Multisig and timelock script witnesses
     pub plutus_witnesses: Vec<PlutusWitness>,
-    // ... add more fields as needed (metadata, etc.)
+    #[serde(default)]
+    pub collateral: Vec<TxInput>, // Inputs forfeited to the fee pot if Plutus scripts fail
+    #[serde(default)]
+    pub collateral_return: Option<TxOutput>, // Change of the collateral when it is forfeited
+    #[serde(default)]
+    pub voting_procedures: Vec<VotingProcedure>, // Votes on open governance actions (Conway)
+    #[serde(default)]
+    pub proposal_procedures: Vec<ProposalProcedure>, // Governance actions proposed (Conway)
+    #[serde(default = "valid")]
+    pub is_valid: bool, // Whether the Plutus scripts pass (phase-2 validation)
+                        // ... add more fields as needed (metadata, etc.)
+}
+
+/// Default of [`Transaction::is_valid`].
+fn valid() -> bool {
+    true
 }
 
 impl Default for Transaction {
     fn default() -> Self {
         Transaction {
-            id: 0,
-            inputs: vec![],
-            outputs: vec![],
-            certificates: vec![],
-            plutus_witnesses: vec![],
+            inputs: Vec::new(),
+            reference_inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee: 0,
+            ttl: None,
+            certificates: Vec::new(),
+            withdrawals: BTreeMap::new(),
+            validity_start: None,
+            mint: MultiAsset::new(),
+            update: None,
+            required_signers: BTreeSet::new(),
+            witnesses: Vec::new(),
+            native_scripts: Vec::new(),
+            plutus_witnesses: Vec::new(),
+            collateral: Vec::new(),
+            collateral_return: None,
+            voting_procedures: Vec::new(),
+            proposal_procedures: Vec::new(),
+            is_valid: true,
         }
     }
 }
 
+impl Transaction {
+    /// Transaction id: Blake2b-256 of the CBOR-encoded body. Witnesses are not part of the body,
+    /// so adding them does not change the id.
+    pub fn id(&self) -> TxId {
+        blake2b_256(&cbor::encode_with(|e| cbor::encode_tx_body(e, self)))
+    }
+
+    /// Sign the transaction id with `key`, adding the witness to the transaction.
+    pub fn sign(&mut self, key: &ed25519_dalek::SigningKey) {
+        self.witnesses.push(VKeyWitness::new(key, &self.id()));
+    }
+
+    /// Key hashes of the witnesses, whether or not their signatures verify.
+    pub fn signers(&self) -> BTreeSet<String> {
+        self.witnesses.iter().map(VKeyWitness::key_hash).collect()
+    }
+
+    /// Size in bytes of the canonical CBOR encoding, used for size limits.
+    pub fn size(&self) -> usize {
+        self.to_cbor().len()
+    }
+
+    /// Whether applying the transaction can fail after its UTXO rules and scripts pass, because
+    /// it has certificates, votes or governance proposals to check against the state.
+    fn has_stateful_parts(&self) -> bool {
+        !self.certificates.is_empty()
+            || !self.voting_procedures.is_empty()
+            || !self.proposal_procedures.is_empty()
+    }
+}
+
 /// Datum for EUTXO (can be any serializable data)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Datum {
@@ -561,44 +1147,52 @@ pub struct Script {
 /// EUTXO output with datum and script
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct EUTxOOutput {
-    pub address: String,
+    pub address: Address,
     pub amount: u64,
     pub datum: Option<Datum>,
     pub script: Option<Script>,
     // TODO: Add multi-asset support
 }
 
-/// Validate a transaction in the EUTXO model (including scripts/datums)
+/// Validate a transaction in the EUTXO model (including scripts/datums): the UTXO rules at
+/// `slot`, then its Plutus scripts.
 #[allow(dead_code)]
-pub async fn validate_eutxo_transaction(_tx: &Transaction) -> bool {
-    false
+pub async fn validate_eutxo_transaction(state: &LedgerState, tx: &Transaction, slot: u64) -> bool {
+    state.validate_utxo(tx, slot).is_ok() && state.validate_plutus_scripts(tx).is_ok()
 }
 
 #[cfg(test)]
 mod tests {
+    use super::plutus::{Builtin, Constant, Term};
     use super::*;
+    use std::rc::Rc;
 
     #[test]
     fn test_stake_pool_registration_and_retirement() {
         let mut state = LedgerState {
             utxos: HashMap::new(),
-            stake_distribution: HashMap::new(),
             delegations: HashMap::new(),
             stake_pools: HashMap::new(),
             pool_retirements: HashMap::new(),
             rewards: HashMap::new(),
+            ..Default::default()
         };
         let reg = StakePoolRegistration {
             pool_id: "pool1".to_string(),
-            owner: "owner1".to_string(),
+            vrf_keyhash: [0u8; 32],
+            owner: cred("owner1"),
             pledge: 1000,
-            cost: 340,
+            cost: 340_000_000,
             margin: 0.05,
-            reward_account: "owner1_reward".to_string(),
+            reward_account: cred("owner1_reward"),
         };
         let cert = Certificate::PoolRegistration(reg.clone());
         assert!(state.apply_certificate(&cert, 0).is_ok());
         assert!(state.stake_pools.contains_key("pool1"));
+        state.deposited = state.params.pool_deposit;
+        state
+            .stake_keys
+            .insert(cred("owner1_reward"), state.params.key_deposit);
         // Retirement
         let retire = StakePoolRetirement {
             pool_id: "pool1".to_string(),
@@ -607,46 +1201,154 @@ mod tests {
         let retire_cert = Certificate::PoolRetirement(retire.clone());
         assert!(state.apply_certificate(&retire_cert, 1).is_ok());
         assert_eq!(state.stake_pools["pool1"].retirement_epoch, Some(5));
-        // Process retirement
+        // Process retirement: the deposit goes back to the reward account, once.
         state.process_pool_retirements(5);
         assert!(!state.stake_pools["pool1"].active);
+        assert_eq!(
+            state.rewards[&cred("owner1_reward")],
+            state.params.pool_deposit
+        );
+        assert_eq!(state.deposited, 0);
+        state.process_pool_retirements(6);
+        assert_eq!(
+            state.rewards[&cred("owner1_reward")],
+            state.params.pool_deposit
+        );
     }
 
     #[test]
     fn test_delegation_and_rewards() {
         let mut state = LedgerState {
             utxos: HashMap::new(),
-            stake_distribution: HashMap::new(),
             delegations: HashMap::new(),
             stake_pools: HashMap::new(),
             pool_retirements: HashMap::new(),
             rewards: HashMap::new(),
+            ..Default::default()
         };
         // Register pool
         let reg = StakePoolRegistration {
             pool_id: "pool2".to_string(),
-            owner: "owner2".to_string(),
+            vrf_keyhash: [0u8; 32],
+            owner: cred("owner2"),
             pledge: 2000,
-            cost: 340,
+            cost: 340_000_000,
             margin: 0.03,
-            reward_account: "owner2_reward".to_string(),
+            reward_account: cred("owner2_reward"),
         };
         let cert = Certificate::PoolRegistration(reg.clone());
         assert!(state.apply_certificate(&cert, 0).is_ok());
-        // Delegate
+        // Delegate, which requires a registered stake key
         let deleg = DelegationCertificate {
-            delegator: "alice".to_string(),
+            delegator: cred("alice"),
             pool_id: "pool2".to_string(),
         };
         let deleg_cert = Certificate::Delegation(deleg.clone());
+        assert!(state.apply_certificate(&deleg_cert, 0).is_err());
+        let key_cert = Certificate::StakeRegistration(cred("alice"));
+        assert!(state.apply_certificate(&key_cert, 0).is_ok());
         assert!(state.apply_certificate(&deleg_cert, 0).is_ok());
-        assert_eq!(state.delegations["alice"], "pool2");
-        // Rewards
-        state.stake_distribution.insert("alice".to_string(), 1000);
-        state.stake_distribution.insert("bob".to_string(), 2000);
-        state.distribute_rewards(300);
-        assert_eq!(state.rewards["alice"], 100);
-        assert_eq!(state.rewards["bob"], 200);
+        assert_eq!(state.delegations[&cred("alice")], "pool2");
+        // Rewards: the pool holds all the stake in circulation, meets its pledge and made every
+        // expected block; its members are paid a boundary after the epoch is rewarded.
+        state
+            .utxos
+            .insert(([1u8; 32], 0), output(addr("alice"), 6000));
+        state
+            .utxos
+            .insert(([1u8; 32], 1), output(addr("owner2"), 2000));
+        state
+            .delegations
+            .insert(cred("owner2"), "pool2".to_string());
+        for account in ["owner2", "owner2_reward"] {
+            state.stake_keys.insert(cred(account), 2_000_000);
+        }
+        state.reserves = rewards::MAX_LOVELACE_SUPPLY - 8000;
+        state.snapshots.set = state.stake_snapshot();
+        state.blocks_made.insert("pool2".to_string(), 21_600);
+        state.new_epoch(1);
+        assert_eq!(state.rewards[&cred("alice")], 0);
+        state.new_epoch(2);
+        assert!(state.rewards[&cred("alice")] > 0);
+        assert!(state.rewards[&cred("owner2_reward")] > 340_000_000);
+        assert!(!state.rewards.contains_key(&cred("owner2")));
+    }
+
+    /// Output of `amount` lovelace to `address`.
+    fn output(address: Address, amount: u64) -> TxOutput {
+        TxOutput {
+            address,
+            amount,
+            assets: None,
+            ..Default::default()
+        }
+    }
+
+    /// Plutus script running `term`.
+    fn plutus_script(term: Term) -> PlutusScript {
+        PlutusScript {
+            code: plutus::flat::encode_program(&plutus::Program {
+                version: (1, 0, 0),
+                term,
+            }),
+        }
+    }
+
+    /// Signing key of the test party `name`.
+    fn key(name: &str) -> ed25519_dalek::SigningKey {
+        ed25519_dalek::SigningKey::from_bytes(&blake2b_256(name.as_bytes()))
+    }
+
+    /// Key hash of the test party `name`.
+    fn key_hash(name: &str) -> String {
+        witness::key_hash(key(name).verifying_key().as_bytes())
+    }
+
+    /// Credential of the test party `name`, for payment and stake alike.
+    fn cred(name: &str) -> Credential {
+        Credential::from_vkey(key(name).verifying_key().as_bytes())
+    }
+
+    /// Testnet base address of the test party `name`, paying and staking with its key.
+    fn addr(name: &str) -> Address {
+        Address::base(Network::Testnet, cred(name), cred(name))
+    }
+
+    /// `tx` signed by the test parties `names`.
+    fn signed(mut tx: Transaction, names: &[&str]) -> Transaction {
+        for name in names {
+            tx.sign(&key(name));
+        }
+        tx
+    }
+
+    /// Seed the UTXO set with a genesis output of `amount` lovelace, spendable with the key of
+    /// "genesis", and return its input.
+    fn genesis(ledger: &mut Ledger, amount: u64) -> TxInput {
+        let input = TxInput {
+            prev_tx: [0xff; 32],
+            index: ledger.state.utxos.len() as u32,
+        };
+        ledger.state.utxos.insert(
+            (input.prev_tx, input.index),
+            output(addr("genesis"), amount),
+        );
+        input
+    }
+
+    fn header(slot: u64, prev_hash: Option<BlockHash>) -> BlockHeader {
+        BlockHeader {
+            slot,
+            epoch: 0,
+            leader: "test-leader".to_string(),
+            issuer_vkey: vec![0u8; 32],
+            vrf_vkey: vec![0u8; 32],
+            vrf_proof: vec![0u8; 80],
+            opcert: OperationalCert::default(),
+            kes_signature: vec![0u8; 448],
+            prev_hash,
+            body_hash: [0u8; 32],
+        }
     }
 
     #[test]
@@ -655,31 +1357,15 @@ mod tests {
         let protocol = crate::protocol::Protocol::new(crate::configuration::ProtocolConfig {
             era: "Shelley".to_string(),
         });
+        let input = genesis(&mut ledger, 2_000_000);
         let tx = Transaction {
-            id: 1,
-            inputs: vec![TxInput {
-                prev_tx: 0,
-                index: 0,
-            }],
-            outputs: vec![TxOutput {
-                address: "addr1".to_string(),
-                amount: 100,
-                assets: None,
-            }],
-            certificates: vec![],
-            plutus_witnesses: vec![],
-        };
-        let block = Block {
-            id: 1,
-            header: BlockHeader {
-                slot: 1,
-                epoch: 0,
-                leader: "test-leader".to_string(),
-                vrf_proof: vec![0u8; 32],
-                kes_signature: vec![0u8; 32],
-            },
-            transactions: vec![],
+            inputs: vec![input],
+            outputs: vec![output(addr("addr1"), 1_800_000)],
+            fee: 200_000,
+            ..Default::default()
         };
+        let tx = signed(tx, &["genesis"]);
+        let block = Block::new(1, header(1, None), vec![]);
         let rt = tokio::runtime::Runtime::new().unwrap();
         assert!(rt.block_on(ledger.validate_transaction(&tx, &protocol)));
         assert!(rt.block_on(ledger.validate_block(&block, &protocol)));
@@ -692,11 +1378,11 @@ mod tests {
             era: "Shelley".to_string(),
         });
         let tx = Transaction {
-            id: 2,
             inputs: vec![],  // Invalid: no inputs
             outputs: vec![], // Invalid: no outputs
             certificates: vec![],
             plutus_witnesses: vec![],
+            ..Default::default()
         };
         let rt = tokio::runtime::Runtime::new().unwrap();
         assert!(!rt.block_on(ledger.validate_transaction(&tx, &protocol)));
@@ -705,112 +1391,1035 @@ mod tests {
     #[test]
     fn utxo_apply_and_double_spend() {
         let mut ledger = Ledger::new(Tracer::default());
-        // Create a coinbase tx (no inputs, one output)
-        let coinbase = Transaction {
-            id: 1,
-            inputs: vec![],
-            outputs: vec![TxOutput {
-                address: "A".to_string(),
-                amount: 100,
-                assets: None,
-            }],
-            certificates: vec![],
-            plutus_witnesses: vec![],
-        };
-        // Apply coinbase
-        assert!(ledger.apply_transaction(&coinbase));
-        // Spend the output
+        let input = genesis(&mut ledger, 100_000_000);
+        // Spend the genesis output
         let spend = Transaction {
-            id: 2,
-            inputs: vec![TxInput {
-                prev_tx: 1,
-                index: 0,
-            }],
-            outputs: vec![TxOutput {
-                address: "B".to_string(),
-                amount: 100,
-                assets: None,
-            }],
-            certificates: vec![],
-            plutus_witnesses: vec![],
+            inputs: vec![input.clone()],
+            outputs: vec![output(addr("B"), 99_800_000)],
+            fee: 200_000,
+            ..Default::default()
         };
+        assert!(!ledger.apply_transaction(&spend));
+        let spend = signed(spend, &["genesis"]);
         assert!(ledger.apply_transaction(&spend));
         // Double-spend should fail
         let double_spend = Transaction {
-            id: 3,
+            inputs: vec![input.clone()],
+            outputs: vec![output(addr("C"), 99_800_000)],
+            fee: 200_000,
+            ..Default::default()
+        };
+        assert_eq!(
+            ledger.apply_tx(&double_spend, 0),
+            Err(LedgerError::BadInput(input))
+        );
+    }
+
+    #[test]
+    fn tx_id_is_the_body_hash() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let tx = Transaction {
+            inputs: vec![input],
+            outputs: vec![output(addr("A"), 9_800_000)],
+            fee: 200_000,
+            ..Default::default()
+        };
+        let mut witnessed = signed(tx.clone(), &["genesis"]);
+        witnessed.plutus_witnesses.push(PlutusWitness {
+            script: PlutusScript { code: vec![1] },
+            datum: PlutusDatum { data: vec![] },
+            redeemer: PlutusRedeemer { data: vec![] },
+            execution_units: (1, 1),
+        });
+        assert_eq!(tx.id(), witnessed.id());
+        let mut other = tx.clone();
+        other.outputs[0] = output(addr("B"), 9_800_000);
+        assert_ne!(tx.id(), other.id());
+
+        // Outputs are keyed by the id of the transaction that created them.
+        assert!(ledger.apply_transaction(&signed(tx.clone(), &["genesis"])));
+        assert!(ledger.state.utxos.contains_key(&(tx.id(), 0)));
+    }
+
+    #[test]
+    fn utxo_block_application() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 50_000_000);
+        let first = Transaction {
+            inputs: vec![input],
+            outputs: vec![output(addr("A"), 49_800_000)],
+            fee: 200_000,
+            ..Default::default()
+        };
+        let first = signed(first, &["genesis"]);
+        // Spends an output created earlier in the same block.
+        let spend = Transaction {
             inputs: vec![TxInput {
-                prev_tx: 1,
+                prev_tx: first.id(),
                 index: 0,
             }],
-            outputs: vec![TxOutput {
-                address: "C".to_string(),
-                amount: 100,
-                assets: None,
-            }],
-            certificates: vec![],
-            plutus_witnesses: vec![],
+            outputs: vec![output(addr("B"), 49_600_000)],
+            fee: 200_000,
+            ..Default::default()
+        };
+        let spend = signed(spend, &["A"]);
+        let (first_id, spend_id) = (first.id(), spend.id());
+        let block = Block::new(1, header(1, None), vec![first, spend]);
+        assert!(ledger.apply_block(&block));
+        // The UTXO set should now have only the spend output
+        assert!(ledger.state.utxos.contains_key(&(spend_id, 0)));
+        assert!(!ledger.state.utxos.contains_key(&(first_id, 0)));
+        assert_eq!(ledger.state.utxos.len(), 1);
+    }
+
+    #[test]
+    fn utxo_rules_reject_invalid_transactions() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let unsigned = Transaction {
+            inputs: vec![input.clone()],
+            outputs: vec![output(addr("A"), 4_000_000), output(addr("B"), 5_800_000)],
+            fee: 200_000,
+            ttl: Some(10),
+            validity_start: Some(2),
+            ..Default::default()
         };
-        assert!(!ledger.apply_transaction(&double_spend));
+        let valid = signed(unsigned.clone(), &["genesis"]);
+        assert_eq!(ledger.check_transaction(&valid, 5), Ok(()));
+        let check = |tx: &Transaction, slot| ledger.check_transaction(tx, slot).unwrap_err();
+
+        assert_eq!(
+            check(&unsigned, 5),
+            LedgerError::MissingVKeyWitness {
+                key_hash: key_hash("genesis"),
+            }
+        );
+        // A signature of another body does not count.
+        let mut tx = valid.clone();
+        tx.outputs.reverse();
+        assert_eq!(
+            check(&tx, 5),
+            LedgerError::InvalidSignature {
+                key_hash: key_hash("genesis"),
+            }
+        );
+        // Required signers listed by the transaction must sign too.
+        let mut tx = unsigned.clone();
+        tx.required_signers.insert(key_hash("B"));
+        assert_eq!(
+            check(&signed(tx.clone(), &["genesis"]), 5),
+            LedgerError::MissingVKeyWitness {
+                key_hash: key_hash("B"),
+            }
+        );
+        assert_eq!(
+            ledger.check_transaction(&signed(tx, &["genesis", "B"]), 5),
+            Ok(())
+        );
+
+        let mut tx = valid.clone();
+        tx.inputs.push(input.clone());
+        assert_eq!(check(&tx, 5), LedgerError::DuplicateInput(input.clone()));
+
+        let mut tx = valid.clone();
+        tx.inputs[0].index = 7;
+        assert!(matches!(check(&tx, 5), LedgerError::BadInput(_)));
+
+        let mut tx = valid.clone();
+        tx.fee = 300_000;
+        assert_eq!(
+            check(&tx, 5),
+            LedgerError::ValueNotConserved {
+                consumed: Value::lovelace(10_000_000),
+                produced: Value::lovelace(10_100_000),
+            }
+        );
+
+        let mut tx = valid.clone();
+        tx.outputs[1].address = Address::reward(Network::Testnet, cred("B"));
+        assert_eq!(
+            check(&tx, 5),
+            LedgerError::OutputToRewardAddress { index: 1 }
+        );
+
+        let mut tx = valid.clone();
+        tx.outputs = vec![output(addr("A"), 9_000_000), output(addr("B"), 800_000)];
+        assert_eq!(
+            check(&tx, 5),
+            LedgerError::OutputTooSmall {
+                index: 1,
+                amount: 800_000,
+                min: 1_000_000,
+            }
+        );
+
+        assert!(matches!(
+            check(&valid, 1),
+            LedgerError::OutsideValidityInterval { slot: 1, .. }
+        ));
+        assert!(matches!(
+            check(&valid, 10),
+            LedgerError::OutsideValidityInterval { slot: 10, .. }
+        ));
+
+        let mut tx = valid.clone();
+        tx.outputs[0].address = Address::Byron(vec![0; 20_000]);
+        assert!(matches!(
+            check(&tx, 5),
+            LedgerError::TxTooLarge { max: 16_384, .. }
+        ));
     }
 
     #[test]
-    fn utxo_block_application() {
+    fn underpaying_transactions_are_rejected_and_fees_feed_rewards() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let tx = |fee| {
+            let tx = Transaction {
+                inputs: vec![input.clone()],
+                outputs: vec![output(addr("A"), 10_000_000 - fee)],
+                fee,
+                ..Default::default()
+            };
+            signed(tx, &["genesis"])
+        };
+        let min = ledger.state.params.min_fee(tx(170_000).size());
+        assert_eq!(min, 44 * tx(170_000).size() as u64 + 155_381);
+        assert_eq!(
+            ledger.check_transaction(&tx(150_000), 0),
+            Err(LedgerError::FeeTooSmall {
+                fee: 150_000,
+                min: 44 * tx(150_000).size() as u64 + 155_381,
+            })
+        );
+        // Without the per-byte part, a lower fee is enough; a hard fork brings the next era's fees.
+        ledger.state.params.min_fee_a = 0;
+        assert_eq!(ledger.check_transaction(&tx(160_000), 0), Ok(()));
+        ledger.enter_era("Allegra");
+        assert_eq!(ledger.state.params.min_fee_a, 44);
+        assert!(ledger.apply_transaction(&tx(170_000)));
+        assert_eq!(ledger.state.fees, 170_000);
+
+        // The epoch's fees join the reward pot at the boundary. Without pools to pay, only the
+        // treasury cut is paid out; the rest goes to the reserves.
+        ledger.state.new_epoch(1);
+        assert_eq!(ledger.state.fees, 0);
+        assert_eq!(ledger.state.reward_update.pot(), 170_000);
+        ledger.state.new_epoch(2);
+        assert_eq!(ledger.state.treasury, 34_000);
+        assert_eq!(ledger.state.reserves, 136_000);
+    }
+
+    #[test]
+    fn withdrawals_and_deposits_balance_the_transaction() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 600_000_000);
+        let (stake1, pool1) = (cred("stake1"), key_hash("pool1"));
+        ledger.state.rewards.insert(stake1, 3_000_000);
+        let registration = Certificate::PoolRegistration(StakePoolRegistration {
+            pool_id: pool1.clone(),
+            vrf_keyhash: [0u8; 32],
+            owner: cred("owner1"),
+            pledge: 1000,
+            cost: 340_000_000,
+            margin: 0.05,
+            reward_account: stake1,
+        });
+        // 600 ADA in + 3 ADA withdrawn = 102.8 ADA out + 0.2 ADA fee + 500 ADA pool deposit
+        let tx = Transaction {
+            inputs: vec![input],
+            outputs: vec![output(addr("A"), 102_800_000)],
+            fee: 200_000,
+            certificates: vec![registration],
+            withdrawals: BTreeMap::from([(stake1, 3_000_000)]),
+            ..Default::default()
+        };
+        let mut partial = tx.clone();
+        partial.withdrawals.insert(stake1, 1_000_000);
+        assert_eq!(
+            ledger.check_transaction(&partial, 0),
+            Err(LedgerError::IncorrectWithdrawal {
+                account: stake1,
+                amount: 1_000_000,
+                balance: Some(3_000_000),
+            })
+        );
+        // The reward account, the pool and its owner must all sign.
+        assert_eq!(
+            ledger.check_transaction(&signed(tx.clone(), &["genesis", "stake1", "pool1"]), 0),
+            Err(LedgerError::MissingVKeyWitness {
+                key_hash: key_hash("owner1"),
+            })
+        );
+        let tx = signed(tx, &["genesis", "stake1", "pool1", "owner1"]);
+        assert_eq!(ledger.apply_tx(&tx, 0), Ok(()));
+        assert_eq!(ledger.state.rewards[&stake1], 0);
+        assert_eq!(ledger.state.deposited, 500_000_000);
+        assert!(ledger.state.stake_pools.contains_key(&pool1));
+        // Re-registering the pool updates it without a new deposit.
+        assert_eq!(ledger.state.deposits(&tx), 0);
+    }
+
+    #[test]
+    fn stake_keys_are_registered_with_a_deposit_and_refunded() {
         let mut ledger = Ledger::new(Tracer::default());
-        let coinbase = Transaction {
-            id: 4,
-            inputs: vec![],
-            outputs: vec![TxOutput {
-                address: "A".to_string(),
-                amount: 50,
-                assets: None,
+        let input = genesis(&mut ledger, 10_000_000);
+        let alice = cred("alice");
+        let delegation = Certificate::Delegation(DelegationCertificate {
+            delegator: alice,
+            pool_id: "pool1".to_string(),
+        });
+        let unregistered = Transaction {
+            inputs: vec![input.clone()],
+            outputs: vec![output(addr("alice"), 9_800_000)],
+            fee: 200_000,
+            certificates: vec![delegation],
+            ..Default::default()
+        };
+        assert_eq!(
+            ledger.check_transaction(&signed(unregistered.clone(), &["genesis"]), 0),
+            Err(LedgerError::MissingVKeyWitness {
+                key_hash: key_hash("alice"),
+            })
+        );
+        assert_eq!(
+            ledger.check_transaction(&signed(unregistered, &["genesis", "alice"]), 0),
+            Err(LedgerError::InvalidCertificate(
+                "Stake key not registered".to_string()
+            ))
+        );
+        // 10 ADA in = 2 ADA key deposit + 7.8 ADA out + 0.2 ADA fee. Registering needs no
+        // signature of the stake key.
+        let register = Transaction {
+            inputs: vec![input],
+            outputs: vec![output(addr("alice"), 7_800_000)],
+            fee: 200_000,
+            certificates: vec![Certificate::StakeRegistration(alice)],
+            ..Default::default()
+        };
+        let register = signed(register, &["genesis"]);
+        assert_eq!(ledger.apply_tx(&register, 0), Ok(()));
+        assert_eq!(ledger.state.stake_keys[&alice], 2_000_000);
+        assert_eq!(ledger.state.deposited, 2_000_000);
+
+        // A non-empty reward account cannot be deregistered, unless it is emptied by the same
+        // transaction: 7.8 ADA in + 1 ADA withdrawn + 2 ADA refund = 10.6 ADA out + 0.2 ADA fee
+        ledger.state.rewards.insert(alice, 1_000_000);
+        let deregister = Transaction {
+            inputs: vec![TxInput {
+                prev_tx: register.id(),
+                index: 0,
             }],
-            certificates: vec![],
-            plutus_witnesses: vec![],
+            outputs: vec![output(addr("alice"), 10_600_000)],
+            fee: 200_000,
+            certificates: vec![Certificate::StakeDeregistration(alice)],
+            withdrawals: BTreeMap::from([(alice, 1_000_000)]),
+            ..Default::default()
         };
-        let spend = Transaction {
-            id: 5,
+        let mut unwithdrawn = deregister.clone();
+        unwithdrawn.withdrawals.clear();
+        unwithdrawn.outputs[0].amount -= 1_000_000;
+        assert_eq!(
+            ledger.check_transaction(&signed(unwithdrawn, &["alice"]), 0),
+            Err(LedgerError::InvalidCertificate(
+                "Reward account is not empty".to_string()
+            ))
+        );
+        assert_eq!(ledger.apply_tx(&signed(deregister, &["alice"]), 0), Ok(()));
+        assert!(ledger.state.stake_keys.is_empty());
+        assert!(!ledger.state.rewards.contains_key(&alice));
+        assert_eq!(ledger.state.deposited, 0);
+    }
+
+    #[test]
+    fn governance_actions_are_voted_on_and_ratified_at_the_epoch_boundary() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 301_000_000_000);
+        let [alice, bob, stake1] = ["alice", "bob", "stake1"].map(cred);
+        let pool1 = key_hash("pool1");
+        let drep1 = cred("drep1");
+        let proposal = |action| ProposalProcedure {
+            deposit: 100_000_000_000,
+            return_account: stake1,
+            action,
+            anchor: governance::Anchor::default(),
+        };
+        let elect = GovAction::UpdateCommittee {
+            removed: BTreeSet::new(),
+            added: BTreeMap::from([(cred("cc1"), 10)]),
+            threshold: Rational::new(2, 3),
+        };
+        let withdraw =
+            GovAction::TreasuryWithdrawals(BTreeMap::from([(cred("stake2"), 5_000_000)]));
+        // 301k ADA in = 3 x 2 ADA key deposits + 500 ADA pool deposit + 500 ADA DRep deposit +
+        // 2 x 100k ADA proposal deposits + 99,993.7 ADA out + 0.3 ADA fee
+        let tx = Transaction {
+            inputs: vec![input],
+            outputs: vec![
+                output(addr("alice"), 89_993_700_000),
+                output(addr("bob"), 10_000_000_000),
+            ],
+            fee: 300_000,
+            certificates: vec![
+                Certificate::StakeRegistration(alice),
+                Certificate::StakeRegistration(bob),
+                Certificate::StakeRegistration(stake1),
+                Certificate::PoolRegistration(StakePoolRegistration {
+                    pool_id: pool1.clone(),
+                    vrf_keyhash: [0u8; 32],
+                    owner: cred("owner1"),
+                    pledge: 1000,
+                    cost: 340_000_000,
+                    margin: 0.05,
+                    reward_account: stake1,
+                }),
+                Certificate::Delegation(DelegationCertificate {
+                    delegator: alice,
+                    pool_id: pool1.clone(),
+                }),
+                Certificate::DRepRegistration(DRepCertificate {
+                    drep: drep1,
+                    deposit: 500_000_000,
+                }),
+                Certificate::VoteDelegation(VoteDelegationCertificate {
+                    delegator: alice,
+                    drep: DRep::Credential(drep1),
+                }),
+                Certificate::VoteDelegation(VoteDelegationCertificate {
+                    delegator: bob,
+                    drep: DRep::AlwaysAbstain,
+                }),
+            ],
+            proposal_procedures: vec![proposal(elect.clone()), proposal(withdraw)],
+            ..Default::default()
+        };
+        // Everyone but the stake keys that only register signs.
+        let signers = ["genesis", "pool1", "owner1", "alice", "drep1", "bob"];
+        let mut underpaid = tx.clone();
+        underpaid.proposal_procedures[1].deposit -= 1_000_000;
+        underpaid.outputs[0].amount += 1_000_000;
+        assert_eq!(
+            ledger.check_transaction(&signed(underpaid, &signers), 0),
+            Err(LedgerError::ProposalDepositIncorrect {
+                deposit: 99_999_000_000,
+                expected: 100_000_000_000,
+            })
+        );
+        let tx = signed(tx, &signers);
+        assert_eq!(ledger.apply_tx(&tx, 0), Ok(()));
+        assert_eq!(ledger.state.deposited, 201_006_000_000);
+        assert_eq!(ledger.state.governance.actions.len(), 2);
+
+        let action = |index| GovActionId {
+            tx_id: tx.id(),
+            index,
+        };
+        let vote = |voter: Voter, index| VotingProcedure {
+            voter,
+            action_id: action(index),
+            vote: Vote::Yes,
+        };
+        let ballot = Transaction {
             inputs: vec![TxInput {
-                prev_tx: 4,
+                prev_tx: tx.id(),
                 index: 0,
             }],
-            outputs: vec![TxOutput {
-                address: "B".to_string(),
-                amount: 50,
-                assets: None,
+            outputs: vec![output(addr("alice"), 89_993_500_000)],
+            fee: 200_000,
+            voting_procedures: vec![
+                vote(Voter::DRep(drep1), 0),
+                vote(Voter::StakePool(pool1.clone()), 0),
+            ],
+            ..Default::default()
+        };
+        let stray = |votes| {
+            let tx = Transaction {
+                voting_procedures: votes,
+                ..ballot.clone()
+            };
+            signed(tx, &["alice", "cc1", "drep1", "pool1"])
+        };
+        assert_eq!(
+            ledger.check_transaction(&stray(vec![vote(Voter::Committee(cred("cc1")), 0)]), 0),
+            Err(LedgerError::UnknownVoter(Voter::Committee(cred("cc1"))))
+        );
+        assert_eq!(
+            ledger.check_transaction(&stray(vec![vote(Voter::StakePool(pool1.clone()), 1)]), 0),
+            Err(LedgerError::VoterNotAllowed {
+                voter: Voter::StakePool(pool1.clone()),
+                action_id: action(1),
+            })
+        );
+        assert_eq!(
+            ledger.check_transaction(&stray(vec![vote(Voter::DRep(drep1), 2)]), 0),
+            Err(LedgerError::UnknownGovAction(action(2)))
+        );
+        // Every voter signs its votes.
+        assert_eq!(
+            ledger.check_transaction(&signed(ballot.clone(), &["alice", "drep1"]), 0),
+            Err(LedgerError::MissingVKeyWitness {
+                key_hash: pool1.clone()
+            })
+        );
+        assert_eq!(
+            ledger.apply_tx(&signed(ballot, &["alice", "drep1", "pool1"]), 0),
+            Ok(())
+        );
+
+        // Alice's DRep and pool elect the committee with the stake of the snapshot taken at the
+        // boundary, as Bob abstains; the deposit goes back to the return account.
+        ledger.state.new_epoch(1);
+        let mark = &ledger.state.snapshots.mark;
+        assert_eq!(mark.pool_stake(&pool1), 89_993_500_000);
+        let committee = ledger.state.governance.committee.as_ref().unwrap();
+        assert_eq!(committee.members, BTreeMap::from([(cred("cc1"), 10)]));
+        assert_eq!(ledger.state.rewards[&stake1], 100_000_000_000);
+        assert_eq!(ledger.state.deposited, 101_006_000_000);
+        // Without committee approval, the treasury withdrawal lapses after its lifetime.
+        ledger.state.new_epoch(6);
+        assert_eq!(ledger.state.governance.actions.len(), 1);
+        ledger.state.new_epoch(7);
+        assert!(ledger.state.governance.actions.is_empty());
+        assert_eq!(ledger.state.rewards[&stake1], 200_000_000_000);
+        assert!(!ledger.state.rewards.contains_key(&cred("stake2")));
+        assert_eq!(ledger.state.deposited, 1_006_000_000);
+    }
+
+    #[test]
+    fn treasury_withdrawals_to_unregistered_accounts_stay_in_the_treasury() {
+        let mut state = LedgerState::default();
+        let [stake1, stake2, cc1] = ["stake1", "stake2", "cc1"].map(cred);
+        state.stake_keys.insert(stake1, 2_000_000);
+        state.treasury = 10_000_000;
+        state.params.drep_voting_thresholds.treasury_withdrawal = Rational::new(0, 1);
+        state.governance.committee = Some(governance::Committee {
+            members: BTreeMap::from([(cc1, 10)]),
+            threshold: Rational::new(1, 1),
+        });
+        let withdraw = GovAction::TreasuryWithdrawals(BTreeMap::from([
+            (stake1, 3_000_000),
+            (stake2, 5_000_000),
+        ]));
+        let id = GovActionId {
+            tx_id: [0u8; 32],
+            index: 0,
+        };
+        let action = governance::GovActionState {
+            proposal: ProposalProcedure {
+                deposit: 0,
+                return_account: stake1,
+                action: withdraw,
+                anchor: governance::Anchor::default(),
+            },
+            expires_after: 5,
+            votes: BTreeMap::from([(Voter::Committee(cc1), Vote::Yes)]),
+        };
+        state.governance.actions.insert(id, action);
+        state.ratify_governance(1);
+        assert!(state.governance.actions.is_empty());
+        assert_eq!(state.rewards[&stake1], 3_000_000);
+        assert!(!state.rewards.contains_key(&stake2));
+        assert_eq!(state.treasury, 7_000_000);
+    }
+
+    #[test]
+    fn tokens_are_minted_preserved_and_burned_across_blocks() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let collateral = genesis(&mut ledger, 5_000_000);
+        // Always succeeds: \_redeemer _context -> ()
+        let policy = plutus_script(Term::Lambda(Box::new(Term::Lambda(Box::new(
+            Term::Constant(Rc::new(Constant::Unit)),
+        )))));
+        let policy_id = policy.hash();
+        let tokens = |amount| Value::token(&policy_id, "tok", amount);
+        let holding = |address: Address, value: Value| TxOutput {
+            address,
+            amount: value.coin,
+            assets: value.to_assets(),
+            ..Default::default()
+        };
+        let witness = PlutusWitness {
+            script: policy.clone(),
+            datum: PlutusDatum { data: vec![] },
+            redeemer: PlutusRedeemer { data: vec![] },
+            execution_units: (10_000, 10_000_000),
+        };
+        let mint = |amount: i64| {
+            MultiAsset::from([(
+                policy_id.clone(),
+                BTreeMap::from([("tok".to_string(), amount)]),
+            )])
+        };
+
+        let mut mint_tx = Transaction {
+            inputs: vec![input],
+            outputs: vec![holding(addr("A"), Value::lovelace(9_800_000) + tokens(100))],
+            fee: 200_000,
+            mint: mint(100),
+            collateral: vec![collateral.clone()],
+            ..Default::default()
+        };
+        assert_eq!(
+            ledger.check_transaction(&mint_tx, 1),
+            Err(LedgerError::MissingMintingPolicy {
+                policy_id: policy_id.clone(),
+            })
+        );
+        mint_tx.plutus_witnesses.push(witness.clone());
+        let mut overminted = mint_tx.clone();
+        overminted.outputs[0] = holding(addr("A"), Value::lovelace(9_800_000) + tokens(101));
+        assert!(matches!(
+            ledger.check_transaction(&overminted, 1),
+            Err(LedgerError::ValueNotConserved { .. })
+        ));
+        let mint_tx = signed(mint_tx, &["genesis"]);
+        let block1 = Block::new(1, header(1, None), vec![mint_tx.clone()]);
+        assert!(ledger.apply_block(&block1));
+        assert_eq!(
+            ledger.state.balance(&addr("A")),
+            Value::lovelace(9_800_000) + tokens(100)
+        );
+
+        let minted = TxInput {
+            prev_tx: mint_tx.id(),
+            index: 0,
+        };
+        // Tokens cannot silently disappear from a transfer.
+        let dropped = Transaction {
+            inputs: vec![minted.clone()],
+            outputs: vec![holding(addr("B"), Value::lovelace(9_600_000))],
+            fee: 200_000,
+            ..Default::default()
+        };
+        assert!(matches!(
+            ledger.check_transaction(&dropped, 2),
+            Err(LedgerError::ValueNotConserved { .. })
+        ));
+        let transfer = Transaction {
+            inputs: vec![minted],
+            outputs: vec![
+                holding(addr("B"), Value::lovelace(2_000_000) + tokens(40)),
+                holding(addr("A"), Value::lovelace(7_600_000) + tokens(60)),
+            ],
+            fee: 200_000,
+            ..Default::default()
+        };
+        let transfer = signed(transfer, &["A"]);
+        let change = TxInput {
+            prev_tx: transfer.id(),
+            index: 1,
+        };
+        let burn = Transaction {
+            inputs: vec![change],
+            outputs: vec![holding(addr("A"), Value::lovelace(7_400_000) + tokens(10))],
+            fee: 200_000,
+            mint: mint(-50),
+            plutus_witnesses: vec![witness],
+            collateral: vec![collateral],
+            ..Default::default()
+        };
+        let burn = signed(burn, &["A", "genesis"]);
+        let block2 = Block::new(2, header(2, Some(block1.hash())), vec![transfer, burn]);
+        assert!(ledger.apply_block(&block2));
+        assert_eq!(
+            ledger.state.balance(&addr("A")),
+            Value::lovelace(7_400_000) + tokens(10)
+        );
+        assert_eq!(
+            ledger.state.balance(&addr("B")),
+            Value::lovelace(2_000_000) + tokens(40)
+        );
+    }
+
+    #[test]
+    fn native_scripts_lock_outputs_and_mint_tokens() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let sig = |name: &str| NativeScript::RequireSignature(key_hash(name));
+        // 2-of-3 treasury multisig, spendable until slot 1000.
+        let treasury = NativeScript::AllOf(vec![
+            NativeScript::MOfN(2, vec![sig("alice"), sig("bob"), sig("carol")]),
+            NativeScript::InvalidHereafter(1_000),
+        ]);
+        let input = TxInput {
+            prev_tx: [0xee; 32],
+            index: 0,
+        };
+        ledger.state.utxos.insert(
+            (input.prev_tx, input.index),
+            output(
+                Address::enterprise(Network::Testnet, treasury.credential()),
+                10_000_000,
+            ),
+        );
+        let unsigned = |native_scripts: Vec<NativeScript>| Transaction {
+            inputs: vec![input.clone()],
+            outputs: vec![output(addr("A"), 9_700_000)],
+            fee: 300_000,
+            ttl: Some(1_000),
+            native_scripts,
+            ..Default::default()
+        };
+        let spend = |signers: &[&str], native_scripts| signed(unsigned(native_scripts), signers);
+        let failed = |script: &NativeScript| {
+            Err(LedgerError::NativeScriptFailed {
+                script_hash: script.hash(),
+            })
+        };
+        assert_eq!(
+            ledger.check_transaction(&spend(&["alice", "bob"], vec![]), 0),
+            Err(LedgerError::MissingScript {
+                script_hash: treasury.hash(),
+            })
+        );
+        assert_eq!(
+            ledger.check_transaction(&spend(&["alice"], vec![treasury.clone()]), 0),
+            failed(&treasury)
+        );
+        let mut late = unsigned(vec![treasury.clone()]);
+        late.ttl = Some(1_001);
+        let late = signed(late, &["alice", "bob"]);
+        assert_eq!(ledger.check_transaction(&late, 0), failed(&treasury));
+
+        // A native minting policy: only "issuer" may mint.
+        let policy = sig("issuer");
+        let mut mint = unsigned(vec![treasury.clone(), policy.clone()]);
+        mint.mint = MultiAsset::from([(policy.hash(), BTreeMap::from([("coin".to_string(), 5)]))]);
+        mint.outputs[0].assets = Some(vec![Asset {
+            policy_id: policy.hash(),
+            asset_name: "coin".to_string(),
+            amount: 5,
+        }]);
+        assert_eq!(
+            ledger.check_transaction(&signed(mint.clone(), &["alice", "carol"]), 0),
+            failed(&policy)
+        );
+        let mint = signed(mint, &["alice", "carol", "issuer"]);
+        assert_eq!(ledger.apply_tx(&mint, 0), Ok(()));
+        assert_eq!(
+            ledger
+                .state
+                .balance(&addr("A"))
+                .quantity(&policy.hash(), "coin"),
+            5
+        );
+    }
+
+    #[test]
+    fn script_reward_accounts_need_their_script() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let script = NativeScript::RequireSignature(key_hash("operator"));
+        let account = script.credential();
+        ledger.state.rewards.insert(account, 2_000_000);
+        let withdraw = |native_scripts: Vec<NativeScript>| Transaction {
+            inputs: vec![input.clone()],
+            outputs: vec![output(addr("A"), 11_800_000)],
+            fee: 200_000,
+            withdrawals: BTreeMap::from([(account, 2_000_000)]),
+            native_scripts,
+            ..Default::default()
+        };
+        assert_eq!(
+            ledger.check_transaction(&signed(withdraw(vec![]), &["genesis", "operator"]), 0),
+            Err(LedgerError::MissingScript {
+                script_hash: script.hash(),
+            })
+        );
+        assert_eq!(
+            ledger.check_transaction(&signed(withdraw(vec![script.clone()]), &["genesis"]), 0),
+            Err(LedgerError::NativeScriptFailed {
+                script_hash: script.hash(),
+            })
+        );
+        let tx = signed(withdraw(vec![script.clone()]), &["genesis", "operator"]);
+        assert_eq!(ledger.apply_tx(&tx, 0), Ok(()));
+        assert_eq!(ledger.state.rewards[&account], 0);
+
+        // Delegating the script credential needs the script too.
+        let delegate = Transaction {
+            inputs: vec![TxInput {
+                prev_tx: tx.id(),
+                index: 0,
             }],
-            certificates: vec![],
-            plutus_witnesses: vec![],
+            outputs: vec![output(addr("A"), 11_600_000)],
+            fee: 200_000,
+            certificates: vec![Certificate::Delegation(DelegationCertificate {
+                delegator: account,
+                pool_id: key_hash("pool1"),
+            })],
+            ..Default::default()
         };
-        let block = Block {
-            id: 1,
-            header: BlockHeader {
-                slot: 1,
+        assert_eq!(
+            ledger.check_transaction(&signed(delegate, &["A"]), 0),
+            Err(LedgerError::MissingScript {
+                script_hash: script.hash(),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_transactions_reject_the_whole_block() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let spend = |name: &str| {
+            let tx = Transaction {
+                inputs: vec![input.clone()],
+                outputs: vec![output(addr(name), 9_800_000)],
+                fee: 200_000,
+                ..Default::default()
+            };
+            signed(tx, &["genesis"])
+        };
+        let block = Block::new(1, header(1, None), vec![spend("A"), spend("B")]);
+        assert!(matches!(
+            ledger.try_apply_block(&block),
+            Err(LedgerError::InvalidTransaction { index: 1, .. })
+        ));
+        // Nothing of the block was applied.
+        assert_eq!(ledger.tip(), None);
+        assert!(
+            ledger
+                .state
+                .utxos
+                .contains_key(&(input.prev_tx, input.index))
+        );
+    }
+
+    #[test]
+    fn update_proposals_are_adopted_at_the_epoch_boundary() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let raise = ParamUpdate {
+            min_fee_a: Some(50),
+            max_block_size: Some(90_112),
+            ..Default::default()
+        };
+        let delegates = [
+            "genesis",
+            "delegate1",
+            "delegate2",
+            "delegate3",
+            "delegate4",
+            "dissent",
+        ];
+        ledger.set_genesis_delegates(delegates.map(key_hash));
+        let mut proposals: BTreeMap<String, ParamUpdate> = delegates[..params::UPDATE_QUORUM]
+            .iter()
+            .map(|name| (key_hash(name), raise.clone()))
+            .collect();
+        let dissent = ParamUpdate {
+            min_fee_a: Some(1),
+            ..Default::default()
+        };
+        proposals.insert(key_hash("dissent"), dissent);
+        let propose = |epoch, proposals: &BTreeMap<String, ParamUpdate>| Transaction {
+            inputs: vec![input.clone()],
+            outputs: vec![output(addr("A"), 9_700_000)],
+            fee: 300_000,
+            update: Some(Update {
+                proposals: proposals.clone(),
+                epoch,
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            ledger.check_transaction(&signed(propose(1, &proposals), &delegates), 0),
+            Err(LedgerError::InvalidUpdate(_))
+        ));
+        // Only genesis delegates propose, and each of them signs.
+        let mut outsider = proposals.clone();
+        outsider.insert(key_hash("mallory"), raise.clone());
+        let mut signers = delegates.to_vec();
+        signers.push("mallory");
+        assert_eq!(
+            ledger.check_transaction(&signed(propose(0, &outsider), &signers), 0),
+            Err(LedgerError::InvalidUpdate(format!(
+                "{} is not a genesis delegate",
+                key_hash("mallory")
+            )))
+        );
+        assert!(matches!(
+            ledger.check_transaction(&signed(propose(0, &proposals), &["genesis"]), 0),
+            Err(LedgerError::MissingVKeyWitness { .. })
+        ));
+        let tx = signed(propose(0, &proposals), &delegates);
+        let first = Block::new(1, header(1, None), vec![tx]);
+        assert_eq!(ledger.try_apply_block(&first), Ok(()));
+        assert_eq!(ledger.state.proposals.len(), params::UPDATE_QUORUM + 1);
+        assert_eq!(ledger.protocol_parameters().min_fee_a, 44);
+
+        // The first block of the next epoch sees the update that reached the quorum.
+        let mut next = header(432_002, Some(first.hash()));
+        next.epoch = 1;
+        assert_eq!(ledger.try_apply_block(&Block::new(2, next, vec![])), Ok(()));
+        assert_eq!(ledger.protocol_parameters().min_fee_a, 50);
+        assert_eq!(ledger.protocol_parameters().max_block_size, 90_112);
+        assert!(ledger.state.proposals.is_empty());
+        // Entering the current era again keeps the adopted parameters.
+        ledger.enter_era("Shelley");
+        assert_eq!(ledger.protocol_parameters().min_fee_a, 50);
+
+        // Without a quorum, the proposals lapse.
+        let mut state = LedgerState::default();
+        state.proposals = (1..params::UPDATE_QUORUM)
+            .map(|i| (format!("genesis{}", i), raise.clone()))
+            .collect();
+        state.new_epoch(1);
+        assert_eq!(state.params, ProtocolParameters::default());
+        assert!(state.proposals.is_empty());
+    }
+
+    #[test]
+    fn blocks_and_pools_respect_size_and_cost_limits() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let tx = Transaction {
+            inputs: vec![input],
+            outputs: vec![output(addr("A"), 9_800_000)],
+            fee: 200_000,
+            ..Default::default()
+        };
+        let tx = signed(tx, &["genesis"]);
+        let block = Block::new(1, header(1, None), vec![tx.clone()]);
+        assert_eq!(block.body_size(), tx.size());
+        ledger.state.params.max_block_size = tx.size() - 1;
+        assert_eq!(
+            ledger.try_apply_block(&block),
+            Err(LedgerError::BlockTooLarge {
+                block_id: 1,
+                size: tx.size(),
+                max: tx.size() - 1,
+            })
+        );
+        ledger.state.params.max_block_size = tx.size();
+        assert_eq!(ledger.try_apply_block(&block), Ok(()));
+
+        let cheap = Certificate::PoolRegistration(StakePoolRegistration {
+            pool_id: "pool1".to_string(),
+            vrf_keyhash: [0u8; 32],
+            owner: cred("owner1"),
+            pledge: 1000,
+            cost: 100,
+            margin: 0.05,
+            reward_account: cred("owner1_reward"),
+        });
+        assert!(ledger.state.apply_certificate(&cheap, 0).is_err());
+    }
+
+    #[test]
+    fn skipped_epochs_each_tick_the_nonce() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let first = Block::new(1, header(1, None), vec![]);
+        let second = Block::new(2, header(2, Some(first.hash())), vec![]);
+        assert_eq!(ledger.try_apply_block(&first), Ok(()));
+        assert_eq!(ledger.try_apply_block(&second), Ok(()));
+        // Epoch 1 mixes in no block of an earlier epoch; epoch 2 mixes in the last of epoch 0.
+        let candidate = ledger.state.nonces.candidate_nonce;
+        let expected = crate::consensus::nonce::combine(&candidate, &first.hash());
+        assert_eq!(ledger.epoch_nonce(1), candidate);
+        assert_eq!(ledger.epoch_nonce(2), expected);
+        let mut third = header(864_250, Some(second.hash()));
+        third.epoch = 2;
+        assert_eq!(
+            ledger.try_apply_block(&Block::new(3, third, vec![])),
+            Ok(())
+        );
+        assert_eq!(ledger.state.nonces.epoch_nonce, expected);
+        assert_eq!(ledger.epoch_nonce(2), expected);
+    }
+
+    #[test]
+    fn blocks_must_follow_the_tip_in_slot_and_epoch() {
+        let mut ledger = Ledger::new(Tracer::default());
+        ledger.state.params.epoch_length = 100;
+        let first = Block::new(1, header(5, None), vec![]);
+        assert_eq!(ledger.try_apply_block(&first), Ok(()));
+        let same_slot = Block::new(2, header(5, Some(first.hash())), vec![]);
+        assert_eq!(
+            ledger.try_apply_block(&same_slot),
+            Err(LedgerError::SlotNotIncreasing {
+                slot: 5,
+                tip_slot: 5,
+            })
+        );
+        // The epoch is the one the slot falls in, whatever the header claims.
+        let stale = Block::new(2, header(150, Some(first.hash())), vec![]);
+        assert_eq!(
+            ledger.try_apply_block(&stale),
+            Err(LedgerError::WrongEpoch {
+                slot: 150,
                 epoch: 0,
-                leader: "test-leader".to_string(),
-                vrf_proof: vec![0u8; 32],
-                kes_signature: vec![0u8; 32],
-            },
-            transactions: vec![coinbase, spend],
+                expected: 1,
+            })
+        );
+        let mut ahead = header(50, Some(first.hash()));
+        ahead.epoch = 1;
+        assert_eq!(
+            ledger.try_apply_block(&Block::new(2, ahead, vec![])),
+            Err(LedgerError::WrongEpoch {
+                slot: 50,
+                epoch: 1,
+                expected: 0,
+            })
+        );
+        let mut next = header(150, Some(first.hash()));
+        next.epoch = 1;
+        assert_eq!(ledger.try_apply_block(&Block::new(2, next, vec![])), Ok(()));
+        assert_eq!(ledger.current_epoch(), 1);
+    }
+
+    #[test]
+    fn blocks_must_extend_the_tip_by_hash() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let first = Block::default();
+        assert!(ledger.apply_block(&first));
+        assert_eq!(ledger.tip().map(|t| t.hash), Some(first.hash()));
+        // Re-applying the same block no longer extends the tip.
+        assert!(!ledger.apply_block(&first));
+
+        let mut second = Block::default();
+        second.id = 2;
+        second.header.slot = 1;
+        second.header.prev_hash = Some(first.hash());
+        let mut tampered = second.clone();
+        tampered.transactions.push(Transaction::default());
+        assert!(!ledger.apply_block(&tampered));
+        assert!(ledger.apply_block(&second));
+
+        let mut fork = Block::default();
+        fork.id = 2;
+        fork.header.leader = "other".to_string();
+        fork.header.prev_hash = Some(first.hash());
+        let main = Chain {
+            blocks: vec![first.clone(), second],
         };
-        assert!(ledger.apply_block(&block));
-        // The UTXO set should now have only the spend output
-        assert!(ledger.state.utxos.contains_key(&(5, 0)));
-        assert!(!ledger.state.utxos.contains_key(&(4, 0)));
+        let other = Chain {
+            blocks: vec![first.clone(), fork],
+        };
+        assert!(main.is_linked() && other.is_linked());
+        assert_eq!(main.intersection(&other), Some(first.hash()));
     }
 
     #[test]
     fn test_chain_selection() {
-        let block = Block {
-            id: 1,
-            header: BlockHeader {
+        let block = Block::new(
+            1,
+            BlockHeader {
                 slot: 1,
                 epoch: 0,
                 leader: "test-leader".to_string(),
-                vrf_proof: vec![0u8; 32],
-                kes_signature: vec![0u8; 32],
+                issuer_vkey: vec![0u8; 32],
+                vrf_vkey: vec![0u8; 32],
+                vrf_proof: vec![0u8; 80],
+                opcert: OperationalCert::default(),
+                kes_signature: vec![0u8; 448],
+                prev_hash: None,
+                body_hash: [0u8; 32],
             },
-            transactions: vec![],
-        };
+            vec![],
+        );
         let chain1 = Chain {
             blocks: vec![block.clone()],
         };
@@ -822,53 +2431,299 @@ mod tests {
         assert_eq!(selected.blocks.len(), 2);
     }
 
+    /// Plutus script spendable with a redeemer equal to the datum:
+    /// `\datum redeemer _context -> force (ifThenElse (equalsData datum redeemer) (delay ())
+    /// (delay error))`
+    fn datum_matching_script() -> PlutusScript {
+        let check = Term::Force(Box::new(Term::Builtin(Builtin::IfThenElse))).apply(
+            Term::Builtin(Builtin::EqualsData)
+                .apply(Term::Var(3))
+                .apply(Term::Var(2)),
+        );
+        let body = Term::Force(Box::new(
+            check
+                .apply(Term::Delay(Box::new(Term::Constant(Rc::new(
+                    Constant::Unit,
+                )))))
+                .apply(Term::Delay(Box::new(Term::Error))),
+        ));
+        plutus_script(Term::Lambda(Box::new(Term::Lambda(Box::new(
+            Term::Lambda(Box::new(body)),
+        )))))
+    }
+
     #[test]
     fn test_plutus_script_validation() {
-        let mut state = LedgerState {
-            utxos: HashMap::new(),
-            stake_distribution: HashMap::new(),
-            delegations: HashMap::new(),
-            stake_pools: HashMap::new(),
-            pool_retirements: HashMap::new(),
-            rewards: HashMap::new(),
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let collateral = genesis(&mut ledger, 5_000_000);
+        let script = datum_matching_script();
+        let script_hash = script.hash();
+        let lock = Transaction {
+            inputs: vec![input],
+            outputs: vec![
+                output(
+                    Address::enterprise(Network::Testnet, script.credential()),
+                    5_000_000,
+                ),
+                output(addr("A"), 4_800_000),
+            ],
+            fee: 200_000,
+            ..Default::default()
         };
-        let script = PlutusScript {
-            code: vec![1, 2, 3],
+        let lock = signed(lock, &["genesis"]);
+        assert_eq!(ledger.apply_tx(&lock, 0), Ok(()));
+
+        let data = |i: i64| PlutusData::Integer(i.into()).to_cbor();
+        let witness = PlutusWitness {
+            script: script.clone(),
+            datum: PlutusDatum { data: data(42) },
+            redeemer: PlutusRedeemer { data: data(42) },
+            execution_units: (100_000, 50_000_000),
         };
-        let datum = PlutusDatum {
-            data: vec![4, 5, 6],
+        // The collateral is locked by the genesis key.
+        let spend = |witnesses: Vec<PlutusWitness>| {
+            let tx = Transaction {
+                inputs: vec![TxInput {
+                    prev_tx: lock.id(),
+                    index: 0,
+                }],
+                outputs: vec![output(addr("B"), 4_800_000)],
+                fee: 200_000,
+                plutus_witnesses: witnesses,
+                collateral: vec![collateral.clone()],
+                ..Default::default()
+            };
+            signed(tx, &["genesis"])
         };
-        let redeemer = PlutusRedeemer {
-            data: vec![7, 8, 9],
+        assert_eq!(
+            ledger.check_transaction(&spend(vec![]), 1),
+            Err(LedgerError::MissingScript {
+                script_hash: script_hash.clone(),
+            })
+        );
+        let wrong_redeemer = PlutusWitness {
+            redeemer: PlutusRedeemer { data: data(7) },
+            ..witness.clone()
         };
-        let witness = PlutusWitness {
-            script: script.clone(),
-            datum: datum.clone(),
-            redeemer: redeemer.clone(),
-            execution_units: (1000, 5000),
+        assert_eq!(
+            ledger.check_transaction(&spend(vec![wrong_redeemer]), 1),
+            Err(LedgerError::PlutusScriptFailed {
+                script_hash: script_hash.clone(),
+                error: PlutusError::ExplicitError,
+            })
+        );
+        let underfunded = PlutusWitness {
+            execution_units: (100_000, 100_000),
+            ..witness.clone()
         };
-        let tx = Transaction {
-            id: 42,
-            inputs: vec![],
-            outputs: vec![],
-            certificates: vec![],
-            plutus_witnesses: vec![witness],
+        assert!(matches!(
+            ledger.check_transaction(&spend(vec![underfunded]), 1),
+            Err(LedgerError::PlutusScriptFailed {
+                error: PlutusError::BudgetExceeded { .. },
+                ..
+            })
+        ));
+        assert_eq!(
+            ledger.check_transaction(&spend(vec![witness.clone(), witness.clone()]), 1),
+            Err(LedgerError::ExtraneousRedeemer {
+                script_hash: script_hash.clone(),
+            })
+        );
+        let tx = spend(vec![witness]);
+        assert_eq!(ledger.apply_tx(&tx, 1), Ok(()));
+        assert_eq!(ledger.state.balance(&addr("B")), Value::lovelace(4_800_000));
+    }
+
+    #[test]
+    fn failing_scripts_forfeit_only_collateral() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 10_000_000);
+        let collateral = genesis(&mut ledger, 3_000_000);
+        let script = datum_matching_script();
+        let lock = Transaction {
+            inputs: vec![input],
+            outputs: vec![
+                output(
+                    Address::enterprise(Network::Testnet, script.credential()),
+                    5_000_000,
+                ),
+                output(addr("A"), 4_800_000),
+            ],
+            fee: 200_000,
+            ..Default::default()
+        };
+        let lock = signed(lock, &["genesis"]);
+        assert_eq!(ledger.apply_tx(&lock, 0), Ok(()));
+        let locked = TxInput {
+            prev_tx: lock.id(),
+            index: 0,
+        };
+
+        let data = |i: i64| PlutusData::Integer(i.into()).to_cbor();
+        let failing = Transaction {
+            inputs: vec![locked.clone()],
+            outputs: vec![output(addr("B"), 4_800_000)],
+            fee: 200_000,
+            plutus_witnesses: vec![PlutusWitness {
+                script,
+                datum: PlutusDatum { data: data(42) },
+                redeemer: PlutusRedeemer { data: data(7) },
+                execution_units: (100_000, 50_000_000),
+            }],
+            collateral: vec![collateral.clone()],
+            collateral_return: Some(output(addr("C"), 2_500_000)),
+            ..Default::default()
+        };
+        let failing = signed(failing, &["genesis"]);
+        let with_collateral = |collateral: Vec<TxInput>, returned: u64| Transaction {
+            collateral,
+            collateral_return: Some(output(addr("C"), returned)),
+            ..failing.clone()
         };
-        assert!(state.validate_plutus_scripts(&tx));
-        // Invalid: empty script
-        let bad_witness = PlutusWitness {
+        assert_eq!(
+            ledger.check_transaction(&with_collateral(vec![], 2_500_000), 1),
+            Err(LedgerError::NoCollateralInputs)
+        );
+        assert_eq!(
+            ledger.check_transaction(&with_collateral(vec![locked.clone()], 2_500_000), 1),
+            Err(LedgerError::CollateralLockedByScript(locked.clone()))
+        );
+        // The fee is 200_000, so at least 300_000 lovelace must be forfeited.
+        assert_eq!(
+            ledger.check_transaction(&with_collateral(vec![collateral.clone()], 2_800_000), 1),
+            Err(LedgerError::InsufficientCollateral {
+                provided: 200_000,
+                required: 300_000,
+            })
+        );
+        let mut passing = failing.clone();
+        passing.plutus_witnesses[0].redeemer.data = data(42);
+        passing.is_valid = false;
+        assert_eq!(
+            ledger.check_transaction(&passing, 1),
+            Err(LedgerError::ValidationTagMismatch)
+        );
+
+        // Submitted as valid, the failing transaction only forfeits its collateral.
+        let mut submitted = ledger.clone();
+        assert!(submitted.apply_transaction(&failing));
+        // In a block it must be marked invalid; a block producer does so, and the block collects
+        // the same collateral.
+        let unmarked = Block::new(1, header(1, None), vec![failing.clone()]);
+        assert!(!ledger.clone().apply_block(&unmarked));
+        let selected = ledger.select_transactions(vec![failing.clone()], 1, 0);
+        assert!(!selected[0].is_valid);
+        assert!(ledger.apply_block(&Block::new(1, header(1, None), selected)));
+        for state in [&submitted.state, &ledger.state] {
+            assert_eq!(state.fees, 200_000 + 500_000);
+            assert!(state.utxos.contains_key(&(locked.prev_tx, locked.index)));
+            assert!(
+                !state
+                    .utxos
+                    .contains_key(&(collateral.prev_tx, collateral.index))
+            );
+            assert_eq!(
+                state.utxos[&(failing.id(), 1)],
+                output(addr("C"), 2_500_000)
+            );
+            assert_eq!(state.balance(&addr("B")), Value::default());
+        }
+    }
+
+    #[test]
+    fn reference_scripts_and_inline_datums() {
+        let mut ledger = Ledger::new(Tracer::default());
+        let input = genesis(&mut ledger, 20_000_000);
+        let collateral = genesis(&mut ledger, 5_000_000);
+        let script = datum_matching_script();
+        let address = Address::enterprise(Network::Testnet, script.credential());
+        let data = |i: i64| PlutusData::Integer(i.into()).to_cbor();
+        let datum = PlutusDatum { data: data(42) };
+        let lock = Transaction {
+            inputs: vec![input],
+            outputs: vec![
+                TxOutput {
+                    datum: Some(DatumOption::Inline(datum.clone())),
+                    ..output(address.clone(), 5_000_000)
+                },
+                TxOutput {
+                    datum: Some(DatumOption::Hash(datum.hash())),
+                    ..output(address.clone(), 5_000_000)
+                },
+                TxOutput {
+                    script_ref: Some(ScriptRef::Plutus(script.clone())),
+                    ..output(addr("R"), 3_000_000)
+                },
+                output(addr("A"), 6_800_000),
+            ],
+            fee: 200_000,
+            ..Default::default()
+        };
+        let lock = signed(lock, &["genesis"]);
+        assert_eq!(ledger.apply_tx(&lock, 0), Ok(()));
+        let utxo = |index: u32| TxInput {
+            prev_tx: lock.id(),
+            index,
+        };
+
+        // Neither witness carries the script: it is read from the referenced output. The first
+        // input holds its datum inline, the second only its hash.
+        let witness = |datum: Vec<u8>| PlutusWitness {
             script: PlutusScript { code: vec![] },
-            datum,
-            redeemer,
-            execution_units: (100, 100),
-        };
-        let bad_tx = Transaction {
-            id: 43,
-            inputs: vec![],
-            outputs: vec![],
-            certificates: vec![],
-            plutus_witnesses: vec![bad_witness],
+            datum: PlutusDatum { data: datum },
+            redeemer: PlutusRedeemer { data: data(42) },
+            execution_units: (100_000, 50_000_000),
         };
-        assert!(!state.validate_plutus_scripts(&bad_tx));
+        let spend = Transaction {
+            inputs: vec![utxo(0), utxo(1)],
+            reference_inputs: vec![utxo(2)],
+            outputs: vec![output(addr("B"), 9_600_000)],
+            fee: 400_000,
+            plutus_witnesses: vec![witness(vec![]), witness(data(42))],
+            collateral: vec![collateral],
+            ..Default::default()
+        };
+        let spend = signed(spend, &["genesis"]);
+        assert_eq!(
+            ledger.check_transaction(
+                &Transaction {
+                    reference_inputs: vec![],
+                    ..spend.clone()
+                },
+                1
+            ),
+            Err(LedgerError::MissingScript {
+                script_hash: script.hash(),
+            })
+        );
+        assert_eq!(
+            ledger.check_transaction(
+                &Transaction {
+                    reference_inputs: vec![utxo(4)],
+                    ..spend.clone()
+                },
+                1
+            ),
+            Err(LedgerError::BadInput(utxo(4)))
+        );
+        assert_eq!(
+            ledger.check_transaction(
+                &Transaction {
+                    plutus_witnesses: vec![witness(vec![]), witness(data(7))],
+                    ..spend.clone()
+                },
+                1
+            ),
+            Err(LedgerError::DatumHashMismatch(utxo(1)))
+        );
+
+        assert_eq!(ledger.check_transaction(&spend, 1), Ok(()));
+        assert_eq!(ledger.apply_tx(&spend, 1), Ok(()));
+        let utxos = &ledger.state.utxos;
+        assert!(!utxos.contains_key(&(lock.id(), 0)));
+        assert!(!utxos.contains_key(&(lock.id(), 1)));
+        // Referenced, not spent.
+        assert!(utxos.contains_key(&(lock.id(), 2)));
     }
 }
@@ -0,0 +1,783 @@
+//! Shelley addresses and credentials (CIP-19).
+//!
+//! An address starts with a header byte: the address type in its high nibble and the network id
+//! in its low nibble. The type says which credentials follow and whether each is the Blake2b-224
+//! hash of a verification key or of a script:
+//! - base addresses (types 0-3) hold a payment and a stake credential;
+//! - pointer addresses (types 4-5) hold a payment credential and a [`Pointer`] to the certificate
+//!   that registered their stake credential;
+//! - enterprise addresses (types 6-7) hold only a payment credential, so they carry no stake;
+//! - reward addresses (types 14-15) hold a stake credential and name a reward account;
+//! - Byron bootstrap addresses (type 8) are CBOR, `[#6.24(bytes .cbor [root, attributes, type]),
+//!   crc32]`, and are kept as their raw bytes.
+//!
+//! Shelley addresses are written in bech32 with the prefix `addr` (`stake` for reward addresses),
+//! followed by `_test` off mainnet; Byron addresses are written in base58. Credentials are
+//! written in the CIP-5 bech32 forms `stake_vkh` and `script`.
+
+use blake2::digest::consts::U28;
+use blake2::{Blake2b, Digest};
+use minicbor::Decoder;
+use minicbor::data::{Tag, Type};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Blake2b-224 hash of a verification key or script.
+pub type Hash28 = [u8; 28];
+
+/// Header type of Byron bootstrap addresses.
+const BYRON_TYPE: u8 = 8;
+
+/// Network an address belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Network {
+    /// Network id 0, used by the test networks.
+    Testnet,
+    /// Network id 1.
+    Mainnet,
+}
+
+impl Network {
+    /// Network id of the address header.
+    pub fn id(self) -> u8 {
+        match self {
+            Network::Testnet => 0,
+            Network::Mainnet => 1,
+        }
+    }
+
+    /// Network with id `id`.
+    pub fn from_id(id: u8) -> Result<Self, AddressError> {
+        match id {
+            0 => Ok(Network::Testnet),
+            1 => Ok(Network::Mainnet),
+            id => Err(AddressError::UnknownNetwork(id)),
+        }
+    }
+}
+
+/// Payment or stake credential: the hash of a verification key or of a script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Credential {
+    KeyHash(Hash28),
+    ScriptHash(Hash28),
+}
+
+impl Credential {
+    /// Key hash credential of the verification key bytes `vkey`.
+    pub fn from_vkey(vkey: &[u8]) -> Self {
+        Credential::KeyHash(Blake2b::<U28>::digest(vkey).into())
+    }
+
+    /// The key or script hash.
+    pub fn hash(&self) -> &Hash28 {
+        match self {
+            Credential::KeyHash(hash) | Credential::ScriptHash(hash) => hash,
+        }
+    }
+
+    /// Whether a script rather than a key controls the credential.
+    pub fn is_script(&self) -> bool {
+        matches!(self, Credential::ScriptHash(_))
+    }
+
+    /// Key hash as hex, the form witnesses and native scripts use, or `None` for a script.
+    pub fn key_hash(&self) -> Option<String> {
+        match self {
+            Credential::KeyHash(hash) => Some(crate::chaindb::hex(hash)),
+            Credential::ScriptHash(_) => None,
+        }
+    }
+
+    /// Script hash as hex, the form scripts are looked up by, or `None` for a key.
+    pub fn script_hash(&self) -> Option<String> {
+        match self {
+            Credential::ScriptHash(hash) => Some(crate::chaindb::hex(hash)),
+            Credential::KeyHash(_) => None,
+        }
+    }
+
+    /// Script hash credential if `script`, key hash credential otherwise.
+    pub(super) fn new(script: bool, hash: Hash28) -> Self {
+        if script {
+            Credential::ScriptHash(hash)
+        } else {
+            Credential::KeyHash(hash)
+        }
+    }
+}
+
+impl fmt::Display for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = if self.is_script() {
+            "script"
+        } else {
+            "stake_vkh"
+        };
+        f.write_str(&bech32::encode(hrp, self.hash()))
+    }
+}
+
+impl FromStr for Credential {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hrp, data) = bech32::decode(s)?;
+        let script = match hrp.as_str() {
+            "addr_vkh" | "stake_vkh" => false,
+            "script" => true,
+            _ => return Err(AddressError::UnexpectedPrefix(hrp)),
+        };
+        let hash = data
+            .try_into()
+            .map_err(|data: Vec<u8>| AddressError::InvalidLength(data.len()))?;
+        Ok(Credential::new(script, hash))
+    }
+}
+
+/// Location of the stake registration certificate a pointer address refers to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pointer {
+    pub slot: u64,
+    pub tx_index: u64,
+    pub cert_index: u64,
+}
+
+/// Shelley or Byron address.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Address {
+    Base {
+        network: Network,
+        payment: Credential,
+        stake: Credential,
+    },
+    Pointer {
+        network: Network,
+        payment: Credential,
+        pointer: Pointer,
+    },
+    Enterprise {
+        network: Network,
+        payment: Credential,
+    },
+    Reward {
+        network: Network,
+        stake: Credential,
+    },
+    /// Byron bootstrap address, as its CBOR bytes.
+    Byron(Vec<u8>),
+}
+
+/// Reasons an address or credential cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AddressError {
+    /// The string is not valid bech32.
+    #[error("Invalid bech32: {0}")]
+    Bech32(&'static str),
+
+    /// The string is not valid base58.
+    #[error("Invalid base58")]
+    Base58,
+
+    /// The bech32 prefix does not match the address or credential.
+    #[error("Unexpected bech32 prefix {0}")]
+    UnexpectedPrefix(String),
+
+    /// The header byte names no address type.
+    #[error("Unknown address header {0:#04x}")]
+    UnknownHeader(u8),
+
+    /// The header byte names no known network.
+    #[error("Unknown network id {0}")]
+    UnknownNetwork(u8),
+
+    /// The address or credential is too short or too long for its type.
+    #[error("Invalid length of {0} bytes")]
+    InvalidLength(usize),
+
+    /// A pointer address does not end with three variable-length naturals.
+    #[error("Invalid pointer")]
+    InvalidPointer,
+
+    /// A Byron address is malformed or its checksum does not match.
+    #[error("Invalid Byron address: {0}")]
+    InvalidByron(&'static str),
+}
+
+impl Address {
+    /// Base address of a payment and a stake credential.
+    pub fn base(network: Network, payment: Credential, stake: Credential) -> Self {
+        Address::Base {
+            network,
+            payment,
+            stake,
+        }
+    }
+
+    /// Enterprise address of a payment credential.
+    pub fn enterprise(network: Network, payment: Credential) -> Self {
+        Address::Enterprise { network, payment }
+    }
+
+    /// Reward address of a stake credential.
+    pub fn reward(network: Network, stake: Credential) -> Self {
+        Address::Reward { network, stake }
+    }
+
+    /// Network of the address. Byron addresses are on mainnet unless their attributes carry a
+    /// protocol magic.
+    pub fn network(&self) -> Network {
+        match self {
+            Address::Base { network, .. }
+            | Address::Pointer { network, .. }
+            | Address::Enterprise { network, .. }
+            | Address::Reward { network, .. } => *network,
+            Address::Byron(bytes) => match byron_payload(bytes) {
+                Ok(true) => Network::Testnet,
+                _ => Network::Mainnet,
+            },
+        }
+    }
+
+    /// Credential that spends the outputs paid to the address; `None` for reward and Byron
+    /// addresses.
+    pub fn payment_credential(&self) -> Option<&Credential> {
+        match self {
+            Address::Base { payment, .. }
+            | Address::Pointer { payment, .. }
+            | Address::Enterprise { payment, .. } => Some(payment),
+            Address::Reward { .. } | Address::Byron(_) => None,
+        }
+    }
+
+    /// Stake credential the address carries: that of a base or reward address. Pointer
+    /// addresses only refer to theirs, and enterprise and Byron addresses have none.
+    pub fn stake_credential(&self) -> Option<&Credential> {
+        match self {
+            Address::Base { stake, .. } | Address::Reward { stake, .. } => Some(stake),
+            _ => None,
+        }
+    }
+
+    /// Whether the address is a Byron bootstrap address.
+    pub fn is_byron(&self) -> bool {
+        matches!(self, Address::Byron(_))
+    }
+
+    /// Binary form of the address: the header byte and its credentials or pointer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = |kind: u8, network: &Network| vec![(kind << 4) | network.id()];
+        let script = |credential: &Credential| u8::from(credential.is_script());
+        match self {
+            Address::Base {
+                network,
+                payment,
+                stake,
+            } => {
+                let mut bytes = header(script(payment) | (script(stake) << 1), network);
+                bytes.extend_from_slice(payment.hash());
+                bytes.extend_from_slice(stake.hash());
+                bytes
+            }
+            Address::Pointer {
+                network,
+                payment,
+                pointer,
+            } => {
+                let mut bytes = header(4 | script(payment), network);
+                bytes.extend_from_slice(payment.hash());
+                for n in [pointer.slot, pointer.tx_index, pointer.cert_index] {
+                    write_natural(&mut bytes, n);
+                }
+                bytes
+            }
+            Address::Enterprise { network, payment } => {
+                let mut bytes = header(6 | script(payment), network);
+                bytes.extend_from_slice(payment.hash());
+                bytes
+            }
+            Address::Reward { network, stake } => {
+                let mut bytes = header(14 | script(stake), network);
+                bytes.extend_from_slice(stake.hash());
+                bytes
+            }
+            Address::Byron(bytes) => bytes.clone(),
+        }
+    }
+
+    /// Read an address from its binary form.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AddressError> {
+        let &header = bytes.first().ok_or(AddressError::InvalidLength(0))?;
+        let kind = header >> 4;
+        if kind == BYRON_TYPE {
+            byron_payload(bytes)?;
+            return Ok(Address::Byron(bytes.to_vec()));
+        }
+        let network = Network::from_id(header & 0x0f)?;
+        let expected = match kind {
+            0..=3 => 57,
+            4 | 5 => bytes.len(),
+            6 | 7 | 14 | 15 => 29,
+            _ => return Err(AddressError::UnknownHeader(header)),
+        };
+        if bytes.len() != expected || bytes.len() < 29 {
+            return Err(AddressError::InvalidLength(bytes.len()));
+        }
+        let hash = |at: usize| -> Hash28 { bytes[at..at + 28].try_into().expect("28 bytes") };
+        let first = Credential::new(kind & 1 == 1, hash(1));
+        Ok(match kind {
+            0..=3 => Address::Base {
+                network,
+                payment: first,
+                stake: Credential::new(kind & 2 == 2, hash(29)),
+            },
+            4 | 5 => {
+                let mut rest = &bytes[29..];
+                let mut next = || read_natural(&mut rest).ok_or(AddressError::InvalidPointer);
+                let pointer = Pointer {
+                    slot: next()?,
+                    tx_index: next()?,
+                    cert_index: next()?,
+                };
+                if !rest.is_empty() {
+                    return Err(AddressError::InvalidPointer);
+                }
+                Address::Pointer {
+                    network,
+                    payment: first,
+                    pointer,
+                }
+            }
+            6 | 7 => Address::Enterprise {
+                network,
+                payment: first,
+            },
+            _ => Address::Reward {
+                network,
+                stake: first,
+            },
+        })
+    }
+
+    /// Bech32 prefix of a Shelley address; `None` for a Byron address.
+    pub fn hrp(&self) -> Option<&'static str> {
+        let mainnet = self.network() == Network::Mainnet;
+        match self {
+            Address::Byron(_) => None,
+            Address::Reward { .. } if mainnet => Some("stake"),
+            Address::Reward { .. } => Some("stake_test"),
+            _ if mainnet => Some("addr"),
+            _ => Some("addr_test"),
+        }
+    }
+}
+
+impl Default for Address {
+    /// Enterprise address of the all-zero key hash on the test network, which no key spends.
+    fn default() -> Self {
+        Address::enterprise(Network::Testnet, Credential::KeyHash([0; 28]))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.hrp() {
+            Some(hrp) => f.write_str(&bech32::encode(hrp, &self.to_bytes())),
+            None => f.write_str(&base58::encode(&self.to_bytes())),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    /// Read a bech32 Shelley address or a base58 Byron address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        if !lower.starts_with("addr") && !lower.starts_with("stake") {
+            let bytes = base58::decode(s).ok_or(AddressError::Base58)?;
+            return match Address::from_bytes(&bytes)? {
+                address @ Address::Byron(_) => Ok(address),
+                _ => Err(AddressError::InvalidByron("not a Byron address")),
+            };
+        }
+        let (hrp, data) = bech32::decode(s)?;
+        let address = Address::from_bytes(&data)?;
+        if address.hrp() != Some(hrp.as_str()) {
+            return Err(AddressError::UnexpectedPrefix(hrp));
+        }
+        Ok(address)
+    }
+}
+
+/// Serialize as the display form, so that addresses and credentials read the same in JSON and
+/// can key JSON maps.
+macro_rules! serde_as_string {
+    ($type:ty) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_as_string!(Address);
+serde_as_string!(Credential);
+
+/// Append `n` as a variable-length natural: big-endian groups of 7 bits, all but the last with
+/// the high bit set.
+fn write_natural(bytes: &mut Vec<u8>, n: u64) {
+    let mut groups = vec![(n & 0x7f) as u8];
+    let mut rest = n >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+/// Read a variable-length natural from the front of `bytes`.
+fn read_natural(bytes: &mut &[u8]) -> Option<u64> {
+    let mut n: u64 = 0;
+    loop {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        n = n.checked_mul(128)? | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+}
+
+/// Check a Byron address, `[#6.24(bytes .cbor [root, attributes, type]), crc32]`, and return
+/// whether its attributes carry a protocol magic (attribute 2), which only test networks set.
+fn byron_payload(bytes: &[u8]) -> Result<bool, AddressError> {
+    let malformed = |_| AddressError::InvalidByron("malformed CBOR");
+    let mut d = Decoder::new(bytes);
+    if d.array().map_err(malformed)? != Some(2) || d.tag().map_err(malformed)? != Tag::Cbor {
+        return Err(AddressError::InvalidByron(
+            "not a tagged payload and checksum",
+        ));
+    }
+    let payload = d.bytes().map_err(malformed)?;
+    let checksum = d.u32().map_err(malformed)?;
+    if d.position() != bytes.len() {
+        return Err(AddressError::InvalidByron("trailing bytes"));
+    }
+    if crc32(payload) != checksum {
+        return Err(AddressError::InvalidByron("checksum mismatch"));
+    }
+    let mut d = Decoder::new(payload);
+    if d.array().map_err(malformed)? != Some(3) || d.bytes().map_err(malformed)?.len() != 28 {
+        return Err(AddressError::InvalidByron(
+            "not a root, attributes and type",
+        ));
+    }
+    let mut magic = false;
+    let entries = d.map().map_err(malformed)?;
+    for _ in 0..entries.ok_or(AddressError::InvalidByron("indefinite attributes"))? {
+        if d.datatype().map_err(malformed)? == Type::U8 {
+            magic |= d.u8().map_err(malformed)? == 2;
+        } else {
+            d.skip().map_err(malformed)?;
+        }
+        d.skip().map_err(malformed)?;
+    }
+    Ok(magic)
+}
+
+/// CRC-32 (IEEE 802.3) of `bytes`, the checksum of Byron addresses.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Bech32 (BIP-173) without its 90-character limit, which Cardano addresses exceed.
+mod bech32 {
+    use super::AddressError;
+
+    const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+
+    fn polymod(values: impl IntoIterator<Item = u8>) -> u32 {
+        let mut checksum = 1u32;
+        for value in values {
+            let top = checksum >> 25;
+            checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(value);
+            for (i, g) in GENERATOR.iter().enumerate() {
+                if (top >> i) & 1 == 1 {
+                    checksum ^= g;
+                }
+            }
+        }
+        checksum
+    }
+
+    fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+        let bytes = hrp.bytes();
+        bytes
+            .clone()
+            .map(|c| c >> 5)
+            .chain([0])
+            .chain(bytes.map(|c| c & 31))
+    }
+
+    /// Regroup `data` from `from`-bit to `to`-bit groups. Without `pad`, leftover bits must be
+    /// fewer than `from` and zero.
+    fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+        let (mut acc, mut bits) = (0u32, 0u32);
+        let mut out = Vec::new();
+        for &value in data {
+            acc = (acc << from) | u32::from(value);
+            bits += from;
+            while bits >= to {
+                bits -= to;
+                out.push(((acc >> bits) & ((1 << to) - 1)) as u8);
+            }
+        }
+        if pad {
+            if bits > 0 {
+                out.push(((acc << (to - bits)) & ((1 << to) - 1)) as u8);
+            }
+        } else if bits >= from || (acc << (to - bits)) & ((1 << to) - 1) != 0 {
+            return None;
+        }
+        Some(out)
+    }
+
+    /// Bech32 string of `data` with prefix `hrp`.
+    pub fn encode(hrp: &str, data: &[u8]) -> String {
+        let data = convert_bits(data, 8, 5, true).expect("padding always succeeds");
+        let checksum = polymod(hrp_expand(hrp).chain(data.iter().copied()).chain([0; 6])) ^ 1;
+        let checksum = (0..6).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8);
+        let mut s = String::from(hrp);
+        s.push('1');
+        s.extend(
+            data.into_iter()
+                .chain(checksum)
+                .map(|v| CHARSET[v as usize] as char),
+        );
+        s
+    }
+
+    /// Prefix and data of a bech32 string.
+    pub fn decode(s: &str) -> Result<(String, Vec<u8>), AddressError> {
+        if s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase()) {
+            return Err(AddressError::Bech32("mixed case"));
+        }
+        let s = s.to_ascii_lowercase();
+        let (hrp, data) = s
+            .rsplit_once('1')
+            .ok_or(AddressError::Bech32("no separator"))?;
+        if hrp.is_empty() || data.len() < 6 {
+            return Err(AddressError::Bech32("too short"));
+        }
+        if hrp.bytes().any(|c| !(33..=126).contains(&c)) {
+            return Err(AddressError::Bech32("invalid prefix character"));
+        }
+        let values = data
+            .bytes()
+            .map(|c| CHARSET.iter().position(|&d| d == c).map(|v| v as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(AddressError::Bech32("invalid character"))?;
+        if polymod(hrp_expand(hrp).chain(values.iter().copied())) != 1 {
+            return Err(AddressError::Bech32("checksum mismatch"));
+        }
+        let data = convert_bits(&values[..values.len() - 6], 5, 8, false)
+            .ok_or(AddressError::Bech32("invalid padding"))?;
+        Ok((hrp.to_string(), data))
+    }
+}
+
+/// Base58 with the Bitcoin alphabet, used for Byron addresses.
+mod base58 {
+    const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    /// Base58 string of `bytes`; each leading zero byte becomes a `1`.
+    pub fn encode(bytes: &[u8]) -> String {
+        let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+        // Little-endian base-58 digits.
+        let mut digits: Vec<u8> = Vec::new();
+        for &byte in &bytes[zeros..] {
+            let mut carry = u32::from(byte);
+            for digit in digits.iter_mut() {
+                carry += u32::from(*digit) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits.push((carry % 58) as u8);
+                carry /= 58;
+            }
+        }
+        std::iter::repeat_n('1', zeros)
+            .chain(digits.iter().rev().map(|&d| ALPHABET[d as usize] as char))
+            .collect()
+    }
+
+    /// Bytes of a base58 string, or `None` if it has characters outside the alphabet.
+    pub fn decode(s: &str) -> Option<Vec<u8>> {
+        let zeros = s.bytes().take_while(|&c| c == b'1').count();
+        // Little-endian bytes.
+        let mut bytes: Vec<u8> = Vec::new();
+        for c in s.bytes().skip(zeros) {
+            let mut carry = ALPHABET.iter().position(|&a| a == c)? as u32;
+            for byte in bytes.iter_mut() {
+                carry += u32::from(*byte) * 58;
+                *byte = carry as u8;
+                carry >>= 8;
+            }
+            while carry > 0 {
+                bytes.push(carry as u8);
+                carry >>= 8;
+            }
+        }
+        Some(
+            std::iter::repeat_n(0, zeros)
+                .chain(bytes.into_iter().rev())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test vectors of CIP-19: the payment key hash, stake key hash and script hash they use.
+    const PAYMENT: &str = "9493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e";
+    const STAKE: &str = "337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+    const SCRIPT: &str = "c37b1b5dc0669f1d3c61a6fddb2e8fde96be87b881c60bce8e8d542f";
+
+    fn hash(hex: &str) -> Hash28 {
+        crate::ledger::cbor::from_hex(hex)
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn cip19_test_vectors_round_trip() {
+        let payment = Credential::KeyHash(hash(PAYMENT));
+        let stake = Credential::KeyHash(hash(STAKE));
+        let script = Credential::ScriptHash(hash(SCRIPT));
+        let pointer = Pointer {
+            slot: 2498243,
+            tx_index: 27,
+            cert_index: 3,
+        };
+        let vectors = [
+            (
+                Address::base(Network::Mainnet, payment, stake),
+                "addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x",
+            ),
+            (
+                Address::base(Network::Mainnet, script, script),
+                "addr1x8phkx6acpnf78fuvxn0mkew3l0fd058hzquvz7w36x4gt7r0vd4msrxnuwnccdxlhdjar77j6lg0wypcc9uar5d2shskhj42g",
+            ),
+            (
+                Address::Pointer {
+                    network: Network::Mainnet,
+                    payment,
+                    pointer,
+                },
+                "addr1gx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer5pnz75xxcrzqf96k",
+            ),
+            (
+                Address::enterprise(Network::Testnet, payment),
+                "addr_test1vz2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzerspjrlsz",
+            ),
+            (
+                Address::reward(Network::Mainnet, stake),
+                "stake1uyehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gh6ffgw",
+            ),
+            (
+                Address::reward(Network::Testnet, script),
+                "stake_test17rphkx6acpnf78fuvxn0mkew3l0fd058hzquvz7w36x4gtcljw6kf",
+            ),
+        ];
+        for (address, bech32) in vectors {
+            assert_eq!(address.to_string(), bech32);
+            assert_eq!(bech32.parse::<Address>(), Ok(address.clone()));
+            assert_eq!(Address::from_bytes(&address.to_bytes()), Ok(address));
+        }
+
+        let enterprise = Address::enterprise(Network::Testnet, payment);
+        assert_eq!(enterprise.payment_credential(), Some(&payment));
+        assert_eq!(enterprise.stake_credential(), None);
+        // The prefix must match the network and kind of address.
+        let wrong = bech32::encode("addr", &enterprise.to_bytes());
+        assert_eq!(
+            wrong.parse::<Address>(),
+            Err(AddressError::UnexpectedPrefix("addr".to_string()))
+        );
+        let mut corrupted = enterprise.to_string();
+        corrupted.pop();
+        corrupted.push('q');
+        assert!(corrupted.parse::<Address>().is_err());
+
+        assert_eq!(script.to_string().parse(), Ok(script));
+        assert_eq!(stake.to_string().parse(), Ok(stake));
+        assert!(stake.to_string().starts_with("stake_vkh1"));
+    }
+
+    #[test]
+    fn byron_addresses_are_checked_and_round_trip() {
+        let byron = "Ae2tdPwUPEZFRbyhz3cpfC2CumGzNkFBN2L42rcUc2yjQpEkxDbkPodpMAi";
+        let address: Address = byron.parse().unwrap();
+        assert!(address.is_byron());
+        assert_eq!(address.network(), Network::Mainnet);
+        assert_eq!(address.payment_credential(), None);
+        assert_eq!(address.to_string(), byron);
+        assert_eq!(Address::from_bytes(&address.to_bytes()), Ok(address));
+
+        let mut bytes = base58::decode(byron).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(
+            Address::from_bytes(&bytes),
+            Err(AddressError::InvalidByron("checksum mismatch"))
+        );
+    }
+
+    #[test]
+    fn pointers_use_variable_length_naturals() {
+        let mut bytes = Vec::new();
+        for n in [0, 127, 128, u64::MAX] {
+            write_natural(&mut bytes, n);
+        }
+        let mut rest = bytes.as_slice();
+        for n in [0, 127, 128, u64::MAX] {
+            assert_eq!(read_natural(&mut rest), Some(n));
+        }
+        assert_eq!(read_natural(&mut rest), None);
+        assert_eq!(read_natural(&mut &[0x81][..]), None);
+    }
+}
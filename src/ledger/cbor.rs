@@ -0,0 +1,2317 @@
+//! Canonical CBOR codec for ledger types.
+//!
+//! Blocks and transactions are encoded in a Cardano-shaped CBOR layout, and the encoding is
+//! canonical: definite lengths, shortest integer forms, map keys in ascending order and empty
+//! optional fields omitted. The same bytes are used for size limits, for hashing (transaction ids,
+//! block body and header hashes) and for storage in the ChainDB.
+//!
+//! Layouts (CDDL-style):
+//! - `block = [id, header, [* transaction_body], [* transaction_witness_set],
+//!   [* invalid_transaction_index]]`
+//! - `header = [header_body, kes_signature]`, with `header_body = [slot, epoch, prev_hash / null,
+//!   leader, issuer_vkey, vrf_vkey, vrf_proof, body_hash, [hot_vkey, counter, kes_period, sigma]]`
+//! - `transaction = [transaction_body, transaction_witness_set, is_valid, null]`
+//! - `transaction_body = {0: [* [tx_id, index]], 1: [* transaction_output], 2: fee, ? 3: ttl,
+//!   ? 4: [* certificate], ? 5: {* stake_credential => coin}, ? 6: update, ? 8: validity_start,
+//!   ? 9: {* policy_id => {* asset_name => int}}, ? 13: [* [tx_id, index]], ? 14: [* key_hash],
+//!   ? 16: transaction_output, ? 18: [* [tx_id, index]],
+//!   ? 19: {* voter => {* [tx_id, index] => [vote, null]}}, ? 20: [* proposal_procedure]}`
+//! - `update = [{* proposer => param_update}, epoch]`
+//! - `param_update = {? 0: min_fee_a, ? 1: min_fee_b, ? 2: max_block_size, ? 3: max_tx_size,
+//!   ? 5: key_deposit, ? 6: pool_deposit, ? 8: n_opt, ? 9: pool_influence,
+//!   ? 10: monetary_expansion, ? 11: treasury_cut, ? 12: decentralisation,
+//!   ? 13: [0] / [1, entropy], ? 14: [major, minor],
+//!   ? 15: min_utxo_value, ? 16: min_pool_cost, ? 18: {* language => [* int]},
+//!   ? 19: [mem_price, step_price], ? 23: collateral_percentage, ? 24: max_collateral_inputs,
+//!   ? 25: [5 * rational], ? 26: [10 * rational], ? 29: gov_action_lifetime,
+//!   ? 30: gov_action_deposit, ? 31: drep_deposit}`, with rationals as
+//!   `#6.30([numerator, denominator])`
+//! - `voter = [0 / 1, committee_member] / [2 / 3, drep] / [4, pool_id]`, with key hash
+//!   credentials under even and script hash credentials under odd kinds, and
+//!   `vote = 0 (no) / 1 (yes) / 2 (abstain)`
+//! - `stake_credential = [0, addr_keyhash] / [1, scripthash]`, also naming reward accounts
+//! - `proposal_procedure = [deposit, stake_credential, gov_action, [url, data_hash]]`, with
+//!   previous action ids and guardrail script hashes written as `null`
+//! - `transaction_output = [address, value] / {0: address, 1: value, ? 2: datum_option,
+//!   ? 3: #6.24(bytes .cbor script)}`, the map only when the output has a datum or reference
+//!   script, with `value = coin / [coin, {* policy_id => {* asset_name => amount}}]` and the
+//!   address in its binary form (see [`super::address`])
+//! - `datum_option = [0, datum_hash] / [1, #6.24(bytes .cbor plutus_data)]`
+//! - `script = [0, native_script] / [1, bytes]`
+//! - `transaction_witness_set = {? 0: [* [vkey, signature]], ? 1: [* native_script],
+//!   ? 3: [* script], ? 4: [* datum], ? 5: [* redeemer]}`
+//! - `native_script = [0, key_hash] / [1, [* native_script]] / [2, [* native_script]]
+//!   / [3, m, [* native_script]] / [4, slot] / [5, slot]`
+//! - `redeemer = [0, index, data, [mem, steps]]`
+//! - `plutus_data = #6.121-127([* plutus_data]) / #6.1280-1400([* plutus_data])
+//!   / #6.102([constructor, [* plutus_data]]) / {* plutus_data => plutus_data} / [* plutus_data]
+//!   / int / bignum / bytes`
+//!
+//! Decoding also accepts real Cardano transactions: Alonzo-style 3- and 4-element transactions,
+//! Alonzo outputs with a datum hash, `#6.258` sets, rational (`#6.30`) pool margins, map-shaped
+//! redeemers, reward addresses and bare owner key hashes in place of stake credentials, and
+//! indefinite-length items. Fields this ledger does not model yet are skipped. Identifiers that
+//! Cardano encodes as bytes (policy ids, asset names, pool, DRep and committee credential hashes)
+//! are kept as lowercase hex strings, while this codec encodes the ledger's own string identifiers
+//! as text. Plutus datums and redeemer data that are not byte strings are kept
+//! as their raw CBOR encoding.
+
+use super::governance::Anchor;
+use super::params::{DRepVotingThresholds, ExUnitPrices, PoolVotingThresholds, ProtocolVersion};
+use super::{
+    Address, Asset, Block, BlockHeader, Certificate, Credential, DRep, DRepCertificate,
+    DatumOption, DelegationCertificate, GovAction, GovActionId, NativeScript, OperationalCert,
+    ParamUpdate, PlutusData, PlutusDatum, PlutusRedeemer, PlutusScript, PlutusWitness,
+    ProposalProcedure, Rational, ScriptRef, StakePoolRegistration, StakePoolRetirement,
+    Transaction, TxInput, TxOutput, Update, VKeyWitness, Value, Vote, VoteDelegationCertificate,
+    Voter, VotingProcedure, value::MultiAsset,
+};
+use crate::consensus::NEUTRAL_NONCE;
+use minicbor::data::{Int, Tag, Type};
+use minicbor::{Decoder, Encoder};
+use num_bigint::{BigInt, Sign};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use thiserror::Error;
+
+/// CBOR tag for sets (Conway).
+const SET_TAG: u64 = 258;
+/// CBOR tag for rational numbers.
+const RATIONAL_TAG: u64 = 30;
+
+/// Encoder writing into a byte vector, which cannot fail.
+pub type Enc = Encoder<Vec<u8>>;
+
+/// Result of writing to an [`Enc`].
+pub type EncodeResult = Result<(), minicbor::encode::Error<Infallible>>;
+
+/// Errors returned when decoding CBOR into ledger types.
+#[derive(Debug, Error)]
+pub enum CborError {
+    /// The input is not well-formed CBOR or has an unexpected type.
+    #[error("Malformed CBOR: {0}")]
+    Malformed(#[from] minicbor::decode::Error),
+
+    /// The input is well-formed but does not describe a valid value.
+    #[error("Invalid {what}: {reason}")]
+    Invalid { what: &'static str, reason: String },
+
+    /// Bytes were left over after the top-level item.
+    #[error("{0} trailing bytes after CBOR item")]
+    TrailingBytes(usize),
+}
+
+impl CborError {
+    fn invalid(what: &'static str, reason: impl std::fmt::Display) -> Self {
+        CborError::Invalid {
+            what,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Types with a canonical CBOR encoding.
+pub trait Cbor: Sized {
+    /// Write the canonical encoding of `self`.
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult;
+
+    /// Read a value, accepting any Cardano-shaped encoding of it.
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError>;
+
+    /// Canonical CBOR bytes of `self`.
+    fn to_cbor(&self) -> Vec<u8> {
+        encode_with(|e| self.encode_cbor(e))
+    }
+
+    /// Decode a value from bytes holding exactly one CBOR item.
+    fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        let mut d = Decoder::new(bytes);
+        let value = Self::decode_cbor(&mut d)?;
+        match bytes.len() - d.position() {
+            0 => Ok(value),
+            n => Err(CborError::TrailingBytes(n)),
+        }
+    }
+}
+
+/// Run an encoding function against a fresh buffer and return the bytes.
+pub(crate) fn encode_with(f: impl FnOnce(&mut Enc) -> EncodeResult) -> Vec<u8> {
+    let mut e = Encoder::new(Vec::new());
+    f(&mut e).expect("writing to a Vec cannot fail");
+    e.into_writer()
+}
+
+/// Decode a hex string, as used for CBOR submitted through the APIs.
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, CborError> {
+    let hex = hex.trim();
+    if !hex.len().is_multiple_of(2) {
+        return Err(CborError::invalid("hex", "odd number of digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| CborError::invalid("hex", format!("bad digit at {}", i)))
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Read the length of an array, or `None` if it is indefinite.
+fn array(d: &mut Decoder<'_>) -> Result<Option<u64>, CborError> {
+    Ok(d.array()?)
+}
+
+/// Skip the elements of an array after its first `read` ones, including the break that ends an
+/// indefinite-length array.
+fn skip_rest(d: &mut Decoder<'_>, len: Option<u64>, read: u64) -> Result<(), CborError> {
+    match len {
+        Some(n) => {
+            for _ in read..n {
+                d.skip()?;
+            }
+        }
+        None => {
+            while d.datatype()? != Type::Break {
+                d.skip()?;
+            }
+            d.skip()?;
+        }
+    }
+    Ok(())
+}
+
+/// Read an array (or a `#6.258` set) by calling `item` for each element.
+fn seq<T>(
+    d: &mut Decoder<'_>,
+    mut item: impl FnMut(&mut Decoder<'_>) -> Result<T, CborError>,
+) -> Result<Vec<T>, CborError> {
+    if d.datatype()? == Type::Tag {
+        match d.tag()? {
+            Tag::Unassigned(SET_TAG) => {}
+            tag => {
+                return Err(CborError::invalid(
+                    "set",
+                    format!("unexpected tag {:?}", tag),
+                ));
+            }
+        }
+    }
+    let mut out = Vec::new();
+    match array(d)? {
+        Some(n) => {
+            for _ in 0..n {
+                out.push(item(d)?);
+            }
+        }
+        None => {
+            while d.datatype()? != Type::Break {
+                out.push(item(d)?);
+            }
+            d.skip()?;
+        }
+    }
+    Ok(out)
+}
+
+/// Read a map by calling `entry` for each key/value pair; `entry` must consume the value.
+fn map_entries(
+    d: &mut Decoder<'_>,
+    mut entry: impl FnMut(&mut Decoder<'_>) -> Result<(), CborError>,
+) -> Result<(), CborError> {
+    match d.map()? {
+        Some(n) => {
+            for _ in 0..n {
+                entry(d)?;
+            }
+        }
+        None => {
+            while d.datatype()? != Type::Break {
+                entry(d)?;
+            }
+            d.skip()?;
+        }
+    }
+    Ok(())
+}
+
+/// Read an identifier: text as-is, bytes as lowercase hex.
+fn ident(d: &mut Decoder<'_>) -> Result<String, CborError> {
+    match d.datatype()? {
+        Type::String | Type::StringIndef => Ok(collect_str(d)?),
+        _ => Ok(to_hex(&collect_bytes(d)?)),
+    }
+}
+
+/// Read a byte string, definite or indefinite.
+fn collect_bytes(d: &mut Decoder<'_>) -> Result<Vec<u8>, CborError> {
+    let mut out = Vec::new();
+    for chunk in d.bytes_iter()? {
+        out.extend_from_slice(chunk?);
+    }
+    Ok(out)
+}
+
+/// Read a text string, definite or indefinite.
+fn collect_str(d: &mut Decoder<'_>) -> Result<String, CborError> {
+    let mut out = String::new();
+    for chunk in d.str_iter()? {
+        out.push_str(chunk?);
+    }
+    Ok(out)
+}
+
+/// Read a 28-byte credential hash.
+fn hash28(d: &mut Decoder<'_>) -> Result<[u8; 28], CborError> {
+    collect_bytes(d)?
+        .as_slice()
+        .try_into()
+        .map_err(|_| CborError::invalid("credential", "expected 28 bytes"))
+}
+
+/// Read a 32-byte hash.
+fn hash32(d: &mut Decoder<'_>, what: &'static str) -> Result<[u8; 32], CborError> {
+    collect_bytes(d)?
+        .as_slice()
+        .try_into()
+        .map_err(|_| CborError::invalid(what, "expected 32 bytes"))
+}
+
+/// Read Plutus data: a byte string as its contents, anything else as its raw encoding.
+fn plutus_data(d: &mut Decoder<'_>) -> Result<Vec<u8>, CborError> {
+    match d.datatype()? {
+        Type::Bytes | Type::BytesIndef => collect_bytes(d),
+        _ => {
+            let start = d.position();
+            d.skip()?;
+            Ok(d.input()[start..d.position()].to_vec())
+        }
+    }
+}
+
+impl Cbor for TxInput {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(2)?.bytes(&self.prev_tx)?.u32(self.index)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let prev_tx = hash32(d, "transaction input")?;
+        let index = d.u32()?;
+        skip_rest(d, len, 2)?;
+        Ok(TxInput { prev_tx, index })
+    }
+}
+
+/// Entries of a text-keyed map in canonical order. Canonical CBOR orders map keys by their
+/// encoding, which for text strings means shorter keys first, then bytewise.
+fn canonical<'a, V>(entries: impl IntoIterator<Item = (&'a String, V)>) -> Vec<(&'a String, V)> {
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort_by(|(a, _), (b, _)| (a.len(), a.as_bytes()).cmp(&(b.len(), b.as_bytes())));
+    entries
+}
+
+/// Write a multi-asset map `{policy_id => {asset_name => quantity}}` in canonical order.
+fn encode_multi_asset<T>(
+    e: &mut Enc,
+    assets: &MultiAsset<T>,
+    mut quantity: impl FnMut(&mut Enc, &T) -> EncodeResult,
+) -> EncodeResult {
+    e.map(assets.len() as u64)?;
+    for (policy, names) in canonical(assets) {
+        e.str(policy)?.map(names.len() as u64)?;
+        for (name, amount) in canonical(names) {
+            e.str(name)?;
+            quantity(e, amount)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a multi-asset map, reading each quantity with `quantity`.
+fn decode_multi_asset<T>(
+    d: &mut Decoder<'_>,
+    mut quantity: impl FnMut(&mut Decoder<'_>) -> Result<T, CborError>,
+) -> Result<MultiAsset<T>, CborError> {
+    let mut assets = MultiAsset::new();
+    map_entries(d, |d| {
+        let names: &mut BTreeMap<String, T> = assets.entry(ident(d)?).or_default();
+        map_entries(d, |d| {
+            let name = ident(d)?;
+            names.insert(name, quantity(d)?);
+            Ok(())
+        })
+    })?;
+    Ok(assets)
+}
+
+/// Write a value: plain coin, or coin plus a canonical multi-asset map.
+fn encode_value(e: &mut Enc, value: &Value) -> EncodeResult {
+    if value.assets.is_empty() {
+        e.u64(value.coin)?;
+        return Ok(());
+    }
+    e.array(2)?.u64(value.coin)?;
+    encode_multi_asset(e, &value.assets, |e, amount| e.u64(*amount).map(|_| ()))
+}
+
+/// Read a value into its coin amount and optional assets.
+fn decode_value(d: &mut Decoder<'_>) -> Result<(u64, Option<Vec<Asset>>), CborError> {
+    if d.datatype()? != Type::Array && d.datatype()? != Type::ArrayIndef {
+        return Ok((d.u64()?, None));
+    }
+    let len = array(d)?;
+    let amount = d.u64()?;
+    let mut assets = Vec::new();
+    map_entries(d, |d| {
+        let policy_id = ident(d)?;
+        map_entries(d, |d| {
+            let asset_name = ident(d)?;
+            assets.push(Asset {
+                policy_id: policy_id.clone(),
+                asset_name,
+                amount: d.u64()?,
+            });
+            Ok(())
+        })
+    })?;
+    skip_rest(d, len, 2)?;
+    Ok((amount, (!assets.is_empty()).then_some(assets)))
+}
+
+impl Cbor for TxOutput {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        if self.datum.is_none() && self.script_ref.is_none() {
+            e.array(2)?.bytes(&self.address.to_bytes())?;
+            return encode_value(e, &Value::from(self));
+        }
+        let fields = 2 + u64::from(self.datum.is_some()) + u64::from(self.script_ref.is_some());
+        e.map(fields)?
+            .u8(0)?
+            .bytes(&self.address.to_bytes())?
+            .u8(1)?;
+        encode_value(e, &Value::from(self))?;
+        match &self.datum {
+            Some(DatumOption::Hash(hash)) => {
+                e.u8(2)?.array(2)?.u8(0)?.bytes(hash)?;
+            }
+            Some(DatumOption::Inline(datum)) => {
+                e.u8(2)?
+                    .array(2)?
+                    .u8(1)?
+                    .tag(Tag::Cbor)?
+                    .bytes(&datum.data)?;
+            }
+            None => {}
+        }
+        if let Some(script) = &self.script_ref {
+            let script = encode_with(|e| match script {
+                ScriptRef::Native(script) => {
+                    e.array(2)?.u8(0)?;
+                    script.encode_cbor(e)
+                }
+                ScriptRef::Plutus(script) => {
+                    e.array(2)?.u8(1)?;
+                    script.encode_cbor(e)
+                }
+            });
+            e.u8(3)?.tag(Tag::Cbor)?.bytes(&script)?;
+        }
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let mut address = None;
+        let mut value = None;
+        let mut datum = None;
+        let mut script_ref = None;
+        match d.datatype()? {
+            // Pre-Babbage: [address, value, ? datum_hash]
+            Type::Array | Type::ArrayIndef => {
+                let len = array(d)?;
+                address = Some(decode_address(d)?);
+                value = Some(decode_value(d)?);
+                let mut read = 2;
+                if len != Some(2) && d.datatype()? != Type::Break {
+                    datum = Some(DatumOption::Hash(hash32(d, "datum hash")?));
+                    read = 3;
+                }
+                skip_rest(d, len, read)?;
+            }
+            // Babbage: {0: address, 1: value, ? 2: datum_option, ? 3: script_ref}
+            _ => map_entries(d, |d| {
+                match d.u64()? {
+                    0 => address = Some(decode_address(d)?),
+                    1 => value = Some(decode_value(d)?),
+                    2 => datum = Some(decode_datum_option(d)?),
+                    3 => script_ref = Some(decode_script_ref(d)?),
+                    _ => d.skip()?,
+                }
+                Ok(())
+            })?,
+        }
+        let address = address.ok_or_else(|| CborError::invalid("output", "missing address"))?;
+        let (amount, assets) =
+            value.ok_or_else(|| CborError::invalid("output", "missing value"))?;
+        Ok(TxOutput {
+            address,
+            amount,
+            assets,
+            datum,
+            script_ref,
+        })
+    }
+}
+
+/// Read the contents of an embedded CBOR item, `#6.24(bytes)`.
+fn embedded_cbor(d: &mut Decoder<'_>, what: &'static str) -> Result<Vec<u8>, CborError> {
+    match d.tag()? {
+        Tag::Cbor => collect_bytes(d),
+        tag => Err(CborError::invalid(
+            what,
+            format!("expected embedded CBOR, found tag {:?}", tag),
+        )),
+    }
+}
+
+/// Read a datum option: `[0, datum_hash] / [1, #6.24(bytes .cbor plutus_data)]`.
+fn decode_datum_option(d: &mut Decoder<'_>) -> Result<DatumOption, CborError> {
+    let len = array(d)?;
+    let datum = match d.u8()? {
+        0 => DatumOption::Hash(hash32(d, "datum hash")?),
+        1 => DatumOption::Inline(PlutusDatum {
+            data: embedded_cbor(d, "inline datum")?,
+        }),
+        kind => {
+            return Err(CborError::invalid(
+                "datum option",
+                format!("unsupported datum option {}", kind),
+            ));
+        }
+    };
+    skip_rest(d, len, 2)?;
+    Ok(datum)
+}
+
+/// Read a reference script: `#6.24(bytes .cbor ([0, native_script] / [1, bytes]))`. Plutus V2
+/// and V3 scripts (kinds 2 and 3) are read as Plutus scripts.
+fn decode_script_ref(d: &mut Decoder<'_>) -> Result<ScriptRef, CborError> {
+    let bytes = embedded_cbor(d, "reference script")?;
+    let d = &mut Decoder::new(&bytes);
+    let len = array(d)?;
+    let script = match d.u8()? {
+        0 => ScriptRef::Native(NativeScript::decode_cbor(d)?),
+        1..=3 => ScriptRef::Plutus(PlutusScript::decode_cbor(d)?),
+        kind => {
+            return Err(CborError::invalid(
+                "reference script",
+                format!("unsupported script type {}", kind),
+            ));
+        }
+    };
+    skip_rest(d, len, 2)?;
+    Ok(script)
+}
+
+impl Cbor for Certificate {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        match self {
+            Certificate::StakeRegistration(c) => {
+                e.array(2)?.u8(0)?;
+                encode_stake_credential(e, c)?;
+            }
+            Certificate::StakeDeregistration(c) => {
+                e.array(2)?.u8(1)?;
+                encode_stake_credential(e, c)?;
+            }
+            Certificate::Delegation(c) => {
+                e.array(3)?.u8(2)?;
+                encode_stake_credential(e, &c.delegator)?;
+                e.str(&c.pool_id)?;
+            }
+            Certificate::PoolRegistration(r) => {
+                e.array(10)?.u8(3)?.str(&r.pool_id)?.bytes(&r.vrf_keyhash)?;
+                e.u64(r.pledge)?.u64(r.cost)?.f64(r.margin)?;
+                encode_stake_credential(e, &r.reward_account)?;
+                e.array(1)?;
+                encode_stake_credential(e, &r.owner)?;
+                e.array(0)?.null()?;
+            }
+            Certificate::PoolRetirement(r) => {
+                e.array(3)?
+                    .u8(4)?
+                    .str(&r.pool_id)?
+                    .u64(r.retirement_epoch)?;
+            }
+            Certificate::VoteDelegation(c) => {
+                e.array(3)?.u8(9)?;
+                encode_stake_credential(e, &c.delegator)?;
+                c.drep.encode_cbor(e)?;
+            }
+            Certificate::DRepRegistration(c) => {
+                e.array(4)?.u8(16)?;
+                encode_stake_credential(e, &c.drep)?;
+                e.u64(c.deposit)?.null()?;
+            }
+            Certificate::DRepDeregistration(c) => {
+                e.array(3)?.u8(17)?;
+                encode_stake_credential(e, &c.drep)?;
+                e.u64(c.deposit)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let kind = d.u8()?;
+        let cert = match kind {
+            0 | 1 => {
+                let credential = stake_credential(d)?;
+                skip_rest(d, len, 2)?;
+                if kind == 0 {
+                    Certificate::StakeRegistration(credential)
+                } else {
+                    Certificate::StakeDeregistration(credential)
+                }
+            }
+            2 => {
+                let delegator = stake_credential(d)?;
+                let pool_id = ident(d)?;
+                skip_rest(d, len, 3)?;
+                Certificate::Delegation(DelegationCertificate { delegator, pool_id })
+            }
+            3 => {
+                let pool_id = ident(d)?;
+                let vrf_keyhash = hash32(d, "VRF key hash")?;
+                let pledge = d.u64()?;
+                let cost = d.u64()?;
+                let margin = decode_margin(d)?;
+                let reward_account = stake_credential(d)?;
+                // Only the first owner, relays and pool metadata are modelled.
+                let owner = seq(d, stake_credential)?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| CborError::invalid("pool registration", "no owner"))?;
+                skip_rest(d, len, 8)?;
+                Certificate::PoolRegistration(StakePoolRegistration {
+                    pool_id,
+                    vrf_keyhash,
+                    owner,
+                    pledge,
+                    cost,
+                    margin,
+                    reward_account,
+                })
+            }
+            4 => {
+                let pool_id = ident(d)?;
+                let retirement_epoch = d.u64()?;
+                skip_rest(d, len, 3)?;
+                Certificate::PoolRetirement(StakePoolRetirement {
+                    pool_id,
+                    retirement_epoch,
+                })
+            }
+            9 => {
+                let delegator = stake_credential(d)?;
+                let drep = DRep::decode_cbor(d)?;
+                skip_rest(d, len, 3)?;
+                Certificate::VoteDelegation(VoteDelegationCertificate { delegator, drep })
+            }
+            16 | 17 => {
+                let drep = stake_credential(d)?;
+                let deposit = d.u64()?;
+                // The registration anchor is not modelled.
+                skip_rest(d, len, 3)?;
+                let cert = DRepCertificate { drep, deposit };
+                if kind == 16 {
+                    Certificate::DRepRegistration(cert)
+                } else {
+                    Certificate::DRepDeregistration(cert)
+                }
+            }
+            kind => {
+                return Err(CborError::invalid(
+                    "certificate",
+                    format!("unsupported certificate type {}", kind),
+                ));
+            }
+        };
+        Ok(cert)
+    }
+}
+
+/// Write a stake credential, `[0, addr_keyhash] / [1, scripthash]`. All stake credentials encode
+/// to the same length, so their order is canonical map key order.
+fn encode_stake_credential(e: &mut Enc, credential: &Credential) -> EncodeResult {
+    e.array(2)?
+        .u8(u8::from(credential.is_script()))?
+        .bytes(credential.hash())?;
+    Ok(())
+}
+
+/// Read a stake credential: `[0, addr_keyhash] / [1, scripthash]`, a key hash (as Cardano
+/// encodes pool owners) or a reward address (as it encodes reward accounts).
+fn stake_credential(d: &mut Decoder<'_>) -> Result<Credential, CborError> {
+    let invalid = |reason| CborError::invalid("stake credential", reason);
+    let hash = |bytes: Vec<u8>| -> Result<[u8; 28], CborError> {
+        bytes.try_into().map_err(|_| invalid("expected 28 bytes"))
+    };
+    if let Type::Array | Type::ArrayIndef = d.datatype()? {
+        let len = array(d)?;
+        let script = match d.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("unknown credential type")),
+        };
+        let hash = hash(collect_bytes(d)?)?;
+        skip_rest(d, len, 2)?;
+        return Ok(if script {
+            Credential::ScriptHash(hash)
+        } else {
+            Credential::KeyHash(hash)
+        });
+    }
+    let bytes = collect_bytes(d)?;
+    if bytes.len() == 28 {
+        return Ok(Credential::KeyHash(hash(bytes)?));
+    }
+    match Address::from_bytes(&bytes) {
+        Ok(Address::Reward { stake, .. }) => Ok(stake),
+        _ => Err(invalid("expected a credential or reward address")),
+    }
+}
+
+/// Read an address from its bytes.
+fn decode_address(d: &mut Decoder<'_>) -> Result<Address, CborError> {
+    Address::from_bytes(&collect_bytes(d)?).map_err(|error| CborError::invalid("address", error))
+}
+
+/// Read a pool margin, written as a float by this codec and as a `#6.30` rational by Cardano.
+fn decode_margin(d: &mut Decoder<'_>) -> Result<f64, CborError> {
+    if d.datatype()? != Type::Tag {
+        return Ok(d.f64()?);
+    }
+    Ok(decode_rational(d, "margin")?.to_f64())
+}
+
+/// Write a rational as `#6.30([numerator, denominator])`.
+fn encode_rational(e: &mut Enc, r: &Rational) -> EncodeResult {
+    e.tag(Tag::Unassigned(RATIONAL_TAG))?
+        .array(2)?
+        .u64(r.numerator)?
+        .u64(r.denominator)?;
+    Ok(())
+}
+
+/// Read a `#6.30` rational with a non-zero denominator.
+fn decode_rational(d: &mut Decoder<'_>, what: &'static str) -> Result<Rational, CborError> {
+    match d.tag()? {
+        Tag::Unassigned(RATIONAL_TAG) => {
+            let len = array(d)?;
+            let numerator = d.u64()?;
+            let denominator = d.u64()?;
+            skip_rest(d, len, 2)?;
+            if denominator == 0 {
+                return Err(CborError::invalid(what, "zero denominator"));
+            }
+            Ok(Rational::new(numerator, denominator))
+        }
+        tag => Err(CborError::invalid(
+            what,
+            format!("unexpected tag {:?}", tag),
+        )),
+    }
+}
+
+/// Write a protocol parameter update, with the keys Cardano uses for each parameter.
+fn encode_param_update(e: &mut Enc, p: &ParamUpdate) -> EncodeResult {
+    let coins = [
+        (0, p.min_fee_a),
+        (1, p.min_fee_b),
+        (2, p.max_block_size.map(|n| n as u64)),
+        (3, p.max_tx_size.map(|n| n as u64)),
+        (5, p.key_deposit),
+        (6, p.pool_deposit),
+        (8, p.n_opt),
+    ];
+    let rewards = [
+        (9, p.pool_influence),
+        (10, p.monetary_expansion),
+        (11, p.treasury_cut),
+    ];
+    let limits = [
+        (23, p.collateral_percentage),
+        (24, p.max_collateral_inputs.map(|n| n as u64)),
+    ];
+    let governance = [
+        (29, p.gov_action_lifetime),
+        (30, p.gov_action_deposit),
+        (31, p.drep_deposit),
+    ];
+    let fields = coins.iter().filter(|(_, v)| v.is_some()).count()
+        + rewards.iter().filter(|(_, v)| v.is_some()).count()
+        + usize::from(p.decentralisation.is_some())
+        + usize::from(p.extra_entropy.is_some())
+        + usize::from(p.protocol_version.is_some())
+        + usize::from(p.min_utxo_value.is_some())
+        + usize::from(p.min_pool_cost.is_some())
+        + usize::from(p.cost_models.is_some())
+        + usize::from(p.ex_unit_prices.is_some())
+        + limits.iter().filter(|(_, v)| v.is_some()).count()
+        + usize::from(p.pool_voting_thresholds.is_some())
+        + usize::from(p.drep_voting_thresholds.is_some())
+        + governance.iter().filter(|(_, v)| v.is_some()).count();
+    e.map(fields as u64)?;
+    for (key, value) in coins {
+        if let Some(value) = value {
+            e.u8(key)?.u64(value)?;
+        }
+    }
+    for (key, value) in rewards {
+        if let Some(value) = value {
+            e.u8(key)?;
+            encode_rational(e, &value)?;
+        }
+    }
+    if let Some(d) = &p.decentralisation {
+        e.u8(12)?;
+        encode_rational(e, d)?;
+    }
+    if let Some(entropy) = &p.extra_entropy {
+        e.u8(13)?;
+        if *entropy == NEUTRAL_NONCE {
+            e.array(1)?.u8(0)?;
+        } else {
+            e.array(2)?.u8(1)?.bytes(entropy)?;
+        }
+    }
+    if let Some(version) = &p.protocol_version {
+        e.u8(14)?.array(2)?.u64(version.major)?.u64(version.minor)?;
+    }
+    if let Some(min) = p.min_utxo_value {
+        e.u8(15)?.u64(min)?;
+    }
+    if let Some(cost) = p.min_pool_cost {
+        e.u8(16)?.u64(cost)?;
+    }
+    if let Some(models) = &p.cost_models {
+        e.u8(18)?.map(models.len() as u64)?;
+        for (language, costs) in models {
+            e.u8(*language)?.array(costs.len() as u64)?;
+            for cost in costs {
+                e.i64(*cost)?;
+            }
+        }
+    }
+    if let Some(prices) = &p.ex_unit_prices {
+        e.u8(19)?.array(2)?;
+        encode_rational(e, &prices.mem)?;
+        encode_rational(e, &prices.steps)?;
+    }
+    for (key, value) in limits {
+        if let Some(value) = value {
+            e.u8(key)?.u64(value)?;
+        }
+    }
+    if let Some(t) = &p.pool_voting_thresholds {
+        e.u8(25)?.array(5)?;
+        for r in [
+            &t.motion_no_confidence,
+            &t.committee_normal,
+            &t.committee_no_confidence,
+            &t.hard_fork_initiation,
+            &t.pp_security_group,
+        ] {
+            encode_rational(e, r)?;
+        }
+    }
+    if let Some(t) = &p.drep_voting_thresholds {
+        e.u8(26)?.array(10)?;
+        for r in [
+            &t.motion_no_confidence,
+            &t.committee_normal,
+            &t.committee_no_confidence,
+            &t.update_to_constitution,
+            &t.hard_fork_initiation,
+            &t.pp_network_group,
+            &t.pp_economic_group,
+            &t.pp_technical_group,
+            &t.pp_gov_group,
+            &t.treasury_withdrawal,
+        ] {
+            encode_rational(e, r)?;
+        }
+    }
+    for (key, value) in governance {
+        if let Some(value) = value {
+            e.u8(key)?.u64(value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read `N` voting thresholds.
+fn decode_thresholds<const N: usize>(d: &mut Decoder<'_>) -> Result<[Rational; N], CborError> {
+    let len = array(d)?;
+    let mut thresholds = [Rational::new(0, 1); N];
+    for threshold in &mut thresholds {
+        *threshold = decode_rational(d, "voting threshold")?;
+    }
+    skip_rest(d, len, N as u64)?;
+    Ok(thresholds)
+}
+
+/// Read a protocol parameter update; parameters this ledger does not model are skipped.
+fn decode_param_update(d: &mut Decoder<'_>) -> Result<ParamUpdate, CborError> {
+    let mut p = ParamUpdate::default();
+    map_entries(d, |d| {
+        match d.u64()? {
+            0 => p.min_fee_a = Some(d.u64()?),
+            1 => p.min_fee_b = Some(d.u64()?),
+            2 => p.max_block_size = Some(d.u64()? as usize),
+            3 => p.max_tx_size = Some(d.u64()? as usize),
+            5 => p.key_deposit = Some(d.u64()?),
+            6 => p.pool_deposit = Some(d.u64()?),
+            8 => p.n_opt = Some(d.u64()?),
+            9 => p.pool_influence = Some(decode_rational(d, "pool influence")?),
+            10 => p.monetary_expansion = Some(decode_rational(d, "monetary expansion")?),
+            11 => p.treasury_cut = Some(decode_rational(d, "treasury cut")?),
+            12 => p.decentralisation = Some(decode_rational(d, "decentralisation")?),
+            13 => {
+                let len = array(d)?;
+                let (entropy, read) = match d.u8()? {
+                    0 => (NEUTRAL_NONCE, 1),
+                    1 => (hash32(d, "extra entropy")?, 2),
+                    kind => {
+                        return Err(CborError::invalid(
+                            "extra entropy",
+                            format!("unsupported nonce {}", kind),
+                        ));
+                    }
+                };
+                skip_rest(d, len, read)?;
+                p.extra_entropy = Some(entropy);
+            }
+            14 => {
+                let len = array(d)?;
+                let major = d.u64()?;
+                let minor = d.u64()?;
+                skip_rest(d, len, 2)?;
+                p.protocol_version = Some(ProtocolVersion { major, minor });
+            }
+            15 => p.min_utxo_value = Some(d.u64()?),
+            16 => p.min_pool_cost = Some(d.u64()?),
+            18 => {
+                let mut models = BTreeMap::new();
+                map_entries(d, |d| {
+                    let language = d.u8()?;
+                    models.insert(language, seq(d, |d| Ok(d.i64()?))?);
+                    Ok(())
+                })?;
+                p.cost_models = Some(models);
+            }
+            19 => {
+                let len = array(d)?;
+                let mem = decode_rational(d, "memory price")?;
+                let steps = decode_rational(d, "step price")?;
+                skip_rest(d, len, 2)?;
+                p.ex_unit_prices = Some(ExUnitPrices { mem, steps });
+            }
+            23 => p.collateral_percentage = Some(d.u64()?),
+            24 => p.max_collateral_inputs = Some(d.u64()? as usize),
+            25 => {
+                let [a, b, c, e, f] = decode_thresholds(d)?;
+                p.pool_voting_thresholds = Some(PoolVotingThresholds {
+                    motion_no_confidence: a,
+                    committee_normal: b,
+                    committee_no_confidence: c,
+                    hard_fork_initiation: e,
+                    pp_security_group: f,
+                });
+            }
+            26 => {
+                let [a, b, c, e, f, g, h, i, j, k] = decode_thresholds(d)?;
+                p.drep_voting_thresholds = Some(DRepVotingThresholds {
+                    motion_no_confidence: a,
+                    committee_normal: b,
+                    committee_no_confidence: c,
+                    update_to_constitution: e,
+                    hard_fork_initiation: f,
+                    pp_network_group: g,
+                    pp_economic_group: h,
+                    pp_technical_group: i,
+                    pp_gov_group: j,
+                    treasury_withdrawal: k,
+                });
+            }
+            29 => p.gov_action_lifetime = Some(d.u64()?),
+            30 => p.gov_action_deposit = Some(d.u64()?),
+            31 => p.drep_deposit = Some(d.u64()?),
+            _ => d.skip()?,
+        }
+        Ok(())
+    })?;
+    Ok(p)
+}
+
+impl Cbor for Update {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(2)?.map(self.proposals.len() as u64)?;
+        for (proposer, proposal) in canonical(&self.proposals) {
+            e.str(proposer)?;
+            encode_param_update(e, proposal)?;
+        }
+        e.u64(self.epoch)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let mut proposals = BTreeMap::new();
+        map_entries(d, |d| {
+            let proposer = ident(d)?;
+            proposals.insert(proposer, decode_param_update(d)?);
+            Ok(())
+        })?;
+        let epoch = d.u64()?;
+        skip_rest(d, len, 2)?;
+        Ok(Update { proposals, epoch })
+    }
+}
+
+impl Cbor for DRep {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        match self {
+            DRep::Credential(credential) => return encode_stake_credential(e, credential),
+            DRep::AlwaysAbstain => e.array(1)?.u8(2)?,
+            DRep::AlwaysNoConfidence => e.array(1)?.u8(3)?,
+        };
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let (drep, read) = match d.u8()? {
+            kind @ (0 | 1) => (DRep::Credential(Credential::new(kind == 1, hash28(d)?)), 2),
+            2 => (DRep::AlwaysAbstain, 1),
+            3 => (DRep::AlwaysNoConfidence, 1),
+            kind => {
+                return Err(CborError::invalid(
+                    "drep",
+                    format!("unsupported DRep type {}", kind),
+                ));
+            }
+        };
+        skip_rest(d, len, read)?;
+        Ok(drep)
+    }
+}
+
+impl Cbor for Voter {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        let (kind, credential) = match self {
+            Voter::Committee(credential) => (0, credential),
+            Voter::DRep(credential) => (2, credential),
+            Voter::StakePool(pool_id) => {
+                e.array(2)?.u8(4)?.str(pool_id)?;
+                return Ok(());
+            }
+        };
+        e.array(2)?
+            .u8(kind + u8::from(credential.is_script()))?
+            .bytes(credential.hash())?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let voter = match d.u8()? {
+            kind @ (0 | 1) => Voter::Committee(Credential::new(kind == 1, hash28(d)?)),
+            kind @ (2 | 3) => Voter::DRep(Credential::new(kind == 3, hash28(d)?)),
+            4 => Voter::StakePool(ident(d)?),
+            kind => {
+                return Err(CborError::invalid(
+                    "voter",
+                    format!("unsupported voter type {}", kind),
+                ));
+            }
+        };
+        skip_rest(d, len, 2)?;
+        Ok(voter)
+    }
+}
+
+impl Cbor for GovActionId {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(2)?.bytes(&self.tx_id)?.u32(self.index)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let tx_id = hash32(d, "governance action id")?;
+        let index = d.u32()?;
+        skip_rest(d, len, 2)?;
+        Ok(GovActionId { tx_id, index })
+    }
+}
+
+impl Cbor for Anchor {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(2)?.str(&self.url)?.bytes(&self.data_hash)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let url = collect_str(d)?;
+        let data_hash = hash32(d, "anchor")?;
+        skip_rest(d, len, 2)?;
+        Ok(Anchor { url, data_hash })
+    }
+}
+
+impl Cbor for GovAction {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        match self {
+            GovAction::ParameterChange(update) => {
+                e.array(4)?.u8(0)?.null()?;
+                encode_param_update(e, update)?;
+                e.null()?;
+            }
+            GovAction::HardForkInitiation(version) => {
+                e.array(3)?.u8(1)?.null()?;
+                e.array(2)?.u64(version.major)?.u64(version.minor)?;
+            }
+            GovAction::TreasuryWithdrawals(withdrawals) => {
+                e.array(3)?.u8(2)?.map(withdrawals.len() as u64)?;
+                for (account, amount) in withdrawals {
+                    encode_stake_credential(e, account)?;
+                    e.u64(*amount)?;
+                }
+                e.null()?;
+            }
+            GovAction::NoConfidence => {
+                e.array(2)?.u8(3)?.null()?;
+            }
+            GovAction::UpdateCommittee {
+                removed,
+                added,
+                threshold,
+            } => {
+                e.array(5)?.u8(4)?.null()?.array(removed.len() as u64)?;
+                for member in removed {
+                    encode_stake_credential(e, member)?;
+                }
+                e.map(added.len() as u64)?;
+                for (member, last_epoch) in added {
+                    encode_stake_credential(e, member)?;
+                    e.u64(*last_epoch)?;
+                }
+                encode_rational(e, threshold)?;
+            }
+            GovAction::NewConstitution(anchor) => {
+                e.array(3)?.u8(5)?.null()?.array(2)?;
+                anchor.encode_cbor(e)?;
+                e.null()?;
+            }
+            GovAction::Info => {
+                e.array(1)?.u8(6)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        // The previous action ids and guardrail script hashes are not modelled.
+        let (action, read) = match d.u8()? {
+            0 => {
+                d.skip()?;
+                (
+                    GovAction::ParameterChange(Box::new(decode_param_update(d)?)),
+                    3,
+                )
+            }
+            1 => {
+                d.skip()?;
+                let version_len = array(d)?;
+                let major = d.u64()?;
+                let minor = d.u64()?;
+                skip_rest(d, version_len, 2)?;
+                let version = ProtocolVersion { major, minor };
+                (GovAction::HardForkInitiation(version), 3)
+            }
+            2 => {
+                let mut withdrawals = BTreeMap::new();
+                map_entries(d, |d| {
+                    let account = stake_credential(d)?;
+                    withdrawals.insert(account, d.u64()?);
+                    Ok(())
+                })?;
+                (GovAction::TreasuryWithdrawals(withdrawals), 2)
+            }
+            3 => {
+                d.skip()?;
+                (GovAction::NoConfidence, 2)
+            }
+            4 => {
+                d.skip()?;
+                let removed = seq(d, stake_credential)?.into_iter().collect();
+                let mut added = BTreeMap::new();
+                map_entries(d, |d| {
+                    let member = stake_credential(d)?;
+                    added.insert(member, d.u64()?);
+                    Ok(())
+                })?;
+                let threshold = decode_rational(d, "committee threshold")?;
+                let action = GovAction::UpdateCommittee {
+                    removed,
+                    added,
+                    threshold,
+                };
+                (action, 5)
+            }
+            5 => {
+                d.skip()?;
+                let constitution_len = array(d)?;
+                let anchor = Anchor::decode_cbor(d)?;
+                skip_rest(d, constitution_len, 1)?;
+                (GovAction::NewConstitution(anchor), 3)
+            }
+            6 => (GovAction::Info, 1),
+            kind => {
+                return Err(CborError::invalid(
+                    "governance action",
+                    format!("unsupported action type {}", kind),
+                ));
+            }
+        };
+        skip_rest(d, len, read)?;
+        Ok(action)
+    }
+}
+
+impl Cbor for ProposalProcedure {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(4)?.u64(self.deposit)?;
+        encode_stake_credential(e, &self.return_account)?;
+        self.action.encode_cbor(e)?;
+        self.anchor.encode_cbor(e)
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let deposit = d.u64()?;
+        let return_account = stake_credential(d)?;
+        let action = GovAction::decode_cbor(d)?;
+        let anchor = Anchor::decode_cbor(d)?;
+        skip_rest(d, len, 4)?;
+        Ok(ProposalProcedure {
+            deposit,
+            return_account,
+            action,
+            anchor,
+        })
+    }
+}
+
+/// Write votes as `{* voter => {* gov_action_id => [vote, null]}}`; a later vote of a voter on
+/// the same action replaces the earlier one.
+fn encode_votes(e: &mut Enc, votes: &[VotingProcedure]) -> EncodeResult {
+    let mut by_voter: BTreeMap<&Voter, BTreeMap<GovActionId, Vote>> = BTreeMap::new();
+    for v in votes {
+        by_voter
+            .entry(&v.voter)
+            .or_default()
+            .insert(v.action_id, v.vote);
+    }
+    e.map(by_voter.len() as u64)?;
+    for (voter, votes) in by_voter {
+        voter.encode_cbor(e)?;
+        e.map(votes.len() as u64)?;
+        for (action_id, vote) in votes {
+            action_id.encode_cbor(e)?;
+            let vote = match vote {
+                Vote::No => 0,
+                Vote::Yes => 1,
+                Vote::Abstain => 2,
+            };
+            e.array(2)?.u8(vote)?.null()?;
+        }
+    }
+    Ok(())
+}
+
+/// Read votes, skipping their anchors.
+fn decode_votes(d: &mut Decoder<'_>) -> Result<Vec<VotingProcedure>, CborError> {
+    let mut procedures = Vec::new();
+    map_entries(d, |d| {
+        let voter = Voter::decode_cbor(d)?;
+        map_entries(d, |d| {
+            let action_id = GovActionId::decode_cbor(d)?;
+            let len = array(d)?;
+            let vote = match d.u8()? {
+                0 => Vote::No,
+                1 => Vote::Yes,
+                2 => Vote::Abstain,
+                vote => {
+                    return Err(CborError::invalid(
+                        "voting procedure",
+                        format!("unsupported vote {}", vote),
+                    ));
+                }
+            };
+            skip_rest(d, len, 1)?;
+            procedures.push(VotingProcedure {
+                voter: voter.clone(),
+                action_id,
+                vote,
+            });
+            Ok(())
+        })
+    })?;
+    Ok(procedures)
+}
+
+impl Cbor for NativeScript {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        let encode_all = |e: &mut Enc, scripts: &[NativeScript]| -> EncodeResult {
+            e.array(scripts.len() as u64)?;
+            scripts.iter().try_for_each(|s| s.encode_cbor(e))
+        };
+        match self {
+            NativeScript::RequireSignature(key_hash) => {
+                e.array(2)?.u8(0)?.str(key_hash)?;
+            }
+            NativeScript::AllOf(scripts) => {
+                e.array(2)?.u8(1)?;
+                encode_all(e, scripts)?;
+            }
+            NativeScript::AnyOf(scripts) => {
+                e.array(2)?.u8(2)?;
+                encode_all(e, scripts)?;
+            }
+            NativeScript::MOfN(m, scripts) => {
+                e.array(3)?.u8(3)?.u32(*m)?;
+                encode_all(e, scripts)?;
+            }
+            NativeScript::InvalidBefore(slot) => {
+                e.array(2)?.u8(4)?.u64(*slot)?;
+            }
+            NativeScript::InvalidHereafter(slot) => {
+                e.array(2)?.u8(5)?.u64(*slot)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let (script, read) = match d.u8()? {
+            0 => (NativeScript::RequireSignature(ident(d)?), 2),
+            1 => (NativeScript::AllOf(seq(d, NativeScript::decode_cbor)?), 2),
+            2 => (NativeScript::AnyOf(seq(d, NativeScript::decode_cbor)?), 2),
+            3 => {
+                let m = d.u32()?;
+                (NativeScript::MOfN(m, seq(d, NativeScript::decode_cbor)?), 3)
+            }
+            4 => (NativeScript::InvalidBefore(d.u64()?), 2),
+            5 => (NativeScript::InvalidHereafter(d.u64()?), 2),
+            kind => {
+                return Err(CborError::invalid(
+                    "native script",
+                    format!("unsupported script type {}", kind),
+                ));
+            }
+        };
+        skip_rest(d, len, read)?;
+        Ok(script)
+    }
+}
+
+impl Cbor for PlutusScript {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.bytes(&self.code)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        Ok(PlutusScript {
+            code: collect_bytes(d)?,
+        })
+    }
+}
+
+impl Cbor for PlutusDatum {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.bytes(&self.data)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        Ok(PlutusDatum {
+            data: plutus_data(d)?,
+        })
+    }
+}
+
+impl Cbor for VKeyWitness {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(2)?.bytes(&self.vkey)?.bytes(&self.signature)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let vkey = collect_bytes(d)?;
+        let signature = collect_bytes(d)?;
+        skip_rest(d, len, 2)?;
+        Ok(VKeyWitness { vkey, signature })
+    }
+}
+
+impl Cbor for PlutusRedeemer {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.bytes(&self.data)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        Ok(PlutusRedeemer {
+            data: plutus_data(d)?,
+        })
+    }
+}
+
+/// First compact constructor tag: constructors 0 to 6 are tags 121 to 127.
+const CONSTR_TAG: u64 = 121;
+/// Constructors 7 to 127 are tags 1280 to 1400.
+const CONSTR_TAG_EXTENDED: u64 = 1280;
+/// Tag for any constructor, as `[constructor, fields]`.
+const CONSTR_TAG_GENERAL: u64 = 102;
+
+/// Deepest nesting of Plutus data accepted when decoding.
+const MAX_DATA_DEPTH: usize = 1_024;
+
+impl Cbor for PlutusData {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        match self {
+            PlutusData::Constr(constructor, fields) => {
+                match *constructor {
+                    c @ 0..=6 => e.tag(Tag::Unassigned(CONSTR_TAG + c))?,
+                    c @ 7..=127 => e.tag(Tag::Unassigned(CONSTR_TAG_EXTENDED + c - 7))?,
+                    c => e
+                        .tag(Tag::Unassigned(CONSTR_TAG_GENERAL))?
+                        .array(2)?
+                        .u64(c)?,
+                };
+                e.array(fields.len() as u64)?;
+                for field in fields {
+                    field.encode_cbor(e)?;
+                }
+            }
+            PlutusData::Map(entries) => {
+                e.map(entries.len() as u64)?;
+                for (k, v) in entries {
+                    k.encode_cbor(e)?;
+                    v.encode_cbor(e)?;
+                }
+            }
+            PlutusData::List(items) => {
+                e.array(items.len() as u64)?;
+                for item in items {
+                    item.encode_cbor(e)?;
+                }
+            }
+            PlutusData::Integer(i) => {
+                match i128::try_from(i).ok().and_then(|i| Int::try_from(i).ok()) {
+                    Some(int) => {
+                        e.int(int)?;
+                    }
+                    None => {
+                        let (tag, n) = match i.sign() {
+                            Sign::Minus => (Tag::NegBignum, -1 - i),
+                            _ => (Tag::PosBignum, i.clone()),
+                        };
+                        e.tag(tag)?.bytes(&n.magnitude().to_bytes_be())?;
+                    }
+                }
+            }
+            PlutusData::Bytes(bytes) => {
+                e.bytes(bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        decode_plutus_data(d, 0)
+    }
+}
+
+fn decode_plutus_data(d: &mut Decoder<'_>, depth: usize) -> Result<PlutusData, CborError> {
+    if depth > MAX_DATA_DEPTH {
+        return Err(CborError::invalid("plutus data", "nested too deeply"));
+    }
+    let item = |d: &mut Decoder<'_>| decode_plutus_data(d, depth + 1);
+    Ok(match d.datatype()? {
+        Type::Tag => match d.tag()? {
+            Tag::Unassigned(t @ CONSTR_TAG..=127) => {
+                PlutusData::Constr(t - CONSTR_TAG, seq(d, item)?)
+            }
+            Tag::Unassigned(t @ CONSTR_TAG_EXTENDED..=1400) => {
+                PlutusData::Constr(t - CONSTR_TAG_EXTENDED + 7, seq(d, item)?)
+            }
+            Tag::Unassigned(CONSTR_TAG_GENERAL) => {
+                let len = array(d)?;
+                let constructor = d.u64()?;
+                let fields = seq(d, item)?;
+                skip_rest(d, len, 2)?;
+                PlutusData::Constr(constructor, fields)
+            }
+            tag @ (Tag::PosBignum | Tag::NegBignum) => {
+                let n = BigInt::from_bytes_be(Sign::Plus, &collect_bytes(d)?);
+                PlutusData::Integer(if tag == Tag::PosBignum { n } else { -1 - n })
+            }
+            tag => {
+                return Err(CborError::invalid(
+                    "plutus data",
+                    format!("unexpected tag {:?}", tag),
+                ));
+            }
+        },
+        Type::Map | Type::MapIndef => {
+            let mut entries = Vec::new();
+            map_entries(d, |d| {
+                entries.push((item(d)?, item(d)?));
+                Ok(())
+            })?;
+            PlutusData::Map(entries)
+        }
+        Type::Array | Type::ArrayIndef => PlutusData::List(seq(d, item)?),
+        Type::Bytes | Type::BytesIndef => PlutusData::Bytes(collect_bytes(d)?),
+        _ => PlutusData::Integer(i128::from(d.int()?).into()),
+    })
+}
+
+/// Write a transaction body.
+pub(crate) fn encode_tx_body(e: &mut Enc, tx: &Transaction) -> EncodeResult {
+    let fields = 3
+        + u64::from(tx.ttl.is_some())
+        + u64::from(!tx.certificates.is_empty())
+        + u64::from(!tx.withdrawals.is_empty())
+        + u64::from(tx.update.is_some())
+        + u64::from(tx.validity_start.is_some())
+        + u64::from(!tx.mint.is_empty())
+        + u64::from(!tx.collateral.is_empty())
+        + u64::from(!tx.required_signers.is_empty())
+        + u64::from(tx.collateral_return.is_some())
+        + u64::from(!tx.reference_inputs.is_empty())
+        + u64::from(!tx.voting_procedures.is_empty())
+        + u64::from(!tx.proposal_procedures.is_empty());
+    e.map(fields)?;
+    e.u8(0)?.array(tx.inputs.len() as u64)?;
+    for input in &tx.inputs {
+        input.encode_cbor(e)?;
+    }
+    e.u8(1)?.array(tx.outputs.len() as u64)?;
+    for output in &tx.outputs {
+        output.encode_cbor(e)?;
+    }
+    e.u8(2)?.u64(tx.fee)?;
+    if let Some(ttl) = tx.ttl {
+        e.u8(3)?.u64(ttl)?;
+    }
+    if !tx.certificates.is_empty() {
+        e.u8(4)?.array(tx.certificates.len() as u64)?;
+        for cert in &tx.certificates {
+            cert.encode_cbor(e)?;
+        }
+    }
+    if !tx.withdrawals.is_empty() {
+        e.u8(5)?.map(tx.withdrawals.len() as u64)?;
+        for (account, amount) in &tx.withdrawals {
+            encode_stake_credential(e, account)?;
+            e.u64(*amount)?;
+        }
+    }
+    if let Some(update) = &tx.update {
+        e.u8(6)?;
+        update.encode_cbor(e)?;
+    }
+    if let Some(start) = tx.validity_start {
+        e.u8(8)?.u64(start)?;
+    }
+    if !tx.mint.is_empty() {
+        e.u8(9)?;
+        encode_multi_asset(e, &tx.mint, |e, amount| e.i64(*amount).map(|_| ()))?;
+    }
+    if !tx.collateral.is_empty() {
+        e.u8(13)?.array(tx.collateral.len() as u64)?;
+        for input in &tx.collateral {
+            input.encode_cbor(e)?;
+        }
+    }
+    if !tx.required_signers.is_empty() {
+        e.u8(14)?.array(tx.required_signers.len() as u64)?;
+        for key_hash in &tx.required_signers {
+            e.str(key_hash)?;
+        }
+    }
+    if let Some(output) = &tx.collateral_return {
+        e.u8(16)?;
+        output.encode_cbor(e)?;
+    }
+    if !tx.reference_inputs.is_empty() {
+        e.u8(18)?.array(tx.reference_inputs.len() as u64)?;
+        for input in &tx.reference_inputs {
+            input.encode_cbor(e)?;
+        }
+    }
+    if !tx.voting_procedures.is_empty() {
+        e.u8(19)?;
+        encode_votes(e, &tx.voting_procedures)?;
+    }
+    if !tx.proposal_procedures.is_empty() {
+        e.u8(20)?.array(tx.proposal_procedures.len() as u64)?;
+        for proposal in &tx.proposal_procedures {
+            proposal.encode_cbor(e)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a transaction body into a transaction without witnesses.
+fn decode_tx_body(d: &mut Decoder<'_>) -> Result<Transaction, CborError> {
+    let mut tx = Transaction::default();
+    map_entries(d, |d| {
+        match d.u64()? {
+            0 => tx.inputs = seq(d, TxInput::decode_cbor)?,
+            1 => tx.outputs = seq(d, TxOutput::decode_cbor)?,
+            2 => tx.fee = d.u64()?,
+            3 => tx.ttl = Some(d.u64()?),
+            4 => tx.certificates = seq(d, Certificate::decode_cbor)?,
+            5 => map_entries(d, |d| {
+                let account = stake_credential(d)?;
+                tx.withdrawals.insert(account, d.u64()?);
+                Ok(())
+            })?,
+            6 => tx.update = Some(Update::decode_cbor(d)?),
+            8 => tx.validity_start = Some(d.u64()?),
+            9 => tx.mint = decode_multi_asset(d, |d| Ok(d.i64()?))?,
+            13 => tx.collateral = seq(d, TxInput::decode_cbor)?,
+            14 => tx.required_signers = seq(d, ident)?.into_iter().collect(),
+            16 => tx.collateral_return = Some(TxOutput::decode_cbor(d)?),
+            18 => tx.reference_inputs = seq(d, TxInput::decode_cbor)?,
+            19 => tx.voting_procedures = decode_votes(d)?,
+            20 => tx.proposal_procedures = seq(d, ProposalProcedure::decode_cbor)?,
+            _ => d.skip()?,
+        }
+        Ok(())
+    })?;
+    Ok(tx)
+}
+
+/// Write the witness set of a transaction.
+fn encode_witness_set(e: &mut Enc, tx: &Transaction) -> EncodeResult {
+    let witnesses = &tx.plutus_witnesses;
+    let fields = u64::from(!tx.witnesses.is_empty())
+        + u64::from(!tx.native_scripts.is_empty())
+        + 3 * u64::from(!witnesses.is_empty());
+    e.map(fields)?;
+    if !tx.witnesses.is_empty() {
+        e.u8(0)?.array(tx.witnesses.len() as u64)?;
+        for w in &tx.witnesses {
+            w.encode_cbor(e)?;
+        }
+    }
+    if !tx.native_scripts.is_empty() {
+        e.u8(1)?.array(tx.native_scripts.len() as u64)?;
+        for script in &tx.native_scripts {
+            script.encode_cbor(e)?;
+        }
+    }
+    if witnesses.is_empty() {
+        return Ok(());
+    }
+    let n = witnesses.len() as u64;
+    e.u8(3)?.array(n)?;
+    for w in witnesses {
+        w.script.encode_cbor(e)?;
+    }
+    e.u8(4)?.array(n)?;
+    for w in witnesses {
+        w.datum.encode_cbor(e)?;
+    }
+    e.u8(5)?.array(n)?;
+    for (index, w) in witnesses.iter().enumerate() {
+        e.array(4)?.u8(0)?.u64(index as u64)?;
+        w.redeemer.encode_cbor(e)?;
+        e.array(2)?
+            .u64(w.execution_units.0)?
+            .u64(w.execution_units.1)?;
+    }
+    Ok(())
+}
+
+/// Read a redeemer's data and execution units, skipping its tag and index.
+fn decode_redeemer_value(d: &mut Decoder<'_>) -> Result<(PlutusRedeemer, (u64, u64)), CborError> {
+    let redeemer = PlutusRedeemer::decode_cbor(d)?;
+    let len = array(d)?;
+    let units = (d.u64()?, d.u64()?);
+    skip_rest(d, len, 2)?;
+    Ok((redeemer, units))
+}
+
+/// Witnesses of a transaction, as read from its witness set.
+struct WitnessSet {
+    vkey_witnesses: Vec<VKeyWitness>,
+    native_scripts: Vec<NativeScript>,
+    plutus_witnesses: Vec<PlutusWitness>,
+}
+
+impl WitnessSet {
+    /// Attach the witnesses to the transaction decoded from the matching body.
+    fn attach(self, tx: &mut Transaction) {
+        tx.witnesses = self.vkey_witnesses;
+        tx.native_scripts = self.native_scripts;
+        tx.plutus_witnesses = self.plutus_witnesses;
+    }
+}
+
+/// Read a witness set, pairing the n-th Plutus script, datum and redeemer into one witness.
+fn decode_witness_set(d: &mut Decoder<'_>) -> Result<WitnessSet, CborError> {
+    let mut vkey_witnesses = Vec::new();
+    let mut native_scripts = Vec::new();
+    let mut scripts = Vec::new();
+    let mut datums = Vec::new();
+    let mut redeemers = Vec::new();
+    map_entries(d, |d| {
+        match d.u64()? {
+            0 => vkey_witnesses = seq(d, VKeyWitness::decode_cbor)?,
+            1 => native_scripts = seq(d, NativeScript::decode_cbor)?,
+            // Plutus V1, V2 and V3 scripts
+            3 | 6 | 7 => scripts.extend(seq(d, PlutusScript::decode_cbor)?),
+            4 => datums = seq(d, PlutusDatum::decode_cbor)?,
+            5 if matches!(d.datatype()?, Type::Map | Type::MapIndef) => {
+                // Conway: {[tag, index] => [data, ex_units]}
+                map_entries(d, |d| {
+                    d.skip()?;
+                    let len = array(d)?;
+                    redeemers.push(decode_redeemer_value(d)?);
+                    skip_rest(d, len, 2)
+                })?
+            }
+            5 => {
+                redeemers = seq(d, |d| {
+                    let len = array(d)?;
+                    d.skip()?; // tag
+                    d.skip()?; // index
+                    let redeemer = decode_redeemer_value(d)?;
+                    skip_rest(d, len, 4)?;
+                    Ok(redeemer)
+                })?
+            }
+            _ => d.skip()?,
+        }
+        Ok(())
+    })?;
+    let n = scripts.len().max(datums.len()).max(redeemers.len());
+    let plutus_witnesses = (0..n)
+        .map(|i| {
+            let (redeemer, execution_units) = redeemers
+                .get(i)
+                .cloned()
+                .unwrap_or((PlutusRedeemer { data: vec![] }, (0, 0)));
+            PlutusWitness {
+                script: scripts
+                    .get(i)
+                    .cloned()
+                    .unwrap_or(PlutusScript { code: vec![] }),
+                datum: datums
+                    .get(i)
+                    .cloned()
+                    .unwrap_or(PlutusDatum { data: vec![] }),
+                redeemer,
+                execution_units,
+            }
+        })
+        .collect();
+    Ok(WitnessSet {
+        vkey_witnesses,
+        native_scripts,
+        plutus_witnesses,
+    })
+}
+
+impl Cbor for Transaction {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(4)?;
+        encode_tx_body(e, self)?;
+        encode_witness_set(e, self)?;
+        e.bool(self.is_valid)?.null()?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let mut tx = decode_tx_body(d)?;
+        decode_witness_set(d)?.attach(&mut tx);
+        // Shelley to Mary: [body, witnesses, auxiliary_data]
+        // Alonzo onwards: [body, witnesses, is_valid, auxiliary_data]
+        if let Some(n) = len.filter(|n| !(3..=4).contains(n)) {
+            return Err(CborError::invalid(
+                "transaction",
+                format!("expected 3 or 4 elements, found {}", n),
+            ));
+        }
+        let mut read = 2;
+        if len != Some(3) && d.datatype()? == Type::Bool {
+            tx.is_valid = d.bool()?;
+            read = 3;
+        }
+        skip_rest(d, len, read)?;
+        Ok(tx)
+    }
+}
+
+/// Write a header body: the header without its KES signature.
+pub(crate) fn encode_header_body(e: &mut Enc, header: &BlockHeader) -> EncodeResult {
+    e.array(9)?.u64(header.slot)?.u64(header.epoch)?;
+    match &header.prev_hash {
+        Some(hash) => e.bytes(hash)?,
+        None => e.null()?,
+    };
+    e.str(&header.leader)?
+        .bytes(&header.issuer_vkey)?
+        .bytes(&header.vrf_vkey)?
+        .bytes(&header.vrf_proof)?
+        .bytes(&header.body_hash)?;
+    let opcert = &header.opcert;
+    e.array(4)?
+        .bytes(&opcert.hot_vkey)?
+        .u64(opcert.counter)?
+        .u64(opcert.kes_period)?
+        .bytes(&opcert.sigma)?;
+    Ok(())
+}
+
+impl Cbor for BlockHeader {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(2)?;
+        encode_header_body(e, self)?;
+        e.bytes(&self.kes_signature)?;
+        Ok(())
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let body_len = array(d)?;
+        let slot = d.u64()?;
+        let epoch = d.u64()?;
+        let prev_hash = if d.datatype()? == Type::Null {
+            d.null()?;
+            None
+        } else {
+            Some(hash32(d, "previous hash")?)
+        };
+        let leader = collect_str(d)?;
+        let issuer_vkey = collect_bytes(d)?;
+        let vrf_vkey = collect_bytes(d)?;
+        let vrf_proof = collect_bytes(d)?;
+        let body_hash = hash32(d, "body hash")?;
+        let opcert_len = array(d)?;
+        let opcert = OperationalCert {
+            hot_vkey: collect_bytes(d)?,
+            counter: d.u64()?,
+            kes_period: d.u64()?,
+            sigma: collect_bytes(d)?,
+        };
+        skip_rest(d, opcert_len, 4)?;
+        skip_rest(d, body_len, 9)?;
+        let kes_signature = collect_bytes(d)?;
+        skip_rest(d, len, 2)?;
+        Ok(BlockHeader {
+            slot,
+            epoch,
+            leader,
+            issuer_vkey,
+            vrf_vkey,
+            vrf_proof,
+            opcert,
+            kes_signature,
+            prev_hash,
+            body_hash,
+        })
+    }
+}
+
+/// Write a block body: the transaction bodies and their witness sets.
+pub(crate) fn encode_block_body(e: &mut Enc, transactions: &[Transaction]) -> EncodeResult {
+    e.array(transactions.len() as u64)?;
+    for tx in transactions {
+        encode_tx_body(e, tx)?;
+    }
+    e.array(transactions.len() as u64)?;
+    for tx in transactions {
+        encode_witness_set(e, tx)?;
+    }
+    let invalid: Vec<usize> = (0..transactions.len())
+        .filter(|&i| !transactions[i].is_valid)
+        .collect();
+    e.array(invalid.len() as u64)?;
+    for index in invalid {
+        e.u64(index as u64)?;
+    }
+    Ok(())
+}
+
+impl Cbor for Block {
+    fn encode_cbor(&self, e: &mut Enc) -> EncodeResult {
+        e.array(5)?.u64(self.id)?;
+        self.header.encode_cbor(e)?;
+        encode_block_body(e, &self.transactions)
+    }
+
+    fn decode_cbor(d: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let len = array(d)?;
+        let id = d.u64()?;
+        let header = BlockHeader::decode_cbor(d)?;
+        let mut transactions = seq(d, decode_tx_body)?;
+        let witnesses = seq(d, decode_witness_set)?;
+        let invalid = match len {
+            Some(4) => Vec::new(),
+            None if d.datatype()? == Type::Break => Vec::new(),
+            _ => seq(d, |d| Ok(d.u64()?))?,
+        };
+        skip_rest(d, len, 5)?;
+        if witnesses.len() != transactions.len() {
+            return Err(CborError::invalid(
+                "block",
+                format!(
+                    "{} transaction bodies but {} witness sets",
+                    transactions.len(),
+                    witnesses.len()
+                ),
+            ));
+        }
+        for (tx, w) in transactions.iter_mut().zip(witnesses) {
+            w.attach(tx);
+        }
+        for index in invalid {
+            transactions
+                .get_mut(index as usize)
+                .ok_or_else(|| {
+                    CborError::invalid("block", format!("invalid transaction index {}", index))
+                })?
+                .is_valid = false;
+        }
+        Ok(Block {
+            id,
+            header,
+            transactions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::address::Network;
+
+    fn cred(n: u8) -> Credential {
+        Credential::KeyHash([n; 28])
+    }
+
+    fn addr(n: u8) -> Address {
+        Address::base(Network::Testnet, cred(n), cred(n))
+    }
+
+    fn output(address: Address, amount: u64) -> TxOutput {
+        TxOutput {
+            address,
+            amount,
+            assets: None,
+            ..Default::default()
+        }
+    }
+
+    fn sample_tx() -> Transaction {
+        Transaction {
+            inputs: vec![TxInput {
+                prev_tx: [3u8; 32],
+                index: 1,
+            }],
+            outputs: vec![
+                output(addr(1), 10),
+                TxOutput {
+                    address: "Ae2tdPwUPEZFRbyhz3cpfC2CumGzNkFBN2L42rcUc2yjQpEkxDbkPodpMAi"
+                        .parse()
+                        .unwrap(),
+                    amount: 2,
+                    assets: Some(vec![
+                        Asset {
+                            policy_id: "p".to_string(),
+                            asset_name: "a".to_string(),
+                            amount: 5,
+                        },
+                        Asset {
+                            policy_id: "p".to_string(),
+                            asset_name: "b".to_string(),
+                            amount: 7,
+                        },
+                    ]),
+                    datum: Some(DatumOption::Inline(PlutusDatum {
+                        data: PlutusData::Integer(42.into()).to_cbor(),
+                    })),
+                    script_ref: Some(ScriptRef::Native(NativeScript::InvalidBefore(5))),
+                },
+                TxOutput {
+                    address: Address::enterprise(Network::Mainnet, Credential::ScriptHash([3; 28])),
+                    amount: 3,
+                    assets: None,
+                    datum: Some(DatumOption::Hash([8u8; 32])),
+                    script_ref: Some(ScriptRef::Plutus(PlutusScript { code: vec![1, 2] })),
+                },
+            ],
+            reference_inputs: vec![TxInput {
+                prev_tx: [4u8; 32],
+                index: 0,
+            }],
+            certificates: vec![
+                Certificate::StakeRegistration(cred(1)),
+                Certificate::Delegation(DelegationCertificate {
+                    delegator: cred(1),
+                    pool_id: "pool1".to_string(),
+                }),
+                Certificate::PoolRegistration(StakePoolRegistration {
+                    pool_id: "pool1".to_string(),
+                    vrf_keyhash: [7u8; 32],
+                    owner: cred(9),
+                    pledge: 100,
+                    cost: 340,
+                    margin: 0.05,
+                    reward_account: cred(1),
+                }),
+                Certificate::PoolRetirement(StakePoolRetirement {
+                    pool_id: "pool1".to_string(),
+                    retirement_epoch: 9,
+                }),
+                Certificate::DRepRegistration(DRepCertificate {
+                    drep: cred(3),
+                    deposit: 500,
+                }),
+                Certificate::VoteDelegation(VoteDelegationCertificate {
+                    delegator: cred(1),
+                    drep: DRep::Credential(cred(3)),
+                }),
+                Certificate::VoteDelegation(VoteDelegationCertificate {
+                    delegator: Credential::ScriptHash([2; 28]),
+                    drep: DRep::AlwaysAbstain,
+                }),
+                Certificate::DRepDeregistration(DRepCertificate {
+                    drep: Credential::ScriptHash([4; 28]),
+                    deposit: 500,
+                }),
+                Certificate::StakeDeregistration(Credential::ScriptHash([2; 28])),
+            ],
+            voting_procedures: vec![
+                VotingProcedure {
+                    voter: Voter::DRep(Credential::ScriptHash([4; 28])),
+                    action_id: GovActionId {
+                        tx_id: [5u8; 32],
+                        index: 0,
+                    },
+                    vote: Vote::Yes,
+                },
+                VotingProcedure {
+                    voter: Voter::StakePool("pool1".to_string()),
+                    action_id: GovActionId {
+                        tx_id: [5u8; 32],
+                        index: 1,
+                    },
+                    vote: Vote::Abstain,
+                },
+            ],
+            proposal_procedures: vec![
+                ProposalProcedure {
+                    deposit: 1000,
+                    return_account: cred(1),
+                    action: GovAction::UpdateCommittee {
+                        removed: [cred(11)].into(),
+                        added: BTreeMap::from([(Credential::ScriptHash([12; 28]), 20)]),
+                        threshold: Rational::new(2, 3),
+                    },
+                    anchor: Anchor {
+                        url: "https://example.com".to_string(),
+                        data_hash: [6u8; 32],
+                    },
+                },
+                ProposalProcedure {
+                    deposit: 1000,
+                    return_account: cred(1),
+                    action: GovAction::TreasuryWithdrawals(BTreeMap::from([(
+                        Credential::ScriptHash([2; 28]),
+                        7,
+                    )])),
+                    anchor: Anchor::default(),
+                },
+            ],
+            fee: 170_000,
+            ttl: Some(100),
+            withdrawals: BTreeMap::from([(Credential::ScriptHash([0; 28]), 1), (cred(1), 2)]),
+            validity_start: Some(5),
+            mint: MultiAsset::from([(
+                "p".to_string(),
+                BTreeMap::from([("a".to_string(), 5), ("burnt".to_string(), -3)]),
+            )]),
+            update: Some(Update {
+                proposals: BTreeMap::from([(
+                    "genesis1".to_string(),
+                    ParamUpdate {
+                        min_fee_a: Some(45),
+                        max_block_size: Some(90_112),
+                        decentralisation: Some(Rational::new(1, 2)),
+                        extra_entropy: Some([0xee; 32]),
+                        n_opt: Some(150),
+                        treasury_cut: Some(Rational::new(1, 5)),
+                        protocol_version: Some(ProtocolVersion { major: 5, minor: 0 }),
+                        cost_models: Some(BTreeMap::from([(0, vec![197_209, -1])])),
+                        ex_unit_prices: Some(ExUnitPrices {
+                            mem: Rational::new(577, 10_000),
+                            steps: Rational::new(721, 10_000_000),
+                        }),
+                        collateral_percentage: Some(150),
+                        ..Default::default()
+                    },
+                )]),
+                epoch: 3,
+            }),
+            required_signers: ["k2".to_string(), "k1".to_string()].into(),
+            witnesses: vec![VKeyWitness {
+                vkey: vec![6; 32],
+                signature: vec![7; 64],
+            }],
+            native_scripts: vec![NativeScript::AllOf(vec![
+                NativeScript::MOfN(
+                    1,
+                    vec![
+                        NativeScript::RequireSignature("k1".to_string()),
+                        NativeScript::AnyOf(vec![]),
+                    ],
+                ),
+                NativeScript::InvalidBefore(5),
+                NativeScript::InvalidHereafter(100),
+            ])],
+            plutus_witnesses: vec![PlutusWitness {
+                script: PlutusScript { code: vec![1, 2] },
+                datum: PlutusDatum { data: vec![3] },
+                redeemer: PlutusRedeemer { data: vec![4] },
+                execution_units: (1000, 5000),
+            }],
+            collateral: vec![TxInput {
+                prev_tx: [4u8; 32],
+                index: 0,
+            }],
+            collateral_return: Some(output(addr(1), 3)),
+            is_valid: false,
+        }
+    }
+
+    #[test]
+    fn block_and_transaction_round_trip() {
+        let tx = sample_tx();
+        assert_eq!(Transaction::from_cbor(&tx.to_cbor()).unwrap(), tx);
+
+        let block = Block::new(
+            7,
+            BlockHeader {
+                slot: 42,
+                epoch: 1,
+                leader: "pool1".to_string(),
+                issuer_vkey: vec![4; 32],
+                vrf_vkey: vec![3; 32],
+                vrf_proof: vec![9; 8],
+                opcert: OperationalCert {
+                    hot_vkey: vec![5; 32],
+                    counter: 2,
+                    kes_period: 11,
+                    sigma: vec![6; 64],
+                },
+                kes_signature: vec![8; 8],
+                prev_hash: Some([1u8; 32]),
+                body_hash: [0u8; 32],
+            },
+            vec![tx.clone(), Transaction::default()],
+        );
+        let decoded = Block::from_cbor(&block.to_cbor()).unwrap();
+        assert_eq!(decoded.id, block.id);
+        assert_eq!(decoded.header, block.header);
+        assert_eq!(decoded.transactions, block.transactions);
+        assert!(decoded.body_matches());
+    }
+
+    #[test]
+    fn encoding_is_canonical() {
+        // Asset order does not change the encoding; assets are sorted and merged.
+        let mut tx = sample_tx();
+        let bytes = tx.to_cbor();
+        tx.outputs[1].assets.as_mut().unwrap().reverse();
+        assert_eq!(tx.to_cbor(), bytes);
+        // Withdrawal keys are ordered by their encoding, so key hashes come before script hashes.
+        let key = |c: Credential, amount: u8| {
+            [
+                encode_with(|e| encode_stake_credential(e, &c)),
+                vec![amount],
+            ]
+            .concat()
+        };
+        let pos = |needle: &[u8]| bytes.windows(needle.len()).position(|w| w == needle);
+        assert!(
+            pos(&key(cred(1), 2)).unwrap() < pos(&key(Credential::ScriptHash([0; 28]), 1)).unwrap()
+        );
+        // Shortest integer forms and definite lengths: [[h'03..', 1]].
+        let input = TxInput {
+            prev_tx: [0u8; 32],
+            index: 1,
+        };
+        let mut expected = vec![0x82, 0x58, 0x20];
+        expected.extend([0u8; 32]);
+        expected.push(0x01);
+        assert_eq!(input.to_cbor(), expected);
+        assert!(matches!(
+            TxInput::from_cbor(&[expected.as_slice(), &[0]].concat()),
+            Err(CborError::TrailingBytes(1))
+        ));
+    }
+
+    #[test]
+    fn plutus_data_round_trips() {
+        // Constructor 0 with no fields is #6.121([]).
+        assert_eq!(
+            PlutusData::Constr(0, vec![]).to_cbor(),
+            vec![0xd8, 0x79, 0x80]
+        );
+        let data = PlutusData::Map(vec![(
+            PlutusData::Bytes(b"key".to_vec()),
+            PlutusData::List(vec![
+                PlutusData::Constr(8, vec![PlutusData::Integer((-5).into())]),
+                PlutusData::Constr(200, vec![]),
+                PlutusData::Integer(i128::MAX.into()),
+                PlutusData::Integer(i128::MIN.into()),
+                PlutusData::Integer(BigInt::from(u128::MAX) * 1_000),
+                PlutusData::Integer(BigInt::from(i128::MIN) * 1_000),
+            ]),
+        )]);
+        assert_eq!(PlutusData::from_cbor(&data.to_cbor()).unwrap(), data);
+        // Indefinite-length lists, as the Haskell node encodes them.
+        assert_eq!(
+            PlutusData::from_cbor(&[0xd8, 0x7a, 0x9f, 0x01, 0x02, 0xff]).unwrap(),
+            PlutusData::Constr(
+                1,
+                vec![PlutusData::Integer(1.into()), PlutusData::Integer(2.into())]
+            )
+        );
+    }
+
+    #[test]
+    fn decodes_cardano_shaped_transaction() {
+        // A Babbage-style transaction: set-tagged inputs, a map-shaped output with an inline
+        // datum, fee and TTL fields, a vkey witness, a rational pool margin and Conway redeemers.
+        let bytes = encode_with(|e| {
+            e.array(4)?.map(4)?;
+            e.u8(0)?.tag(Tag::Unassigned(SET_TAG))?.array(1)?;
+            e.array(2)?.bytes(&[5u8; 32])?.u8(0)?;
+            e.u8(1)?.begin_array()?;
+            e.map(3)?
+                .u8(0)?
+                .bytes(&[[0x61].as_slice(), &[0xab; 28]].concat())?
+                .u8(1)?;
+            e.array(2)?.u64(1_500_000)?.map(1)?.bytes(&[0xcc])?.map(1)?;
+            e.bytes(b"tok")?.u8(3)?;
+            e.u8(2)?.array(2)?.u8(1)?.tag(Tag::Cbor)?.bytes(&[0])?;
+            e.end()?;
+            e.u8(2)?.u64(170_000)?;
+            e.u8(4)?
+                .array(1)?
+                .array(10)?
+                .u8(3)?
+                .bytes(&[0xaa])?
+                .bytes(&[0xbb; 32])?;
+            e.u64(500)?.u64(340)?.tag(Tag::Unassigned(RATIONAL_TAG))?;
+            e.array(2)?
+                .u8(1)?
+                .u8(20)?
+                .bytes(&[[0xe1].as_slice(), &[0xee; 28]].concat())?
+                .array(1)?
+                .bytes(&[0xdd; 28])?;
+            e.array(0)?.null()?;
+            e.map(3)?
+                .u8(0)?
+                .array(1)?
+                .array(2)?
+                .bytes(&[1])?
+                .bytes(&[2])?;
+            e.u8(6)?.array(1)?.bytes(&[0x4d])?;
+            e.u8(5)?.map(1)?.array(2)?.u8(0)?.u8(0)?;
+            e.array(2)?.array(0)?.array(2)?.u64(10)?.u64(20)?;
+            e.bool(true)?.null()?;
+            Ok(())
+        });
+        let tx = Transaction::from_cbor(&bytes).unwrap();
+        assert_eq!(tx.inputs[0].prev_tx, [5u8; 32]);
+        assert_eq!(tx.fee, 170_000);
+        assert_eq!(
+            tx.outputs[0].address,
+            Address::enterprise(Network::Mainnet, cred(0xab))
+        );
+        assert_eq!(tx.outputs[0].amount, 1_500_000);
+        assert_eq!(
+            tx.outputs[0].assets,
+            Some(vec![Asset {
+                policy_id: "cc".to_string(),
+                asset_name: "746f6b".to_string(),
+                amount: 3,
+            }])
+        );
+        assert_eq!(
+            tx.outputs[0].datum,
+            Some(DatumOption::Inline(PlutusDatum { data: vec![0] }))
+        );
+        match &tx.certificates[0] {
+            Certificate::PoolRegistration(r) => {
+                assert_eq!(r.pool_id, "aa");
+                assert_eq!(r.vrf_keyhash, [0xbb; 32]);
+                assert_eq!(r.owner, cred(0xdd));
+                assert_eq!(r.reward_account, cred(0xee));
+                assert!((r.margin - 0.05).abs() < 1e-12);
+            }
+            other => panic!("expected pool registration, got {:?}", other),
+        }
+        assert_eq!(
+            tx.witnesses,
+            vec![VKeyWitness {
+                vkey: vec![1],
+                signature: vec![2],
+            }]
+        );
+        let w = &tx.plutus_witnesses[0];
+        assert_eq!(w.script.code, vec![0x4d]);
+        assert_eq!(w.redeemer.data, vec![0x80]); // raw encoding of the empty list
+        assert_eq!(w.execution_units, (10, 20));
+
+        assert!(matches!(
+            Transaction::from_cbor(&[0x82, 0xa0, 0xa0]),
+            Err(CborError::Invalid { .. })
+        ));
+        assert!(Transaction::from_cbor(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
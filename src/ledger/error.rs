@@ -0,0 +1,212 @@
+//! Error types for the ledger rules.
+
+use super::governance::{GovActionId, Voter};
+use super::{Credential, PlutusError, TxInput, Value};
+use crate::chaindb::hex;
+use thiserror::Error;
+
+/// Reasons a transaction or block is rejected by the ledger rules.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LedgerError {
+    /// The transaction spends no inputs.
+    #[error("Transaction spends no inputs")]
+    InputSetEmpty,
+
+    /// An input refers to an output that is not in the UTXO set (missing or already spent).
+    #[error("Input {}#{} is not in the UTXO set", hex(&.0.prev_tx), .0.index)]
+    BadInput(TxInput),
+
+    /// The same input is spent twice by one transaction.
+    #[error("Input {}#{} is spent more than once", hex(&.0.prev_tx), .0.index)]
+    DuplicateInput(TxInput),
+
+    /// An input or collateral input is locked by an address without a payment credential: a
+    /// Byron address, whose bootstrap witnesses this ledger does not support, or a reward address.
+    #[error("Input {}#{} is locked by an address without a payment credential", hex(&.0.prev_tx), .0.index)]
+    NoPaymentCredential(TxInput),
+
+    /// Inputs plus withdrawals plus minted tokens differ from outputs plus fee plus deposits
+    /// plus burned tokens.
+    #[error("Value not conserved: consumed {consumed}, produced {produced}")]
+    ValueNotConserved { consumed: Value, produced: Value },
+
+    /// Summing the values of a transaction overflowed.
+    #[error("Value overflow")]
+    ValueOverflow,
+
+    /// Tokens are minted or burned under a policy whose script is not in the transaction.
+    #[error("Missing minting policy script for {policy_id}")]
+    MissingMintingPolicy { policy_id: String },
+
+    /// An input, a withdrawn reward account or a certificate credential is locked by a script that
+    /// is not in the transaction.
+    #[error("Missing script {script_hash}")]
+    MissingScript { script_hash: String },
+
+    /// A native script of the transaction is not satisfied by its signers and validity interval.
+    #[error("Native script {script_hash} is not satisfied")]
+    NativeScriptFailed { script_hash: String },
+
+    /// A key that must sign the transaction has no witness.
+    #[error("Missing signature of key {key_hash}")]
+    MissingVKeyWitness { key_hash: String },
+
+    /// A witness's signature does not verify against the transaction id.
+    #[error("Invalid signature of key {key_hash}")]
+    InvalidSignature { key_hash: String },
+
+    /// A Plutus script purpose (an input it locks, a policy it mints under, a certificate or a
+    /// reward account) has no witness left for the script.
+    #[error("Missing redeemer for Plutus script {script_hash}")]
+    MissingRedeemer { script_hash: String },
+
+    /// A Plutus script authorises a Conway certificate, which PlutusV1 script contexts cannot
+    /// represent.
+    #[error("Plutus script {script_hash} cannot certify a Conway certificate")]
+    UnsupportedCertificate { script_hash: String },
+
+    /// A Plutus witness does not match any script purpose of the transaction.
+    #[error("Plutus witness for script {script_hash} has no purpose in the transaction")]
+    ExtraneousRedeemer { script_hash: String },
+
+    /// The protocol parameters have no valid cost model for the language of a Plutus script the
+    /// transaction runs.
+    #[error("No valid cost model for Plutus language {0}")]
+    MissingCostModel(u8),
+
+    /// The datum a Plutus witness supplies for a spent input does not hash to the input's datum
+    /// hash.
+    #[error("Datum for input {}#{} does not match its datum hash", hex(&.0.prev_tx), .0.index)]
+    DatumHashMismatch(TxInput),
+
+    /// The transaction runs Plutus scripts but has no collateral inputs.
+    #[error("Transaction runs Plutus scripts without collateral")]
+    NoCollateralInputs,
+
+    /// The transaction has more collateral inputs than the protocol parameters allow.
+    #[error("{count} collateral inputs exceed the maximum of {max}")]
+    TooManyCollateralInputs { count: usize, max: usize },
+
+    /// A collateral input is locked by a script rather than a key.
+    #[error("Collateral input {}#{} is locked by a script", hex(&.0.prev_tx), .0.index)]
+    CollateralLockedByScript(TxInput),
+
+    /// The collateral minus the collateral return is not pure lovelace.
+    #[error("Collateral {collateral} minus its return {returned} is not a lovelace amount")]
+    CollateralNotBalanced { collateral: Value, returned: Value },
+
+    /// The collateral does not cover the required percentage of the fee.
+    #[error("Collateral of {provided} lovelace is below the required {required}")]
+    InsufficientCollateral { provided: u64, required: u64 },
+
+    /// The transaction is marked as failing phase-2 validation, but its scripts pass.
+    #[error("Transaction is marked invalid but its scripts pass")]
+    ValidationTagMismatch,
+
+    /// A Plutus script failed phase-2 validation.
+    #[error("Plutus script {script_hash} failed: {error}")]
+    PlutusScriptFailed {
+        script_hash: String,
+        error: PlutusError,
+    },
+
+    /// An output pays to a reward address, which names a reward account rather than locking
+    /// outputs.
+    #[error("Output {index} pays to a reward address")]
+    OutputToRewardAddress { index: usize },
+
+    /// An output holds less than the minimum UTxO value.
+    #[error("Output {index} holds {amount} lovelace, below the minimum of {min}")]
+    OutputTooSmall { index: usize, amount: u64, min: u64 },
+
+    /// The slot lies outside the transaction's validity interval `[start, ttl)`.
+    #[error("Slot {slot} is outside the validity interval [{start:?}, {ttl:?})")]
+    OutsideValidityInterval {
+        slot: u64,
+        start: Option<u64>,
+        ttl: Option<u64>,
+    },
+
+    /// The encoded transaction is larger than the maximum transaction size.
+    #[error("Transaction size {size} exceeds the maximum of {max} bytes")]
+    TxTooLarge { size: usize, max: usize },
+
+    /// The fee is below the minimum fee for the transaction's size.
+    #[error("Fee {fee} is below the minimum fee of {min}")]
+    FeeTooSmall { fee: u64, min: u64 },
+
+    /// A withdrawal does not drain its reward account exactly.
+    #[error("Withdrawal of {amount} from {account} does not match its reward balance {balance:?}")]
+    IncorrectWithdrawal {
+        account: Credential,
+        amount: u64,
+        balance: Option<u64>,
+    },
+
+    /// A certificate cannot be applied to the ledger state.
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    /// A governance proposal does not pay the governance action deposit.
+    #[error("Proposal deposit {deposit} differs from the governance action deposit {expected}")]
+    ProposalDepositIncorrect { deposit: u64, expected: u64 },
+
+    /// A governance action cannot be enacted as proposed.
+    #[error("Malformed governance proposal: {0}")]
+    MalformedProposal(String),
+
+    /// A vote refers to a governance action that is not open.
+    #[error("Governance action {}#{} is not open", hex(&.0.tx_id), .0.index)]
+    UnknownGovAction(GovActionId),
+
+    /// A vote comes from an unknown committee member, an unregistered DRep or an inactive pool.
+    #[error("Unknown voter {0:?}")]
+    UnknownVoter(Voter),
+
+    /// A voter's role has no say on the governance action it votes on.
+    #[error("{voter:?} cannot vote on governance action {}#{}", hex(&.action_id.tx_id), .action_id.index)]
+    VoterNotAllowed {
+        voter: Voter,
+        action_id: GovActionId,
+    },
+
+    /// An update proposal is malformed or not for the current epoch.
+    #[error("Invalid update proposal: {0}")]
+    InvalidUpdate(String),
+
+    /// The block body is larger than the maximum block size.
+    #[error("Block {block_id} body size {size} exceeds the maximum of {max} bytes")]
+    BlockTooLarge {
+        block_id: u64,
+        size: usize,
+        max: usize,
+    },
+
+    /// The block's `prev_hash` is not the hash of the ledger tip.
+    #[error("Block {block_id} does not extend the ledger tip")]
+    DoesNotExtendTip { block_id: u64 },
+
+    /// The block's slot is not after the slot of the ledger tip.
+    #[error("Block slot {slot} is not after the tip slot {tip_slot}")]
+    SlotNotIncreasing { slot: u64, tip_slot: u64 },
+
+    /// The block's epoch is not the epoch its slot falls in.
+    #[error("Block of slot {slot} claims epoch {epoch}, but the slot is in epoch {expected}")]
+    WrongEpoch {
+        slot: u64,
+        epoch: u64,
+        expected: u64,
+    },
+
+    /// The block's body hash does not match its transactions.
+    #[error("Block {block_id} body does not match its header")]
+    BodyHashMismatch { block_id: u64 },
+
+    /// A transaction of a block failed the ledger rules.
+    #[error("Transaction {index} of block {block_id} is invalid: {error}")]
+    InvalidTransaction {
+        block_id: u64,
+        index: usize,
+        error: Box<LedgerError>,
+    },
+}
@@ -0,0 +1,533 @@
+//! Conway on-chain governance: delegated representatives, governance actions, votes and their
+//! ratification.
+//!
+//! Delegated representatives (DReps) register with a deposit, and stake credentials delegate
+//! their vote to a DRep or to one of the predefined always-abstain and always-no-confidence
+//! options (see [`Certificate`](super::Certificate)). A transaction may propose governance
+//! actions, each paying [`gov_action_deposit`](super::ProtocolParameters::gov_action_deposit),
+//! and vote on open actions as a DRep, a stake pool or a constitutional committee member. Each
+//! role only votes on the actions it has a say on.
+//!
+//! At each epoch boundary the open actions are ratified in priority order (no-confidence motions
+//! first, info actions last), then by id. An action is enacted when the yes votes of every role
+//! involved reach its threshold: DReps and pools vote with the stake delegated to them in the
+//! [mark snapshot](super::stake) taken at the boundary, the committee with one vote per member
+//! against its own threshold. Abstentions are left out and missing votes count as no; stake
+//! delegated to always-no-confidence votes yes on no-confidence motions and no on everything
+//! else. Enacting a no-confidence motion, a committee update, a new constitution or a hard fork
+//! delays the remaining actions to the next boundary. Enacted actions and actions open for more
+//! than `gov_action_lifetime` epochs are removed, and their deposit is returned to their return
+//! account (or the treasury, if it is not registered). Info actions are never enacted.
+//!
+//! Simplifications: committee members vote with their cold credential (there are no hot keys),
+//! actions do not chain to the previously enacted action of their kind, and actions are enacted
+//! at the boundary they are ratified at.
+
+use super::params::{DRepVotingThresholds, ProtocolVersion};
+use super::{Credential, LedgerError, LedgerState, ParamUpdate, Rational, Transaction, TxId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Governance action id: the transaction proposing the action and its index among the
+/// transaction's proposals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GovActionId {
+    pub tx_id: TxId,
+    pub index: u32,
+}
+
+/// Off-chain document (a rationale, the constitution text) by URL and Blake2b-256 hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Anchor {
+    pub url: String,
+    pub data_hash: [u8; 32],
+}
+
+/// What a stake credential delegates its vote to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DRep {
+    /// A registered DRep, by credential.
+    Credential(Credential),
+    /// Abstain on every action.
+    AlwaysAbstain,
+    /// Vote yes on no-confidence motions and no on everything else.
+    AlwaysNoConfidence,
+}
+
+/// Constitutional committee: member credentials with the last epoch of their term, and the
+/// fraction of members that must approve an action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Committee {
+    pub members: BTreeMap<Credential, u64>,
+    pub threshold: Rational,
+}
+
+/// Governance action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GovAction {
+    /// Change protocol parameters (other than the protocol version).
+    ParameterChange(Box<ParamUpdate>),
+    /// Move to the next protocol version.
+    HardForkInitiation(ProtocolVersion),
+    /// Pay lovelace from the treasury to reward accounts.
+    TreasuryWithdrawals(BTreeMap<Credential, u64>),
+    /// Dismiss the constitutional committee.
+    NoConfidence,
+    /// Remove and add committee members (with the last epoch of their term) and set the
+    /// committee threshold. Elects a new committee after a no-confidence motion.
+    UpdateCommittee {
+        removed: BTreeSet<Credential>,
+        added: BTreeMap<Credential, u64>,
+        threshold: Rational,
+    },
+    /// Replace the constitution.
+    NewConstitution(Anchor),
+    /// Poll with no effect on chain.
+    Info,
+}
+
+impl GovAction {
+    /// Ratification order: lower goes first.
+    fn priority(&self) -> u8 {
+        match self {
+            GovAction::NoConfidence => 0,
+            GovAction::UpdateCommittee { .. } => 1,
+            GovAction::NewConstitution(_) => 2,
+            GovAction::HardForkInitiation(_) => 3,
+            GovAction::ParameterChange(_) => 4,
+            GovAction::TreasuryWithdrawals(_) => 5,
+            GovAction::Info => 6,
+        }
+    }
+
+    /// Whether enacting the action delays all other actions to the next epoch boundary.
+    fn is_delaying(&self) -> bool {
+        self.priority() <= 3
+    }
+
+    /// Whether `voter`'s role votes on the action.
+    fn allows(&self, voter: &Voter) -> bool {
+        match (voter, self) {
+            (Voter::DRep(_), _) | (_, GovAction::Info) => true,
+            (Voter::Committee(_), action) => !matches!(
+                action,
+                GovAction::NoConfidence | GovAction::UpdateCommittee { .. }
+            ),
+            (Voter::StakePool(_), action) => match action {
+                GovAction::ParameterChange(update) => is_security_relevant(update),
+                GovAction::NoConfidence
+                | GovAction::UpdateCommittee { .. }
+                | GovAction::HardForkInitiation(_) => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Governance action proposed by a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposalProcedure {
+    /// Deposit paid, which must equal the `gov_action_deposit` parameter.
+    pub deposit: u64,
+    /// Reward account the deposit is returned to.
+    pub return_account: Credential,
+    pub action: GovAction,
+    /// Rationale of the proposal.
+    pub anchor: Anchor,
+}
+
+/// Who votes: a committee member or a DRep by credential, or a stake pool by pool id.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Voter {
+    Committee(Credential),
+    DRep(Credential),
+    StakePool(String),
+}
+
+/// Vote on a governance action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Vote {
+    No,
+    Yes,
+    Abstain,
+}
+
+/// Vote cast by a transaction. A later vote of the same voter on the same action replaces the
+/// earlier one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VotingProcedure {
+    pub voter: Voter,
+    pub action_id: GovActionId,
+    pub vote: Vote,
+}
+
+/// Open governance action with the votes cast on it so far.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GovActionState {
+    pub proposal: ProposalProcedure,
+    /// Last epoch the action can be voted on in.
+    pub expires_after: u64,
+    pub votes: BTreeMap<Voter, Vote>,
+}
+
+/// Governance state: open actions, the committee (`None` after a no-confidence motion, until a
+/// new one is elected) and the constitution.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Governance {
+    pub actions: BTreeMap<GovActionId, GovActionState>,
+    pub committee: Option<Committee>,
+    pub constitution: Anchor,
+}
+
+/// Whether a parameter update touches the security group of parameters, on which stake pools
+/// vote.
+fn is_security_relevant(update: &ParamUpdate) -> bool {
+    update.max_block_size.is_some()
+        || update.max_tx_size.is_some()
+        || update.min_fee_a.is_some()
+        || update.min_fee_b.is_some()
+        || update.min_utxo_value.is_some()
+        || update.gov_action_deposit.is_some()
+}
+
+/// DRep threshold of a parameter update: the highest threshold of the parameter groups it
+/// touches.
+fn parameter_change_threshold(update: &ParamUpdate, t: &DRepVotingThresholds) -> Rational {
+    let network = update.max_block_size.is_some()
+        || update.max_tx_size.is_some()
+        || update.max_collateral_inputs.is_some();
+    let economic = update.min_fee_a.is_some()
+        || update.min_fee_b.is_some()
+        || update.key_deposit.is_some()
+        || update.pool_deposit.is_some()
+        || update.min_pool_cost.is_some()
+        || update.min_utxo_value.is_some()
+        || update.ex_unit_prices.is_some()
+        || update.monetary_expansion.is_some()
+        || update.treasury_cut.is_some();
+    let technical = update.decentralisation.is_some()
+        || update.n_opt.is_some()
+        || update.pool_influence.is_some()
+        || update.cost_models.is_some()
+        || update.collateral_percentage.is_some()
+        || update.extra_entropy.is_some();
+    let governance = update.pool_voting_thresholds.is_some()
+        || update.drep_voting_thresholds.is_some()
+        || update.gov_action_lifetime.is_some()
+        || update.gov_action_deposit.is_some()
+        || update.drep_deposit.is_some();
+    [
+        (network, t.pp_network_group),
+        (economic, t.pp_economic_group),
+        (technical, t.pp_technical_group),
+        (governance, t.pp_gov_group),
+    ]
+    .into_iter()
+    .filter(|(touched, _)| *touched)
+    .map(|(_, threshold)| threshold)
+    .fold(Rational::new(0, 1), |a, b| {
+        let a_below_b = u128::from(a.numerator) * u128::from(b.denominator)
+            < u128::from(b.numerator) * u128::from(a.denominator);
+        if a_below_b { b } else { a }
+    })
+}
+
+/// Yes weight and non-abstaining weight of weighted votes; a missing vote counts as no.
+fn tally(votes: impl IntoIterator<Item = (Option<Vote>, u64)>) -> (u64, u64) {
+    votes
+        .into_iter()
+        .fold((0, 0), |(yes, total), (vote, weight)| match vote {
+            Some(Vote::Abstain) => (yes, total),
+            Some(Vote::Yes) => (yes + weight, total + weight),
+            _ => (yes, total + weight),
+        })
+}
+
+/// Whether `yes` out of `total` reaches `threshold`. With nothing counted, only a zero threshold
+/// is reached.
+fn reaches((yes, total): (u64, u64), threshold: Rational) -> bool {
+    if total == 0 {
+        return threshold.numerator == 0;
+    }
+    u128::from(yes) * u128::from(threshold.denominator)
+        >= u128::from(threshold.numerator) * u128::from(total)
+}
+
+impl LedgerState {
+    /// Stake of the mark snapshot whose vote is delegated to each registered DRep or predefined
+    /// option.
+    pub fn drep_stake(&self) -> HashMap<&DRep, u64> {
+        let mut stake = HashMap::new();
+        for (delegator, drep) in &self.vote_delegations {
+            if let DRep::Credential(credential) = drep
+                && !self.dreps.contains_key(credential)
+            {
+                continue;
+            }
+            let amount = self
+                .snapshots
+                .mark
+                .stake
+                .get(delegator)
+                .copied()
+                .unwrap_or(0);
+            *stake.entry(drep).or_insert(0) += amount;
+        }
+        stake
+    }
+
+    /// Check a proposal against the governance parameters and the current protocol version.
+    pub fn validate_proposal(&self, proposal: &ProposalProcedure) -> Result<(), LedgerError> {
+        let expected = self.params.gov_action_deposit;
+        if proposal.deposit != expected {
+            return Err(LedgerError::ProposalDepositIncorrect {
+                deposit: proposal.deposit,
+                expected,
+            });
+        }
+        let malformed = |reason: &str| Err(LedgerError::MalformedProposal(reason.to_string()));
+        match &proposal.action {
+            GovAction::ParameterChange(update) if update.is_empty() => {
+                malformed("parameter change changes nothing")
+            }
+            GovAction::ParameterChange(update) if update.protocol_version.is_some() => {
+                malformed("the protocol version changes through hard fork initiation")
+            }
+            GovAction::HardForkInitiation(version) => {
+                let current = self.params.protocol_version;
+                let next_major = version.major == current.major + 1 && version.minor == 0;
+                let next_minor =
+                    version.major == current.major && version.minor == current.minor + 1;
+                if next_major || next_minor {
+                    Ok(())
+                } else {
+                    malformed("hard fork does not follow the current protocol version")
+                }
+            }
+            GovAction::TreasuryWithdrawals(withdrawals)
+                if withdrawals.is_empty() || withdrawals.values().any(|&amount| amount == 0) =>
+            {
+                malformed("treasury withdrawals must pay a positive amount")
+            }
+            GovAction::UpdateCommittee { threshold, .. }
+                if threshold.denominator == 0 || threshold.numerator > threshold.denominator =>
+            {
+                malformed("committee threshold is not between 0 and 1")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the governance actions proposed by the transaction `tx_id` in `epoch`. They can be
+    /// voted on until `gov_action_lifetime` epochs later.
+    pub(super) fn propose(
+        &mut self,
+        tx: &Transaction,
+        tx_id: TxId,
+        epoch: u64,
+    ) -> Result<(), LedgerError> {
+        for (index, proposal) in tx.proposal_procedures.iter().enumerate() {
+            self.validate_proposal(proposal)?;
+            let id = GovActionId {
+                tx_id,
+                index: index as u32,
+            };
+            self.governance.actions.insert(
+                id,
+                GovActionState {
+                    proposal: proposal.clone(),
+                    expires_after: epoch + self.params.gov_action_lifetime,
+                    votes: BTreeMap::new(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Record the votes of a transaction. Voters must be a committee member, a registered DRep
+    /// or an active stake pool, voting on an open action their role has a say on.
+    pub(super) fn cast_votes(&mut self, tx: &Transaction) -> Result<(), LedgerError> {
+        for procedure in &tx.voting_procedures {
+            let known = match &procedure.voter {
+                Voter::Committee(member) => self
+                    .governance
+                    .committee
+                    .as_ref()
+                    .is_some_and(|c| c.members.contains_key(member)),
+                Voter::DRep(drep) => self.dreps.contains_key(drep),
+                Voter::StakePool(pool_id) => {
+                    self.stake_pools.get(pool_id).is_some_and(|p| p.active)
+                }
+            };
+            if !known {
+                return Err(LedgerError::UnknownVoter(procedure.voter.clone()));
+            }
+            let action = self
+                .governance
+                .actions
+                .get_mut(&procedure.action_id)
+                .ok_or(LedgerError::UnknownGovAction(procedure.action_id))?;
+            if !action.proposal.action.allows(&procedure.voter) {
+                return Err(LedgerError::VoterNotAllowed {
+                    voter: procedure.voter.clone(),
+                    action_id: procedure.action_id,
+                });
+            }
+            action.votes.insert(procedure.voter.clone(), procedure.vote);
+        }
+        Ok(())
+    }
+
+    /// Ratify and enact the open governance actions at the start of `epoch`, then remove the
+    /// expired ones, returning the deposits of removed actions.
+    pub fn ratify_governance(&mut self, epoch: u64) {
+        let mut ids: Vec<GovActionId> = self.governance.actions.keys().copied().collect();
+        ids.sort_by_key(|id| self.governance.actions[id].proposal.action.priority());
+        let mut delayed = false;
+        for id in ids {
+            let state = &self.governance.actions[&id];
+            let expired = state.expires_after < epoch;
+            let enacted = !delayed && self.is_ratified(state, epoch) && {
+                let action = state.proposal.action.clone();
+                self.enact(&action)
+            };
+            if !enacted && !expired {
+                continue;
+            }
+            if let Some(state) = self.governance.actions.remove(&id) {
+                delayed |= enacted && state.proposal.action.is_delaying();
+                let ProposalProcedure {
+                    deposit,
+                    return_account,
+                    ..
+                } = state.proposal;
+                self.deposited = self.deposited.saturating_sub(deposit);
+                self.credit_reward(return_account, deposit);
+            }
+        }
+    }
+
+    /// Whether the votes on an action reach the thresholds of every role voting on it.
+    fn is_ratified(&self, state: &GovActionState, epoch: u64) -> bool {
+        let drep = &self.params.drep_voting_thresholds;
+        let pool = &self.params.pool_voting_thresholds;
+        let has_committee = self.governance.committee.is_some();
+        let (committee, drep, pool) = match &state.proposal.action {
+            GovAction::Info => return false,
+            GovAction::NoConfidence => (
+                false,
+                Some(drep.motion_no_confidence),
+                Some(pool.motion_no_confidence),
+            ),
+            GovAction::UpdateCommittee { .. } if has_committee => (
+                false,
+                Some(drep.committee_normal),
+                Some(pool.committee_normal),
+            ),
+            GovAction::UpdateCommittee { .. } => (
+                false,
+                Some(drep.committee_no_confidence),
+                Some(pool.committee_no_confidence),
+            ),
+            GovAction::NewConstitution(_) => (true, Some(drep.update_to_constitution), None),
+            GovAction::HardForkInitiation(_) => (
+                true,
+                Some(drep.hard_fork_initiation),
+                Some(pool.hard_fork_initiation),
+            ),
+            GovAction::ParameterChange(update) => (
+                true,
+                Some(parameter_change_threshold(update, drep)),
+                is_security_relevant(update).then_some(pool.pp_security_group),
+            ),
+            GovAction::TreasuryWithdrawals(_) => (true, Some(drep.treasury_withdrawal), None),
+        };
+        (!committee || self.committee_accepts(state, epoch))
+            && drep.is_none_or(|threshold| reaches(self.drep_tally(state), threshold))
+            && pool.is_none_or(|threshold| reaches(self.pool_tally(state), threshold))
+    }
+
+    /// Whether the committee members in office in `epoch` approve the action.
+    fn committee_accepts(&self, state: &GovActionState, epoch: u64) -> bool {
+        let Some(committee) = &self.governance.committee else {
+            return false;
+        };
+        let votes = committee
+            .members
+            .iter()
+            .filter(|(_, last_epoch)| **last_epoch >= epoch)
+            .map(|(member, _)| {
+                let vote = state.votes.get(&Voter::Committee(*member)).copied();
+                (vote, 1)
+            });
+        reaches(tally(votes), committee.threshold)
+    }
+
+    /// DRep votes on the action, weighted by delegated stake.
+    fn drep_tally(&self, state: &GovActionState) -> (u64, u64) {
+        let no_confidence = state.proposal.action == GovAction::NoConfidence;
+        tally(self.drep_stake().into_iter().map(|(drep, stake)| {
+            let vote = match drep {
+                DRep::Credential(credential) => state.votes.get(&Voter::DRep(*credential)).copied(),
+                DRep::AlwaysAbstain => Some(Vote::Abstain),
+                DRep::AlwaysNoConfidence if no_confidence => Some(Vote::Yes),
+                DRep::AlwaysNoConfidence => Some(Vote::No),
+            };
+            (vote, stake)
+        }))
+    }
+
+    /// Votes of stake pools on the action, weighted by their stake in the mark snapshot.
+    fn pool_tally(&self, state: &GovActionState) -> (u64, u64) {
+        tally(
+            self.snapshots
+                .mark
+                .pool_stake
+                .iter()
+                .map(|(pool_id, &stake)| {
+                    let vote = state.votes.get(&Voter::StakePool(pool_id.clone()));
+                    (vote.copied(), stake)
+                }),
+        )
+    }
+
+    /// Enact a ratified action. A treasury withdrawal exceeding the treasury is not enacted, and
+    /// what it pays to unregistered accounts stays in the treasury.
+    fn enact(&mut self, action: &GovAction) -> bool {
+        match action {
+            GovAction::ParameterChange(update) => self.params = update.apply_to(&self.params),
+            GovAction::HardForkInitiation(version) => self.params.protocol_version = *version,
+            GovAction::TreasuryWithdrawals(withdrawals) => {
+                let total = withdrawals
+                    .values()
+                    .try_fold(0u64, |total, &amount| total.checked_add(amount));
+                match total {
+                    Some(total) if total <= self.treasury => self.treasury -= total,
+                    _ => return false,
+                }
+                for (account, &amount) in withdrawals {
+                    self.credit_reward(*account, amount);
+                }
+            }
+            GovAction::NoConfidence => self.governance.committee = None,
+            GovAction::UpdateCommittee {
+                removed,
+                added,
+                threshold,
+            } => {
+                let committee = self.governance.committee.get_or_insert_with(|| Committee {
+                    members: BTreeMap::new(),
+                    threshold: *threshold,
+                });
+                committee
+                    .members
+                    .retain(|member, _| !removed.contains(member));
+                committee.members.extend(added.clone());
+                committee.threshold = *threshold;
+            }
+            GovAction::NewConstitution(anchor) => self.governance.constitution = anchor.clone(),
+            GovAction::Info => return false,
+        }
+        true
+    }
+}
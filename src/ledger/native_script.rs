@@ -0,0 +1,105 @@
+//! Native scripts (Allegra era): multisig and timelock conditions.
+//!
+//! A native script is evaluated against the key hashes that signed a transaction and the
+//! transaction's validity interval `[validity_start, ttl)`. Timelocks only look at the interval,
+//! never at the current slot, so a script's result is the same in every slot the transaction is
+//! valid in. Outputs paid to an address with the script's [`NativeScript::credential`] can only be
+//! spent by a transaction that carries the script and satisfies it; a native script whose hash is
+//! a policy id can mint and burn that policy's tokens.
+
+use super::Credential;
+use super::cbor::Cbor;
+use blake2::digest::consts::U28;
+use blake2::{Blake2b, Digest};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Tag prefixed to native scripts when hashing them.
+const NATIVE_SCRIPT_TAG: u8 = 0;
+
+/// Multisig and timelock script.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NativeScript {
+    /// Satisfied if the key with this hash signed the transaction.
+    RequireSignature(String),
+    /// Satisfied if every script is.
+    AllOf(Vec<NativeScript>),
+    /// Satisfied if at least one script is.
+    AnyOf(Vec<NativeScript>),
+    /// Satisfied if at least `m` of the scripts are.
+    MOfN(u32, Vec<NativeScript>),
+    /// Satisfied if the transaction is not valid before this slot.
+    InvalidBefore(u64),
+    /// Satisfied if the transaction is not valid in this slot or after it.
+    InvalidHereafter(u64),
+}
+
+impl NativeScript {
+    /// Script hash as hex: Blake2b-224 of the native script tag followed by the CBOR-encoded
+    /// script.
+    pub fn hash(&self) -> String {
+        crate::chaindb::hex(self.credential().hash())
+    }
+
+    /// Script hash credential, which locks outputs and reward accounts with this script.
+    pub fn credential(&self) -> Credential {
+        let mut hasher = Blake2b::<U28>::new();
+        hasher.update([NATIVE_SCRIPT_TAG]);
+        hasher.update(self.to_cbor());
+        Credential::ScriptHash(hasher.finalize().into())
+    }
+
+    /// Evaluate the script for a transaction signed by `signers` and valid in
+    /// `[validity_start, ttl)`.
+    pub fn evaluate(
+        &self,
+        signers: &BTreeSet<String>,
+        validity_start: Option<u64>,
+        ttl: Option<u64>,
+    ) -> bool {
+        let eval = |script: &NativeScript| script.evaluate(signers, validity_start, ttl);
+        match self {
+            NativeScript::RequireSignature(key_hash) => signers.contains(key_hash),
+            NativeScript::AllOf(scripts) => scripts.iter().all(eval),
+            NativeScript::AnyOf(scripts) => scripts.iter().any(eval),
+            NativeScript::MOfN(m, scripts) => {
+                scripts.iter().filter(|s| eval(s)).count() >= *m as usize
+            }
+            NativeScript::InvalidBefore(slot) => validity_start.is_some_and(|start| *slot <= start),
+            NativeScript::InvalidHereafter(slot) => ttl.is_some_and(|ttl| ttl <= *slot),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multisig_and_timelocks() {
+        let sig = |key: &str| NativeScript::RequireSignature(key.to_string());
+        let two_of_three = NativeScript::MOfN(2, vec![sig("a"), sig("b"), sig("c")]);
+        let signers = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect();
+        assert!(two_of_three.evaluate(&signers(&["a", "c"]), None, None));
+        assert!(!two_of_three.evaluate(&signers(&["b"]), None, None));
+
+        // Spendable by "a" from slot 100 until slot 200.
+        let window = NativeScript::AllOf(vec![
+            sig("a"),
+            NativeScript::InvalidBefore(100),
+            NativeScript::InvalidHereafter(200),
+        ]);
+        let a = signers(&["a"]);
+        assert!(window.evaluate(&a, Some(100), Some(200)));
+        assert!(!window.evaluate(&a, Some(99), Some(200)));
+        assert!(!window.evaluate(&a, Some(100), Some(201)));
+        // An open interval never satisfies a timelock.
+        assert!(!window.evaluate(&a, None, Some(150)));
+        assert!(NativeScript::AnyOf(vec![sig("x"), window]).evaluate(&a, Some(150), Some(160)));
+        assert!(NativeScript::AllOf(vec![]).evaluate(&a, None, None));
+
+        let credential = two_of_three.credential();
+        assert_eq!(credential.script_hash(), Some(two_of_three.hash()));
+        assert_eq!(two_of_three.hash().len(), 56);
+    }
+}
@@ -0,0 +1,477 @@
+//! Protocol parameters and the update proposals that change them.
+//!
+//! The ledger keeps the parameters in effect in [`LedgerState::params`]. Transactions may carry an
+//! [`Update`] proposing new values for the current epoch; proposals are collected in
+//! [`LedgerState::proposals`] and, at the next epoch boundary, an update proposed identically by
+//! at least [`UPDATE_QUORUM`] proposers is adopted. Proposers are the genesis delegates in
+//! [`LedgerState::genesis_delegates`], by key hash, and must sign the transaction carrying their
+//! proposals. The security parameter `k`, the active slot coefficient `f` and the epoch length
+//! are fixed at genesis and cannot be changed by proposals.
+
+use super::plutus::CostModel;
+use super::plutus::costs::{PLUTUS_V1, PLUTUS_V2};
+use super::{LedgerError, LedgerState};
+use crate::consensus::{NEUTRAL_NONCE, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Number of proposers (genesis delegates) that must propose the same update in an epoch for it
+/// to be adopted, as in the Shelley mainnet genesis.
+pub const UPDATE_QUORUM: usize = 5;
+
+/// Non-negative rational number, encoded in CBOR as `#6.30([numerator, denominator])`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rational {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Rational {
+    pub const fn new(numerator: u64, denominator: u64) -> Self {
+        Rational {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Approximate value as a float.
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+}
+
+/// Protocol version; its major number identifies the era.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ProtocolVersion {
+    pub major: u64,
+    pub minor: u64,
+}
+
+impl ProtocolVersion {
+    /// First protocol version of an era, by era name.
+    pub fn of_era(era: &str) -> Self {
+        let major = match era {
+            "Byron" => 1,
+            "Shelley" => 2,
+            "Allegra" => 3,
+            "Mary" => 4,
+            "Alonzo" => 5,
+            "Babbage" => 7,
+            _ => 9, // Conway
+        };
+        ProtocolVersion { major, minor: 0 }
+    }
+}
+
+/// Prices of Plutus execution units, in lovelace per unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExUnitPrices {
+    pub mem: Rational,
+    pub steps: Rational,
+}
+
+/// Stake pool voting thresholds on governance actions (Conway).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolVotingThresholds {
+    pub motion_no_confidence: Rational,
+    pub committee_normal: Rational,
+    pub committee_no_confidence: Rational,
+    pub hard_fork_initiation: Rational,
+    /// Parameter changes touching the security group of parameters.
+    pub pp_security_group: Rational,
+}
+
+impl Default for PoolVotingThresholds {
+    fn default() -> Self {
+        let half = Rational::new(51, 100);
+        Self {
+            motion_no_confidence: half,
+            committee_normal: half,
+            committee_no_confidence: half,
+            hard_fork_initiation: half,
+            pp_security_group: half,
+        }
+    }
+}
+
+/// DRep voting thresholds on governance actions (Conway). A parameter change must reach the
+/// highest threshold among the parameter groups it touches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DRepVotingThresholds {
+    pub motion_no_confidence: Rational,
+    pub committee_normal: Rational,
+    pub committee_no_confidence: Rational,
+    pub update_to_constitution: Rational,
+    pub hard_fork_initiation: Rational,
+    pub pp_network_group: Rational,
+    pub pp_economic_group: Rational,
+    pub pp_technical_group: Rational,
+    pub pp_gov_group: Rational,
+    pub treasury_withdrawal: Rational,
+}
+
+impl Default for DRepVotingThresholds {
+    fn default() -> Self {
+        let two_thirds = Rational::new(67, 100);
+        Self {
+            motion_no_confidence: two_thirds,
+            committee_normal: two_thirds,
+            committee_no_confidence: Rational::new(60, 100),
+            update_to_constitution: Rational::new(75, 100),
+            hard_fork_initiation: Rational::new(60, 100),
+            pp_network_group: two_thirds,
+            pp_economic_group: two_thirds,
+            pp_technical_group: two_thirds,
+            pp_gov_group: Rational::new(75, 100),
+            treasury_withdrawal: two_thirds,
+        }
+    }
+}
+
+/// Protocol parameters in effect in an epoch. Defaults are the mainnet values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolParameters {
+    /// Fee per byte of the CBOR-encoded transaction, in lovelace.
+    pub min_fee_a: u64,
+    /// Constant part of the minimum fee, in lovelace.
+    pub min_fee_b: u64,
+    /// Maximum size in bytes of a block body (its encoded transactions).
+    pub max_block_size: usize,
+    /// Maximum size in bytes of a CBOR-encoded transaction.
+    pub max_tx_size: usize,
+    /// Deposit paid when a stake key is registered.
+    pub key_deposit: u64,
+    /// Deposit paid when a stake pool is registered for the first time.
+    pub pool_deposit: u64,
+    /// Lowest fixed cost per epoch a stake pool may declare, in lovelace.
+    pub min_pool_cost: u64,
+    /// Minimum lovelace every output must hold.
+    pub min_utxo_value: u64,
+    /// Security parameter `k`: the maximum rollback, in blocks. Fixed at genesis.
+    pub security_param: u64,
+    /// Active slot coefficient `f`: the fraction of slots expected to have a leader. Fixed at
+    /// genesis.
+    pub active_slot_coeff: Rational,
+    /// Number of slots in an epoch. Fixed at genesis.
+    pub epoch_length: u64,
+    /// Number of slots in a KES period. Fixed at genesis.
+    pub slots_per_kes_period: u64,
+    /// Decentralisation parameter `d`: the fraction of slots reserved for genesis delegates.
+    pub decentralisation: Rational,
+    /// Extra entropy mixed into the next epoch nonce; the neutral nonce adds none.
+    pub extra_entropy: Nonce,
+    /// Desired number of stake pools `n_opt` (`k` in the rewards formula); a pool saturates at
+    /// `1 / n_opt` of the stake.
+    pub n_opt: u64,
+    /// Pledge influence `a0`.
+    pub pool_influence: Rational,
+    /// Monetary expansion `ρ`: the fraction of the reserves released each epoch.
+    pub monetary_expansion: Rational,
+    /// Treasury cut `τ`: the fraction of the reward pot going to the treasury.
+    pub treasury_cut: Rational,
+    /// Protocol version; a new major version marks a hard fork.
+    pub protocol_version: ProtocolVersion,
+    /// Plutus cost models, keyed by language (0 = PlutusV1, 1 = PlutusV2, 2 = PlutusV3).
+    pub cost_models: BTreeMap<u8, Vec<i64>>,
+    /// Prices of Plutus execution units.
+    pub ex_unit_prices: ExUnitPrices,
+    /// Collateral a transaction running Plutus scripts must provide, as a percentage of its fee.
+    pub collateral_percentage: u64,
+    /// Maximum number of collateral inputs of a transaction.
+    pub max_collateral_inputs: usize,
+    /// Stake pool voting thresholds on governance actions.
+    pub pool_voting_thresholds: PoolVotingThresholds,
+    /// DRep voting thresholds on governance actions.
+    pub drep_voting_thresholds: DRepVotingThresholds,
+    /// Number of epochs a governance action stays open for votes after the one it is proposed in.
+    pub gov_action_lifetime: u64,
+    /// Deposit paid with each governance action proposal, returned when it is removed.
+    pub gov_action_deposit: u64,
+    /// Deposit paid when a DRep registers, returned when it retires.
+    pub drep_deposit: u64,
+}
+
+impl Default for ProtocolParameters {
+    fn default() -> Self {
+        Self {
+            min_fee_a: 44,
+            min_fee_b: 155_381,
+            max_block_size: 65_536,
+            max_tx_size: 16_384,
+            key_deposit: 2_000_000,
+            pool_deposit: 500_000_000,
+            min_pool_cost: 340_000_000,
+            min_utxo_value: 1_000_000,
+            security_param: 2_160,
+            active_slot_coeff: Rational::new(1, 20),
+            epoch_length: 432_000,
+            slots_per_kes_period: 129_600,
+            decentralisation: Rational::new(0, 1),
+            extra_entropy: NEUTRAL_NONCE,
+            n_opt: 500,
+            pool_influence: Rational::new(3, 10),
+            monetary_expansion: Rational::new(3, 1_000),
+            treasury_cut: Rational::new(1, 5),
+            protocol_version: ProtocolVersion::of_era("Shelley"),
+            cost_models: [PLUTUS_V1, PLUTUS_V2]
+                .into_iter()
+                .map(|language| (language, CostModel::default_params(language)))
+                .collect(),
+            ex_unit_prices: ExUnitPrices {
+                mem: Rational::new(577, 10_000),
+                steps: Rational::new(721, 10_000_000),
+            },
+            collateral_percentage: 150,
+            max_collateral_inputs: 3,
+            pool_voting_thresholds: PoolVotingThresholds::default(),
+            drep_voting_thresholds: DRepVotingThresholds::default(),
+            gov_action_lifetime: 6,
+            gov_action_deposit: 100_000_000_000,
+            drep_deposit: 500_000_000,
+        }
+    }
+}
+
+impl ProtocolParameters {
+    /// Parameters an era starts with, by era name. Byron has larger blocks, a smaller size
+    /// limit, no minimum UTxO value, no staking and a fully federated chain; later eras use the
+    /// mainnet values.
+    pub fn for_era(era: &str) -> Self {
+        let protocol_version = ProtocolVersion::of_era(era);
+        match era {
+            "Byron" => Self {
+                max_block_size: 2_000_000,
+                max_tx_size: 4_096,
+                key_deposit: 0,
+                pool_deposit: 0,
+                min_pool_cost: 0,
+                min_utxo_value: 0,
+                decentralisation: Rational::new(1, 1),
+                protocol_version,
+                ..Self::default()
+            },
+            _ => Self {
+                protocol_version,
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Minimum fee of a transaction of `size` bytes.
+    pub fn min_fee(&self, size: usize) -> u64 {
+        self.min_fee_a
+            .saturating_mul(size as u64)
+            .saturating_add(self.min_fee_b)
+    }
+
+    /// Number of slots at the end of an epoch whose blocks no longer contribute to the next epoch
+    /// nonce: `4k / f`, rounded up.
+    pub fn randomness_stabilisation_window(&self) -> u64 {
+        let f = self.active_slot_coeff;
+        let window = (4 * u128::from(self.security_param) * u128::from(f.denominator))
+            .div_ceil(u128::from(f.numerator.max(1)));
+        u64::try_from(window).unwrap_or(u64::MAX)
+    }
+
+    /// KES period of `slot`. A KES period is at least one slot long.
+    pub fn kes_period_of(&self, slot: u64) -> u64 {
+        slot / self.slots_per_kes_period.max(1)
+    }
+
+    /// Epoch of `slot`. An epoch is at least one slot long.
+    pub fn epoch_of(&self, slot: u64) -> u64 {
+        slot / self.epoch_length.max(1)
+    }
+
+    /// Price of Plutus execution units `(mem, steps)`, rounded up to the lovelace.
+    pub fn script_fee(&self, (mem, steps): (u64, u64)) -> u64 {
+        let price = |units: u64, price: Rational| {
+            let lovelace = (u128::from(units) * u128::from(price.numerator))
+                .div_ceil(u128::from(price.denominator.max(1)));
+            u64::try_from(lovelace).unwrap_or(u64::MAX)
+        };
+        price(mem, self.ex_unit_prices.mem).saturating_add(price(steps, self.ex_unit_prices.steps))
+    }
+}
+
+/// New values for some protocol parameters. `k`, `f` and the epoch length are not updatable.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamUpdate {
+    pub min_fee_a: Option<u64>,
+    pub min_fee_b: Option<u64>,
+    pub max_block_size: Option<usize>,
+    pub max_tx_size: Option<usize>,
+    pub key_deposit: Option<u64>,
+    pub pool_deposit: Option<u64>,
+    pub min_pool_cost: Option<u64>,
+    pub min_utxo_value: Option<u64>,
+    pub decentralisation: Option<Rational>,
+    pub extra_entropy: Option<Nonce>,
+    pub n_opt: Option<u64>,
+    pub pool_influence: Option<Rational>,
+    pub monetary_expansion: Option<Rational>,
+    pub treasury_cut: Option<Rational>,
+    pub protocol_version: Option<ProtocolVersion>,
+    pub cost_models: Option<BTreeMap<u8, Vec<i64>>>,
+    pub ex_unit_prices: Option<ExUnitPrices>,
+    pub collateral_percentage: Option<u64>,
+    pub max_collateral_inputs: Option<usize>,
+    pub pool_voting_thresholds: Option<PoolVotingThresholds>,
+    pub drep_voting_thresholds: Option<DRepVotingThresholds>,
+    pub gov_action_lifetime: Option<u64>,
+    pub gov_action_deposit: Option<u64>,
+    pub drep_deposit: Option<u64>,
+}
+
+impl ParamUpdate {
+    /// Whether the update changes no parameter.
+    pub fn is_empty(&self) -> bool {
+        *self == ParamUpdate::default()
+    }
+
+    /// Parameters `params` with this update applied.
+    pub fn apply_to(&self, params: &ProtocolParameters) -> ProtocolParameters {
+        let mut next = params.clone();
+        macro_rules! set {
+            ($($field:ident),*) => {
+                $(if let Some(value) = &self.$field {
+                    next.$field = value.clone();
+                })*
+            };
+        }
+        set!(
+            min_fee_a,
+            min_fee_b,
+            max_block_size,
+            max_tx_size,
+            key_deposit,
+            pool_deposit,
+            min_pool_cost,
+            min_utxo_value,
+            decentralisation,
+            extra_entropy,
+            n_opt,
+            pool_influence,
+            monetary_expansion,
+            treasury_cut,
+            protocol_version,
+            cost_models,
+            ex_unit_prices,
+            collateral_percentage,
+            max_collateral_inputs,
+            pool_voting_thresholds,
+            drep_voting_thresholds,
+            gov_action_lifetime,
+            gov_action_deposit,
+            drep_deposit
+        );
+        next
+    }
+}
+
+/// Update proposal carried by a transaction: parameter updates by proposer, for an epoch.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Update {
+    pub proposals: BTreeMap<String, ParamUpdate>,
+    pub epoch: u64,
+}
+
+impl LedgerState {
+    /// Check an update proposal submitted in `epoch`: it must be for that epoch, come from at
+    /// least one proposer, only from genesis delegates, and change at least one parameter per
+    /// proposer.
+    pub fn validate_update(&self, update: &Update, epoch: u64) -> Result<(), LedgerError> {
+        if update.epoch != epoch {
+            return Err(LedgerError::InvalidUpdate(format!(
+                "proposal for epoch {} submitted in epoch {}",
+                update.epoch, epoch
+            )));
+        }
+        if update.proposals.is_empty() {
+            return Err(LedgerError::InvalidUpdate("no proposals".to_string()));
+        }
+        if let Some(proposer) = update
+            .proposals
+            .keys()
+            .find(|proposer| !self.genesis_delegates.contains(*proposer))
+        {
+            return Err(LedgerError::InvalidUpdate(format!(
+                "{} is not a genesis delegate",
+                proposer
+            )));
+        }
+        if let Some((proposer, _)) = update.proposals.iter().find(|(_, p)| p.is_empty()) {
+            return Err(LedgerError::InvalidUpdate(format!(
+                "empty proposal from {}",
+                proposer
+            )));
+        }
+        Ok(())
+    }
+
+    /// Record the proposals of an update; a later proposal replaces an earlier one from the same
+    /// proposer.
+    pub fn register_update(&mut self, update: &Update) {
+        for (proposer, proposal) in &update.proposals {
+            self.proposals.insert(proposer.clone(), proposal.clone());
+        }
+    }
+
+    /// Cross the boundary into `epoch`: pay the rewards computed at the previous boundary, rotate
+    /// the stake snapshots (see [`super::stake`]), retire the pools due to retire and refund their
+    /// deposits, compute the rewards of the epoch that just ended (see [`super::rewards`]),
+    /// ratify and enact governance actions (see
+    /// [`super::governance`]), then adopt the update proposed identically by at least
+    /// [`UPDATE_QUORUM`] proposers, if there is one, and discard the epoch's proposals.
+    pub fn new_epoch(&mut self, epoch: u64) {
+        self.pay_rewards();
+        self.rotate_snapshots();
+        self.process_pool_retirements(epoch);
+        self.schedule_rewards();
+        self.ratify_governance(epoch);
+        let proposals = std::mem::take(&mut self.proposals);
+        let adopted = proposals.values().find(|candidate| {
+            proposals.values().filter(|p| p == candidate).count() >= UPDATE_QUORUM
+        });
+        if let Some(update) = adopted {
+            self.params = update.apply_to(&self.params);
+        }
+    }
+
+    /// Cross every boundary from epoch `from` into epoch `to`, including those of epochs without
+    /// blocks: tick the nonce with the extra entropy in effect at the end of each epoch, then
+    /// cross into the next one (see [`Self::new_epoch`]).
+    pub fn cross_epochs(&mut self, from: u64, to: u64) {
+        for epoch in from + 1..=to {
+            self.nonces.tick(&self.params.extra_entropy);
+            self.new_epoch(epoch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_apply_only_the_fields_they_set() {
+        let update = ParamUpdate {
+            min_fee_a: Some(50),
+            cost_models: Some(BTreeMap::from([(0, vec![1, 2, 3])])),
+            ..Default::default()
+        };
+        let params = update.apply_to(&ProtocolParameters::default());
+        assert_eq!(params.min_fee_a, 50);
+        assert_eq!(params.min_fee_b, 155_381);
+        assert_eq!(params.cost_models[&0], vec![1, 2, 3]);
+        assert!(ParamUpdate::default().is_empty());
+
+        let byron = ProtocolParameters::for_era("Byron");
+        assert_eq!(
+            byron.protocol_version,
+            ProtocolVersion { major: 1, minor: 0 }
+        );
+        assert!(byron.protocol_version < ProtocolParameters::for_era("Alonzo").protocol_version);
+        assert_eq!(byron.decentralisation.to_f64(), 1.0);
+    }
+}
@@ -0,0 +1,318 @@
+//! Untyped Plutus Core: terms, flat decoding and a CEK machine for phase-2 validation.
+//!
+//! A [`PlutusScript`](super::PlutusScript) holds a flat-encoded UPLC program, optionally wrapped
+//! in one or more CBOR byte strings as scripts appear on chain. To validate a transaction, each
+//! Plutus script purpose (a spent input locked by the script, a policy it mints under, a
+//! certificate it authorises or a reward account it locks) is paired with a
+//! [`PlutusWitness`](super::PlutusWitness), whose script may instead come from a reference
+//! script of an output the transaction spends or references; the script is applied to
+//! the datum of the spent output (when spending), the witness's redeemer and the
+//! [script context](context) built from the transaction, and run on the [CEK machine](machine)
+//! within the witness's execution units, priced by the PlutusV1 cost model of the protocol
+//! parameters. The script succeeds if evaluation ends without an error and within budget.
+
+pub mod builtins;
+pub mod context;
+pub mod costs;
+pub mod flat;
+pub mod machine;
+
+pub use builtins::Builtin;
+pub use context::ScriptPurpose;
+pub use costs::CostModel;
+pub use machine::Budget;
+
+use super::cbor::Cbor;
+use super::{
+    Credential, DatumOption, LedgerError, LedgerState, NativeScript, PlutusDatum, PlutusScript,
+    PlutusWitness, ScriptRef, Transaction, TxInput,
+};
+use num_bigint::BigInt;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+use thiserror::Error;
+
+/// Plutus data: the values datums, redeemers and script contexts are made of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlutusData {
+    Constr(u64, Vec<PlutusData>),
+    Map(Vec<(PlutusData, PlutusData)>),
+    List(Vec<PlutusData>),
+    Integer(BigInt),
+    Bytes(Vec<u8>),
+}
+
+impl PlutusData {
+    /// Data held in datum or redeemer bytes: their CBOR decoding, or the bytes themselves if
+    /// they are not CBOR-encoded data.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        PlutusData::from_cbor(bytes).unwrap_or_else(|_| PlutusData::Bytes(bytes.to_vec()))
+    }
+}
+
+/// Type of a UPLC constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Integer,
+    ByteString,
+    String,
+    Unit,
+    Bool,
+    List(Box<Type>),
+    Pair(Box<Type>, Box<Type>),
+    Data,
+}
+
+/// UPLC constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constant {
+    Integer(BigInt),
+    ByteString(Vec<u8>),
+    String(String),
+    Unit,
+    Bool(bool),
+    /// List with the type of its elements.
+    List(Type, Vec<Constant>),
+    Pair(Box<Constant>, Box<Constant>),
+    Data(PlutusData),
+}
+
+impl Constant {
+    /// Type of the constant.
+    pub fn type_of(&self) -> Type {
+        match self {
+            Constant::Integer(_) => Type::Integer,
+            Constant::ByteString(_) => Type::ByteString,
+            Constant::String(_) => Type::String,
+            Constant::Unit => Type::Unit,
+            Constant::Bool(_) => Type::Bool,
+            Constant::List(elem, _) => Type::List(Box::new(elem.clone())),
+            Constant::Pair(a, b) => Type::Pair(Box::new(a.type_of()), Box::new(b.type_of())),
+            Constant::Data(_) => Type::Data,
+        }
+    }
+}
+
+/// UPLC term, with variables as 1-based de Bruijn indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Var(usize),
+    Delay(Box<Term>),
+    Lambda(Box<Term>),
+    Apply(Box<Term>, Box<Term>),
+    Constant(Rc<Constant>),
+    Force(Box<Term>),
+    Error,
+    Builtin(Builtin),
+    Constr(u64, Vec<Term>),
+    Case(Box<Term>, Vec<Term>),
+}
+
+impl Term {
+    /// Application of `self` to `arg`.
+    pub fn apply(self, arg: Term) -> Term {
+        Term::Apply(Box::new(self), Box::new(arg))
+    }
+
+    /// Constant term holding `data`.
+    pub fn data(data: PlutusData) -> Term {
+        Term::Constant(Rc::new(Constant::Data(data)))
+    }
+}
+
+/// UPLC program: a language version and a closed term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub version: (u64, u64, u64),
+    pub term: Term,
+}
+
+/// Reasons a Plutus script fails.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PlutusError {
+    /// The script bytes are not a flat-encoded program.
+    #[error("Malformed script: {0}")]
+    Decode(String),
+
+    /// The script evaluated the `error` term.
+    #[error("Script raised an error")]
+    ExplicitError,
+
+    /// Evaluation went wrong: an unbound variable, a type error, a failing builtin, ...
+    #[error("Evaluation failed: {0}")]
+    EvaluationFailure(String),
+
+    /// The script needed more execution units than its witness provides.
+    #[error("Budget of {} memory units and {} steps exceeded", .budget.mem, .budget.steps)]
+    BudgetExceeded { budget: Budget },
+}
+
+/// Outcome of a successful evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    /// Execution units used.
+    pub consumed: Budget,
+    /// Messages emitted by `trace`.
+    pub logs: Vec<String>,
+}
+
+/// Decode a script, apply it to `args` and run it within `budget`, priced by `costs`.
+pub fn evaluate_script(
+    code: &[u8],
+    args: Vec<PlutusData>,
+    costs: &CostModel,
+    budget: Budget,
+) -> Result<Evaluation, PlutusError> {
+    let program = flat::decode_script(code)?;
+    let term = args
+        .into_iter()
+        .fold(program.term, |term, arg| term.apply(Term::data(arg)));
+    machine::Machine::new(costs, budget).run(&term)
+}
+
+impl LedgerState {
+    /// Phase-2 validation: run the Plutus scripts of a transaction whose UTXO rules hold.
+    ///
+    /// The purposes are the inputs locked by a Plutus script, in input order, then the policies
+    /// minted or burned under one, in policy id order, then the certificates authorised by one, in
+    /// certificate order, then the reward accounts withdrawn from that one locks, in account
+    /// order. A Conway certificate cannot be authorised by a Plutus script. Each purpose takes the first unused witness
+    /// for its script, or with an empty script if the script is a reference script of an output
+    /// the transaction spends or references, and every witness must be taken. A spending script
+    /// is applied to the datum of the spent output (inline, or the witness's datum, which must
+    /// match the output's datum hash if it has one), the witness's redeemer and the script
+    /// context; any other script to the redeemer and the context. Scripts are PlutusV1, priced by
+    /// the PlutusV1 cost model of the protocol parameters, which must have one if any script runs.
+    pub fn validate_plutus_scripts(&self, tx: &Transaction) -> Result<(), LedgerError> {
+        let reference_scripts = self.reference_scripts(tx);
+        let native: BTreeSet<String> = tx
+            .native_scripts
+            .iter()
+            .map(NativeScript::hash)
+            .chain(reference_scripts.iter().filter_map(|s| match s {
+                ScriptRef::Native(script) => Some(script.hash()),
+                ScriptRef::Plutus(_) => None,
+            }))
+            .collect();
+        let referenced: BTreeMap<String, &PlutusScript> = reference_scripts
+            .iter()
+            .filter_map(|s| match s {
+                ScriptRef::Plutus(script) => Some((script.hash(), script)),
+                ScriptRef::Native(_) => None,
+            })
+            .collect();
+        let mut purposes = Vec::new();
+        for input in &tx.inputs {
+            let locked = self
+                .utxos
+                .get(&(input.prev_tx, input.index))
+                .and_then(|output| output.address.payment_credential())
+                .and_then(|credential| credential.script_hash())
+                .filter(|hash| !native.contains(hash));
+            if let Some(script_hash) = locked {
+                purposes.push((script_hash, ScriptPurpose::Spending(input.clone())));
+            }
+        }
+        for policy_id in tx.mint.keys().filter(|p| !native.contains(*p)) {
+            purposes.push((policy_id.clone(), ScriptPurpose::Minting(policy_id.clone())));
+        }
+        for cert in &tx.certificates {
+            let locked = cert
+                .authorizing_credential()
+                .and_then(Credential::script_hash)
+                .filter(|hash| !native.contains(hash));
+            if let Some(script_hash) = locked {
+                if context::dcert(cert).is_none() {
+                    return Err(LedgerError::UnsupportedCertificate { script_hash });
+                }
+                purposes.push((script_hash, ScriptPurpose::Certifying(cert.clone())));
+            }
+        }
+        for account in tx.withdrawals.keys() {
+            let locked = account.script_hash().filter(|hash| !native.contains(hash));
+            if let Some(script_hash) = locked {
+                purposes.push((script_hash, ScriptPurpose::Rewarding(*account)));
+            }
+        }
+
+        let witnesses = &tx.plutus_witnesses;
+        let hashes: Vec<String> = witnesses.iter().map(|w| w.script.hash()).collect();
+        let mut used = vec![false; hashes.len()];
+        let mut paired = Vec::with_capacity(purposes.len());
+        for (script_hash, purpose) in purposes {
+            let by_reference = referenced.get(&script_hash);
+            let index = (0..hashes.len())
+                .find(|&i| {
+                    !used[i]
+                        && (hashes[i] == script_hash
+                            || (by_reference.is_some() && witnesses[i].script.code.is_empty()))
+                })
+                .ok_or_else(|| LedgerError::MissingRedeemer {
+                    script_hash: script_hash.clone(),
+                })?;
+            used[index] = true;
+            let witness = &witnesses[index];
+            let script = match by_reference {
+                Some(script) if witness.script.code.is_empty() => *script,
+                _ => &witness.script,
+            };
+            paired.push((script_hash, purpose, witness, script));
+        }
+        if let Some(index) = used.iter().position(|used| !used) {
+            return Err(LedgerError::ExtraneousRedeemer {
+                script_hash: hashes[index].clone(),
+            });
+        }
+
+        if paired.is_empty() {
+            return Ok(());
+        }
+        let costs = self
+            .params
+            .cost_models
+            .get(&costs::PLUTUS_V1)
+            .and_then(|params| CostModel::from_params(costs::PLUTUS_V1, params))
+            .ok_or(LedgerError::MissingCostModel(costs::PLUTUS_V1))?;
+
+        let spending: Vec<&PlutusWitness> = paired
+            .iter()
+            .filter(|(_, purpose, _, _)| matches!(purpose, ScriptPurpose::Spending(_)))
+            .map(|(_, _, witness, _)| *witness)
+            .collect();
+        let tx_info = context::tx_info(self, tx, &spending);
+        for (script_hash, purpose, witness, script) in paired {
+            let context = context::script_context(&tx_info, &purpose);
+            let redeemer = PlutusData::from_bytes(&witness.redeemer.data);
+            let args = match &purpose {
+                ScriptPurpose::Spending(input) => {
+                    let datum = self.spending_datum(input, witness)?;
+                    vec![PlutusData::from_bytes(&datum.data), redeemer, context]
+                }
+                ScriptPurpose::Minting(_)
+                | ScriptPurpose::Rewarding(_)
+                | ScriptPurpose::Certifying(_) => vec![redeemer, context],
+            };
+            let budget = Budget::from(witness.execution_units);
+            evaluate_script(&script.code, args, &costs, budget)
+                .map_err(|error| LedgerError::PlutusScriptFailed { script_hash, error })?;
+        }
+        Ok(())
+    }
+
+    /// Datum a spending script runs with: the spent output's inline datum, or else the witness's
+    /// datum, which must hash to the output's datum hash if it has one.
+    fn spending_datum<'a>(
+        &'a self,
+        input: &TxInput,
+        witness: &'a PlutusWitness,
+    ) -> Result<&'a PlutusDatum, LedgerError> {
+        let output = self.utxos.get(&(input.prev_tx, input.index));
+        match output.and_then(|output| output.datum.as_ref()) {
+            Some(DatumOption::Inline(datum)) => Ok(datum),
+            Some(DatumOption::Hash(hash)) if witness.datum.hash() != *hash => {
+                Err(LedgerError::DatumHashMismatch(input.clone()))
+            }
+            _ => Ok(&witness.datum),
+        }
+    }
+}
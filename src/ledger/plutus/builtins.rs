@@ -0,0 +1,508 @@
+//! Built-in functions of Untyped Plutus Core.
+//!
+//! Builtins are identified by their flat tag. Each one takes a fixed number of forces (its type
+//! arguments) and then a fixed number of value arguments; the machine calls [`Builtin::call`]
+//! once all of them have been supplied.
+
+use super::machine::Value;
+use super::{Constant, PlutusData, PlutusError, Type};
+use crate::ledger::cbor::Cbor;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use num_bigint::{BigInt, Sign};
+
+macro_rules! builtins {
+    ($($name:ident = $tag:literal, $forces:literal, $arity:literal;)*) => {
+        /// Built-in function, with its flat tag as discriminant.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Builtin {
+            $($name = $tag,)*
+        }
+
+        impl Builtin {
+            /// Builtin with flat tag `tag`, if it is supported.
+            pub fn from_tag(tag: u8) -> Option<Builtin> {
+                match tag {
+                    $($tag => Some(Builtin::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// Number of times the builtin must be forced before it is applied.
+            pub fn forces(self) -> usize {
+                match self {
+                    $(Builtin::$name => $forces,)*
+                }
+            }
+
+            /// Number of arguments the builtin takes.
+            pub fn arity(self) -> usize {
+                match self {
+                    $(Builtin::$name => $arity,)*
+                }
+            }
+        }
+    };
+}
+
+builtins! {
+    AddInteger = 0, 0, 2;
+    SubtractInteger = 1, 0, 2;
+    MultiplyInteger = 2, 0, 2;
+    DivideInteger = 3, 0, 2;
+    QuotientInteger = 4, 0, 2;
+    RemainderInteger = 5, 0, 2;
+    ModInteger = 6, 0, 2;
+    EqualsInteger = 7, 0, 2;
+    LessThanInteger = 8, 0, 2;
+    LessThanEqualsInteger = 9, 0, 2;
+    AppendByteString = 10, 0, 2;
+    ConsByteString = 11, 0, 2;
+    SliceByteString = 12, 0, 3;
+    LengthOfByteString = 13, 0, 1;
+    IndexByteString = 14, 0, 2;
+    EqualsByteString = 15, 0, 2;
+    LessThanByteString = 16, 0, 2;
+    LessThanEqualsByteString = 17, 0, 2;
+    Sha2_256 = 18, 0, 1;
+    Sha3_256 = 19, 0, 1;
+    Blake2b256 = 20, 0, 1;
+    VerifyEd25519Signature = 21, 0, 3;
+    AppendString = 22, 0, 2;
+    EqualsString = 23, 0, 2;
+    EncodeUtf8 = 24, 0, 1;
+    DecodeUtf8 = 25, 0, 1;
+    IfThenElse = 26, 1, 3;
+    ChooseUnit = 27, 1, 2;
+    Trace = 28, 1, 2;
+    FstPair = 29, 2, 1;
+    SndPair = 30, 2, 1;
+    ChooseList = 31, 2, 3;
+    MkCons = 32, 1, 2;
+    HeadList = 33, 1, 1;
+    TailList = 34, 1, 1;
+    NullList = 35, 1, 1;
+    ChooseData = 36, 1, 6;
+    ConstrData = 37, 0, 2;
+    MapData = 38, 0, 1;
+    ListData = 39, 0, 1;
+    IData = 40, 0, 1;
+    BData = 41, 0, 1;
+    UnConstrData = 42, 0, 1;
+    UnMapData = 43, 0, 1;
+    UnListData = 44, 0, 1;
+    UnIData = 45, 0, 1;
+    UnBData = 46, 0, 1;
+    EqualsData = 47, 0, 2;
+    MkPairData = 48, 0, 2;
+    MkNilData = 49, 0, 1;
+    MkNilPairData = 50, 0, 1;
+    SerialiseData = 51, 0, 1;
+}
+
+impl Builtin {
+    /// Flat tag of the builtin.
+    pub fn tag(self) -> u8 {
+        self as u8
+    }
+
+    /// Apply the builtin to its arguments; messages passed to `trace` are appended to `logs`.
+    pub fn call<'a>(
+        self,
+        mut args: Vec<Value<'a>>,
+        logs: &mut Vec<String>,
+    ) -> Result<Value<'a>, PlutusError> {
+        use Builtin::*;
+        let result = match self {
+            AddInteger => Constant::Integer(arith(&args, |a, b| Some(a + b))?),
+            SubtractInteger => Constant::Integer(arith(&args, |a, b| Some(a - b))?),
+            MultiplyInteger => Constant::Integer(arith(&args, |a, b| Some(a * b))?),
+            DivideInteger => Constant::Integer(arith(&args, |a, b| {
+                let (q, r) = quot_rem(a, b)?;
+                Some(if r.sign() != Sign::NoSign && r.sign() != b.sign() {
+                    q - 1
+                } else {
+                    q
+                })
+            })?),
+            QuotientInteger => Constant::Integer(arith(&args, |a, b| Some(quot_rem(a, b)?.0))?),
+            RemainderInteger => Constant::Integer(arith(&args, |a, b| Some(quot_rem(a, b)?.1))?),
+            ModInteger => Constant::Integer(arith(&args, |a, b| {
+                let (_, r) = quot_rem(a, b)?;
+                Some(if r.sign() != Sign::NoSign && r.sign() != b.sign() {
+                    r + b
+                } else {
+                    r
+                })
+            })?),
+            EqualsInteger => Constant::Bool(integer(&args[0])? == integer(&args[1])?),
+            LessThanInteger => Constant::Bool(integer(&args[0])? < integer(&args[1])?),
+            LessThanEqualsInteger => Constant::Bool(integer(&args[0])? <= integer(&args[1])?),
+            AppendByteString => Constant::ByteString([bytes(&args[0])?, bytes(&args[1])?].concat()),
+            ConsByteString => {
+                // The lowest byte of the two's complement is the integer modulo 256.
+                let byte = integer(&args[0])?.to_signed_bytes_le()[0];
+                Constant::ByteString([&[byte], bytes(&args[1])?].concat())
+            }
+            SliceByteString => {
+                let bs = bytes(&args[2])?;
+                let start = clamp(integer(&args[0])?, bs.len());
+                let end = start + clamp(integer(&args[1])?, bs.len() - start);
+                Constant::ByteString(bs[start..end].to_vec())
+            }
+            LengthOfByteString => Constant::Integer(bytes(&args[0])?.len().into()),
+            IndexByteString => {
+                let bs = bytes(&args[0])?;
+                let byte = usize::try_from(integer(&args[1])?)
+                    .ok()
+                    .and_then(|i| bs.get(i))
+                    .ok_or_else(|| failure("indexByteString out of bounds"))?;
+                Constant::Integer((*byte).into())
+            }
+            EqualsByteString => Constant::Bool(bytes(&args[0])? == bytes(&args[1])?),
+            LessThanByteString => Constant::Bool(bytes(&args[0])? < bytes(&args[1])?),
+            LessThanEqualsByteString => Constant::Bool(bytes(&args[0])? <= bytes(&args[1])?),
+            Sha2_256 => Constant::ByteString(sha2::Sha256::digest(bytes(&args[0])?).to_vec()),
+            Sha3_256 => Constant::ByteString(sha3::Sha3_256::digest(bytes(&args[0])?).to_vec()),
+            Blake2b256 => Constant::ByteString(Blake2b::<U32>::digest(bytes(&args[0])?).to_vec()),
+            VerifyEd25519Signature => {
+                let key: &[u8; 32] = bytes(&args[0])?
+                    .try_into()
+                    .map_err(|_| failure("Ed25519 public key must be 32 bytes"))?;
+                let signature: &[u8; 64] = bytes(&args[2])?
+                    .try_into()
+                    .map_err(|_| failure("Ed25519 signature must be 64 bytes"))?;
+                let key = ed25519_dalek::VerifyingKey::from_bytes(key)
+                    .map_err(|_| failure("invalid Ed25519 public key"))?;
+                let signature = ed25519_dalek::Signature::from_bytes(signature);
+                Constant::Bool(key.verify_strict(bytes(&args[1])?, &signature).is_ok())
+            }
+            AppendString => Constant::String(format!("{}{}", string(&args[0])?, string(&args[1])?)),
+            EqualsString => Constant::Bool(string(&args[0])? == string(&args[1])?),
+            EncodeUtf8 => Constant::ByteString(string(&args[0])?.as_bytes().to_vec()),
+            DecodeUtf8 => Constant::String(
+                String::from_utf8(bytes(&args[0])?.to_vec())
+                    .map_err(|_| failure("decodeUtf8 of invalid UTF-8"))?,
+            ),
+            IfThenElse => {
+                let branch = if boolean(&args[0])? { 1 } else { 2 };
+                return Ok(args.swap_remove(branch));
+            }
+            ChooseUnit => {
+                unit(&args[0])?;
+                return Ok(args.swap_remove(1));
+            }
+            Trace => {
+                logs.push(string(&args[0])?.to_string());
+                return Ok(args.swap_remove(1));
+            }
+            FstPair => pair(&args[0])?.0.clone(),
+            SndPair => pair(&args[0])?.1.clone(),
+            ChooseList => {
+                let branch = if list(&args[0])?.1.is_empty() { 1 } else { 2 };
+                return Ok(args.swap_remove(branch));
+            }
+            MkCons => {
+                let head = constant(&args[0])?;
+                let (elem, items) = list(&args[1])?;
+                if head.type_of() != *elem {
+                    return Err(failure("mkCons of an element of the wrong type"));
+                }
+                let items = std::iter::once(head).chain(items).cloned().collect();
+                Constant::List(elem.clone(), items)
+            }
+            HeadList => list(&args[0])?
+                .1
+                .first()
+                .cloned()
+                .ok_or_else(|| failure("headList of an empty list"))?,
+            TailList => match list(&args[0])? {
+                (_, []) => return Err(failure("tailList of an empty list")),
+                (elem, [_, rest @ ..]) => Constant::List(elem.clone(), rest.to_vec()),
+            },
+            NullList => Constant::Bool(list(&args[0])?.1.is_empty()),
+            ChooseData => {
+                let branch = match data(&args[0])? {
+                    PlutusData::Constr(..) => 1,
+                    PlutusData::Map(_) => 2,
+                    PlutusData::List(_) => 3,
+                    PlutusData::Integer(_) => 4,
+                    PlutusData::Bytes(_) => 5,
+                };
+                return Ok(args.swap_remove(branch));
+            }
+            ConstrData => {
+                let tag = u64::try_from(integer(&args[0])?)
+                    .map_err(|_| failure("constrData with a negative tag"))?;
+                let fields = list(&args[1])?.1.iter().map(as_data);
+                Constant::Data(PlutusData::Constr(tag, fields.collect::<Result<_, _>>()?))
+            }
+            MapData => {
+                let entries = list(&args[0])?
+                    .1
+                    .iter()
+                    .map(|entry| match entry {
+                        Constant::Pair(k, v) => Ok((as_data(k)?, as_data(v)?)),
+                        _ => Err(failure("mapData of a list of non-pairs")),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Constant::Data(PlutusData::Map(entries))
+            }
+            ListData => {
+                let items = list(&args[0])?.1.iter().map(as_data);
+                Constant::Data(PlutusData::List(items.collect::<Result<_, _>>()?))
+            }
+            IData => Constant::Data(PlutusData::Integer(integer(&args[0])?.clone())),
+            BData => Constant::Data(PlutusData::Bytes(bytes(&args[0])?.to_vec())),
+            UnConstrData => match data(&args[0])? {
+                PlutusData::Constr(tag, fields) => Constant::Pair(
+                    Box::new(Constant::Integer((*tag).into())),
+                    Box::new(data_list(fields)),
+                ),
+                _ => return Err(failure("unConstrData of non-constructor data")),
+            },
+            UnMapData => match data(&args[0])? {
+                PlutusData::Map(entries) => Constant::List(
+                    data_pair_type(),
+                    entries
+                        .iter()
+                        .map(|(k, v)| {
+                            Constant::Pair(
+                                Box::new(Constant::Data(k.clone())),
+                                Box::new(Constant::Data(v.clone())),
+                            )
+                        })
+                        .collect(),
+                ),
+                _ => return Err(failure("unMapData of non-map data")),
+            },
+            UnListData => match data(&args[0])? {
+                PlutusData::List(items) => data_list(items),
+                _ => return Err(failure("unListData of non-list data")),
+            },
+            UnIData => match data(&args[0])? {
+                PlutusData::Integer(i) => Constant::Integer(i.clone()),
+                _ => return Err(failure("unIData of non-integer data")),
+            },
+            UnBData => match data(&args[0])? {
+                PlutusData::Bytes(bs) => Constant::ByteString(bs.clone()),
+                _ => return Err(failure("unBData of non-bytes data")),
+            },
+            EqualsData => Constant::Bool(data(&args[0])? == data(&args[1])?),
+            MkPairData => Constant::Pair(
+                Box::new(Constant::Data(data(&args[0])?.clone())),
+                Box::new(Constant::Data(data(&args[1])?.clone())),
+            ),
+            MkNilData => {
+                unit(&args[0])?;
+                Constant::List(Type::Data, Vec::new())
+            }
+            MkNilPairData => {
+                unit(&args[0])?;
+                Constant::List(data_pair_type(), Vec::new())
+            }
+            SerialiseData => Constant::ByteString(data(&args[0])?.to_cbor()),
+        };
+        Ok(Value::con(result))
+    }
+}
+
+fn failure(reason: &str) -> PlutusError {
+    PlutusError::EvaluationFailure(reason.to_string())
+}
+
+fn constant<'v>(value: &'v Value<'_>) -> Result<&'v Constant, PlutusError> {
+    match value {
+        Value::Con(constant) => Ok(constant),
+        _ => Err(failure("builtin applied to a non-constant")),
+    }
+}
+
+/// Apply an integer operation to the two arguments; `None` means division by zero.
+fn arith(
+    args: &[Value<'_>],
+    op: impl Fn(&BigInt, &BigInt) -> Option<BigInt>,
+) -> Result<BigInt, PlutusError> {
+    op(integer(&args[0])?, integer(&args[1])?).ok_or_else(|| failure("division by zero"))
+}
+
+/// Quotient and remainder of `a` by `b`, both rounded towards zero, unless `b` is zero.
+fn quot_rem(a: &BigInt, b: &BigInt) -> Option<(BigInt, BigInt)> {
+    (b.sign() != Sign::NoSign).then(|| (a / b, a % b))
+}
+
+/// `i` clamped to `0..=max`.
+fn clamp(i: &BigInt, max: usize) -> usize {
+    match i.sign() {
+        Sign::Minus => 0,
+        _ => usize::try_from(i).map_or(max, |i| i.min(max)),
+    }
+}
+
+fn integer<'v>(value: &'v Value<'_>) -> Result<&'v BigInt, PlutusError> {
+    match constant(value)? {
+        Constant::Integer(i) => Ok(i),
+        _ => Err(failure("expected an integer")),
+    }
+}
+
+fn bytes<'v>(value: &'v Value<'_>) -> Result<&'v [u8], PlutusError> {
+    match constant(value)? {
+        Constant::ByteString(bs) => Ok(bs),
+        _ => Err(failure("expected a bytestring")),
+    }
+}
+
+fn string<'v>(value: &'v Value<'_>) -> Result<&'v str, PlutusError> {
+    match constant(value)? {
+        Constant::String(s) => Ok(s),
+        _ => Err(failure("expected a string")),
+    }
+}
+
+fn boolean(value: &Value<'_>) -> Result<bool, PlutusError> {
+    match constant(value)? {
+        Constant::Bool(b) => Ok(*b),
+        _ => Err(failure("expected a boolean")),
+    }
+}
+
+fn unit(value: &Value<'_>) -> Result<(), PlutusError> {
+    match constant(value)? {
+        Constant::Unit => Ok(()),
+        _ => Err(failure("expected unit")),
+    }
+}
+
+fn pair<'v>(value: &'v Value<'_>) -> Result<(&'v Constant, &'v Constant), PlutusError> {
+    match constant(value)? {
+        Constant::Pair(a, b) => Ok((a, b)),
+        _ => Err(failure("expected a pair")),
+    }
+}
+
+fn list<'v>(value: &'v Value<'_>) -> Result<(&'v Type, &'v [Constant]), PlutusError> {
+    match constant(value)? {
+        Constant::List(elem, items) => Ok((elem, items)),
+        _ => Err(failure("expected a list")),
+    }
+}
+
+fn data<'v>(value: &'v Value<'_>) -> Result<&'v PlutusData, PlutusError> {
+    match constant(value)? {
+        Constant::Data(d) => Ok(d),
+        _ => Err(failure("expected data")),
+    }
+}
+
+fn as_data(constant: &Constant) -> Result<PlutusData, PlutusError> {
+    match constant {
+        Constant::Data(d) => Ok(d.clone()),
+        _ => Err(failure("expected a list of data")),
+    }
+}
+
+fn data_list(items: &[PlutusData]) -> Constant {
+    Constant::List(
+        Type::Data,
+        items.iter().cloned().map(Constant::Data).collect(),
+    )
+}
+
+fn data_pair_type() -> Type {
+    Type::Pair(Box::new(Type::Data), Box::new(Type::Data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(builtin: Builtin, args: Vec<Constant>) -> Result<Constant, PlutusError> {
+        let args = args.into_iter().map(Value::con).collect();
+        match builtin.call(args, &mut Vec::new())? {
+            Value::Con(result) => Ok((*result).clone()),
+            other => panic!("non-constant result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn integer_division_rounds_like_haskell() {
+        let int = |i: i128| Constant::Integer(i.into());
+        let div = |builtin, a, b| call(builtin, vec![int(a), int(b)]);
+        use Builtin::*;
+        assert_eq!(div(DivideInteger, -7, 2), Ok(int(-4)));
+        assert_eq!(div(QuotientInteger, -7, 2), Ok(int(-3)));
+        assert_eq!(div(ModInteger, -7, 2), Ok(int(1)));
+        assert_eq!(div(RemainderInteger, -7, 2), Ok(int(-1)));
+        assert_eq!(div(ModInteger, 7, -2), Ok(int(-1)));
+        assert!(div(DivideInteger, 1, 0).is_err());
+    }
+
+    #[test]
+    fn integers_are_unbounded() {
+        let int = |i: BigInt| Constant::Integer(i);
+        let max = BigInt::from(i128::MAX);
+        let square = call(
+            Builtin::MultiplyInteger,
+            vec![int(max.clone()), int(max.clone())],
+        );
+        assert_eq!(square, Ok(int(&max * &max)));
+        assert_eq!(
+            call(
+                Builtin::DivideInteger,
+                vec![square.unwrap(), int(max.clone())]
+            ),
+            Ok(int(max.clone()))
+        );
+        assert_eq!(
+            call(
+                Builtin::ConsByteString,
+                vec![int(-max - 2), Constant::ByteString(vec![])]
+            ),
+            Ok(Constant::ByteString(vec![0xff]))
+        );
+    }
+
+    #[test]
+    fn bytestrings_hashes_and_data() {
+        let bs = |b: &[u8]| Constant::ByteString(b.to_vec());
+        let int = |i: i64| Constant::Integer(i.into());
+        assert_eq!(
+            call(
+                Builtin::SliceByteString,
+                vec![int(1), int(10), bs(&[1, 2, 3])]
+            ),
+            Ok(bs(&[2, 3]))
+        );
+        assert_eq!(
+            call(Builtin::ConsByteString, vec![int(257), bs(&[])]),
+            Ok(bs(&[1]))
+        );
+        assert!(call(Builtin::IndexByteString, vec![bs(&[1]), int(1)]).is_err());
+        assert_eq!(
+            call(Builtin::Sha2_256, vec![bs(b"abc")]),
+            Ok(bs(&crate::ledger::cbor::from_hex(
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            )
+            .unwrap()))
+        );
+
+        let fields = Constant::List(
+            Type::Data,
+            vec![Constant::Data(PlutusData::Integer(5.into()))],
+        );
+        let constr = call(Builtin::ConstrData, vec![int(2), fields.clone()]).unwrap();
+        assert_eq!(
+            call(Builtin::UnConstrData, vec![constr]),
+            Ok(Constant::Pair(Box::new(int(2)), Box::new(fields)))
+        );
+        assert!(
+            call(
+                Builtin::UnIData,
+                vec![Constant::Data(PlutusData::List(vec![]))]
+            )
+            .is_err()
+        );
+        assert!(call(Builtin::AddInteger, vec![int(1), bs(&[])]).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Script context: the view of a transaction that Plutus scripts receive.
+//!
+//! The context follows the Plutus V1 `ScriptContext` layout, `Constr 0 [tx_info, purpose]`, with
+//! `tx_info = Constr 0 [inputs, outputs, fee, mint, dcerts, withdrawals, valid_range,
+//! signatories, datums, tx_id]`. Three departures: the validity range is in slots rather than
+//! POSIX time, identifiers that are not hex (this ledger's own names) are passed as their UTF-8
+//! bytes, and outputs to Byron addresses, which V1 cannot show, show the address bytes as a key
+//! hash credential. As in V1, reference inputs are not part of the context, and outputs show an
+//! inline datum as its hash.
+
+use super::PlutusData;
+use crate::ledger::cbor::from_hex;
+use crate::ledger::{
+    Address, Certificate, Credential, DatumOption, LedgerState, PlutusWitness, Transaction, TxId,
+    TxInput, TxOutput, Value,
+};
+use num_bigint::BigInt;
+
+/// Why a script runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptPurpose {
+    /// Spending an input locked by the script.
+    Spending(TxInput),
+    /// Minting or burning tokens of the policy with this id.
+    Minting(String),
+    /// Withdrawing from the reward account of this credential.
+    Rewarding(Credential),
+    /// Applying a certificate authorised by the script; it must have a [`dcert`].
+    Certifying(Certificate),
+}
+
+impl ScriptPurpose {
+    fn to_data(&self) -> PlutusData {
+        match self {
+            ScriptPurpose::Minting(policy_id) => constr(0, vec![bytes(policy_id)]),
+            ScriptPurpose::Spending(input) => constr(1, vec![out_ref(input)]),
+            ScriptPurpose::Rewarding(account) => constr(2, vec![staking_credential(account)]),
+            ScriptPurpose::Certifying(cert) => constr(3, dcert(cert).into_iter().collect()),
+        }
+    }
+}
+
+/// Script context for the script running for `purpose`, given the [`tx_info`] of its
+/// transaction.
+pub fn script_context(tx_info: &PlutusData, purpose: &ScriptPurpose) -> PlutusData {
+    constr(0, vec![tx_info.clone(), purpose.to_data()])
+}
+
+/// Transaction part of the script context of `tx`, resolving its inputs in `state`. `witnesses`
+/// are the Plutus witnesses paired with the transaction's spending purposes; their datums are
+/// listed by hash.
+pub fn tx_info(state: &LedgerState, tx: &Transaction, witnesses: &[&PlutusWitness]) -> PlutusData {
+    let inputs = tx
+        .inputs
+        .iter()
+        .filter_map(|input| {
+            let output = state.utxos.get(&(input.prev_tx, input.index))?;
+            Some(constr(0, vec![out_ref(input), tx_out(output)]))
+        })
+        .collect();
+    let mint = tx
+        .mint
+        .iter()
+        .map(|(policy_id, assets)| {
+            let assets = assets
+                .iter()
+                .map(|(name, &amount)| (bytes(name), PlutusData::Integer(BigInt::from(amount))))
+                .collect();
+            (bytes(policy_id), PlutusData::Map(assets))
+        })
+        .collect();
+    let withdrawals = tx
+        .withdrawals
+        .iter()
+        .map(|(account, &amount)| {
+            constr(
+                0,
+                vec![
+                    staking_credential(account),
+                    PlutusData::Integer(BigInt::from(amount)),
+                ],
+            )
+        })
+        .collect();
+    let datums = witnesses
+        .iter()
+        .filter(|w| !w.datum.data.is_empty())
+        .map(|w| {
+            let hash = PlutusData::Bytes(w.datum.hash().to_vec());
+            constr(0, vec![hash, PlutusData::from_bytes(&w.datum.data)])
+        })
+        .collect();
+    constr(
+        0,
+        vec![
+            PlutusData::List(inputs),
+            PlutusData::List(tx.outputs.iter().map(tx_out).collect()),
+            value(&Value::lovelace(tx.fee)),
+            PlutusData::Map(mint),
+            PlutusData::List(tx.certificates.iter().filter_map(dcert).collect()),
+            PlutusData::List(withdrawals),
+            valid_range(tx.validity_start, tx.ttl),
+            PlutusData::List(tx.required_signers.iter().map(|k| bytes(k)).collect()),
+            PlutusData::List(datums),
+            tx_id(&tx.id()),
+        ],
+    )
+}
+
+fn constr(tag: u64, fields: Vec<PlutusData>) -> PlutusData {
+    PlutusData::Constr(tag, fields)
+}
+
+/// Identifier as bytes: hex decoded, anything else as UTF-8.
+fn bytes(ident: &str) -> PlutusData {
+    PlutusData::Bytes(from_hex(ident).unwrap_or_else(|_| ident.as_bytes().to_vec()))
+}
+
+fn tx_id(id: &TxId) -> PlutusData {
+    constr(0, vec![PlutusData::Bytes(id.to_vec())])
+}
+
+fn out_ref(input: &TxInput) -> PlutusData {
+    constr(
+        0,
+        vec![
+            tx_id(&input.prev_tx),
+            PlutusData::Integer(BigInt::from(input.index)),
+        ],
+    )
+}
+
+/// `Just x` or `Nothing`.
+fn maybe(value: Option<PlutusData>) -> PlutusData {
+    match value {
+        Some(x) => constr(0, vec![x]),
+        None => constr(1, vec![]),
+    }
+}
+
+/// Key hash or script hash credential.
+fn credential(credential: &Credential) -> PlutusData {
+    let hash = PlutusData::Bytes(credential.hash().to_vec());
+    constr(u64::from(credential.is_script()), vec![hash])
+}
+
+fn staking_credential(account: &Credential) -> PlutusData {
+    constr(0, vec![credential(account)])
+}
+
+/// Address as its payment credential and staking credential, a hash or a pointer.
+fn address(address: &Address) -> PlutusData {
+    let Some(payment) = address.payment_credential() else {
+        return constr(
+            0,
+            vec![
+                constr(0, vec![PlutusData::Bytes(address.to_bytes())]),
+                maybe(None),
+            ],
+        );
+    };
+    let stake = match address {
+        Address::Pointer { pointer, .. } => Some(constr(
+            1,
+            [pointer.slot, pointer.tx_index, pointer.cert_index]
+                .into_iter()
+                .map(|n| PlutusData::Integer(BigInt::from(n)))
+                .collect(),
+        )),
+        _ => address.stake_credential().map(staking_credential),
+    };
+    constr(0, vec![credential(payment), maybe(stake)])
+}
+
+/// Output with its datum hash; an inline datum is passed as its hash.
+fn tx_out(output: &TxOutput) -> PlutusData {
+    let address = address(&output.address);
+    let datum_hash = output.datum.as_ref().map(|datum| match datum {
+        DatumOption::Hash(hash) => PlutusData::Bytes(hash.to_vec()),
+        DatumOption::Inline(datum) => PlutusData::Bytes(datum.hash().to_vec()),
+    });
+    constr(
+        0,
+        vec![address, value(&Value::from(output)), maybe(datum_hash)],
+    )
+}
+
+/// Value as a map from policy id to asset name to quantity, with lovelace under the empty policy
+/// id and asset name.
+fn value(value: &Value) -> PlutusData {
+    let lovelace = (
+        PlutusData::Bytes(vec![]),
+        PlutusData::Map(vec![(
+            PlutusData::Bytes(vec![]),
+            PlutusData::Integer(BigInt::from(value.coin)),
+        )]),
+    );
+    let tokens = value.assets.iter().map(|(policy_id, assets)| {
+        let assets = assets
+            .iter()
+            .map(|(name, &amount)| (bytes(name), PlutusData::Integer(BigInt::from(amount))))
+            .collect();
+        (bytes(policy_id), PlutusData::Map(assets))
+    });
+    PlutusData::Map(std::iter::once(lovelace).chain(tokens).collect())
+}
+
+/// Certificate as V1 `DCert`; Conway certificates have none and are left out.
+pub fn dcert(cert: &Certificate) -> Option<PlutusData> {
+    let data = match cert {
+        Certificate::StakeRegistration(c) => constr(0, vec![staking_credential(c)]),
+        Certificate::StakeDeregistration(c) => constr(1, vec![staking_credential(c)]),
+        Certificate::Delegation(d) => {
+            constr(2, vec![staking_credential(&d.delegator), bytes(&d.pool_id)])
+        }
+        Certificate::PoolRegistration(reg) => constr(
+            3,
+            vec![
+                bytes(&reg.pool_id),
+                PlutusData::Bytes(reg.owner.hash().to_vec()),
+            ],
+        ),
+        Certificate::PoolRetirement(ret) => constr(
+            4,
+            vec![
+                bytes(&ret.pool_id),
+                PlutusData::Integer(BigInt::from(ret.retirement_epoch)),
+            ],
+        ),
+        Certificate::DRepRegistration(_)
+        | Certificate::DRepDeregistration(_)
+        | Certificate::VoteDelegation(_) => return None,
+    };
+    Some(data)
+}
+
+/// Validity interval `[validity_start, ttl)`; a missing bound is infinite.
+fn valid_range(validity_start: Option<u64>, ttl: Option<u64>) -> PlutusData {
+    let closure = |closed: bool| constr(u64::from(closed), vec![]);
+    let bound = |slot: Option<u64>, infinite: u64, closed: bool| match slot {
+        Some(slot) => constr(
+            0,
+            vec![
+                constr(1, vec![PlutusData::Integer(BigInt::from(slot))]),
+                closure(closed),
+            ],
+        ),
+        None => constr(0, vec![constr(infinite, vec![]), closure(true)]),
+    };
+    constr(
+        0,
+        vec![bound(validity_start, 0, true), bound(ttl, 2, false)],
+    )
+}
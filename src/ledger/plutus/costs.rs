@@ -0,0 +1,513 @@
+//! Cost models: the execution units each machine step and builtin call costs.
+//!
+//! The cost model protocol parameter of a Plutus language is a list of integers, the parameters
+//! of its cost model in the alphabetical order of their names. Each machine step (such as
+//! `cekVarCost`) has a CPU then a memory cost. Each builtin (such as `addInteger`) has the
+//! parameters of its CPU cost function, then those of its memory cost function, each in the
+//! order of their names. Cost functions take the sizes of the builtin's arguments, in words.
+
+use super::builtins::Builtin;
+use super::machine::Budget;
+use std::collections::HashMap;
+
+/// Language of PlutusV1 scripts in the cost model protocol parameters.
+pub const PLUTUS_V1: u8 = 0;
+/// Language of PlutusV2 scripts in the cost model protocol parameters.
+pub const PLUTUS_V2: u8 = 1;
+
+/// Cost of a builtin call as a function of the sizes `x`, `y` and `z` of its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostFunction {
+    Constant(i64),
+    /// `intercept + slope * x`
+    LinearInX(i64, i64),
+    /// `intercept + slope * y`
+    LinearInY(i64, i64),
+    /// `intercept + slope * z`
+    LinearInZ(i64, i64),
+    /// `intercept + slope * (x + y)`
+    AddedSizes(i64, i64),
+    /// `intercept + slope * x * y`
+    MultipliedSizes(i64, i64),
+    /// `intercept + slope * min(x, y)`
+    MinSize(i64, i64),
+    /// `intercept + slope * max(x, y)`
+    MaxSize(i64, i64),
+    /// `intercept + slope * max(minimum, x - y)`, as `(intercept, slope, minimum)`
+    SubtractedSizes(i64, i64, i64),
+    /// `intercept + slope * x` if `x == y`, else `constant`, as `(constant, intercept, slope)`
+    LinearOnDiagonal(i64, i64, i64),
+    /// `constant` if `x < y`, else `intercept + slope * x * y`, as `(constant, intercept, slope)`
+    ConstAboveDiagonal(i64, i64, i64),
+}
+
+impl CostFunction {
+    /// Cost of a call with arguments of sizes `sizes`.
+    pub fn cost(&self, sizes: &[i64]) -> i64 {
+        use CostFunction::*;
+        let size = |i: usize| sizes.get(i).copied().unwrap_or(0);
+        let (x, y, z) = (size(0), size(1), size(2));
+        let linear =
+            |intercept: i64, slope: i64, n: i64| intercept.saturating_add(slope.saturating_mul(n));
+        match *self {
+            Constant(cost) => cost,
+            LinearInX(intercept, slope) => linear(intercept, slope, x),
+            LinearInY(intercept, slope) => linear(intercept, slope, y),
+            LinearInZ(intercept, slope) => linear(intercept, slope, z),
+            AddedSizes(intercept, slope) => linear(intercept, slope, x.saturating_add(y)),
+            MultipliedSizes(intercept, slope) => linear(intercept, slope, x.saturating_mul(y)),
+            MinSize(intercept, slope) => linear(intercept, slope, x.min(y)),
+            MaxSize(intercept, slope) => linear(intercept, slope, x.max(y)),
+            SubtractedSizes(intercept, slope, minimum) => {
+                linear(intercept, slope, x.saturating_sub(y).max(minimum))
+            }
+            LinearOnDiagonal(constant, intercept, slope) => {
+                if x == y {
+                    linear(intercept, slope, x)
+                } else {
+                    constant
+                }
+            }
+            ConstAboveDiagonal(constant, intercept, slope) => {
+                if x < y {
+                    constant
+                } else {
+                    linear(intercept, slope, x.saturating_mul(y))
+                }
+            }
+        }
+    }
+
+    /// Parameters of the function, in the order of their names.
+    fn parameters_mut(&mut self) -> Vec<&mut i64> {
+        use CostFunction::*;
+        match self {
+            Constant(cost) => vec![cost],
+            LinearInX(intercept, slope)
+            | LinearInY(intercept, slope)
+            | LinearInZ(intercept, slope)
+            | AddedSizes(intercept, slope)
+            | MultipliedSizes(intercept, slope)
+            | MinSize(intercept, slope)
+            | MaxSize(intercept, slope) => vec![intercept, slope],
+            SubtractedSizes(intercept, slope, minimum) => vec![intercept, minimum, slope],
+            LinearOnDiagonal(constant, intercept, slope)
+            | ConstAboveDiagonal(constant, intercept, slope) => vec![constant, intercept, slope],
+        }
+    }
+}
+
+/// CPU and memory cost functions of a builtin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinCost {
+    pub cpu: CostFunction,
+    pub mem: CostFunction,
+}
+
+impl BuiltinCost {
+    /// Execution units of a call with arguments of sizes `sizes`.
+    pub fn budget(&self, sizes: &[i64]) -> Budget {
+        Budget::new(self.mem.cost(sizes), self.cpu.cost(sizes))
+    }
+}
+
+/// Costs charged by the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostModel {
+    /// Charged once, before evaluation starts.
+    pub startup: Budget,
+    pub var: Budget,
+    pub constant: Budget,
+    pub lambda: Budget,
+    pub delay: Budget,
+    pub force: Budget,
+    /// Charged for computing an application, and for constructors and case expressions, which
+    /// PlutusV1 and V2 cost models do not price.
+    pub apply: Budget,
+    /// Charged for computing a builtin term, before it is called.
+    pub builtin: Budget,
+    /// Costs of calling the builtins of the language; the others are not available.
+    pub builtins: HashMap<Builtin, BuiltinCost>,
+}
+
+impl Default for CostModel {
+    /// The PlutusV1 cost model with its default parameters.
+    fn default() -> Self {
+        CostModel::load(PLUTUS_V1, Some).unwrap_or_else(CostModel::empty)
+    }
+}
+
+impl CostModel {
+    /// Cost model of `language` with the parameters `params`, as in the cost model protocol
+    /// parameter. None if the language is not supported or parameters are missing; parameters
+    /// beyond those of the model are ignored.
+    pub fn from_params(language: u8, params: &[i64]) -> Option<CostModel> {
+        let mut params = params.iter().copied();
+        CostModel::load(language, |_| params.next())
+    }
+
+    /// Default parameters of the cost model of `language`: those of mainnet at the Vasil hard
+    /// fork. Empty if the language is not supported.
+    pub fn default_params(language: u8) -> Vec<i64> {
+        let mut params = Vec::new();
+        CostModel::load(language, |default| {
+            params.push(default);
+            Some(default)
+        });
+        params
+    }
+
+    fn empty() -> Self {
+        CostModel {
+            startup: Budget::default(),
+            var: Budget::default(),
+            constant: Budget::default(),
+            lambda: Budget::default(),
+            delay: Budget::default(),
+            force: Budget::default(),
+            apply: Budget::default(),
+            builtin: Budget::default(),
+            builtins: HashMap::new(),
+        }
+    }
+
+    /// Cost model of `language`, with each default parameter replaced, in the order of the
+    /// cost model protocol parameter, by what `param` returns for it.
+    fn load(language: u8, mut param: impl FnMut(i64) -> Option<i64>) -> Option<CostModel> {
+        let mut entries = entries(language)?;
+        entries.sort_by_key(|(name, _)| *name);
+        let mut model = CostModel::empty();
+        for (_, entry) in entries {
+            match entry {
+                Entry::Step(step, default) => {
+                    let steps = param(default.steps)?;
+                    let mem = param(default.mem)?;
+                    *model.step_mut(step) = Budget::new(mem, steps);
+                }
+                Entry::Builtin(builtin, mut cost) => {
+                    let parameters = cost.cpu.parameters_mut().into_iter();
+                    for parameter in parameters.chain(cost.mem.parameters_mut()) {
+                        *parameter = param(*parameter)?;
+                    }
+                    if let Some(builtin) = builtin {
+                        model.builtins.insert(builtin, cost);
+                    }
+                }
+            }
+        }
+        Some(model)
+    }
+
+    fn step_mut(&mut self, step: Step) -> &mut Budget {
+        match step {
+            Step::Apply => &mut self.apply,
+            Step::Builtin => &mut self.builtin,
+            Step::Const => &mut self.constant,
+            Step::Delay => &mut self.delay,
+            Step::Force => &mut self.force,
+            Step::Lam => &mut self.lambda,
+            Step::Startup => &mut self.startup,
+            Step::Var => &mut self.var,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Apply,
+    Builtin,
+    Const,
+    Delay,
+    Force,
+    Lam,
+    Startup,
+    Var,
+}
+
+/// Costs named in a cost model: a machine step, or a builtin, which this machine may not
+/// implement.
+enum Entry {
+    Step(Step, Budget),
+    Builtin(Option<Builtin>, BuiltinCost),
+}
+
+/// Costs of the cost model of `language` with their names and default parameters.
+fn entries(language: u8) -> Option<Vec<(&'static str, Entry)>> {
+    use Builtin::*;
+    use CostFunction::*;
+    let step = |step, steps| Entry::Step(step, Budget::new(100, steps));
+    let call = |builtin, cpu, mem| Entry::Builtin(Some(builtin), BuiltinCost { cpu, mem });
+    let division = |builtin| {
+        call(
+            builtin,
+            ConstAboveDiagonal(196_500, 453_240, 220),
+            SubtractedSizes(0, 1, 1),
+        )
+    };
+    let mut entries = vec![
+        ("cekApplyCost", step(Step::Apply, 23_000)),
+        ("cekBuiltinCost", step(Step::Builtin, 23_000)),
+        ("cekConstCost", step(Step::Const, 23_000)),
+        ("cekDelayCost", step(Step::Delay, 23_000)),
+        ("cekForceCost", step(Step::Force, 23_000)),
+        ("cekLamCost", step(Step::Lam, 23_000)),
+        ("cekStartupCost", step(Step::Startup, 100)),
+        ("cekVarCost", step(Step::Var, 23_000)),
+        (
+            "addInteger",
+            call(AddInteger, MaxSize(205_665, 812), MaxSize(1, 1)),
+        ),
+        (
+            "subtractInteger",
+            call(SubtractInteger, MaxSize(205_665, 812), MaxSize(1, 1)),
+        ),
+        (
+            "multiplyInteger",
+            call(
+                MultiplyInteger,
+                AddedSizes(69_522, 11_687),
+                AddedSizes(0, 1),
+            ),
+        ),
+        ("divideInteger", division(DivideInteger)),
+        ("quotientInteger", division(QuotientInteger)),
+        ("remainderInteger", division(RemainderInteger)),
+        ("modInteger", division(ModInteger)),
+        (
+            "equalsInteger",
+            call(EqualsInteger, MinSize(208_512, 421), Constant(1)),
+        ),
+        (
+            "lessThanInteger",
+            call(LessThanInteger, MinSize(208_896, 511), Constant(1)),
+        ),
+        (
+            "lessThanEqualsInteger",
+            call(LessThanEqualsInteger, MinSize(204_924, 473), Constant(1)),
+        ),
+        (
+            "appendByteString",
+            call(AppendByteString, AddedSizes(1_000, 571), AddedSizes(0, 1)),
+        ),
+        (
+            "consByteString",
+            call(ConsByteString, LinearInY(221_973, 511), AddedSizes(0, 1)),
+        ),
+        (
+            "sliceByteString",
+            call(SliceByteString, LinearInZ(265_318, 0), LinearInZ(4, 0)),
+        ),
+        (
+            "lengthOfByteString",
+            call(LengthOfByteString, Constant(1_000), Constant(10)),
+        ),
+        (
+            "indexByteString",
+            call(IndexByteString, Constant(57_667), Constant(4)),
+        ),
+        (
+            "equalsByteString",
+            call(
+                EqualsByteString,
+                LinearOnDiagonal(245_000, 216_773, 62),
+                Constant(1),
+            ),
+        ),
+        (
+            "lessThanByteString",
+            call(LessThanByteString, MinSize(197_145, 156), Constant(1)),
+        ),
+        (
+            "lessThanEqualsByteString",
+            call(LessThanEqualsByteString, MinSize(197_145, 156), Constant(1)),
+        ),
+        (
+            "sha2_256",
+            call(Sha2_256, LinearInX(806_990, 30_482), Constant(4)),
+        ),
+        (
+            "sha3_256",
+            call(Sha3_256, LinearInX(1_927_926, 82_523), Constant(4)),
+        ),
+        (
+            "blake2b_256",
+            call(Blake2b256, LinearInX(117_366, 10_475), Constant(4)),
+        ),
+        (
+            "verifyEd25519Signature",
+            call(
+                VerifyEd25519Signature,
+                LinearInY(57_996_947, 18_975),
+                Constant(10),
+            ),
+        ),
+        (
+            "appendString",
+            call(AppendString, AddedSizes(1_000, 24_177), AddedSizes(4, 1)),
+        ),
+        (
+            "equalsString",
+            call(
+                EqualsString,
+                LinearOnDiagonal(187_000, 1_000, 52_998),
+                Constant(1),
+            ),
+        ),
+        (
+            "encodeUtf8",
+            call(EncodeUtf8, LinearInX(1_000, 28_662), LinearInX(4, 2)),
+        ),
+        (
+            "decodeUtf8",
+            call(DecodeUtf8, LinearInX(497_525, 14_068), LinearInX(4, 2)),
+        ),
+        (
+            "ifThenElse",
+            call(IfThenElse, Constant(80_556), Constant(1)),
+        ),
+        (
+            "chooseUnit",
+            call(ChooseUnit, Constant(46_417), Constant(4)),
+        ),
+        ("trace", call(Trace, Constant(212_342), Constant(32))),
+        ("fstPair", call(FstPair, Constant(80_436), Constant(32))),
+        ("sndPair", call(SndPair, Constant(85_931), Constant(32))),
+        (
+            "chooseList",
+            call(ChooseList, Constant(175_354), Constant(32)),
+        ),
+        ("mkCons", call(MkCons, Constant(65_493), Constant(32))),
+        ("headList", call(HeadList, Constant(43_249), Constant(32))),
+        ("tailList", call(TailList, Constant(41_182), Constant(32))),
+        ("nullList", call(NullList, Constant(60_091), Constant(32))),
+        (
+            "chooseData",
+            call(ChooseData, Constant(19_537), Constant(32)),
+        ),
+        (
+            "constrData",
+            call(ConstrData, Constant(89_141), Constant(32)),
+        ),
+        ("mapData", call(MapData, Constant(64_832), Constant(32))),
+        ("listData", call(ListData, Constant(52_467), Constant(32))),
+        ("iData", call(IData, Constant(1_000), Constant(32))),
+        ("bData", call(BData, Constant(1_000), Constant(32))),
+        (
+            "unConstrData",
+            call(UnConstrData, Constant(32_696), Constant(32)),
+        ),
+        ("unMapData", call(UnMapData, Constant(38_314), Constant(32))),
+        (
+            "unListData",
+            call(UnListData, Constant(32_247), Constant(32)),
+        ),
+        ("unIData", call(UnIData, Constant(43_357), Constant(32))),
+        ("unBData", call(UnBData, Constant(31_220), Constant(32))),
+        (
+            "equalsData",
+            call(EqualsData, MinSize(1_060_367, 12_586), Constant(1)),
+        ),
+        (
+            "mkPairData",
+            call(MkPairData, Constant(76_511), Constant(32)),
+        ),
+        ("mkNilData", call(MkNilData, Constant(22_558), Constant(32))),
+        (
+            "mkNilPairData",
+            call(MkNilPairData, Constant(16_563), Constant(32)),
+        ),
+    ];
+    match language {
+        PLUTUS_V1 => {}
+        PLUTUS_V2 => entries.extend([
+            (
+                "serialiseData",
+                call(
+                    SerialiseData,
+                    LinearInX(1_159_724, 392_670),
+                    LinearInX(0, 2),
+                ),
+            ),
+            // SECP256k1 signatures are not supported; their costs are skipped.
+            (
+                "verifyEcdsaSecp256k1Signature",
+                Entry::Builtin(
+                    None,
+                    BuiltinCost {
+                        cpu: Constant(35_892_428),
+                        mem: Constant(10),
+                    },
+                ),
+            ),
+            (
+                "verifySchnorrSecp256k1Signature",
+                Entry::Builtin(
+                    None,
+                    BuiltinCost {
+                        cpu: LinearInY(38_887_044, 32_947),
+                        mem: Constant(10),
+                    },
+                ),
+            ),
+        ]),
+        _ => return None,
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PlutusV1 cost model of mainnet since the Vasil hard fork.
+    const MAINNET_V1: [i64; 166] = [
+        205665, 812, 1, 1, 1000, 571, 0, 1, 1000, 24177, 4, 1, 1000, 32, 117366, 10475, 4, 23000,
+        100, 23000, 100, 23000, 100, 23000, 100, 23000, 100, 23000, 100, 100, 100, 23000, 100,
+        19537, 32, 175354, 32, 46417, 4, 221973, 511, 0, 1, 89141, 32, 497525, 14068, 4, 2, 196500,
+        453240, 220, 0, 1, 1, 1000, 28662, 4, 2, 245000, 216773, 62, 1, 1060367, 12586, 1, 208512,
+        421, 1, 187000, 1000, 52998, 1, 80436, 32, 43249, 32, 1000, 32, 80556, 1, 57667, 4, 1000,
+        10, 197145, 156, 1, 197145, 156, 1, 204924, 473, 1, 208896, 511, 1, 52467, 32, 64832, 32,
+        65493, 32, 22558, 32, 16563, 32, 76511, 32, 196500, 453240, 220, 0, 1, 1, 69522, 11687, 0,
+        1, 60091, 32, 196500, 453240, 220, 0, 1, 1, 196500, 453240, 220, 0, 1, 1, 806990, 30482, 4,
+        1927926, 82523, 4, 265318, 0, 4, 0, 85931, 32, 205665, 812, 1, 1, 41182, 32, 212342, 32,
+        31220, 32, 32696, 32, 43357, 32, 32247, 32, 38314, 32, 57996947, 18975, 10,
+    ];
+
+    #[test]
+    fn parameters_are_listed_in_the_order_of_their_names() {
+        assert_eq!(CostModel::default_params(PLUTUS_V1), MAINNET_V1);
+        assert_eq!(CostModel::default_params(PLUTUS_V2).len(), 175);
+        assert!(CostModel::default_params(2).is_empty());
+
+        let mut params = MAINNET_V1;
+        params[0] = 1; // addInteger-cpu-arguments-intercept
+        params[29] = 7; // cekStartupCost-exBudgetCPU
+        let model = CostModel::from_params(PLUTUS_V1, &params).unwrap();
+        assert_eq!(model.startup, Budget::new(100, 7));
+        assert_eq!(
+            model.builtins[&Builtin::AddInteger].cpu,
+            CostFunction::MaxSize(1, 812)
+        );
+        assert_eq!(
+            model.builtins[&Builtin::DivideInteger].mem,
+            CostFunction::SubtractedSizes(0, 1, 1)
+        );
+        assert!(!model.builtins.contains_key(&Builtin::SerialiseData));
+        assert_eq!(CostModel::from_params(PLUTUS_V1, &params[..165]), None);
+        assert!(CostModel::from_params(PLUTUS_V1, &[&params[..], &[9]].concat()).is_some());
+    }
+
+    #[test]
+    fn cost_functions_follow_argument_sizes() {
+        use CostFunction::*;
+        assert_eq!(MaxSize(10, 2).cost(&[3, 5]), 20);
+        assert_eq!(LinearInZ(10, 2).cost(&[3, 5, 7]), 24);
+        assert_eq!(SubtractedSizes(0, 1, 1).cost(&[5, 2]), 3);
+        assert_eq!(SubtractedSizes(0, 1, 1).cost(&[2, 5]), 1);
+        assert_eq!(LinearOnDiagonal(100, 1, 2).cost(&[3, 3]), 7);
+        assert_eq!(LinearOnDiagonal(100, 1, 2).cost(&[3, 4]), 100);
+        assert_eq!(ConstAboveDiagonal(100, 1, 2).cost(&[3, 4]), 100);
+        assert_eq!(ConstAboveDiagonal(100, 1, 2).cost(&[4, 3]), 25);
+        assert_eq!(MultipliedSizes(1, i64::MAX).cost(&[2, 2]), i64::MAX);
+    }
+}
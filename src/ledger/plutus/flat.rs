@@ -0,0 +1,458 @@
+//! Flat encoding of UPLC programs, the format of Plutus scripts on chain.
+//!
+//! Flat is a bit-level format, most significant bit first: terms have 4-bit tags, builtins 7-bit
+//! tags, naturals are 7-bit groups (least significant first) each preceded by a continuation
+//! bit, lists are items each preceded by a `1` bit and ended by a `0` bit, and byte strings are
+//! byte-aligned by a filler (`0*1`) and split in chunks of at most 255 bytes. Lambda binders
+//! carry no name: variables are de Bruijn indices. Data constants are CBOR inside a byte string.
+
+use super::{Builtin, Constant, PlutusData, PlutusError, Program, Term, Type};
+use crate::ledger::cbor::Cbor;
+use num_bigint::{BigInt, BigUint, Sign};
+use std::rc::Rc;
+
+/// Deepest term nesting accepted, so that malformed scripts cannot exhaust the stack.
+const MAX_DEPTH: usize = 4_096;
+
+/// Decode the code of a script: a flat program, possibly wrapped in CBOR byte strings as scripts
+/// appear in witness sets.
+pub fn decode_script(code: &[u8]) -> Result<Program, PlutusError> {
+    let mut code = code.to_vec();
+    while let Some(inner) = unwrap_cbor_bytes(&code) {
+        code = inner;
+    }
+    decode_program(&code)
+}
+
+/// Contents of `bytes` if it is exactly one CBOR byte string.
+fn unwrap_cbor_bytes(bytes: &[u8]) -> Option<Vec<u8>> {
+    let first = *bytes.first()?;
+    if first >> 5 != 2 {
+        return None;
+    }
+    let mut d = minicbor::Decoder::new(bytes);
+    let mut inner = Vec::new();
+    for chunk in d.bytes_iter().ok()? {
+        inner.extend_from_slice(chunk.ok()?);
+    }
+    (d.position() == bytes.len()).then_some(inner)
+}
+
+/// Decode a flat-encoded program.
+pub fn decode_program(bytes: &[u8]) -> Result<Program, PlutusError> {
+    let mut r = Reader { bytes, pos: 0 };
+    let version = (r.natural()?, r.natural()?, r.natural()?);
+    let term = r.term(0)?;
+    r.filler()?;
+    if r.pos != bytes.len() * 8 {
+        return Err(decode_error("trailing bytes after the program"));
+    }
+    Ok(Program { version, term })
+}
+
+/// Flat encoding of a program.
+pub fn encode_program(program: &Program) -> Vec<u8> {
+    let mut w = Writer::default();
+    let (major, minor, patch) = program.version;
+    for n in [major, minor, patch] {
+        w.natural(u128::from(n));
+    }
+    w.term(&program.term);
+    w.filler();
+    w.bytes
+}
+
+fn decode_error(reason: impl Into<String>) -> PlutusError {
+    PlutusError::Decode(reason.into())
+}
+
+/// Reads bits from a byte slice, most significant bit first.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    /// Position in bits.
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bit(&mut self) -> Result<bool, PlutusError> {
+        let byte = self
+            .bytes
+            .get(self.pos / 8)
+            .ok_or_else(|| decode_error("unexpected end of script"))?;
+        let bit = byte >> (7 - self.pos % 8) & 1 == 1;
+        self.pos += 1;
+        Ok(bit)
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u8, PlutusError> {
+        let mut out = 0;
+        for _ in 0..n {
+            out = out << 1 | u8::from(self.bit()?);
+        }
+        Ok(out)
+    }
+
+    /// Skip the `0*1` filler that pads to a byte boundary.
+    fn filler(&mut self) -> Result<(), PlutusError> {
+        while !self.bit()? {}
+        if !self.pos.is_multiple_of(8) {
+            return Err(decode_error("filler does not end on a byte boundary"));
+        }
+        Ok(())
+    }
+
+    fn big_natural(&mut self) -> Result<BigUint, PlutusError> {
+        let mut groups = Vec::new();
+        loop {
+            let more = self.bit()?;
+            groups.push(self.bits(7)?);
+            if !more {
+                break;
+            }
+        }
+        BigUint::from_radix_le(&groups, 128).ok_or_else(|| decode_error("malformed natural"))
+    }
+
+    fn natural(&mut self) -> Result<u64, PlutusError> {
+        u64::try_from(self.big_natural()?).map_err(|_| decode_error("natural number too large"))
+    }
+
+    fn integer(&mut self) -> Result<BigInt, PlutusError> {
+        let n = self.big_natural()?;
+        // Zigzag: 0, -1, 1, -2, ... are 0, 1, 2, 3, ...
+        let magnitude = BigInt::from((&n + 1u32) >> 1);
+        Ok(if n.bit(0) { -magnitude } else { magnitude })
+    }
+
+    /// Read a flat list by calling `item` for each element.
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, PlutusError>,
+    ) -> Result<Vec<T>, PlutusError> {
+        let mut out = Vec::new();
+        while self.bit()? {
+            out.push(item(self)?);
+        }
+        Ok(out)
+    }
+
+    fn byte_string(&mut self) -> Result<Vec<u8>, PlutusError> {
+        self.filler()?;
+        let mut out = Vec::new();
+        loop {
+            let start = self.pos / 8;
+            let len = *self
+                .bytes
+                .get(start)
+                .ok_or_else(|| decode_error("unexpected end of script"))?
+                as usize;
+            let chunk = self
+                .bytes
+                .get(start + 1..start + 1 + len)
+                .ok_or_else(|| decode_error("unexpected end of script"))?;
+            out.extend_from_slice(chunk);
+            self.pos += 8 * (1 + len);
+            if len == 0 {
+                return Ok(out);
+            }
+        }
+    }
+
+    fn term(&mut self, depth: usize) -> Result<Term, PlutusError> {
+        if depth > MAX_DEPTH {
+            return Err(decode_error("term nested too deeply"));
+        }
+        let sub = |r: &mut Self| r.term(depth + 1).map(Box::new);
+        Ok(match self.bits(4)? {
+            0 => match usize::try_from(self.natural()?) {
+                Ok(index) if index > 0 => Term::Var(index),
+                _ => return Err(decode_error("invalid de Bruijn index")),
+            },
+            1 => Term::Delay(sub(self)?),
+            2 => Term::Lambda(sub(self)?),
+            3 => Term::Apply(sub(self)?, sub(self)?),
+            4 => {
+                let tags = self.list(|r| r.bits(4))?;
+                let (ty, rest) = parse_type(&tags)?;
+                if !rest.is_empty() {
+                    return Err(decode_error("malformed constant type"));
+                }
+                Term::Constant(Rc::new(self.constant(&ty, depth)?))
+            }
+            5 => Term::Force(sub(self)?),
+            6 => Term::Error,
+            7 => {
+                let tag = self.bits(7)?;
+                Term::Builtin(
+                    Builtin::from_tag(tag)
+                        .ok_or_else(|| decode_error(format!("unsupported builtin {}", tag)))?,
+                )
+            }
+            8 => Term::Constr(self.natural()?, self.list(|r| r.term(depth + 1))?),
+            9 => Term::Case(sub(self)?, self.list(|r| r.term(depth + 1))?),
+            tag => return Err(decode_error(format!("unknown term tag {}", tag))),
+        })
+    }
+
+    fn constant(&mut self, ty: &Type, depth: usize) -> Result<Constant, PlutusError> {
+        if depth > MAX_DEPTH {
+            return Err(decode_error("constant nested too deeply"));
+        }
+        Ok(match ty {
+            Type::Integer => Constant::Integer(self.integer()?),
+            Type::ByteString => Constant::ByteString(self.byte_string()?),
+            Type::String => Constant::String(
+                String::from_utf8(self.byte_string()?)
+                    .map_err(|_| decode_error("string constant is not UTF-8"))?,
+            ),
+            Type::Unit => Constant::Unit,
+            Type::Bool => Constant::Bool(self.bit()?),
+            Type::List(elem) => Constant::List(
+                (**elem).clone(),
+                self.list(|r| r.constant(elem, depth + 1))?,
+            ),
+            Type::Pair(a, b) => Constant::Pair(
+                Box::new(self.constant(a, depth + 1)?),
+                Box::new(self.constant(b, depth + 1)?),
+            ),
+            Type::Data => Constant::Data(
+                PlutusData::from_cbor(&self.byte_string()?)
+                    .map_err(|e| decode_error(format!("data constant: {}", e)))?,
+            ),
+        })
+    }
+}
+
+/// Parse a type from its tags, returning the remaining tags.
+fn parse_type(tags: &[u8]) -> Result<(Type, &[u8]), PlutusError> {
+    let malformed = || decode_error("malformed constant type");
+    let (first, rest) = tags.split_first().ok_or_else(malformed)?;
+    Ok(match (first, rest) {
+        (0, _) => (Type::Integer, rest),
+        (1, _) => (Type::ByteString, rest),
+        (2, _) => (Type::String, rest),
+        (3, _) => (Type::Unit, rest),
+        (4, _) => (Type::Bool, rest),
+        (8, _) => (Type::Data, rest),
+        (7, [5, rest @ ..]) => {
+            let (elem, rest) = parse_type(rest)?;
+            (Type::List(Box::new(elem)), rest)
+        }
+        (7, [7, 6, rest @ ..]) => {
+            let (a, rest) = parse_type(rest)?;
+            let (b, rest) = parse_type(rest)?;
+            (Type::Pair(Box::new(a), Box::new(b)), rest)
+        }
+        _ => return Err(malformed()),
+    })
+}
+
+fn type_tags(ty: &Type, out: &mut Vec<u8>) {
+    match ty {
+        Type::Integer => out.push(0),
+        Type::ByteString => out.push(1),
+        Type::String => out.push(2),
+        Type::Unit => out.push(3),
+        Type::Bool => out.push(4),
+        Type::Data => out.push(8),
+        Type::List(elem) => {
+            out.extend([7, 5]);
+            type_tags(elem, out);
+        }
+        Type::Pair(a, b) => {
+            out.extend([7, 7, 6]);
+            type_tags(a, out);
+            type_tags(b, out);
+        }
+    }
+}
+
+/// Writes bits into a byte vector, most significant bit first.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    /// Number of bits used in the last byte; 8 when it is full.
+    used: u8,
+}
+
+impl Writer {
+    fn bit(&mut self, bit: bool) {
+        if self.used.is_multiple_of(8) {
+            self.bytes.push(0);
+            self.used = 0;
+        }
+        if bit {
+            *self.bytes.last_mut().expect("byte pushed above") |= 0x80 >> self.used;
+        }
+        self.used += 1;
+    }
+
+    fn bits(&mut self, n: u32, value: u8) {
+        for i in (0..n).rev() {
+            self.bit(value >> i & 1 == 1);
+        }
+    }
+
+    fn filler(&mut self) {
+        while self.used % 8 != 7 {
+            self.bit(false);
+        }
+        self.bit(true);
+    }
+
+    fn natural(&mut self, n: u128) {
+        self.big_natural(&BigUint::from(n));
+    }
+
+    fn big_natural(&mut self, n: &BigUint) {
+        let groups = n.to_radix_le(128);
+        for (i, &group) in groups.iter().enumerate() {
+            self.bit(i + 1 < groups.len());
+            self.bits(7, group);
+        }
+    }
+
+    fn integer(&mut self, i: &BigInt) {
+        let n = i.magnitude() << 1u32;
+        self.big_natural(&if i.sign() == Sign::Minus { n - 1u32 } else { n });
+    }
+
+    fn byte_string(&mut self, bytes: &[u8]) {
+        self.filler();
+        for chunk in bytes.chunks(255) {
+            self.bytes.push(chunk.len() as u8);
+            self.bytes.extend_from_slice(chunk);
+        }
+        self.bytes.push(0);
+    }
+
+    fn term(&mut self, term: &Term) {
+        match term {
+            Term::Var(index) => {
+                self.bits(4, 0);
+                self.natural(*index as u128);
+            }
+            Term::Delay(body) => {
+                self.bits(4, 1);
+                self.term(body);
+            }
+            Term::Lambda(body) => {
+                self.bits(4, 2);
+                self.term(body);
+            }
+            Term::Apply(fun, arg) => {
+                self.bits(4, 3);
+                self.term(fun);
+                self.term(arg);
+            }
+            Term::Constant(constant) => {
+                self.bits(4, 4);
+                let mut tags = Vec::new();
+                type_tags(&constant.type_of(), &mut tags);
+                for tag in tags {
+                    self.bit(true);
+                    self.bits(4, tag);
+                }
+                self.bit(false);
+                self.constant(constant);
+            }
+            Term::Force(body) => {
+                self.bits(4, 5);
+                self.term(body);
+            }
+            Term::Error => self.bits(4, 6),
+            Term::Builtin(builtin) => {
+                self.bits(4, 7);
+                self.bits(7, builtin.tag());
+            }
+            Term::Constr(tag, fields) => {
+                self.bits(4, 8);
+                self.natural(u128::from(*tag));
+                self.terms(fields);
+            }
+            Term::Case(scrutinee, branches) => {
+                self.bits(4, 9);
+                self.term(scrutinee);
+                self.terms(branches);
+            }
+        }
+    }
+
+    fn terms(&mut self, terms: &[Term]) {
+        for term in terms {
+            self.bit(true);
+            self.term(term);
+        }
+        self.bit(false);
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Integer(i) => self.integer(i),
+            Constant::ByteString(bytes) => self.byte_string(bytes),
+            Constant::String(s) => self.byte_string(s.as_bytes()),
+            Constant::Unit => {}
+            Constant::Bool(b) => self.bit(*b),
+            Constant::List(_, items) => {
+                for item in items {
+                    self.bit(true);
+                    self.constant(item);
+                }
+                self.bit(false);
+            }
+            Constant::Pair(a, b) => {
+                self.constant(a);
+                self.constant(b);
+            }
+            Constant::Data(data) => self.byte_string(&data.to_cbor()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_known_scripts() {
+        // (program 1.0.0 (lam x x)), flat-encoded.
+        let identity = decode_program(&[0x01, 0x00, 0x00, 0x20, 0x01, 0x01]).unwrap();
+        assert_eq!(identity.version, (1, 0, 0));
+        assert_eq!(identity.term, Term::Lambda(Box::new(Term::Var(1))));
+        // The same program as it appears in a witness set: wrapped in two CBOR byte strings.
+        let wrapped = [0x47, 0x46, 0x01, 0x00, 0x00, 0x20, 0x01, 0x01];
+        assert_eq!(decode_script(&wrapped), Ok(identity));
+        assert!(decode_program(&[0x01, 0x00, 0x00, 0x20, 0x01]).is_err());
+        assert!(decode_program(&[0x01, 0x00, 0x00, 0x20, 0x01, 0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn programs_round_trip() {
+        let int = |i: BigInt| Term::Constant(Rc::new(Constant::Integer(i)));
+        let term = Term::Lambda(Box::new(Term::Force(Box::new(
+            Term::Builtin(Builtin::IfThenElse)
+                .apply(Term::Constant(Rc::new(Constant::Bool(true))))
+                .apply(int((-300).into()))
+                .apply(int(BigInt::from(i128::MIN) * 1_000)),
+        ))))
+        .apply(Term::Constant(Rc::new(Constant::List(
+            Type::Pair(Box::new(Type::String), Box::new(Type::Data)),
+            vec![Constant::Pair(
+                Box::new(Constant::String("datum".to_string())),
+                Box::new(Constant::Data(PlutusData::Constr(
+                    0,
+                    vec![PlutusData::Bytes(vec![7; 300])],
+                ))),
+            )],
+        ))))
+        .apply(Term::Case(
+            Box::new(Term::Constr(1, vec![Term::Error])),
+            vec![Term::Delay(Box::new(Term::Var(3)))],
+        ));
+        let program = Program {
+            version: (1, 1, 0),
+            term,
+        };
+        assert_eq!(decode_program(&encode_program(&program)), Ok(program));
+    }
+}
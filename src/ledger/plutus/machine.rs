@@ -0,0 +1,370 @@
+//! CEK machine evaluating UPLC terms within a budget of execution units.
+//!
+//! The machine keeps an explicit stack of frames instead of recursing, so deep terms cannot
+//! overflow the native stack. Every machine step and every builtin call is charged against the
+//! budget, as priced by the [cost model](super::costs), and evaluation stops as soon as it is
+//! exhausted.
+
+use super::builtins::Builtin;
+use super::costs::CostModel;
+use super::{Constant, Evaluation, PlutusData, PlutusError, Term};
+use std::rc::Rc;
+
+/// Execution units: memory and CPU steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub mem: i64,
+    pub steps: i64,
+}
+
+impl Budget {
+    pub const fn new(mem: i64, steps: i64) -> Self {
+        Budget { mem, steps }
+    }
+}
+
+impl From<(u64, u64)> for Budget {
+    /// Budget from `(mem, steps)` execution units, as in a Plutus witness.
+    fn from((mem, steps): (u64, u64)) -> Self {
+        let clamp = |units: u64| i64::try_from(units).unwrap_or(i64::MAX);
+        Budget::new(clamp(mem), clamp(steps))
+    }
+}
+
+/// Environment of a closure: the values of its free variables, innermost first.
+#[derive(Debug, Clone, Default)]
+pub struct Env<'a>(Option<Rc<(Value<'a>, Env<'a>)>>);
+
+impl<'a> Env<'a> {
+    fn extend(&self, value: Value<'a>) -> Self {
+        Env(Some(Rc::new((value, self.clone()))))
+    }
+
+    /// Value of the variable with 1-based de Bruijn index `index`.
+    fn lookup(&self, index: usize) -> Option<&Value<'a>> {
+        let mut env = self;
+        for _ in 0..index.checked_sub(1)? {
+            env = &env.0.as_ref()?.1;
+        }
+        env.0.as_ref().map(|frame| &frame.0)
+    }
+}
+
+/// Result of evaluating a term.
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    Con(Rc<Constant>),
+    Delay(&'a Term, Env<'a>),
+    Lambda(&'a Term, Env<'a>),
+    /// Builtin with the number of forces and the arguments applied so far.
+    Builtin(Builtin, usize, Vec<Value<'a>>),
+    Constr(u64, Vec<Value<'a>>),
+}
+
+impl Value<'_> {
+    pub fn con(constant: Constant) -> Self {
+        Value::Con(Rc::new(constant))
+    }
+
+    /// Size in 8-byte words, used to cost builtin calls.
+    pub fn size(&self) -> i64 {
+        match self {
+            Value::Con(constant) => constant_size(constant),
+            _ => 1,
+        }
+    }
+}
+
+/// Number of 8-byte words holding `bytes` bytes, at least one.
+fn words(bytes: u64) -> i64 {
+    i64::try_from(bytes.div_ceil(8).max(1)).unwrap_or(i64::MAX)
+}
+
+fn constant_size(constant: &Constant) -> i64 {
+    match constant {
+        Constant::Integer(i) => words(i.bits().div_ceil(8)),
+        Constant::ByteString(bytes) => words(bytes.len() as u64),
+        Constant::String(s) => (s.chars().count() as i64).max(1),
+        Constant::Unit | Constant::Bool(_) => 1,
+        Constant::List(_, items) => items.iter().map(constant_size).sum::<i64>().max(1),
+        Constant::Pair(a, b) => constant_size(a) + constant_size(b),
+        Constant::Data(data) => data_size(data),
+    }
+}
+
+/// Size of Plutus data: four words per node plus the size of its integers and bytes.
+fn data_size(data: &PlutusData) -> i64 {
+    4 + match data {
+        PlutusData::Constr(_, fields) | PlutusData::List(fields) => {
+            fields.iter().map(data_size).sum()
+        }
+        PlutusData::Map(entries) => entries
+            .iter()
+            .map(|(k, v)| data_size(k) + data_size(v))
+            .sum(),
+        PlutusData::Integer(i) => words(i.bits().div_ceil(8)),
+        PlutusData::Bytes(bytes) => words(bytes.len() as u64),
+    }
+}
+
+/// What to do with the value returned by the term being computed.
+enum Frame<'a> {
+    /// Force it.
+    Force,
+    /// It is a function: compute the argument next.
+    ApplyArg(&'a Term, Env<'a>),
+    /// It is the argument of this function.
+    ApplyFun(Value<'a>),
+    /// It is a function: apply it to this value.
+    ApplyTo(Value<'a>),
+    /// It is the next field of a constructor; the remaining fields follow.
+    Constr(u64, Vec<Value<'a>>, &'a [Term], Env<'a>),
+    /// It is the scrutinee of a case expression with these branches.
+    Case(&'a [Term], Env<'a>),
+}
+
+/// Next move of the machine.
+enum Step<'a> {
+    Compute(&'a Term, Env<'a>),
+    Return(Value<'a>),
+}
+
+/// CEK machine state shared by all steps of one evaluation.
+pub struct Machine<'c> {
+    costs: &'c CostModel,
+    budget: Budget,
+    remaining: Budget,
+    logs: Vec<String>,
+}
+
+impl<'c> Machine<'c> {
+    pub fn new(costs: &'c CostModel, budget: Budget) -> Self {
+        Machine {
+            costs,
+            budget,
+            remaining: budget,
+            logs: Vec::new(),
+        }
+    }
+
+    /// Evaluate a closed term.
+    pub fn run(mut self, term: &Term) -> Result<Evaluation, PlutusError> {
+        self.spend(self.costs.startup)?;
+        let mut stack = Vec::new();
+        let mut step = Step::Compute(term, Env::default());
+        loop {
+            step = match step {
+                Step::Compute(term, env) => self.compute(term, env, &mut stack)?,
+                Step::Return(value) => match stack.pop() {
+                    Some(frame) => self.return_to(frame, value, &mut stack)?,
+                    None => break,
+                },
+            };
+        }
+        Ok(Evaluation {
+            consumed: Budget::new(
+                self.budget.mem - self.remaining.mem,
+                self.budget.steps - self.remaining.steps,
+            ),
+            logs: self.logs,
+        })
+    }
+
+    fn spend(&mut self, cost: Budget) -> Result<(), PlutusError> {
+        self.remaining.mem = self.remaining.mem.saturating_sub(cost.mem);
+        self.remaining.steps = self.remaining.steps.saturating_sub(cost.steps);
+        if self.remaining.mem < 0 || self.remaining.steps < 0 {
+            return Err(PlutusError::BudgetExceeded {
+                budget: self.budget,
+            });
+        }
+        Ok(())
+    }
+
+    fn compute<'a>(
+        &mut self,
+        term: &'a Term,
+        env: Env<'a>,
+        stack: &mut Vec<Frame<'a>>,
+    ) -> Result<Step<'a>, PlutusError> {
+        let costs = self.costs;
+        self.spend(match term {
+            Term::Var(_) => costs.var,
+            Term::Delay(_) => costs.delay,
+            Term::Lambda(_) => costs.lambda,
+            Term::Apply(..) | Term::Constr(..) | Term::Case(..) => costs.apply,
+            Term::Constant(_) => costs.constant,
+            Term::Force(_) => costs.force,
+            Term::Error => Budget::default(),
+            Term::Builtin(_) => costs.builtin,
+        })?;
+        let value = match term {
+            Term::Var(index) => env.lookup(*index).cloned().ok_or_else(|| {
+                PlutusError::EvaluationFailure(format!("unbound variable {}", index))
+            })?,
+            Term::Delay(body) => Value::Delay(body, env),
+            Term::Lambda(body) => Value::Lambda(body, env),
+            Term::Apply(fun, arg) => {
+                stack.push(Frame::ApplyArg(arg, env.clone()));
+                return Ok(Step::Compute(fun, env));
+            }
+            Term::Constant(constant) => Value::Con(constant.clone()),
+            Term::Force(body) => {
+                stack.push(Frame::Force);
+                return Ok(Step::Compute(body, env));
+            }
+            Term::Error => return Err(PlutusError::ExplicitError),
+            Term::Builtin(builtin) => Value::Builtin(*builtin, 0, Vec::new()),
+            Term::Constr(tag, fields) => match fields.split_first() {
+                None => Value::Constr(*tag, Vec::new()),
+                Some((first, rest)) => {
+                    stack.push(Frame::Constr(*tag, Vec::new(), rest, env.clone()));
+                    return Ok(Step::Compute(first, env));
+                }
+            },
+            Term::Case(scrutinee, branches) => {
+                stack.push(Frame::Case(branches, env.clone()));
+                return Ok(Step::Compute(scrutinee, env));
+            }
+        };
+        Ok(Step::Return(value))
+    }
+
+    fn return_to<'a>(
+        &mut self,
+        frame: Frame<'a>,
+        value: Value<'a>,
+        stack: &mut Vec<Frame<'a>>,
+    ) -> Result<Step<'a>, PlutusError> {
+        match frame {
+            Frame::Force => self.force(value),
+            Frame::ApplyArg(arg, env) => {
+                stack.push(Frame::ApplyFun(value));
+                Ok(Step::Compute(arg, env))
+            }
+            Frame::ApplyFun(fun) => self.apply(fun, value),
+            Frame::ApplyTo(arg) => self.apply(value, arg),
+            Frame::Constr(tag, mut done, rest, env) => {
+                done.push(value);
+                match rest.split_first() {
+                    None => Ok(Step::Return(Value::Constr(tag, done))),
+                    Some((next, rest)) => {
+                        stack.push(Frame::Constr(tag, done, rest, env.clone()));
+                        Ok(Step::Compute(next, env))
+                    }
+                }
+            }
+            Frame::Case(branches, env) => {
+                let Value::Constr(tag, fields) = value else {
+                    return Err(failure("case on a non-constructor value"));
+                };
+                let branch = usize::try_from(tag)
+                    .ok()
+                    .and_then(|tag| branches.get(tag))
+                    .ok_or_else(|| failure("no case branch for constructor"))?;
+                stack.extend(fields.into_iter().rev().map(Frame::ApplyTo));
+                Ok(Step::Compute(branch, env))
+            }
+        }
+    }
+
+    fn force<'a>(&mut self, value: Value<'a>) -> Result<Step<'a>, PlutusError> {
+        match value {
+            Value::Delay(body, env) => Ok(Step::Compute(body, env)),
+            Value::Builtin(builtin, forces, args)
+                if forces < builtin.forces() && args.is_empty() =>
+            {
+                Ok(Step::Return(Value::Builtin(builtin, forces + 1, args)))
+            }
+            _ => Err(failure("force of a non-delayed value")),
+        }
+    }
+
+    fn apply<'a>(&mut self, fun: Value<'a>, arg: Value<'a>) -> Result<Step<'a>, PlutusError> {
+        match fun {
+            Value::Lambda(body, env) => Ok(Step::Compute(body, env.extend(arg))),
+            Value::Builtin(builtin, forces, mut args)
+                if forces == builtin.forces() && args.len() < builtin.arity() =>
+            {
+                args.push(arg);
+                if args.len() < builtin.arity() {
+                    return Ok(Step::Return(Value::Builtin(builtin, forces, args)));
+                }
+                let cost = self.costs.builtins.get(&builtin).ok_or_else(|| {
+                    failure("builtin not available in the language of the script")
+                })?;
+                let sizes: Vec<i64> = args.iter().map(Value::size).collect();
+                self.spend(cost.budget(&sizes))?;
+                builtin.call(args, &mut self.logs).map(Step::Return)
+            }
+            Value::Builtin(..) => Err(failure("builtin applied before it is fully forced")),
+            _ => Err(failure("application of a non-function")),
+        }
+    }
+}
+
+fn failure(reason: &str) -> PlutusError {
+    PlutusError::EvaluationFailure(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(i: i64) -> Term {
+        Term::Constant(Rc::new(Constant::Integer(i.into())))
+    }
+
+    fn run(term: &Term, budget: Budget) -> Result<Evaluation, PlutusError> {
+        Machine::new(&CostModel::default(), budget).run(term)
+    }
+
+    #[test]
+    fn budgets_are_charged_and_enforced() {
+        // (\x -> x) 1: startup, then apply, lambda, constant and variable steps.
+        let identity = Term::Lambda(Box::new(Term::Var(1))).apply(int(1));
+        let budget = Budget::new(1_000_000, 1_000_000);
+        let evaluation = run(&identity, budget).unwrap();
+        assert_eq!(evaluation.consumed, Budget::new(500, 92_100));
+        assert_eq!(
+            run(&identity, Budget::new(1_000_000, 92_099)),
+            Err(PlutusError::BudgetExceeded {
+                budget: Budget::new(1_000_000, 92_099)
+            })
+        );
+
+        // An infinite loop runs out of budget instead of hanging.
+        let omega = Term::Lambda(Box::new(Term::Var(1).apply(Term::Var(1))));
+        let looping = omega.clone().apply(omega);
+        assert!(matches!(
+            run(&looping, budget),
+            Err(PlutusError::BudgetExceeded { .. })
+        ));
+        assert_eq!(run(&Term::Error, budget), Err(PlutusError::ExplicitError));
+        assert!(matches!(
+            run(&int(1).apply(int(2)), budget),
+            Err(PlutusError::EvaluationFailure(_))
+        ));
+    }
+
+    #[test]
+    fn constructors_and_case() {
+        // case (constr 1 [2, 3]) [error, \a b -> subtractInteger a b] = -1
+        let subtract = Term::Lambda(Box::new(Term::Lambda(Box::new(
+            Term::Builtin(Builtin::SubtractInteger)
+                .apply(Term::Var(2))
+                .apply(Term::Var(1)),
+        ))));
+        let term = Term::Case(
+            Box::new(Term::Constr(1, vec![int(2), int(3)])),
+            vec![Term::Error, subtract],
+        );
+        let budget = Budget::new(1_000_000, 10_000_000);
+        assert!(run(&term, budget).is_ok());
+        let missing = Term::Case(Box::new(Term::Constr(2, vec![])), vec![Term::Error]);
+        assert!(matches!(
+            run(&missing, budget),
+            Err(PlutusError::EvaluationFailure(_))
+        ));
+    }
+}
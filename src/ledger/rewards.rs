@@ -0,0 +1,319 @@
+//! Shelley reward calculation.
+//!
+//! At each epoch boundary the ledger pays out the [`RewardUpdate`] computed at the previous
+//! boundary, rotates the stake snapshots (see [`super::stake`]) and computes the next update from
+//! the go snapshot, the blocks each pool made in the epoch that just ended and the fees collected
+//! in it:
+//!
+//! - Monetary expansion releases `ρ·η` of the reserves, where `η` is the number of blocks made
+//!   over the number expected, `(1 - d)·f·epoch_length`, capped at 1 (`η = 1` while `d ≥ 0.8`).
+//!   Together with the fees it forms the reward pot.
+//! - The treasury takes `τ` of the pot; the rest, `R`, is shared among the pools.
+//! - A pool with relative stake `σ` and relative pledge `s` (relative to the circulating supply
+//!   and capped at `z0 = 1/n_opt` as `σ'` and `s'`) earns at most
+//!   `R / (1 + a0) · (σ' + s'·a0·(σ' - s'·(z0 - σ')/z0)/z0)`, and nothing if its owner does not
+//!   hold the pledge. That maximum is scaled by the pool's apparent performance: its share of the
+//!   blocks made over its share of the active stake (1 while `d ≥ 0.8`).
+//! - The pool's reward account takes the pool's cost, then the margin of the rest, then the
+//!   owner's share of what is left; every other member takes its share of what is left in
+//!   proportion to its stake.
+//! - Reward accounts are credited at the next boundary, when the lovelace not paid out returns to
+//!   the reserves. Rewards of stake credentials that are no longer registered go to the treasury.
+//!
+//! The pot and the treasury cut are computed exactly; pool and member rewards use floating point
+//! and are rounded down.
+
+use super::{Credential, LedgerState, ProtocolParameters, Rational};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Total lovelace supply: the reserves plus everything in circulation.
+pub const MAX_LOVELACE_SUPPLY: u64 = 45_000_000_000_000_000;
+
+/// Rewards computed at an epoch boundary and paid at the next one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardUpdate {
+    /// Lovelace released from the reserves.
+    pub expansion: u64,
+    /// Fees collected in the epoch.
+    pub fees: u64,
+    /// Treasury cut of the reward pot.
+    pub treasury: u64,
+    /// Rewards per reward account.
+    pub rewards: HashMap<Credential, u64>,
+}
+
+impl RewardUpdate {
+    /// Reward pot: the monetary expansion plus the fees.
+    pub fn pot(&self) -> u64 {
+        self.expansion + self.fees
+    }
+
+    /// Lovelace of the pot neither paid to reward accounts nor to the treasury.
+    pub fn undistributed(&self) -> u64 {
+        let paid: u64 = self.rewards.values().sum();
+        self.pot().saturating_sub(self.treasury + paid)
+    }
+}
+
+/// `amount · r`, rounded down.
+fn scale(amount: u64, r: Rational) -> u64 {
+    let scaled = u128::from(amount) * u128::from(r.numerator) / u128::from(r.denominator.max(1));
+    u64::try_from(scaled).unwrap_or(u64::MAX)
+}
+
+/// Blocks expected in an epoch outside the slots reserved for genesis delegates:
+/// `(1 - d)·f·epoch_length`, rounded down.
+fn expected_blocks(params: &ProtocolParameters) -> u64 {
+    let (f, d) = (params.active_slot_coeff, params.decentralisation);
+    let numerator = u128::from(params.epoch_length)
+        * u128::from(f.numerator)
+        * u128::from(d.denominator.saturating_sub(d.numerator));
+    let denominator = u128::from(f.denominator.max(1)) * u128::from(d.denominator.max(1));
+    u64::try_from(numerator / denominator).unwrap_or(u64::MAX)
+}
+
+/// Maximal reward of a pool with capped relative stake `sigma` and capped relative pledge `s`
+/// out of `r`, with saturation point `z0` and pledge influence `a0`.
+fn max_pool_reward(r: f64, sigma: f64, s: f64, z0: f64, a0: f64) -> f64 {
+    r / (1.0 + a0) * (sigma + s * a0 * (sigma - s * (z0 - sigma) / z0) / z0)
+}
+
+impl LedgerState {
+    /// Rewards of the epoch that just ended, computed from the go snapshot, the blocks made and
+    /// the fee pot.
+    pub fn reward_update(&self) -> RewardUpdate {
+        let params = &self.params;
+        let overlay = params.decentralisation.to_f64() >= 0.8;
+        let blocks: u64 = self.blocks_made.values().sum();
+        let expected = expected_blocks(params);
+        let expansion = if overlay || blocks >= expected {
+            scale(self.reserves, params.monetary_expansion)
+        } else {
+            let eta = Rational::new(blocks, expected);
+            scale(scale(self.reserves, params.monetary_expansion), eta)
+        };
+        let mut update = RewardUpdate {
+            expansion,
+            fees: self.fees,
+            ..Default::default()
+        };
+        update.treasury = scale(update.pot(), params.treasury_cut);
+        let r = (update.pot() - update.treasury) as f64;
+
+        let go = &self.snapshots.go;
+        let circulation = MAX_LOVELACE_SUPPLY.saturating_sub(self.reserves) as f64;
+        let active = go.active_stake() as f64;
+        let z0 = 1.0 / params.n_opt.max(1) as f64;
+        let a0 = params.pool_influence.to_f64();
+        for (pool_id, pool) in &go.pools {
+            let pool_stake = go.pool_stake(pool_id);
+            let owner_stake = match go.delegations.get(&pool.owner) {
+                Some(delegated_to) if delegated_to == pool_id => {
+                    go.stake.get(&pool.owner).copied().unwrap_or(0)
+                }
+                _ => 0,
+            };
+            if pool_stake == 0 || circulation == 0.0 || owner_stake < pool.pledge {
+                continue;
+            }
+            let sigma = (pool_stake as f64 / circulation).min(z0);
+            let s = (pool.pledge as f64 / circulation).min(z0);
+            let performance = if overlay {
+                1.0
+            } else {
+                let made = self.blocks_made.get(pool_id).copied().unwrap_or(0);
+                (made as f64 / blocks.max(1) as f64) / (pool_stake as f64 / active)
+            };
+            let pool_reward = (performance * max_pool_reward(r, sigma, s, z0, a0)) as u64;
+            let mut credit = |account: &Credential, amount: u64| {
+                if amount > 0 {
+                    *update.rewards.entry(*account).or_insert(0) += amount;
+                }
+            };
+            if pool_reward <= pool.cost {
+                credit(&pool.reward_account, pool_reward);
+                continue;
+            }
+            let rest = (pool_reward - pool.cost) as f64;
+            let margin = pool.margin.clamp(0.0, 1.0);
+            let share = |stake: u64| stake as f64 / pool_stake as f64;
+            let leader = rest * (margin + (1.0 - margin) * share(owner_stake));
+            credit(&pool.reward_account, pool.cost + leader as u64);
+            for (member, delegated_to) in &go.delegations {
+                if delegated_to != pool_id || *member == pool.owner {
+                    continue;
+                }
+                let stake = go.stake.get(member).copied().unwrap_or(0);
+                credit(member, (rest * (1.0 - margin) * share(stake)) as u64);
+            }
+        }
+        update
+    }
+
+    /// Credit `amount` to the reward account of a registered stake credential, or to the
+    /// treasury if `account` is not registered.
+    pub(super) fn credit_reward(&mut self, account: Credential, amount: u64) {
+        if self.stake_keys.contains_key(&account) {
+            *self.rewards.entry(account).or_insert(0) += amount;
+        } else {
+            self.treasury += amount;
+        }
+    }
+
+    /// Pay the pending reward update: credit the reward accounts (rewards of unregistered
+    /// accounts go to the treasury) and the treasury, and return what was not paid out to the
+    /// reserves.
+    pub fn pay_rewards(&mut self) {
+        let update = std::mem::take(&mut self.reward_update);
+        self.reserves += update.undistributed();
+        self.treasury += update.treasury;
+        for (account, amount) in update.rewards {
+            self.credit_reward(account, amount);
+        }
+    }
+
+    /// Compute the rewards of the epoch that just ended and hold them until the next boundary:
+    /// the expansion leaves the reserves, the fee pot is emptied and the block counts restart.
+    pub fn schedule_rewards(&mut self) {
+        let update = self.reward_update();
+        self.reserves -= update.expansion;
+        self.fees = 0;
+        self.blocks_made.clear();
+        self.reward_update = update;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Address, Network, StakePool, StakePoolRegistration, TxOutput};
+    use super::*;
+
+    /// Key hash credential spelling `name`.
+    fn cred(name: &str) -> Credential {
+        let mut hash = [0u8; 28];
+        hash[..name.len()].copy_from_slice(name.as_bytes());
+        Credential::KeyHash(hash)
+    }
+
+    /// Base address paying and staking with `cred(name)`.
+    fn addr(name: &str) -> Address {
+        Address::base(Network::Testnet, cred(name), cred(name))
+    }
+
+    #[test]
+    fn pledge_raises_the_maximal_pool_reward() {
+        let (z0, a0) = (0.5, 0.3);
+        assert!((max_pool_reward(1000.0, 0.5, 0.5, z0, a0) - 500.0).abs() < 1e-9);
+        assert!((max_pool_reward(1000.0, 0.5, 0.0, z0, a0) - 1000.0 / 2.6).abs() < 1e-9);
+        assert_eq!(max_pool_reward(1000.0, 0.25, 0.0, z0, 0.0), 250.0);
+    }
+
+    #[test]
+    fn rewards_follow_stake_performance_margin_and_pledge() {
+        let mut state = LedgerState::default();
+        state.params.epoch_length = 260;
+        state.params.n_opt = 2;
+        state.params.pool_influence = Rational::new(0, 1);
+        // 800k ADA in circulation; pool1 is saturated at half of it.
+        state.reserves = MAX_LOVELACE_SUPPLY - 800_000_000_000;
+        state.fees = 2_400_000;
+        let pools = [
+            ("pool1", "owner1", 100_000_000_000, 0.5),
+            ("pool2", "owner2", 200_000_000_000, 0.0),
+            ("pool3", "owner3", 100_000_000_000, 0.0),
+        ];
+        for (pool_id, owner, pledge, margin) in pools {
+            let registration = StakePoolRegistration {
+                pool_id: pool_id.to_string(),
+                vrf_keyhash: [0u8; 32],
+                owner: cred(owner),
+                pledge,
+                cost: 340_000_000,
+                margin,
+                reward_account: cred(&format!("{}_reward", pool_id)),
+            };
+            let pool = StakePool {
+                registration,
+                active: true,
+                retirement_epoch: None,
+            };
+            state.stake_pools.insert(pool_id.to_string(), pool);
+        }
+        // pool3's owner holds less than its pledge.
+        let holdings = [
+            ("owner1", "pool1", 100_000_000_000),
+            ("alice", "pool1", 300_000_000_000),
+            ("owner2", "pool2", 200_000_000_000),
+            ("owner3", "pool3", 50_000_000_000),
+        ];
+        for (index, (holder, pool_id, amount)) in holdings.into_iter().enumerate() {
+            let output = TxOutput {
+                address: addr(holder),
+                amount,
+                ..Default::default()
+            };
+            state.utxos.insert(([1u8; 32], index as u32), output);
+            state.delegations.insert(cred(holder), pool_id.to_string());
+        }
+        // The snapshot becomes go at the boundary. All 13 expected blocks were made, by each pool
+        // in proportion to its stake.
+        state.snapshots.set = state.stake_snapshot();
+        for (pool_id, blocks) in [("pool1", 8), ("pool2", 4), ("pool3", 1)] {
+            state.blocks_made.insert(pool_id.to_string(), blocks);
+        }
+
+        let reserves = state.reserves;
+        state.new_epoch(1);
+        let update = state.reward_update.clone();
+        assert_eq!(update.expansion, reserves * 3 / 1_000);
+        assert_eq!(update.fees, 2_400_000);
+        assert_eq!(update.treasury, update.pot() / 5);
+        assert_eq!(state.reserves, reserves - update.expansion);
+        assert_eq!(state.fees, 0);
+        assert!(state.blocks_made.is_empty());
+        // Rewards are only credited at the next boundary.
+        assert!(state.rewards.is_empty());
+
+        let r = update.pot() - update.treasury;
+        // pool1 earns half of R: after its cost, the margin and the owner's quarter of the stake
+        // make 5/8 of the rest for the pool, and Alice's three quarters 3/8.
+        let rest = r / 2 - 340_000_000;
+        assert_eq!(
+            update.rewards[&cred("pool1_reward")],
+            340_000_000 + rest * 5 / 8
+        );
+        assert_eq!(update.rewards[&cred("alice")], rest * 3 / 8);
+        // pool2 is owned by its only member and earns a quarter of R.
+        assert_eq!(update.rewards[&cred("pool2_reward")], r / 4);
+        assert!(!update.rewards.contains_key(&cred("pool3_reward")));
+        assert!(!update.rewards.contains_key(&cred("owner1")));
+
+        // Rewards of the unregistered pool2 reward account go to the treasury.
+        for account in ["alice", "pool1_reward"] {
+            state.stake_keys.insert(cred(account), 2_000_000);
+        }
+        let treasury = state.treasury;
+        let reserves = state.reserves;
+        state.new_epoch(2);
+        assert_eq!(state.rewards[&cred("alice")], rest * 3 / 8);
+        assert!(!state.rewards.contains_key(&cred("pool2_reward")));
+        assert_eq!(
+            state.treasury,
+            treasury + update.treasury + update.rewards[&cred("pool2_reward")]
+        );
+        assert_eq!(state.reserves, reserves + update.undistributed());
+    }
+
+    #[test]
+    fn missing_blocks_reduce_expansion_and_pool_rewards() {
+        let mut state = LedgerState::default();
+        state.params.epoch_length = 100;
+        state.reserves = 10_000_000_000;
+        // 2 of the 5 expected blocks.
+        state.blocks_made.insert("pool1".to_string(), 2);
+        assert_eq!(state.reward_update().expansion, 12_000_000);
+        state.params.decentralisation = Rational::new(4, 5);
+        assert_eq!(state.reward_update().expansion, 30_000_000);
+    }
+}
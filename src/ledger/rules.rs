@@ -0,0 +1,447 @@
+//! Shelley-style UTXO transition rules.
+//!
+//! A transaction is checked against the ledger state at the slot of the block it goes into:
+//! - it spends at least one input, no input twice, and only outputs in the UTXO set that are
+//!   locked by a payment credential (not by a Byron address); its reference inputs, which it
+//!   reads but does not spend, are in the UTXO set too;
+//! - the slot lies in its validity interval `[validity_start, ttl)`;
+//! - its CBOR encoding is at most `max_tx_size` bytes;
+//! - it pays at least the minimum fee `min_fee_a * size + min_fee_b`, plus the price of the
+//!   execution units of its Plutus witnesses;
+//! - every output pays to a payment address (not a reward address) and holds at least
+//!   `min_utxo_value` lovelace;
+//! - every withdrawal drains its reward account exactly;
+//! - every script locking one of its inputs, a reward account it withdraws from or the
+//!   credential of one of its certificates, and every policy it mints or burns under, has its
+//!   script in the transaction or in an output it spends or references, and native scripts are
+//!   satisfied by the transaction's signers and validity interval;
+//! - value, lovelace and every native token, is preserved:
+//!   `inputs + withdrawals + refunds + minted = outputs + fee + deposits + burned`;
+//! - if it carries Plutus witnesses, it has between one and `max_collateral_inputs` key-locked
+//!   collateral inputs, and the collateral minus its return is lovelace covering
+//!   `collateral_percentage` percent of the fee.
+//! - every witness signature verifies against the transaction id, and every required signer
+//!   (see [`super::witness`]) has a witness.
+//!
+//! Plutus scripts are then run (phase-2 validation, see [`super::plutus`]). A transaction whose
+//! scripts fail must be marked with `is_valid = false`: applying it only moves its collateral,
+//! minus the collateral return, to the fee pot. Update proposals, certificates, governance
+//! proposals and votes (see [`super::governance`]) are checked when the transaction is applied, in
+//! the epoch of its block.
+
+use super::value::split_mint;
+use super::{
+    Address, Certificate, Credential, LedgerError, LedgerState, ScriptRef, Transaction, Value,
+};
+use std::collections::{BTreeSet, HashSet};
+
+/// Check that the script with hash `script_hash` is in the transaction or among the
+/// `reference_scripts` it can use and, for a native script, that the transaction satisfies it
+/// with the keys of its witnesses, `signers`. Plutus scripts are run separately, by
+/// [`LedgerState::validate_plutus_scripts`]. `missing` is the error when the script is absent.
+fn check_script(
+    tx: &Transaction,
+    reference_scripts: &[&ScriptRef],
+    signers: &BTreeSet<String>,
+    script_hash: &str,
+    missing: impl FnOnce() -> LedgerError,
+) -> Result<(), LedgerError> {
+    let native = tx
+        .native_scripts
+        .iter()
+        .chain(reference_scripts.iter().filter_map(|s| match s {
+            ScriptRef::Native(script) => Some(script),
+            ScriptRef::Plutus(_) => None,
+        }))
+        .find(|s| s.hash() == script_hash);
+    if let Some(script) = native {
+        if script.evaluate(signers, tx.validity_start, tx.ttl) {
+            return Ok(());
+        }
+        return Err(LedgerError::NativeScriptFailed {
+            script_hash: script_hash.to_string(),
+        });
+    }
+    let plutus = tx
+        .plutus_witnesses
+        .iter()
+        .any(|w| w.script.hash() == script_hash)
+        || reference_scripts
+            .iter()
+            .any(|s| matches!(s, ScriptRef::Plutus(_)) && s.hash() == script_hash);
+    if plutus {
+        return Ok(());
+    }
+    Err(missing())
+}
+
+/// Sum of values, failing on overflow.
+fn sum(values: impl IntoIterator<Item = Value>) -> Result<Value, LedgerError> {
+    values
+        .into_iter()
+        .try_fold(Value::default(), |total, value| {
+            total.checked_add(&value).ok_or(LedgerError::ValueOverflow)
+        })
+}
+
+impl LedgerState {
+    /// Deposits a transaction pays: one key deposit per stake credential it registers, one pool
+    /// deposit per pool it registers for the first time, the deposits of the DReps it registers
+    /// and those of the governance actions it proposes. Re-registering an existing pool updates
+    /// its parameters without a new deposit.
+    pub fn deposits(&self, tx: &Transaction) -> u64 {
+        let new_pools: HashSet<&str> = tx
+            .certificates
+            .iter()
+            .filter_map(|cert| match cert {
+                Certificate::PoolRegistration(reg)
+                    if !self.stake_pools.contains_key(&reg.pool_id) =>
+                {
+                    Some(reg.pool_id.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        let certs = tx.certificates.iter().map(|cert| match cert {
+            Certificate::StakeRegistration(_) => self.params.key_deposit,
+            Certificate::DRepRegistration(reg) => reg.deposit,
+            _ => 0,
+        });
+        let proposals = tx.proposal_procedures.iter().map(|p| p.deposit);
+        certs.chain(proposals).fold(
+            new_pools.len() as u64 * self.params.pool_deposit,
+            |total, deposit| total.saturating_add(deposit),
+        )
+    }
+
+    /// Deposits a transaction gets back: the deposits paid for the stake credentials it
+    /// deregisters and those of the DReps it retires.
+    pub fn refunds(&self, tx: &Transaction) -> u64 {
+        tx.certificates
+            .iter()
+            .map(|cert| match cert {
+                Certificate::StakeDeregistration(credential) => self
+                    .stake_keys
+                    .get(credential)
+                    .copied()
+                    .unwrap_or(self.params.key_deposit),
+                Certificate::DRepDeregistration(dereg) => dereg.deposit,
+                _ => 0,
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Scripts held by the outputs a transaction spends or references, which it can use without
+    /// carrying them. Inputs that are not in the UTXO set are ignored.
+    pub fn reference_scripts<'a>(&'a self, tx: &'a Transaction) -> Vec<&'a ScriptRef> {
+        tx.inputs
+            .iter()
+            .chain(&tx.reference_inputs)
+            .filter_map(|input| self.utxos.get(&(input.prev_tx, input.index)))
+            .filter_map(|output| output.script_ref.as_ref())
+            .collect()
+    }
+
+    /// Check a transaction against the UTXO rules at `slot`, without changing the state.
+    pub fn validate_utxo(&self, tx: &Transaction, slot: u64) -> Result<(), LedgerError> {
+        if tx.inputs.is_empty() {
+            return Err(LedgerError::InputSetEmpty);
+        }
+        let mut spent = HashSet::new();
+        let mut inputs = Vec::with_capacity(tx.inputs.len());
+        let mut locked = Vec::new();
+        for input in &tx.inputs {
+            if !spent.insert((input.prev_tx, input.index)) {
+                return Err(LedgerError::DuplicateInput(input.clone()));
+            }
+            let output = self
+                .utxos
+                .get(&(input.prev_tx, input.index))
+                .ok_or_else(|| LedgerError::BadInput(input.clone()))?;
+            let credential = output
+                .address
+                .payment_credential()
+                .ok_or_else(|| LedgerError::NoPaymentCredential(input.clone()))?;
+            inputs.push(Value::from(output));
+            locked.extend(credential.script_hash());
+        }
+        for input in &tx.reference_inputs {
+            if !self.utxos.contains_key(&(input.prev_tx, input.index)) {
+                return Err(LedgerError::BadInput(input.clone()));
+            }
+        }
+        if tx.validity_start.is_some_and(|start| slot < start)
+            || tx.ttl.is_some_and(|ttl| slot >= ttl)
+        {
+            return Err(LedgerError::OutsideValidityInterval {
+                slot,
+                start: tx.validity_start,
+                ttl: tx.ttl,
+            });
+        }
+        let size = tx.size();
+        if size > self.params.max_tx_size {
+            return Err(LedgerError::TxTooLarge {
+                size,
+                max: self.params.max_tx_size,
+            });
+        }
+        let execution_units = tx
+            .plutus_witnesses
+            .iter()
+            .fold((0u64, 0u64), |(mem, steps), w| {
+                (
+                    mem.saturating_add(w.execution_units.0),
+                    steps.saturating_add(w.execution_units.1),
+                )
+            });
+        let min_fee = self
+            .params
+            .min_fee(size)
+            .saturating_add(self.params.script_fee(execution_units));
+        if tx.fee < min_fee {
+            return Err(LedgerError::FeeTooSmall {
+                fee: tx.fee,
+                min: min_fee,
+            });
+        }
+        // The collateral return is added after the outputs.
+        if let Some(index) = tx
+            .outputs
+            .iter()
+            .chain(&tx.collateral_return)
+            .position(|o| matches!(o.address, Address::Reward { .. }))
+        {
+            return Err(LedgerError::OutputToRewardAddress { index });
+        }
+        let min = self.params.min_utxo_value;
+        if let Some((index, output)) = tx.outputs.iter().enumerate().find(|(_, o)| o.amount < min) {
+            return Err(LedgerError::OutputTooSmall {
+                index,
+                amount: output.amount,
+                min,
+            });
+        }
+        for (account, &amount) in &tx.withdrawals {
+            let balance = self.rewards.get(account).copied();
+            if balance != Some(amount) {
+                return Err(LedgerError::IncorrectWithdrawal {
+                    account: *account,
+                    amount,
+                    balance,
+                });
+            }
+        }
+        let reference_scripts = self.reference_scripts(tx);
+        let signers = tx.signers();
+        for script_hash in &locked {
+            check_script(tx, &reference_scripts, &signers, script_hash, || {
+                LedgerError::MissingScript {
+                    script_hash: script_hash.to_string(),
+                }
+            })?;
+        }
+        let authorizing = tx.withdrawals.keys().chain(
+            tx.certificates
+                .iter()
+                .filter_map(Certificate::authorizing_credential),
+        );
+        for script_hash in authorizing.filter_map(Credential::script_hash) {
+            check_script(tx, &reference_scripts, &signers, &script_hash, || {
+                LedgerError::MissingScript {
+                    script_hash: script_hash.clone(),
+                }
+            })?;
+        }
+        for policy_id in tx.mint.keys() {
+            check_script(tx, &reference_scripts, &signers, policy_id, || {
+                LedgerError::MissingMintingPolicy {
+                    policy_id: policy_id.clone(),
+                }
+            })?;
+        }
+        let (minted, burned) = split_mint(&tx.mint);
+        let withdrawn = tx
+            .withdrawals
+            .values()
+            .map(|&amount| Value::lovelace(amount));
+        let consumed = sum(inputs
+            .into_iter()
+            .chain(withdrawn)
+            .chain([Value::lovelace(self.refunds(tx)), minted]))?;
+        let produced = sum(tx.outputs.iter().map(Value::from).chain([
+            Value::lovelace(tx.fee),
+            Value::lovelace(self.deposits(tx)),
+            burned,
+        ]))?;
+        if consumed != produced {
+            return Err(LedgerError::ValueNotConserved { consumed, produced });
+        }
+        if !tx.plutus_witnesses.is_empty() {
+            self.validate_collateral(tx)?;
+        }
+        self.validate_witnesses(tx, &signers)
+    }
+
+    /// Check that the witnesses of a transaction, whose keys are `signers`, are valid signatures
+    /// of its id and cover its required signers.
+    fn validate_witnesses(
+        &self,
+        tx: &Transaction,
+        signers: &BTreeSet<String>,
+    ) -> Result<(), LedgerError> {
+        let tx_id = tx.id();
+        if let Some(witness) = tx.witnesses.iter().find(|w| !w.verify(&tx_id)) {
+            return Err(LedgerError::InvalidSignature {
+                key_hash: witness.key_hash(),
+            });
+        }
+        match self
+            .required_signers(tx)
+            .into_iter()
+            .find(|key_hash| !signers.contains(key_hash))
+        {
+            Some(key_hash) => Err(LedgerError::MissingVKeyWitness { key_hash }),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the collateral of a transaction running Plutus scripts.
+    fn validate_collateral(&self, tx: &Transaction) -> Result<(), LedgerError> {
+        if tx.collateral.is_empty() {
+            return Err(LedgerError::NoCollateralInputs);
+        }
+        let max = self.params.max_collateral_inputs;
+        if tx.collateral.len() > max {
+            return Err(LedgerError::TooManyCollateralInputs {
+                count: tx.collateral.len(),
+                max,
+            });
+        }
+        let provided = self.collateral(tx)?;
+        let required =
+            (u128::from(tx.fee) * u128::from(self.params.collateral_percentage)).div_ceil(100);
+        if u128::from(provided) < required {
+            return Err(LedgerError::InsufficientCollateral {
+                provided,
+                required: u64::try_from(required).unwrap_or(u64::MAX),
+            });
+        }
+        Ok(())
+    }
+
+    /// Lovelace a transaction forfeits if its scripts fail: its key-locked collateral inputs
+    /// minus the collateral return, which must leave no tokens.
+    fn collateral(&self, tx: &Transaction) -> Result<u64, LedgerError> {
+        let mut inputs = Vec::with_capacity(tx.collateral.len());
+        for input in &tx.collateral {
+            let output = self
+                .utxos
+                .get(&(input.prev_tx, input.index))
+                .ok_or_else(|| LedgerError::BadInput(input.clone()))?;
+            match output.address.payment_credential() {
+                Some(credential) if credential.is_script() => {
+                    return Err(LedgerError::CollateralLockedByScript(input.clone()));
+                }
+                Some(_) => {}
+                None => return Err(LedgerError::NoPaymentCredential(input.clone())),
+            }
+            inputs.push(Value::from(output));
+        }
+        let collateral = sum(inputs)?;
+        let returned = tx
+            .collateral_return
+            .as_ref()
+            .map(Value::from)
+            .unwrap_or_default();
+        match collateral.checked_sub(&returned) {
+            Some(forfeited) if forfeited.assets.is_empty() => Ok(forfeited.coin),
+            _ => Err(LedgerError::CollateralNotBalanced {
+                collateral,
+                returned,
+            }),
+        }
+    }
+
+    /// Apply a transaction whose scripts fail: spend its collateral inputs, add the collateral
+    /// return after its outputs and move the rest of the collateral to the fee pot.
+    fn collect_collateral(&mut self, tx: &Transaction) -> Result<(), LedgerError> {
+        let forfeited = self.collateral(tx)?;
+        for input in &tx.collateral {
+            self.utxos.remove(&(input.prev_tx, input.index));
+        }
+        if let Some(output) = &tx.collateral_return {
+            let index = tx.outputs.len() as u32;
+            self.utxos.insert((tx.id(), index), output.clone());
+        }
+        self.fees += forfeited;
+        Ok(())
+    }
+
+    /// Validate a transaction at `slot` and apply it. A transaction marked invalid only has its
+    /// collateral collected, and only if its scripts do fail. Otherwise its update proposal is
+    /// recorded, withdrawn reward accounts are drained (so that a transaction can empty an account
+    /// and deregister it), its certificates are applied in `epoch`, its governance actions and
+    /// votes are recorded, deposits are taken and refunds paid, the
+    /// fee goes to the fee pot, inputs are spent and outputs are added under the transaction id.
+    ///
+    /// If a certificate, proposal or vote is rejected, the ones before it stay applied, so callers
+    /// that need atomicity apply the transaction to a copy of the state.
+    pub fn apply_tx(&mut self, tx: &Transaction, slot: u64, epoch: u64) -> Result<(), LedgerError> {
+        self.validate_utxo(tx, slot)?;
+        match self.validate_plutus_scripts(tx) {
+            Ok(()) if tx.is_valid => {}
+            Ok(()) => return Err(LedgerError::ValidationTagMismatch),
+            Err(LedgerError::PlutusScriptFailed { .. }) if !tx.is_valid => {
+                return self.collect_collateral(tx);
+            }
+            Err(error) => return Err(error),
+        }
+        if let Some(update) = &tx.update {
+            self.validate_update(update, epoch)?;
+            self.register_update(update);
+        }
+        let deposits = self.deposits(tx);
+        let refunds = self.refunds(tx);
+        for account in tx.withdrawals.keys() {
+            self.rewards.insert(*account, 0);
+        }
+        for cert in &tx.certificates {
+            self.apply_certificate(cert, epoch)
+                .map_err(LedgerError::InvalidCertificate)?;
+        }
+        let tx_id = tx.id();
+        self.propose(tx, tx_id, epoch)?;
+        self.cast_votes(tx)?;
+        self.deposited = (self.deposited + deposits).saturating_sub(refunds);
+        self.fees += tx.fee;
+        for input in &tx.inputs {
+            self.utxos.remove(&(input.prev_tx, input.index));
+        }
+        for (idx, output) in tx.outputs.iter().enumerate() {
+            self.utxos.insert((tx_id, idx as u32), output.clone());
+        }
+        Ok(())
+    }
+
+    /// Apply a transaction the way a block producer includes it: if its Plutus scripts fail, it
+    /// is applied marked invalid, forfeiting its collateral. Returns whether it was applied as
+    /// valid.
+    pub fn apply_tx_or_collateral(
+        &mut self,
+        tx: &Transaction,
+        slot: u64,
+        epoch: u64,
+    ) -> Result<bool, LedgerError> {
+        match self.apply_tx(tx, slot, epoch) {
+            Err(LedgerError::PlutusScriptFailed { .. }) if tx.is_valid => {
+                let invalid = Transaction {
+                    is_valid: false,
+                    ..tx.clone()
+                };
+                self.apply_tx(&invalid, slot, epoch).map(|()| false)
+            }
+            result => result.map(|()| tx.is_valid),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Stake distribution snapshots taken at epoch boundaries.
+//!
+//! A [`StakeSnapshot`] aggregates the lovelace held by unspent outputs and reward accounts per
+//! stake credential, together with the pool delegations in effect, the parameters of the active
+//! pools and the resulting stake of each. An output counts towards the stake credential of its
+//! address: base addresses carry one, while enterprise, pointer and Byron addresses carry no stake
+//! (pointers are not resolved, as in Conway).
+//!
+//! Three snapshots are kept and rotated at every epoch boundary, as in the Shelley ledger: the
+//! one taken at the boundary into epoch `e` is *mark*; a boundary later it becomes *set*, the
+//! distribution slot leaders of epoch `e + 2` are elected with; a boundary after that it becomes
+//! *go*, the distribution the rewards paid at the start of epoch `e + 3` are computed from.
+//! Governance ratification uses the freshest snapshot, *mark*.
+
+use super::{Credential, LedgerState, StakePoolRegistration};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Stake distribution at an epoch boundary.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StakeSnapshot {
+    /// Lovelace per stake credential.
+    pub stake: HashMap<Credential, u64>,
+    /// Pool each stake credential delegates to.
+    pub delegations: HashMap<Credential, String>,
+    /// Stake delegated to each active pool.
+    pub pool_stake: HashMap<String, u64>,
+    /// Parameters of each active pool.
+    pub pools: HashMap<String, StakePoolRegistration>,
+}
+
+impl StakeSnapshot {
+    /// Total stake of all credentials, delegated or not.
+    pub fn total_stake(&self) -> u64 {
+        self.stake.values().sum()
+    }
+
+    /// Stake delegated to active pools.
+    pub fn active_stake(&self) -> u64 {
+        self.pool_stake.values().sum()
+    }
+
+    /// Stake delegated to `pool_id`; 0 for an unknown or inactive pool.
+    pub fn pool_stake(&self, pool_id: &str) -> u64 {
+        self.pool_stake.get(pool_id).copied().unwrap_or(0)
+    }
+
+    /// Share of the active stake delegated to `pool_id`, in `[0, 1]`.
+    pub fn relative_stake(&self, pool_id: &str) -> f64 {
+        let active = self.active_stake();
+        if active == 0 {
+            return 0.0;
+        }
+        self.pool_stake(pool_id) as f64 / active as f64
+    }
+}
+
+/// The mark, set and go snapshots, from the most recent to the oldest.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshots {
+    pub mark: StakeSnapshot,
+    pub set: StakeSnapshot,
+    pub go: StakeSnapshot,
+}
+
+impl LedgerState {
+    /// Stake distribution of the current state.
+    pub fn stake_snapshot(&self) -> StakeSnapshot {
+        let mut stake: HashMap<Credential, u64> = HashMap::new();
+        for output in self.utxos.values() {
+            if let Some(&credential) = output.address.stake_credential() {
+                *stake.entry(credential).or_insert(0) += output.amount;
+            }
+        }
+        for (&account, &balance) in &self.rewards {
+            *stake.entry(account).or_insert(0) += balance;
+        }
+        let mut pool_stake = HashMap::new();
+        for (delegator, pool_id) in &self.delegations {
+            if self.stake_pools.get(pool_id).is_some_and(|p| p.active) {
+                let amount = stake.get(delegator).copied().unwrap_or(0);
+                *pool_stake.entry(pool_id.clone()).or_insert(0) += amount;
+            }
+        }
+        let pools = self
+            .stake_pools
+            .iter()
+            .filter(|(_, pool)| pool.active)
+            .map(|(pool_id, pool)| (pool_id.clone(), pool.registration.clone()))
+            .collect();
+        StakeSnapshot {
+            stake,
+            delegations: self.delegations.clone(),
+            pool_stake,
+            pools,
+        }
+    }
+
+    /// Rotate the snapshots at an epoch boundary: go takes set, set takes mark, and mark is taken
+    /// from the current state.
+    pub fn rotate_snapshots(&mut self) {
+        let mark = self.stake_snapshot();
+        let snapshots = &mut self.snapshots;
+        snapshots.go = std::mem::replace(&mut snapshots.set, std::mem::take(&mut snapshots.mark));
+        snapshots.mark = mark;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        Address, Block, BlockHeader, Ledger, Network, OperationalCert, StakePool,
+        StakePoolRegistration, TxOutput,
+    };
+    use super::*;
+    use crate::tracing::tracers::Tracer;
+
+    /// Key hash credential spelling `name`.
+    fn cred(name: &str) -> Credential {
+        let mut hash = [0u8; 28];
+        hash[..name.len()].copy_from_slice(name.as_bytes());
+        Credential::KeyHash(hash)
+    }
+
+    /// Base address paying and staking with `cred(name)`.
+    fn addr(name: &str) -> Address {
+        Address::base(Network::Testnet, cred(name), cred(name))
+    }
+
+    fn pool(pool_id: &str, active: bool) -> StakePool {
+        StakePool {
+            registration: StakePoolRegistration {
+                pool_id: pool_id.to_string(),
+                vrf_keyhash: [0u8; 32],
+                owner: cred("owner"),
+                pledge: 0,
+                cost: 340_000_000,
+                margin: 0.0,
+                reward_account: cred("owner"),
+            },
+            active,
+            retirement_epoch: None,
+        }
+    }
+
+    fn header(slot: u64, epoch: u64, prev_hash: Option<[u8; 32]>) -> BlockHeader {
+        BlockHeader {
+            slot,
+            epoch,
+            leader: "pool1".to_string(),
+            issuer_vkey: vec![0u8; 32],
+            vrf_vkey: vec![0u8; 32],
+            vrf_proof: vec![0u8; 80],
+            opcert: OperationalCert::default(),
+            kes_signature: vec![0u8; 448],
+            prev_hash,
+            body_hash: [0u8; 32],
+        }
+    }
+
+    #[test]
+    fn snapshots_aggregate_utxos_and_rewards_and_rotate() {
+        let mut state = LedgerState::default();
+        for (index, (address, amount)) in [("alice", 10), ("alice", 5), ("bob", 7), ("carol", 3)]
+            .into_iter()
+            .enumerate()
+        {
+            let output = TxOutput {
+                address: addr(address),
+                amount,
+                ..Default::default()
+            };
+            state.utxos.insert(([1u8; 32], index as u32), output);
+        }
+        // Enterprise addresses carry no stake.
+        let output = TxOutput {
+            address: Address::enterprise(Network::Testnet, cred("alice")),
+            amount: 100,
+            ..Default::default()
+        };
+        state.utxos.insert(([2u8; 32], 0), output);
+        state.rewards.insert(cred("bob"), 1);
+        for (pool_id, active) in [("pool1", true), ("pool2", false)] {
+            state
+                .stake_pools
+                .insert(pool_id.to_string(), pool(pool_id, active));
+        }
+        for (delegator, pool_id) in [("alice", "pool1"), ("bob", "pool1"), ("carol", "pool2")] {
+            state
+                .delegations
+                .insert(cred(delegator), pool_id.to_string());
+        }
+
+        let snapshot = state.stake_snapshot();
+        assert_eq!(snapshot.stake[&cred("alice")], 15);
+        assert_eq!(snapshot.stake[&cred("bob")], 8);
+        assert_eq!(snapshot.total_stake(), 26);
+        // Stake delegated to a retired pool is not active.
+        assert_eq!(snapshot.pool_stake("pool1"), 23);
+        assert_eq!(snapshot.pool_stake("pool2"), 0);
+        assert_eq!(snapshot.pools.keys().collect::<Vec<_>>(), ["pool1"]);
+        assert_eq!(snapshot.relative_stake("pool1"), 1.0);
+
+        state.rotate_snapshots();
+        state.delegations.remove(&cred("bob"));
+        state.rotate_snapshots();
+        assert_eq!(state.snapshots.mark.pool_stake("pool1"), 15);
+        assert_eq!(state.snapshots.set, snapshot);
+        assert_eq!(state.snapshots.go, StakeSnapshot::default());
+        state.rotate_snapshots();
+        assert_eq!(state.snapshots.go, snapshot);
+    }
+
+    #[test]
+    fn leaders_are_elected_with_the_set_snapshot() {
+        let mut ledger = Ledger::new(Tracer::default());
+        ledger.state.params.epoch_length = 10;
+        let snapshots = &mut ledger.state.snapshots;
+        snapshots.mark.pool_stake.insert("pool1".to_string(), 2);
+        snapshots.set.pool_stake.insert("pool1".to_string(), 1);
+        let first = Block::new(1, header(1, 0, None), vec![]);
+        assert_eq!(ledger.try_apply_block(&first), Ok(()));
+        assert_eq!(ledger.leader_stake_distribution(0).pool_stake("pool1"), 1);
+        // Before the ledger crosses into epoch 1, the mark snapshot is the one that becomes set.
+        assert_eq!(ledger.leader_stake_distribution(1).pool_stake("pool1"), 2);
+        let next = Block::new(2, header(12, 1, Some(first.hash())), vec![]);
+        assert_eq!(ledger.try_apply_block(&next), Ok(()));
+        assert_eq!(ledger.leader_stake_distribution(1).pool_stake("pool1"), 2);
+    }
+
+    #[test]
+    fn skipped_epochs_each_rotate_the_snapshots() {
+        let mut ledger = Ledger::new(Tracer::default());
+        ledger.state.params.epoch_length = 10;
+        let state = &mut ledger.state;
+        let output = TxOutput {
+            address: addr("alice"),
+            amount: 10,
+            ..Default::default()
+        };
+        state.utxos.insert(([1u8; 32], 0), output);
+        state
+            .stake_pools
+            .insert("pool1".to_string(), pool("pool1", true));
+        state.delegations.insert(cred("alice"), "pool1".to_string());
+        let snapshot = state.stake_snapshot();
+        let first = Block::new(1, header(1, 0, None), vec![]);
+        assert_eq!(ledger.try_apply_block(&first), Ok(()));
+        // A block of epoch 3 also takes the ledger across the boundaries of the empty epochs 1
+        // and 2, whose snapshots elect its leader.
+        assert_eq!(*ledger.leader_stake_distribution(3), snapshot);
+        let next = Block::new(2, header(30, 3, Some(first.hash())), vec![]);
+        assert_eq!(ledger.try_apply_block(&next), Ok(()));
+        assert_eq!(ledger.state.snapshots.go, snapshot);
+        assert_eq!(*ledger.leader_stake_distribution(3), snapshot);
+    }
+}
@@ -0,0 +1,259 @@
+//! Multi-asset values (Mary era): lovelace plus native tokens.
+
+use super::{Asset, TxOutput};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Add, Sub};
+
+/// Token quantities keyed by policy id, then asset name.
+pub type MultiAsset<T> = BTreeMap<String, BTreeMap<String, T>>;
+
+/// Lovelace plus a bundle of native tokens.
+///
+/// Values are kept normalised: no zero quantities and no empty policies, so two values holding
+/// the same tokens compare equal. Values are partially ordered component-wise: `a <= b` when `b`
+/// holds at least as much of every component as `a`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Value {
+    pub coin: u64,
+    pub assets: MultiAsset<u64>,
+}
+
+impl Value {
+    /// Value holding only lovelace.
+    pub fn lovelace(coin: u64) -> Self {
+        Value {
+            coin,
+            assets: MultiAsset::new(),
+        }
+    }
+
+    /// Value of lovelace plus a list of assets. Repeated tokens are merged, saturating at
+    /// `u64::MAX`, so any asset list has a well-defined value.
+    pub fn from_assets(coin: u64, assets: &[Asset]) -> Self {
+        assets.iter().fold(Value::lovelace(coin), |value, asset| {
+            value.saturating_add(&Value::token(
+                &asset.policy_id,
+                &asset.asset_name,
+                asset.amount,
+            ))
+        })
+    }
+
+    /// Value holding `amount` of a single token and no lovelace.
+    pub fn token(policy_id: &str, asset_name: &str, amount: u64) -> Self {
+        let mut value = Value::default();
+        if amount > 0 {
+            value.assets.insert(
+                policy_id.to_string(),
+                BTreeMap::from([(asset_name.to_string(), amount)]),
+            );
+        }
+        value
+    }
+
+    /// Quantity held of one token.
+    pub fn quantity(&self, policy_id: &str, asset_name: &str) -> u64 {
+        self.assets
+            .get(policy_id)
+            .and_then(|names| names.get(asset_name))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Whether the value holds no lovelace and no tokens.
+    pub fn is_zero(&self) -> bool {
+        self.coin == 0 && self.assets.is_empty()
+    }
+
+    /// Tokens as a list of assets, or `None` for a lovelace-only value.
+    pub fn to_assets(&self) -> Option<Vec<Asset>> {
+        let assets: Vec<Asset> = self
+            .assets
+            .iter()
+            .flat_map(|(policy_id, names)| {
+                names.iter().map(move |(asset_name, &amount)| Asset {
+                    policy_id: policy_id.clone(),
+                    asset_name: asset_name.clone(),
+                    amount,
+                })
+            })
+            .collect();
+        (!assets.is_empty()).then_some(assets)
+    }
+
+    /// Sum of two values, or `None` on overflow.
+    pub fn checked_add(&self, other: &Value) -> Option<Value> {
+        self.combine(other, u64::checked_add)
+    }
+
+    /// Sum of two values, saturating every component at `u64::MAX`.
+    pub fn saturating_add(&self, other: &Value) -> Value {
+        self.combine(other, |a, b| Some(a.saturating_add(b)))
+            .expect("saturating addition cannot fail")
+    }
+
+    /// Add `other` component-wise with `add`, which returns `None` on overflow.
+    fn combine(&self, other: &Value, add: impl Fn(u64, u64) -> Option<u64>) -> Option<Value> {
+        let mut sum = self.clone();
+        sum.coin = add(sum.coin, other.coin)?;
+        for (policy_id, names) in &other.assets {
+            let held = sum.assets.entry(policy_id.clone()).or_default();
+            for (asset_name, &amount) in names {
+                let quantity = held.entry(asset_name.clone()).or_insert(0);
+                *quantity = add(*quantity, amount)?;
+            }
+        }
+        Some(sum)
+    }
+
+    /// Difference of two values, or `None` if `other` holds more of any component than `self`.
+    pub fn checked_sub(&self, other: &Value) -> Option<Value> {
+        let mut diff = self.clone();
+        diff.coin = diff.coin.checked_sub(other.coin)?;
+        for (policy_id, names) in &other.assets {
+            for (asset_name, &amount) in names {
+                let held = diff.assets.get_mut(policy_id)?;
+                let quantity = held.get_mut(asset_name)?;
+                *quantity = quantity.checked_sub(amount)?;
+                if *quantity == 0 {
+                    held.remove(asset_name);
+                }
+            }
+            if diff
+                .assets
+                .get(policy_id)
+                .is_some_and(|held| held.is_empty())
+            {
+                diff.assets.remove(policy_id);
+            }
+        }
+        Some(diff)
+    }
+
+    /// Whether every component of `self` is at most the same component of `other`.
+    fn le_components(&self, other: &Value) -> bool {
+        self.coin <= other.coin
+            && self.assets.iter().all(|(policy_id, names)| {
+                names
+                    .iter()
+                    .all(|(asset_name, &amount)| amount <= other.quantity(policy_id, asset_name))
+            })
+    }
+}
+
+/// Split a transaction's mint field into the tokens it mints and the tokens it burns.
+pub fn split_mint(mint: &MultiAsset<i64>) -> (Value, Value) {
+    let (mut minted, mut burned) = (Value::default(), Value::default());
+    for (policy_id, names) in mint {
+        for (asset_name, &amount) in names {
+            let token = Value::token(policy_id, asset_name, amount.unsigned_abs());
+            if amount > 0 {
+                minted = minted + token;
+            } else {
+                burned = burned + token;
+            }
+        }
+    }
+    (minted, burned)
+}
+
+impl From<&TxOutput> for Value {
+    fn from(output: &TxOutput) -> Self {
+        Value::from_assets(output.amount, output.assets.as_deref().unwrap_or_default())
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.le_components(other), other.le_components(self)) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (false, false) => None,
+        }
+    }
+}
+
+impl Add for Value {
+    type Output = Value;
+
+    /// Panics on overflow, like integer addition.
+    fn add(self, other: Value) -> Value {
+        self.checked_add(&other).expect("value overflow")
+    }
+}
+
+impl Sub for Value {
+    type Output = Value;
+
+    /// Panics if `other` holds more of any component than `self`, like integer subtraction.
+    fn sub(self, other: Value) -> Value {
+        self.checked_sub(&other).expect("value underflow")
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} lovelace", self.coin)?;
+        for (policy_id, names) in &self.assets {
+            for (asset_name, amount) in names {
+                write!(f, " + {} {}.{}", amount, policy_id, asset_name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_and_ordering() {
+        let a = Value::lovelace(10) + Value::token("p", "x", 5);
+        let b = Value::lovelace(4) + Value::token("p", "x", 5) + Value::token("q", "y", 1);
+        assert_eq!(a.quantity("p", "x"), 5);
+        assert_eq!(a.partial_cmp(&b), None);
+        assert!(Value::lovelace(4) < b);
+
+        let sum = a.clone() + b.clone();
+        assert_eq!(sum.coin, 14);
+        assert_eq!(sum.quantity("p", "x"), 10);
+        // Subtracting every token of a policy drops the policy.
+        assert_eq!(sum.clone() - b.clone(), a);
+        assert_eq!(a.checked_sub(&b), None);
+        assert_eq!((b.clone() - Value::token("q", "y", 1)).assets.len(), 1);
+        assert_eq!(
+            Value::lovelace(u64::MAX).checked_add(&Value::lovelace(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn assets_are_merged_and_normalised() {
+        let asset = |name: &str, amount| Asset {
+            policy_id: "p".to_string(),
+            asset_name: name.to_string(),
+            amount,
+        };
+        let value = Value::from_assets(7, &[asset("x", 2), asset("y", 0), asset("x", 3)]);
+        assert_eq!(value, Value::lovelace(7) + Value::token("p", "x", 5));
+        let saturated = Value::from_assets(0, &[asset("x", u64::MAX), asset("x", 1)]);
+        assert_eq!(saturated.quantity("p", "x"), u64::MAX);
+
+        let mint = MultiAsset::from([(
+            "p".to_string(),
+            BTreeMap::from([("x".to_string(), 3), ("y".to_string(), -2)]),
+        )]);
+        assert_eq!(
+            split_mint(&mint),
+            (Value::token("p", "x", 3), Value::token("p", "y", 2))
+        );
+        assert_eq!(value.to_assets(), Some(vec![asset("x", 5)]));
+        assert_eq!(Value::lovelace(7).to_assets(), None);
+        assert_eq!(value.to_string(), "7 lovelace + 5 p.x");
+    }
+}
@@ -0,0 +1,151 @@
+//! Ed25519 verification key witnesses (Shelley).
+//!
+//! A transaction is signed by attaching, for every key it needs, the verification key and its
+//! Ed25519 signature of the transaction id, the hash of the body. Witnesses are not part of the
+//! body, so signing does not change the id. Keys are identified by their key hash, Blake2b-224
+//! of the verification key as hex: the form native scripts and required signers use.
+//!
+//! A transaction must carry a valid witness for each of its required signers (see
+//! [`LedgerState::required_signers`]):
+//! - the payment key of every key-locked output it spends or puts up as collateral;
+//! - the stake key of every reward account it withdraws from;
+//! - the stake key of the credential it deregisters, delegates or delegates votes of;
+//! - the pool key and the owner of a pool it registers, and the pool key of a pool it retires;
+//! - the key of a DRep it registers or retires;
+//! - the key of every committee member and DRep voting in it, and the pool key of every voting
+//!   pool;
+//! - the key of every genesis delegate proposing a parameter update in it;
+//! - the key hashes listed in its `required_signers`.
+//!
+//! Outputs, reward accounts and certificate credentials locked by a script hash need their script
+//! instead of a signature (see [`super::rules`]).
+//! Registering a stake credential needs no signature, as it only costs its owner a deposit. Pool
+//! ids are key hashes.
+
+use super::{Certificate, Credential, LedgerState, Transaction, TxId, Voter};
+use blake2::digest::consts::U28;
+use blake2::{Blake2b, Digest};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Verification key and its signature of a transaction id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VKeyWitness {
+    /// Ed25519 verification key (32 bytes)
+    pub vkey: Vec<u8>,
+    /// Ed25519 signature of the transaction id (64 bytes)
+    pub signature: Vec<u8>,
+}
+
+impl VKeyWitness {
+    /// Witness of `key` for the transaction with id `tx_id`.
+    pub fn new(key: &SigningKey, tx_id: &TxId) -> Self {
+        VKeyWitness {
+            vkey: key.verifying_key().to_bytes().to_vec(),
+            signature: key.sign(tx_id).to_bytes().to_vec(),
+        }
+    }
+
+    /// Hash of the verification key.
+    pub fn key_hash(&self) -> String {
+        key_hash(&self.vkey)
+    }
+
+    /// Whether the signature is a valid signature of `tx_id` by the verification key. Keys and
+    /// signatures of the wrong length never verify.
+    pub fn verify(&self, tx_id: &TxId) -> bool {
+        let Ok(vkey) = <&[u8; 32]>::try_from(self.vkey.as_slice()) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        VerifyingKey::from_bytes(vkey).is_ok_and(|key| key.verify(tx_id, &signature).is_ok())
+    }
+}
+
+/// Key hash of verification key bytes: Blake2b-224 as hex.
+pub fn key_hash(vkey: &[u8]) -> String {
+    crate::chaindb::hex(&Blake2b::<U28>::digest(vkey))
+}
+
+impl Certificate {
+    /// Credential whose owner must authorise the certificate: the stake credential it
+    /// deregisters, delegates or delegates votes of, the owner of a pool it registers, or the DRep
+    /// it registers or retires.
+    pub fn authorizing_credential(&self) -> Option<&Credential> {
+        match self {
+            Certificate::StakeDeregistration(credential) => Some(credential),
+            Certificate::Delegation(deleg) => Some(&deleg.delegator),
+            Certificate::PoolRegistration(reg) => Some(&reg.owner),
+            Certificate::VoteDelegation(deleg) => Some(&deleg.delegator),
+            Certificate::DRepRegistration(cert) | Certificate::DRepDeregistration(cert) => {
+                Some(&cert.drep)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl LedgerState {
+    /// Key hashes that must sign a transaction. Inputs and collateral inputs that are not in the
+    /// UTXO set are ignored; they are rejected by the UTXO rules.
+    pub fn required_signers(&self, tx: &Transaction) -> BTreeSet<String> {
+        let spent = tx
+            .inputs
+            .iter()
+            .chain(&tx.collateral)
+            .filter_map(|input| self.utxos.get(&(input.prev_tx, input.index)))
+            .filter_map(|output| output.address.payment_credential());
+        let stake = tx
+            .certificates
+            .iter()
+            .filter_map(Certificate::authorizing_credential);
+        let keys = tx.certificates.iter().filter_map(|cert| match cert {
+            Certificate::PoolRegistration(reg) => Some(&reg.pool_id),
+            Certificate::PoolRetirement(ret) => Some(&ret.pool_id),
+            _ => None,
+        });
+        let voters = tx
+            .voting_procedures
+            .iter()
+            .filter_map(|procedure| match &procedure.voter {
+                Voter::Committee(credential) | Voter::DRep(credential) => credential.key_hash(),
+                Voter::StakePool(pool_id) => Some(pool_id.clone()),
+            });
+        let proposers = tx.update.iter().flat_map(|update| update.proposals.keys());
+        spent
+            .chain(tx.withdrawals.keys())
+            .chain(stake)
+            .filter_map(Credential::key_hash)
+            .chain(keys.chain(proposers).chain(&tx.required_signers).cloned())
+            .chain(voters)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn witnesses_verify_only_their_transaction() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let witness = VKeyWitness::new(&key, &[1u8; 32]);
+        assert!(witness.verify(&[1u8; 32]));
+        assert!(!witness.verify(&[2u8; 32]));
+        assert_eq!(witness.key_hash(), key_hash(key.verifying_key().as_bytes()));
+        assert_eq!(witness.key_hash().len(), 56);
+
+        let mut forged = witness.clone();
+        forged.signature[0] ^= 1;
+        assert!(!forged.verify(&[1u8; 32]));
+        forged.signature.pop();
+        assert!(!forged.verify(&[1u8; 32]));
+        assert_eq!(
+            Credential::from_vkey(&witness.vkey).key_hash(),
+            Some(witness.key_hash())
+        );
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! Handles transaction pool, relay, and validation logic.
 
-use crate::ledger::Transaction;
+use crate::ledger::{Transaction, TxId};
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 
@@ -58,9 +58,9 @@ impl Mempool {
 
     /// Remove a transaction from the mempool by id.
     #[allow(dead_code)]
-    pub fn remove_transaction(&self, tx_id: u64) {
+    pub fn remove_transaction(&self, tx_id: &TxId) {
         let mut pool = self.pool.lock().unwrap();
-        if let Some(pos) = pool.iter().position(|tx| tx.id == tx_id) {
+        if let Some(pos) = pool.iter().position(|tx| tx.id() == *tx_id) {
             pool.remove(pos);
         }
     }
@@ -81,36 +81,36 @@ mod tests {
     fn mempool_add_and_remove() {
         let mempool = Mempool::new(2);
         let tx1 = Transaction {
-            id: 1,
             inputs: vec![],
             outputs: vec![TxOutput {
-                address: "A".to_string(),
                 amount: 10,
                 assets: None,
+                ..Default::default()
             }],
             certificates: vec![],
             plutus_witnesses: vec![],
+            ..Default::default()
         };
         let tx2 = Transaction {
-            id: 2,
             inputs: vec![],
             outputs: vec![TxOutput {
-                address: "B".to_string(),
                 amount: 20,
                 assets: None,
+                ..Default::default()
             }],
             certificates: vec![],
             plutus_witnesses: vec![],
+            ..Default::default()
         };
         assert!(mempool.add_transaction(tx1.clone()));
         assert!(mempool.add_transaction(tx2.clone()));
         assert!(!mempool.add_transaction(tx2.clone())); // full
         let txs = mempool.get_transactions();
         assert_eq!(txs.len(), 2);
-        mempool.remove_transaction(1);
+        mempool.remove_transaction(&tx1.id());
         let txs = mempool.get_transactions();
         assert_eq!(txs.len(), 1);
-        assert_eq!(txs[0].id, 2);
+        assert_eq!(txs[0].id(), tx2.id());
         mempool.clear();
         assert_eq!(mempool.get_transactions().len(), 0);
     }
@@ -1,5 +1,5 @@
 // Conversion utilities between ledger types and proto types for gRPC API
-use crate::ledger::{Block as LedgerBlock, BlockHeader as LedgerBlockHeader, Transaction as LedgerTransaction, TxInput as LedgerTxInput, TxOutput as LedgerTxOutput};
+use crate::ledger::{Block as LedgerBlock, BlockHeader as LedgerBlockHeader, OperationalCert as LedgerOperationalCert, Transaction as LedgerTransaction, TxInput as LedgerTxInput, TxOutput as LedgerTxOutput};
 use crate::chaindb_proto;
 
 impl From<LedgerBlockHeader> for chaindb_proto::BlockHeader {
@@ -8,8 +8,24 @@ impl From<LedgerBlockHeader> for chaindb_proto::BlockHeader {
             slot: h.slot,
             epoch: h.epoch,
             leader: h.leader,
+            issuer_vkey: h.issuer_vkey,
+            vrf_vkey: h.vrf_vkey,
             vrf_proof: h.vrf_proof,
+            opcert: Some(h.opcert.into()),
             kes_signature: h.kes_signature,
+            prev_hash: h.prev_hash.map(|p| p.to_vec()).unwrap_or_default(),
+            body_hash: h.body_hash.to_vec(),
+        }
+    }
+}
+
+impl From<LedgerOperationalCert> for chaindb_proto::OperationalCert {
+    fn from(c: LedgerOperationalCert) -> Self {
+        Self {
+            hot_vkey: c.hot_vkey,
+            counter: c.counter,
+            kes_period: c.kes_period,
+            sigma: c.sigma,
         }
     }
 }
@@ -17,7 +33,7 @@ impl From<LedgerBlockHeader> for chaindb_proto::BlockHeader {
 impl From<LedgerTxInput> for chaindb_proto::TxInput {
     fn from(i: LedgerTxInput) -> Self {
         Self {
-            prev_tx: i.prev_tx,
+            prev_tx: i.prev_tx.to_vec(),
             index: i.index,
         }
     }
@@ -26,7 +42,7 @@ impl From<LedgerTxInput> for chaindb_proto::TxInput {
 impl From<LedgerTxOutput> for chaindb_proto::TxOutput {
     fn from(o: LedgerTxOutput) -> Self {
         chaindb_proto::TxOutput {
-            address: o.address,
+            address: o.address.to_string(),
             amount: o.amount,
             // If proto supports assets, map them; otherwise, ignore or add a TODO
             // assets: o.assets.map(|assets| assets.into_iter().map(Into::into).collect()),
@@ -37,9 +53,10 @@ impl From<LedgerTxOutput> for chaindb_proto::TxOutput {
 impl From<LedgerTransaction> for chaindb_proto::Transaction {
     fn from(t: LedgerTransaction) -> Self {
         Self {
-            id: t.id,
+            id: t.id().to_vec(),
             inputs: t.inputs.into_iter().map(Into::into).collect(),
             outputs: t.outputs.into_iter().map(Into::into).collect(),
+            fee: t.fee,
         }
     }
 }
@@ -48,6 +65,7 @@ impl From<LedgerBlock> for chaindb_proto::Block {
     fn from(b: LedgerBlock) -> Self {
         Self {
             id: b.id,
+            hash: b.hash().to_vec(),
             header: Some(b.header.into()),
             transactions: b.transactions.into_iter().map(Into::into).collect(),
         }
@@ -57,12 +75,18 @@ impl From<LedgerBlock> for chaindb_proto::Block {
 pub fn block_to_proto(b: &crate::ledger::Block) -> crate::chaindb_proto::Block {
     crate::chaindb_proto::Block {
         id: b.id,
+        hash: b.hash().to_vec(),
         header: Some(crate::chaindb_proto::BlockHeader {
             slot: b.header.slot,
             epoch: b.header.epoch,
             leader: b.header.leader.clone(),
+            issuer_vkey: b.header.issuer_vkey.clone(),
+            vrf_vkey: b.header.vrf_vkey.clone(),
             vrf_proof: b.header.vrf_proof.clone(),
+            opcert: Some(b.header.opcert.clone().into()),
             kes_signature: b.header.kes_signature.clone(),
+            prev_hash: b.header.prev_hash.map(|p| p.to_vec()).unwrap_or_default(),
+            body_hash: b.header.body_hash.to_vec(),
         }),
         transactions: b.transactions.iter().map(transaction_to_proto).collect(),
     }
@@ -70,18 +94,72 @@ pub fn block_to_proto(b: &crate::ledger::Block) -> crate::chaindb_proto::Block {
 
 pub fn txoutput_to_proto(o: &crate::ledger::TxOutput) -> crate::chaindb_proto::TxOutput {
     crate::chaindb_proto::TxOutput {
-        address: o.address.clone(),
+        address: o.address.to_string(),
         amount: o.amount,
     }
 }
 
 pub fn transaction_to_proto(t: &crate::ledger::Transaction) -> crate::chaindb_proto::Transaction {
     crate::chaindb_proto::Transaction {
-        id: t.id,
+        id: t.id().to_vec(),
         inputs: t.inputs.iter().map(|i| crate::chaindb_proto::TxInput {
-            prev_tx: i.prev_tx,
+            prev_tx: i.prev_tx.to_vec(),
             index: i.index,
         }).collect(),
         outputs: t.outputs.iter().map(txoutput_to_proto).collect(),
+        fee: t.fee,
+    }
+}
+
+pub fn rational_to_proto(r: &crate::ledger::Rational) -> crate::chaindb_proto::Rational {
+    crate::chaindb_proto::Rational {
+        numerator: r.numerator,
+        denominator: r.denominator,
+    }
+}
+
+pub fn protocol_parameters_to_proto(p: &crate::ledger::ProtocolParameters) -> crate::chaindb_proto::ProtocolParameters {
+    crate::chaindb_proto::ProtocolParameters {
+        min_fee_a: p.min_fee_a,
+        min_fee_b: p.min_fee_b,
+        max_block_size: p.max_block_size as u64,
+        max_tx_size: p.max_tx_size as u64,
+        key_deposit: p.key_deposit,
+        pool_deposit: p.pool_deposit,
+        min_pool_cost: p.min_pool_cost,
+        min_utxo_value: p.min_utxo_value,
+        security_param: p.security_param,
+        active_slot_coeff: Some(rational_to_proto(&p.active_slot_coeff)),
+        decentralisation: Some(rational_to_proto(&p.decentralisation)),
+        protocol_major: p.protocol_version.major,
+        protocol_minor: p.protocol_version.minor,
+        cost_models: p.cost_models.iter().map(|(language, costs)| {
+            (u32::from(*language), crate::chaindb_proto::CostModel { costs: costs.clone() })
+        }).collect(),
+        price_mem: Some(rational_to_proto(&p.ex_unit_prices.mem)),
+        price_steps: Some(rational_to_proto(&p.ex_unit_prices.steps)),
+        collateral_percentage: p.collateral_percentage,
+        max_collateral_inputs: p.max_collateral_inputs as u64,
+        pool_voting_thresholds: {
+            let t = &p.pool_voting_thresholds;
+            [t.motion_no_confidence, t.committee_normal, t.committee_no_confidence, t.hard_fork_initiation, t.pp_security_group]
+                .iter().map(rational_to_proto).collect()
+        },
+        drep_voting_thresholds: {
+            let t = &p.drep_voting_thresholds;
+            [t.motion_no_confidence, t.committee_normal, t.committee_no_confidence, t.update_to_constitution, t.hard_fork_initiation,
+             t.pp_network_group, t.pp_economic_group, t.pp_technical_group, t.pp_gov_group, t.treasury_withdrawal]
+                .iter().map(rational_to_proto).collect()
+        },
+        gov_action_lifetime: p.gov_action_lifetime,
+        gov_action_deposit: p.gov_action_deposit,
+        drep_deposit: p.drep_deposit,
+        epoch_length: p.epoch_length,
+        n_opt: p.n_opt,
+        pool_influence: Some(rational_to_proto(&p.pool_influence)),
+        monetary_expansion: Some(rational_to_proto(&p.monetary_expansion)),
+        treasury_cut: Some(rational_to_proto(&p.treasury_cut)),
+        extra_entropy: p.extra_entropy.to_vec(),
+        slots_per_kes_period: p.slots_per_kes_period,
     }
 }
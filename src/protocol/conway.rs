@@ -46,8 +46,9 @@ impl EraLogic for ConwayProtocol {
     fn name(&self) -> &'static str {
         "Conway"
     }
-    fn validate_transaction(&self, tx: &wallet::Transaction) -> bool {
-        // TODO: Implement Conway-specific logic
+    fn validate_transaction(&self, _tx: &wallet::Transaction) -> bool {
+        // DRep certificates, governance proposals and votes are checked by the ledger's rules,
+        // see `ledger::governance`
         true
     }
     fn validate_block(&self, _block: &crate::ledger::Block) -> bool {
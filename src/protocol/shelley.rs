@@ -43,6 +43,6 @@ impl ProtocolHandler for ShelleyHandler {
         println!("[ShelleyHandler] Block received: {:?}", block.id);
     }
     fn on_transaction_received(&self, tx: &crate::ledger::Transaction) {
-        println!("[ShelleyHandler] Tx received: {:?}", tx.id);
+        println!("[ShelleyHandler] Tx received: {:?}", tx.id());
     }
 }
@@ -140,15 +140,14 @@ impl ServiceManager {
                         
                         // Try to produce a block if we're the slot leader
                         if slot_info.is_slot_leader {
-                            consensus_guard.produce_block_from_mempool(
+                            // The header is led and signed by the node's Praos keys
+                            Ok(consensus_guard.produce_block_from_mempool(
                                 &*ledger_guard,
                                 &*protocol,
                                 slot_info.slot,
                                 slot_info.epoch,
-                                slot_info.leader_id.clone(),
-                                slot_info.vrf_proof.clone(),
-                                slot_info.kes_signature.clone(),
-                            ).await
+                                &slot_info.vrf_proof,
+                            ).await)
                         } else {
                             Ok(None)
                         }
@@ -136,6 +136,12 @@ pub struct Tracer {
     handlers: Arc<Mutex<Vec<Box<dyn Fn(&TraceEvent) + Send + Sync>>>>,
 }
 
+impl std::fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracer").finish_non_exhaustive()
+    }
+}
+
 impl Tracer {
     /// Emit a trace event to all registered handlers.
     pub fn trace(&self, event: TraceEvent) {
@@ -1,6 +1,9 @@
-#[derive(Debug, Clone)]
-pub struct Address;
+// Wallet addresses: the ledger's typed addresses, built from derived key pairs.
+pub use crate::ledger::{Address, Network};
 
-impl Address {
-    pub fn from_key_pair(_key: &crate::wallet::keys::KeyPair) -> Self { Address }
+use crate::wallet::keys::KeyPair;
+
+/// Base address paying to the `payment` key and delegating with the `stake` key.
+pub fn base_address(network: Network, payment: &KeyPair, stake: &KeyPair) -> Address {
+    Address::base(network, payment.credential(), stake.credential())
 }
@@ -1,26 +1,75 @@
-// Minimal stubs for wallet::keys
-pub struct KeyPair;
+// Wallet keys
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use ed25519_dalek::SigningKey;
+
+/// Ed25519 payment key pair.
+#[derive(Debug, Clone)]
+pub struct KeyPair {
+    signing_key: SigningKey,
+}
+
+impl KeyPair {
+    /// Key pair of a 32-byte secret seed.
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        KeyPair {
+            signing_key: SigningKey::from_bytes(seed),
+        }
+    }
+
+    pub fn signing_key(&self) -> &SigningKey {
+        &self.signing_key
+    }
+
+    /// Hash of the verification key, which locks the outputs paid to it.
+    pub fn key_hash(&self) -> String {
+        crate::ledger::witness::key_hash(self.signing_key.verifying_key().as_bytes())
+    }
+
+    /// Key hash credential of the verification key, for payment or stake.
+    pub fn credential(&self) -> crate::ledger::Credential {
+        crate::ledger::Credential::from_vkey(self.signing_key.verifying_key().as_bytes())
+    }
+}
+
+/// Root seed keys are derived from. Derivation hashes the seed with the path; it is not
+/// BIP32-Ed25519, so keys do not match other Cardano wallets.
 #[derive(Debug)]
-pub struct HDWallet;
+pub struct HDWallet {
+    seed: [u8; 32],
+}
+
 #[derive(Debug)]
-pub struct DerivationPath;
+pub struct DerivationPath(Vec<u32>);
 
 impl DerivationPath {
-    pub fn new(_path: Vec<u32>) -> Self {
-        DerivationPath
+    pub fn new(path: Vec<u32>) -> Self {
+        DerivationPath(path)
     }
 }
 pub const CARDANO_PURPOSE: u32 = 1852;
 pub const CARDANO_COIN_TYPE: u32 = 1815;
 
 impl HDWallet {
-    pub fn from_mnemonic(_mnemonic: &str, _password: &str) -> Option<Self> {
-        Some(HDWallet)
+    pub fn from_mnemonic(mnemonic: &str, password: &str) -> Option<Self> {
+        let mut hasher = Blake2b::<U32>::new();
+        hasher.update(mnemonic.as_bytes());
+        hasher.update([0]);
+        hasher.update(password.as_bytes());
+        Some(HDWallet {
+            seed: hasher.finalize().into(),
+        })
     }
-    pub fn generate_new(_password: &str) -> Option<Self> {
-        Some(HDWallet)
+    pub fn generate_new(password: &str) -> Option<Self> {
+        let entropy: [u8; 32] = rand::random();
+        Self::from_mnemonic(&crate::chaindb::hex(&entropy), password)
     }
-    pub fn derive_key_pair(&self, _path: &DerivationPath) -> Option<(String, String)> {
-        Some(("public_key".to_string(), "private_key".to_string()))
+    pub fn derive_key_pair(&self, path: &DerivationPath) -> Option<KeyPair> {
+        let mut hasher = Blake2b::<U32>::new();
+        hasher.update(self.seed);
+        for index in &path.0 {
+            hasher.update(index.to_be_bytes());
+        }
+        Some(KeyPair::from_seed(&hasher.finalize().into()))
     }
 }
@@ -9,7 +9,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use self::address::Address;
+use self::address::{Address, Network};
 use self::keys::{DerivationPath, HDWallet, KeyPair};
 use self::transaction::TransactionBuilder;
 use self::utxo::UtxoSet;
@@ -31,6 +31,8 @@ pub struct WalletMetadata {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub last_used: chrono::DateTime<chrono::Utc>,
     pub address_discovery_gap_limit: u32,
+    /// Network the wallet's addresses are on.
+    pub network: Network,
 }
 
 #[derive(Debug)]
@@ -70,6 +72,7 @@ impl Wallet {
             created_at: now,
             last_used: now,
             address_discovery_gap_limit: 20,
+            network: Network::Testnet,
         };
 
         let state = WalletState {
@@ -95,7 +98,8 @@ impl Wallet {
         &self.name
     }
 
-    /// Derives a new address at the given path
+    /// Derives a new base address at the given path, staking with the account's stake key
+    /// (`m/1852'/1815'/account'/2/0`)
     pub fn derive_address(
         &mut self,
         account: u32,
@@ -120,19 +124,29 @@ impl Wallet {
             address_index,
         ]);
 
-        let key_pair = self
-            .hd_wallet
-            .derive_key_pair(&path)
-            .ok_or_else(|| anyhow::anyhow!("Failed to derive key pair"))?;
-        // TODO: Replace with real KeyPair type when available
-        Ok(Address::from_key_pair(&KeyPair))
+        let stake_path = DerivationPath::new(vec![
+            crate::wallet::keys::CARDANO_PURPOSE,
+            crate::wallet::keys::CARDANO_COIN_TYPE,
+            account,
+            2,
+            0,
+        ]);
+
+        let derive = |path| {
+            self.hd_wallet
+                .derive_key_pair(path)
+                .ok_or_else(|| anyhow::anyhow!("Failed to derive key pair"))
+        };
+        let (payment, stake) = (derive(&path)?, derive(&stake_path)?);
+        Ok(address::base_address(self.metadata.network, &payment, &stake))
     }
 
-    /// Creates a transaction that sends funds to the given addresses
+    /// Creates a transaction that sends funds to the given addresses, paying the minimum fee
+    /// of the current protocol parameters
     pub fn create_transaction(
         &mut self,
         outputs: Vec<(Address, u64)>,
-        fee_algo: impl Fn(usize, usize) -> u64,
+        params: &crate::ledger::ProtocolParameters,
     ) -> Result<Transaction> {
         let mut builder = TransactionBuilder::new();
 
@@ -154,7 +168,7 @@ impl Wallet {
 
         // Calculate and set fee
         let tx_size_bytes = builder.estimate_size();
-        let fee = fee_algo(tx_size_bytes, outputs.len());
+        let fee = params.min_fee(tx_size_bytes);
         builder.set_fee(fee);
 
         // Add change output if needed
@@ -172,17 +186,15 @@ impl Wallet {
     fn sign_transaction(&self, mut builder: TransactionBuilder) -> Result<Transaction> {
         // For each input, find the appropriate key and sign
         for (input_idx, input) in builder.get_inputs().iter().enumerate() {
-            if let Some(address_str) = self.utxo_set.get_address(input) {
-                // Convert address_str to Address type if needed
-                // Here we assume Address::from_string exists, otherwise adjust accordingly
-                // let address = Address::from_string(&address_str);
-                // For now, use a dummy Address
-                let address = Address::from_key_pair(&KeyPair);
-                if let Some(_key_pair) = self.find_key_for_address(&address)? {
-                    // builder.sign_input(input_idx, &key_pair)?;
-                    // Use a dummy key for now
-                    builder.sign_input(input_idx, "dummy_key");
-                }
+            let Some(address) = self
+                .utxo_set
+                .get_address(input)
+                .and_then(|address| address.parse::<Address>().ok())
+            else {
+                continue;
+            };
+            if let Some(key_pair) = self.find_key_for_address(&address)? {
+                builder.sign_input(input_idx, &key_pair);
             }
         }
 
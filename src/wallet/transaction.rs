@@ -1,18 +1,35 @@
 // Minimal stubs for wallet::transaction
+use crate::wallet::keys::KeyPair;
+
 pub struct Transaction;
 #[derive(Debug)]
-pub struct TransactionBuilder;
+pub struct TransactionBuilder {
+    signers: Vec<KeyPair>,
+}
 
 impl TransactionBuilder {
     pub fn new() -> Self {
-        TransactionBuilder
+        TransactionBuilder {
+            signers: Vec::new(),
+        }
     }
     pub fn get_inputs(&self) -> Vec<String> {
         vec![]
     }
-    pub fn sign_input(&mut self, _idx: usize, _key: &str) -> bool {
+    /// Sign input `_idx` with `key`: the key signs the transaction id when the transaction is
+    /// built. A key spending several inputs signs once.
+    pub fn sign_input(&mut self, _idx: usize, key: &KeyPair) -> bool {
+        if !self.signers.iter().any(|k| k.key_hash() == key.key_hash()) {
+            self.signers.push(key.clone());
+        }
         true
     }
+    /// Add the witnesses of the keys the inputs were signed with to `tx`.
+    pub fn sign(&self, tx: &mut crate::ledger::Transaction) {
+        for key in &self.signers {
+            tx.sign(key.signing_key());
+        }
+    }
     pub fn build(&self) -> Option<Transaction> {
         Some(Transaction)
     }
@@ -13,6 +13,7 @@ fn test_chaindb_application_queries() {
         let mut count = 0;
         use futures::StreamExt;
         while let Some(block) = stream.next().await {
+            let block = block.unwrap();
             count += 1;
             assert!(block.id > 0);
         }
@@ -0,0 +1,108 @@
+//! Error reporting tests for ChainDB: missing items, corrupt data and incompatible formats.
+
+use Super_Cardano_node::chaindb::immutable::DEFAULT_CHUNK_SIZE;
+use Super_Cardano_node::chaindb::{ChainDB, ChainDBError, DB_FORMAT_VERSION};
+use Super_Cardano_node::ledger::{Block, LedgerState};
+use futures::StreamExt;
+use tokio::runtime::Runtime;
+
+#[test]
+fn test_missing_and_corrupt_data_return_typed_errors() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/chaindb_errors";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut db = ChainDB::open(dir).await.unwrap();
+        for id in 1..=2 {
+            let mut block = Block {
+                id,
+                ..Default::default()
+            };
+            block.header.prev_hash = db.tip_hash();
+            db.append_block(&block, &LedgerState::default())
+                .await
+                .unwrap();
+        }
+
+        assert!(matches!(
+            db.load_block(7).await,
+            Err(ChainDBError::NotFound { what: "block", .. })
+        ));
+        assert!(db.load_block_by_slot(7).await.unwrap_err().is_not_found());
+        assert!(
+            db.load_block_by_hash(&[0u8; 32])
+                .await
+                .unwrap_err()
+                .is_not_found()
+        );
+        assert!(db.load_state(7).await.unwrap_err().is_not_found());
+
+        std::fs::write(format!("{}/ledger/diff-1.bin", dir), b"\xff\xff\xff").unwrap();
+        assert!(matches!(
+            db.load_state(1).await,
+            Err(ChainDBError::Corrupt { .. })
+        ));
+        assert!(matches!(
+            db.query_utxo(1, &[0u8; 32], 0).await,
+            Err(ChainDBError::Corrupt { .. })
+        ));
+
+        // A corrupt block ends up in the block stream as an error, not as a gap.
+        let tip: String = db.tip_hash().unwrap().iter().map(|b| format!("{:02x}", b)).collect();
+        for entry in std::fs::read_dir(format!("{}/volatile", dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.to_string_lossy().ends_with(&format!("{}.block", tip)) {
+                std::fs::write(&path, b"\xff\xff\xff").unwrap();
+            }
+        }
+        let streamed: Vec<_> = db.stream_blocks().await.unwrap().collect().await;
+        assert_eq!(streamed.len(), 2);
+        assert_eq!(streamed[0].as_ref().unwrap().id, 1);
+        assert!(matches!(streamed[1], Err(ChainDBError::Corrupt { .. })));
+    });
+}
+
+#[test]
+fn test_incompatible_format_version_is_rejected() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/chaindb_version";
+        let _ = std::fs::remove_dir_all(dir);
+        drop(ChainDB::open(dir).await.unwrap());
+        let stamped = std::fs::read_to_string(format!("{}/version", dir)).unwrap();
+        assert_eq!(
+            stamped.lines().collect::<Vec<_>>(),
+            [
+                DB_FORMAT_VERSION.to_string(),
+                DEFAULT_CHUNK_SIZE.to_string()
+            ]
+        );
+
+        // A store written with another chunk size cannot be read with this one.
+        std::fs::write(
+            format!("{}/version", dir),
+            format!("{}\n{}\n", DB_FORMAT_VERSION, DEFAULT_CHUNK_SIZE + 1),
+        )
+        .unwrap();
+        match ChainDB::open(dir).await {
+            Err(ChainDBError::ChunkSizeMismatch { found, expected }) => {
+                assert_eq!(found, DEFAULT_CHUNK_SIZE + 1);
+                assert_eq!(expected, DEFAULT_CHUNK_SIZE);
+            }
+            other => panic!("expected ChunkSizeMismatch, got {:?}", other.map(|_| ())),
+        }
+
+        std::fs::write(
+            format!("{}/version", dir),
+            format!("{}\n", DB_FORMAT_VERSION + 1),
+        )
+        .unwrap();
+        match ChainDB::open(dir).await {
+            Err(ChainDBError::VersionMismatch { found, expected }) => {
+                assert_eq!(found, DB_FORMAT_VERSION + 1);
+                assert_eq!(expected, DB_FORMAT_VERSION);
+            }
+            other => panic!("expected VersionMismatch, got {:?}", other.map(|_| ())),
+        }
+    });
+}
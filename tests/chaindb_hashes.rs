@@ -0,0 +1,110 @@
+//! Block hash tests for ChainDB: prev-hash linkage and switching forks by hash.
+
+use Super_Cardano_node::chaindb::{ChainDB, ChainDBError};
+use Super_Cardano_node::configuration::ChainDBConfig;
+use Super_Cardano_node::ledger::{Block, BlockHash, LedgerState};
+use Super_Cardano_node::tracing::tracers::Tracer;
+use tokio::runtime::Runtime;
+
+fn block(id: u64, leader: &str, prev_hash: Option<BlockHash>) -> Block {
+    let mut block = Block {
+        id,
+        ..Block::default()
+    };
+    block.header.slot = id;
+    block.header.leader = leader.to_string();
+    block.header.prev_hash = prev_hash;
+    block
+}
+
+fn config(security_param: u64) -> ChainDBConfig {
+    ChainDBConfig {
+        security_param,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_blocks_must_extend_the_tip_by_hash() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/chaindb_hash_linkage";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = LedgerState::default();
+        let mut db = ChainDB::open(dir).await.unwrap();
+        assert_eq!(db.tip_hash(), None);
+
+        let first = block(1, "a", None);
+        db.append_block(&first, &state).await.unwrap();
+        assert_eq!(db.tip_hash(), Some(first.hash()));
+        assert_eq!(db.hash_of(1), Some(first.hash()));
+
+        // Wrong parent, no parent, and a body that does not match the header are all rejected.
+        for bad in [
+            block(2, "a", Some([7u8; 32])),
+            block(2, "a", None),
+            Block {
+                transactions: vec![Default::default()],
+                ..block(2, "a", db.tip_hash())
+            },
+        ] {
+            assert!(matches!(
+                db.append_block(&bad, &state).await,
+                Err(ChainDBError::InvalidBlock { block_id: 2, .. })
+            ));
+        }
+        assert_eq!(db.block_ids().await.unwrap(), vec![1]);
+
+        let second = block(2, "a", db.tip_hash());
+        db.append_block(&second, &state).await.unwrap();
+        drop(db);
+
+        let db = ChainDB::open(dir).await.unwrap();
+        assert_eq!(db.tip_hash(), Some(second.hash()));
+        let loaded = db.load_block_by_hash(&second.hash()).await.unwrap();
+        assert_eq!(loaded.header.prev_hash, Some(first.hash()));
+    });
+}
+
+#[test]
+fn test_forks_with_equal_ids_are_told_apart_by_hash() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/chaindb_hash_fork";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = LedgerState::default();
+        let mut db = ChainDB::open_with_config(dir, config(3), Tracer::default())
+            .await
+            .unwrap();
+        let genesis = block(1, "a", None);
+        db.append_block(&genesis, &state).await.unwrap();
+        let ours = block(2, "a", db.tip_hash());
+        db.append_block(&ours, &state).await.unwrap();
+        let theirs = block(2, "b", Some(genesis.hash()));
+        assert_ne!(ours.hash(), theirs.hash());
+
+        // Switch to the other fork by rolling back to the intersection hash.
+        db.rollback_to_hash(Some(&genesis.hash())).await.unwrap();
+        db.append_block(&theirs, &state).await.unwrap();
+        assert_eq!(db.tip_hash(), Some(theirs.hash()));
+        assert_eq!(db.load_block(2).await.unwrap().header.leader, "b");
+        assert_eq!(
+            db.load_block_by_hash(&ours.hash())
+                .await
+                .unwrap()
+                .header
+                .leader,
+            "a"
+        );
+
+        assert!(
+            db.rollback_to_hash(Some(&ours.hash()))
+                .await
+                .unwrap_err()
+                .is_not_found()
+        );
+        db.rollback_to_hash(None).await.unwrap();
+        assert!(db.block_ids().await.unwrap().is_empty());
+        assert_eq!(db.tip_hash(), None);
+    });
+}
@@ -0,0 +1,133 @@
+//! LedgerDB tests: snapshots plus per-block diffs, replay and configurable retention.
+
+use Super_Cardano_node::chaindb::{ChainDB, ChainDBError};
+use Super_Cardano_node::configuration::{ChainDBConfig, LedgerDBConfig};
+use Super_Cardano_node::ledger::{
+    Address, Block, BlockHash, Credential, LedgerState, Network, TxOutput,
+};
+use Super_Cardano_node::tracing::tracers::Tracer;
+use tokio::runtime::Runtime;
+
+fn block(id: u64, prev_hash: Option<BlockHash>) -> Block {
+    let mut block = Block {
+        id,
+        ..Block::default()
+    };
+    block.header.slot = id;
+    block.header.prev_hash = prev_hash;
+    block
+}
+
+/// State after block `id`: each block spends the previous block's output and creates a new one.
+fn state_after(id: u64) -> LedgerState {
+    let mut state = LedgerState::default();
+    state.utxos.insert(
+        ([id as u8; 32], 0),
+        TxOutput {
+            address: Address::enterprise(Network::Testnet, Credential::KeyHash([id as u8; 28])),
+            amount: id * 10,
+            assets: None,
+            ..Default::default()
+        },
+    );
+    state.snapshots.mark = state.stake_snapshot();
+    state
+}
+
+fn config(security_param: u64, pruning: bool) -> ChainDBConfig {
+    ChainDBConfig {
+        security_param,
+        ledger: LedgerDBConfig {
+            max_snapshots: 2,
+            pruning,
+            snapshot_interval: 3,
+            ..Default::default()
+        },
+    }
+}
+
+#[test]
+fn test_states_are_replayed_from_snapshots_and_diffs() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/ledgerdb_replay";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut db = ChainDB::open_with_config(dir, config(10, false), Tracer::default())
+            .await
+            .unwrap();
+        for id in 1..=10 {
+            db.append_block(&block(id, db.tip_hash()), &state_after(id))
+                .await
+                .unwrap();
+        }
+        assert!(db.ledger().has_snapshot(3));
+        assert!(db.ledger().has_snapshot(9));
+        assert!(!db.ledger().has_snapshot(4));
+        for id in 1..=10 {
+            assert_eq!(
+                db.load_state(id).await.unwrap().utxos,
+                state_after(id).utxos
+            );
+        }
+        drop(db);
+
+        let mut db = ChainDB::open_with_config(dir, config(10, false), Tracer::default())
+            .await
+            .unwrap();
+        let tip = db.load_state(10).await.unwrap();
+        assert_eq!(tip.snapshots, state_after(10).snapshots);
+
+        db.rollback_to(5).await.unwrap();
+        assert!(!db.ledger().has_snapshot(9));
+        assert!(db.load_state(6).await.unwrap_err().is_not_found());
+        db.append_block(&block(6, db.tip_hash()), &state_after(6))
+            .await
+            .unwrap();
+        assert_eq!(db.load_state(6).await.unwrap().utxos, state_after(6).utxos);
+    });
+}
+
+#[test]
+fn test_pruning_keeps_newest_snapshots_only() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/ledgerdb_pruning";
+        let _ = std::fs::remove_dir_all(dir);
+        let mut db = ChainDB::open_with_config(dir, config(2, true), Tracer::default())
+            .await
+            .unwrap();
+        for id in 1..=10 {
+            db.append_block(&block(id, db.tip_hash()), &state_after(id))
+                .await
+                .unwrap();
+        }
+        // Snapshots at blocks 3, 6 and 9; only the newest two are retained.
+        assert!(!db.ledger().has_snapshot(3));
+        assert!(db.ledger().has_snapshot(6));
+        assert!(db.ledger().has_snapshot(9));
+        for id in 1..6 {
+            assert!(matches!(
+                db.load_state(id).await,
+                Err(ChainDBError::NotFound { .. })
+            ));
+        }
+        for id in 6..=10 {
+            assert_eq!(
+                db.load_state(id).await.unwrap().utxos,
+                state_after(id).utxos
+            );
+        }
+        assert!(!std::path::Path::new(&format!("{}/ledger/diff-6.bin", dir)).exists());
+        assert!(std::path::Path::new(&format!("{}/ledger/diff-7.bin", dir)).exists());
+
+        // Every state within k of the tip stays reachable for rollbacks.
+        assert!(matches!(
+            db.rollback_to(4).await,
+            Err(ChainDBError::RollbackTooDeep { depth: 6, max: 2 })
+        ));
+        assert_eq!(db.block_ids().await.unwrap().len(), 10);
+        db.rollback_to(8).await.unwrap();
+        assert_eq!(db.load_state(8).await.unwrap().utxos, state_after(8).utxos);
+        assert_eq!(db.load_state(7).await.unwrap().utxos, state_after(7).utxos);
+    });
+}
@@ -25,7 +25,9 @@ fn test_application_api_usage() {
         let shared_read = shared.read().await;
         let mut block_stream = Box::pin(shared_read.api_stream_blocks().await.unwrap());
         use futures::StreamExt;
-        while let Some(_block) = block_stream.next().await {}
+        while let Some(block) = block_stream.next().await {
+            block.unwrap();
+        }
         let mut utxo_stream = Box::pin(shared_read.api_stream_utxos(1).await.unwrap());
         while let Some(_utxo) = utxo_stream.next().await {}
     });
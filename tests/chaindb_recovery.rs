@@ -0,0 +1,149 @@
+//! Crash-safety tests for ChainDB: simulated crashes at every write step and torn writes.
+
+use Super_Cardano_node::chaindb::journal::WriteStep;
+use Super_Cardano_node::chaindb::{ChainDB, Repair};
+use Super_Cardano_node::configuration::ChainDBConfig;
+use Super_Cardano_node::ledger::{Block, BlockHash, LedgerState};
+use Super_Cardano_node::tracing::tracers::{TraceEvent, Tracer};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+
+fn block(id: u64, prev_hash: Option<BlockHash>) -> Block {
+    let mut block = Block {
+        id,
+        ..Block::default()
+    };
+    block.header.slot = id;
+    block.header.prev_hash = prev_hash;
+    block
+}
+
+fn recording_tracer() -> (Tracer, Arc<Mutex<Vec<String>>>) {
+    let tracer = Tracer::default();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    tracer.register_tracer(move |event| {
+        if let TraceEvent::ChainDB(msg) = event {
+            sink.lock().unwrap().push(msg.clone());
+        }
+    });
+    (tracer, events)
+}
+
+#[test]
+fn test_crash_at_every_write_step_is_rolled_back() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let steps = [
+            WriteStep::Journal,
+            WriteStep::Block,
+            WriteStep::Index,
+            WriteStep::StateTemp,
+            WriteStep::State,
+        ];
+        for step in steps {
+            let dir = format!("./testdata/chaindb_crash_{:?}", step);
+            let _ = std::fs::remove_dir_all(&dir);
+            let state = LedgerState::default();
+            let mut db = ChainDB::open(&dir).await.unwrap();
+            db.append_block(&block(1, db.tip_hash()), &state)
+                .await
+                .unwrap();
+            db.simulate_crash_after(Some(step));
+            assert!(
+                db.append_block(&block(2, db.tip_hash()), &state)
+                    .await
+                    .is_err()
+            );
+            drop(db);
+
+            let (tracer, events) = recording_tracer();
+            let mut db = ChainDB::open_with_tracer(&dir, tracer).await.unwrap();
+            assert!(
+                db.recovery_report()
+                    .contains(&Repair::RolledBackAppend { block_id: 2 }),
+                "step {:?}: {:?}",
+                step,
+                db.recovery_report()
+            );
+            assert!(
+                events
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|e| e.starts_with("Recovery:"))
+            );
+            assert_eq!(db.block_ids().await.unwrap(), vec![1]);
+            assert!(db.load_block(2).await.is_err());
+            assert!(db.load_state(2).await.is_err());
+            assert!(db.load_state(1).await.is_ok());
+
+            db.append_block(&block(2, db.tip_hash()), &state)
+                .await
+                .unwrap();
+            drop(db);
+            let db = ChainDB::open(&dir).await.unwrap();
+            assert!(db.recovery_report().is_empty());
+            assert_eq!(db.load_block(2).await.unwrap().id, 2);
+        }
+    });
+}
+
+#[test]
+fn test_torn_entries_are_truncated() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/chaindb_torn";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = LedgerState::default();
+        // With k = 0 every block is promoted to the immutable store right away.
+        let config = ChainDBConfig {
+            security_param: 0,
+            ..Default::default()
+        };
+        let open = || ChainDB::open_with_config(dir, config.clone(), Tracer::default());
+        let mut db = open().await.unwrap();
+        db.append_block(&block(1, db.tip_hash()), &state)
+            .await
+            .unwrap();
+        db.append_block(&block(2, db.tip_hash()), &state)
+            .await
+            .unwrap();
+        drop(db);
+
+        let data = format!("{}/immutable/chunk-00000.dat", dir);
+        let index = format!("{}/immutable/chunk-00000.idx", dir);
+        let append = |path: &str, bytes: &[u8]| {
+            let mut file = OpenOptions::new().append(true).open(path).unwrap();
+            file.write_all(bytes).unwrap();
+        };
+        append(&data, b"{\"id\":3,\"hea");
+        append(&index, &[0u8; 7]);
+        let db = open().await.unwrap();
+        let report = db.recovery_report();
+        assert!(report.contains(&Repair::TornIndexRecord { chunk: 0 }));
+        assert!(report.contains(&Repair::TrailingBytes {
+            chunk: 0,
+            bytes: 12
+        }));
+        assert_eq!(db.block_ids().await.unwrap(), vec![1, 2]);
+        drop(db);
+
+        let len = std::fs::metadata(&data).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&data)
+            .unwrap()
+            .set_len(len - 5)
+            .unwrap();
+        let db = open().await.unwrap();
+        assert!(db.recovery_report().contains(&Repair::TornBlock {
+            chunk: 0,
+            block_id: 2
+        }));
+        assert_eq!(db.block_ids().await.unwrap(), vec![1]);
+        assert_eq!(db.load_block(1).await.unwrap().id, 1);
+    });
+}
@@ -0,0 +1,114 @@
+//! Volatile/immutable split tests: k-deep rollback limit, promotion and fork retention.
+
+use Super_Cardano_node::chaindb::{ChainDB, ChainDBError};
+use Super_Cardano_node::configuration::ChainDBConfig;
+use Super_Cardano_node::ledger::{Block, BlockHash, LedgerState};
+use Super_Cardano_node::tracing::tracers::Tracer;
+use tokio::runtime::Runtime;
+
+fn block(id: u64, leader: &str, prev_hash: Option<BlockHash>) -> Block {
+    let mut block = Block {
+        id,
+        ..Block::default()
+    };
+    block.header.slot = id;
+    block.header.leader = leader.to_string();
+    block.header.prev_hash = prev_hash;
+    block
+}
+
+fn config(security_param: u64) -> ChainDBConfig {
+    ChainDBConfig {
+        security_param,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_blocks_deeper_than_k_are_promoted_and_final() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/chaindb_volatile_promote";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = LedgerState::default();
+        let mut db = ChainDB::open_with_config(dir, config(3), Tracer::default())
+            .await
+            .unwrap();
+        assert_eq!(db.immutable_tip(), None);
+        for id in 1..=6 {
+            db.append_block(&block(id, "a", db.tip_hash()), &state)
+                .await
+                .unwrap();
+        }
+        assert_eq!(db.immutable_tip(), Some(3));
+        assert_eq!(db.block_ids().await.unwrap(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(db.load_block(2).await.unwrap().id, 2);
+        assert_eq!(db.load_block_by_slot(5).await.unwrap().id, 5);
+
+        match db.rollback_to(2).await {
+            Err(ChainDBError::RollbackTooDeep { depth, max }) => {
+                assert_eq!(depth, 4);
+                assert_eq!(max, 3);
+            }
+            other => panic!("expected RollbackTooDeep, got {:?}", other),
+        }
+        assert_eq!(db.block_ids().await.unwrap().len(), 6);
+        drop(db);
+
+        let mut db = ChainDB::open_with_config(dir, config(3), Tracer::default())
+            .await
+            .unwrap();
+        assert!(db.recovery_report().is_empty());
+        assert_eq!(db.immutable_tip(), Some(3));
+        db.rollback_to(3).await.unwrap();
+        assert_eq!(db.block_ids().await.unwrap(), vec![1, 2, 3]);
+        assert!(db.load_block(4).await.is_err());
+    });
+}
+
+#[test]
+fn test_forks_are_kept_until_they_fall_behind_the_immutable_tip() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let dir = "./testdata/chaindb_volatile_fork";
+        let _ = std::fs::remove_dir_all(dir);
+        let state = LedgerState::default();
+        let mut db = ChainDB::open_with_config(dir, config(2), Tracer::default())
+            .await
+            .unwrap();
+        db.append_block(&block(1, "a", None), &state).await.unwrap();
+        let old = block(2, "a", db.tip_hash());
+        db.append_block(&old, &state).await.unwrap();
+        let old_hash = old.hash();
+
+        // Switch to a fork at block 1: the abandoned block 2 stays retrievable by hash.
+        db.rollback_to(1).await.unwrap();
+        db.append_block(&block(2, "b", db.tip_hash()), &state)
+            .await
+            .unwrap();
+        assert_eq!(db.load_block(2).await.unwrap().header.leader, "b");
+        assert_eq!(
+            db.load_block_by_hash(&old_hash)
+                .await
+                .unwrap()
+                .header
+                .leader,
+            "a"
+        );
+
+        // Once slot 2 is immutable, the fork block is garbage-collected.
+        for id in 3..=4 {
+            db.append_block(&block(id, "b", db.tip_hash()), &state)
+                .await
+                .unwrap();
+        }
+        assert_eq!(db.immutable_tip(), Some(2));
+        assert!(
+            db.load_block_by_hash(&old_hash)
+                .await
+                .unwrap_err()
+                .is_not_found()
+        );
+        assert_eq!(db.load_block(2).await.unwrap().header.leader, "b");
+    });
+}
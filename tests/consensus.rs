@@ -1,6 +1,6 @@
 //! Tests for Ouroboros consensus logic (Praos, slot leadership).
 
-use crate::consensus::{praos_is_leader, PraosKeys};
+use crate::consensus::{praos_is_leader, vrf, vrf_input, PraosKeys, NEUTRAL_NONCE};
 
 #[test]
 fn test_praos_leader_threshold() {
@@ -8,34 +8,44 @@ fn test_praos_leader_threshold() {
     let slot = 42;
     let stake = 100.0;
     let total_stake = 1000.0;
-    let is_leader = praos_is_leader(slot, &keys, stake, total_stake);
-    assert!(is_leader == false || is_leader == true);
+    if let Some(proof) = praos_is_leader(slot, &NEUTRAL_NONCE, &keys, stake, total_stake, 0.05) {
+        let vkey = keys.vrf.verification_key();
+        assert!(vrf::verify(&vkey, &proof, &vrf_input(slot, &NEUTRAL_NONCE)).is_ok());
+    }
+    assert!(praos_is_leader(slot, &NEUTRAL_NONCE, &keys, 0.0, total_stake, 0.05).is_none());
 }
 
 #[test]
 fn test_consensus_block_validation() {
-    use Super_Cardano_node::ledger::{Block, BlockHeader, Transaction, TxInput, TxOutput};
+    use Super_Cardano_node::ledger::{
+        Block, BlockHeader, OperationalCert, Transaction, TxInput, TxOutput,
+    };
     use Super_Cardano_node::consensus::Consensus;
     use Super_Cardano_node::configuration::ConsensusConfig;
     use Super_Cardano_node::tracing::tracers::Tracer;
     let consensus = Consensus::new(ConsensusConfig::default(), Tracer::default());
-    let block = Block {
-        id: 1,
-        header: BlockHeader {
+    let block = Block::new(
+        1,
+        BlockHeader {
             slot: 10,
             epoch: 1,
             leader: "test-leader".to_string(),
-            vrf_proof: vec![0u8; 32],
-            kes_signature: vec![0u8; 32],
+            issuer_vkey: vec![0u8; 32],
+            vrf_vkey: vec![0u8; 32],
+            vrf_proof: vec![0u8; 80],
+            opcert: OperationalCert::default(),
+            kes_signature: vec![0u8; 448],
+            prev_hash: None,
+            body_hash: [0u8; 32],
         },
-        transactions: vec![Transaction {
-            id: 1,
-            inputs: vec![TxInput { prev_tx: 0, index: 0 }],
-            outputs: vec![TxOutput { address: "A".to_string(), amount: 10, assets: None }],
+        vec![Transaction {
+            inputs: vec![TxInput { prev_tx: [0u8; 32], index: 0 }],
+            outputs: vec![TxOutput { amount: 10, assets: None, ..Default::default() }],
             certificates: vec![],
             plutus_witnesses: vec![],
+            ..Default::default()
         }],
-    };
+    );
     let rt = tokio::runtime::Runtime::new().unwrap();
     assert!(rt.block_on(consensus.validate_block(&block)));
     // Invalid: missing leader
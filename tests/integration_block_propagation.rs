@@ -2,7 +2,7 @@
 
 #[tokio::test]
 async fn test_block_propagation_and_fork_choice() {
-    use crate::ledger::{Ledger, Block, Transaction, Chain, select_chain};
+    use crate::ledger::{Ledger, Block, BlockHeader, Transaction, Chain, select_chain};
     use crate::protocol::Protocol;
     use crate::mempool::Mempool;
     use std::sync::Arc;
@@ -20,11 +20,18 @@ async fn test_block_propagation_and_fork_choice() {
             &mut *l,
             protocol.hard_fork.era_logic.as_ref(),
             &*mempool.lock().await,
-            0, // slot
-            0, // epoch
-            "test-leader".to_string(), // leader
-            vec![0u8; 32], // vrf_proof
-            vec![0u8; 32], // kes_signature
+            BlockHeader {
+                slot: 0,
+                epoch: 0,
+                leader: "test-leader".to_string(),
+                issuer_vkey: vec![],
+                vrf_vkey: vec![0u8; 32],
+                vrf_proof: vec![0u8; 80],
+                opcert: Default::default(),
+                kes_signature: vec![0u8; 32],
+                prev_hash: None,
+                body_hash: [0u8; 32],
+            },
         ).await.unwrap()
     };
     {
@@ -1,7 +1,7 @@
 //! Property-based tests for consensus and networking in Super Cardano Node
 
 use proptest::prelude::*;
-use crate::ledger::{Transaction, TxInput, TxOutput};
+use crate::ledger::{Address, Credential, Network, Transaction, TxInput, TxOutput};
 use crate::mempool::Mempool;
 
 // Local wrapper for Transaction to allow Arbitrary impl
@@ -13,15 +13,14 @@ impl Arbitrary for PropTransaction {
     type Strategy = proptest::strategy::BoxedStrategy<Self>;
     fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
         (
-            any::<u64>(),
-            proptest::collection::vec((any::<u64>(), any::<u32>()), 0..4),
-            proptest::collection::vec((any::<String>(), any::<u64>()), 0..4)
-        ).prop_map(|(id, inputs, outputs)| PropTransaction(Transaction {
-            id,
+            proptest::collection::vec((any::<[u8; 32]>(), any::<u32>()), 0..4),
+            proptest::collection::vec((any::<[u8; 28]>(), any::<u64>()), 0..4)
+        ).prop_map(|(inputs, outputs)| PropTransaction(Transaction {
             inputs: inputs.into_iter().map(|(prev_tx, index)| TxInput { prev_tx, index }).collect(),
-            outputs: outputs.into_iter().map(|(address, amount)| TxOutput { address, amount, assets: None }).collect(),
+            outputs: outputs.into_iter().map(|(hash, amount)| TxOutput { address: Address::enterprise(Network::Testnet, Credential::KeyHash(hash)), amount, assets: None, ..Default::default() }).collect(),
             certificates: vec![],
             plutus_witnesses: vec![],
+            ..Default::default()
         })).boxed()
     }
 }